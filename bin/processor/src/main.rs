@@ -17,6 +17,7 @@ use shared::{
 	config::config,
 	consumption::{delete_consumption, get_consumption, write_batch_consumption},
 	registry::registered_paras,
+	retention::prune_old_consumption,
 };
 use std::collections::BTreeMap;
 use types::WeightConsumption;
@@ -24,7 +25,7 @@ use types::WeightConsumption;
 const LOG_TARGET: &str = "processor";
 
 fn main() {
-	env_logger::init();
+	shared::logger::init_logger();
 
 	let outputs = config().outputs;
 	let paras = registered_paras();
@@ -81,4 +82,7 @@ fn main() {
 
 		(0..outputs).for_each(|output_index| delete_consumption(para.clone(), output_index));
 	});
+
+	// The processed consumption is only written by the processor, so it is pruned here.
+	prune_old_consumption(None);
 }
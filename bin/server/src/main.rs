@@ -18,10 +18,27 @@
 /// This API exposes two main endpoints:
 /// - `/consumption`: Used to query consumption data associated with a parachain.
 /// - `/register`: Used to register a parachain for consumption tracking.
-use rocket_cors::CorsOptions;
+use rocket::fairing::AdHoc;
 use routes::{
-	consumption::consumption, extend_subscription::extend_subscription, register::register_para,
+	admin::{link_para_migration, list_tracker_errors, pause, refresh_chaindata, resume},
+	availability::availability,
+	consumption::{consumption, consumption_latest, consumption_stream},
+	endpoints::list_routes,
+	extend_subscription::extend_subscription,
+	health::{health, readiness},
+	idempotency::idempotency_cache,
+	live::{consumption_live, live_feed},
+	order::{place_order, settle_order, settlement_job},
+	payment::registration_payment,
+	rate_limit::rate_limiter,
+	reactivation::reactivation_cost,
+	register::{register_para, register_paras},
 	registry::registry,
+	signature::pubkey,
+	subscription::{subscription_status, subscription_statuses},
+	top_up::top_up,
+	unregister::unregister_para,
+	update::update_registration,
 };
 
 #[macro_use]
@@ -29,7 +46,66 @@ extern crate rocket;
 
 #[launch]
 fn rocket() -> _ {
-	rocket::build()
-		.attach(CorsOptions::default().to_cors().unwrap())
-		.mount("/", routes![consumption, register_para, registry, extend_subscription])
+	// Fail fast on a misconfiguration, rather than once the misconfigured part is first used.
+	if let Err(err) = shared::config::validate() {
+		panic!("Invalid configuration: {}", err);
+	}
+
+	#[allow(unused_mut)]
+	let mut mounted = routes![
+		consumption,
+		consumption_stream,
+		consumption_latest,
+		consumption_live,
+		register_para,
+		register_paras,
+		unregister_para,
+		registry,
+		extend_subscription,
+		reactivation_cost,
+		pubkey,
+		subscription_status,
+		subscription_statuses,
+		health,
+		readiness,
+		top_up,
+		list_routes,
+		link_para_migration,
+		pause,
+		resume,
+		list_tracker_errors,
+		refresh_chaindata,
+		place_order,
+		settle_order,
+		registration_payment,
+		availability,
+		update_registration
+	];
+
+	#[cfg(feature = "chart")]
+	mounted.extend(routes![routes::chart::consumption_chart]);
+
+	#[cfg(feature = "metrics")]
+	mounted.extend(routes![routes::metrics::metrics]);
+
+	// Rocket stops on `SIGTERM` and `Ctrl-C`, giving the pending requests
+	// `shutdown_timeout` seconds to finish.
+	let figment = rocket::Config::figment()
+		.merge(("shutdown.grace", shared::config::config().shutdown_timeout));
+
+	let mut rocket = rocket::custom(figment);
+	if let Some(cors) = routes::cors::cors() {
+		rocket = rocket.attach(cors);
+	}
+
+	rocket
+		.attach(settlement_job())
+		.attach(rate_limiter())
+		.attach(idempotency_cache())
+		.attach(live_feed())
+		.attach(AdHoc::on_liftoff("Expiry notifications", |rocket| {
+			let shutdown = rocket.shutdown();
+			Box::pin(async move { shared::expiry::spawn_expiry_notifier(shutdown) })
+		}))
+		.mount("/", mounted)
 }
@@ -1,7 +1,7 @@
 use clap::Parser;
 
 /// Arguments for the tracker.
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
 	/// Specifies the index of the RPC to be used.
@@ -10,4 +10,18 @@ pub struct Args {
 	/// `rpc_index` selects which RPC from the list will be used.
 	#[arg(short, long)]
 	pub rpc_index: usize,
+
+	/// Specifies the number of finalized blocks for which the consumption is fetched
+	/// concurrently.
+	///
+	/// The consumption is still recorded in the order of the blocks.
+	#[arg(short, long, default_value_t = 1)]
+	pub prefetch_depth: usize,
+
+	/// Specifies the number of historical blocks for which the consumption is fetched
+	/// concurrently while backfilling a parachain.
+	///
+	/// The consumption is still recorded in the order of the blocks.
+	#[arg(short, long, default_value_t = 8)]
+	pub backfill_parallelism: usize,
 }
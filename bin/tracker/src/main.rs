@@ -40,42 +40,191 @@
 //!
 //! The percentages themselves are stored by representing them as decimal numbers;
 //! for example, 50.5% is stored as 0.505 with a precision of three decimals.
+//!
+//! When enabled, the block hash and the split between the weight consumed by inherents and by
+//! the other extrinsics are stored in additional trailing columns.
 
 const LOG_TARGET: &str = "tracker";
 
+/// The delay before the first attempt to reconnect to a parachain.
+const INITIAL_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+/// The maximum delay between two attempts to reconnect to a parachain.
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(300);
+/// The error reported when the watchdog considers the block subscription stalled.
+const STALLED_SUBSCRIPTION: &str = "No new finalized block received within the staleness threshold";
+
 use clap::Parser;
-use shared::{consumption::write_consumption, registry::registered_paras, round_to};
-use subxt::{blocks::Block, utils::H256, OnlineClient, PolkadotConfig};
-use types::{Parachain, Timestamp, WeightConsumption};
+use futures::{future::ready, stream, StreamExt};
+use shared::{
+	alert::{fire_weight_alert, WeightMonitor},
+	chaindata::has_chaindata,
+	config::{
+		config, is_sampled, recorded_dimensions, sampled_blocks, sampling_interval,
+		weight_threshold,
+	},
+	consumption::{latest_consumption, write_consumption},
+	current_timestamp,
+	registry::{dedup_registry, registered_paras},
+	round_to,
+	subscription::{subscription_state, SubscriptionState},
+	tracker::{backfill_range, note_tracker_error, write_tracker_state, TrackerState},
+};
+use shutdown::Shutdown;
+use std::time::Duration;
+use subxt::{
+	backend::rpc::{rpc_params, RpcClient},
+	blocks::Block,
+	utils::H256,
+	OnlineClient, PolkadotConfig,
+};
+use types::{InherentSplit, Parachain, Timestamp, WeightConsumption, WeightDimension};
 
 mod cli;
+#[cfg(feature = "kafka")]
+mod publisher;
+mod reconciliation;
+mod shutdown;
+mod watchdog;
 
 #[subxt::subxt(runtime_metadata_path = "../../artifacts/metadata.scale")]
 mod polkadot {}
 
+use polkadot::{
+	runtime_types::frame_support::dispatch::DispatchClass,
+	system::events::{ExtrinsicFailed, ExtrinsicSuccess},
+};
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-	env_logger::init();
+	shared::logger::init_logger();
 
 	let args = cli::Args::parse();
 
+	shared::config::validate().map_err(|err| format!("Invalid configuration: {}", err))?;
+
+	// Self-heal duplicate registry entries so that we don't end up subscribing to the same
+	// parachain multiple times.
+	if let Err(err) = dedup_registry() {
+		log::error!(
+			target: LOG_TARGET,
+			"Failed to deduplicate the registry: {:?}",
+			err
+		);
+	}
+
+	let offboarded = match config().reconcile_registry {
+		Some(mode) => reconciliation::reconcile_registry(mode).await,
+		None => vec![],
+	};
+
+	let started_at = current_timestamp();
+	if let Err(err) = write_tracker_state(args.rpc_index, &TrackerState { started_at, offboarded })
+	{
+		log::error!(
+			target: LOG_TARGET,
+			"Failed to write the tracker state: {:?}",
+			err
+		);
+	}
+	#[cfg(feature = "metrics")]
+	if let Err(err) = shared::tracker::reset_tracker_metrics(args.rpc_index) {
+		log::error!(
+			target: LOG_TARGET,
+			"Failed to reset the tracker metrics: {:?}",
+			err
+		);
+	}
+
+	// The consumption recorded before this is discarded, since it may be noisy while the
+	// connections are stabilizing.
+	let warmup_end = started_at.saturating_add(config().tracker_warmup);
+
+	#[cfg(feature = "kafka")]
+	if let Some(kafka_config) = config().kafka {
+		if let Err(err) = publisher::init(kafka_config) {
+			log::error!(
+				target: LOG_TARGET,
+				"Failed to initialize the Kafka publisher: {:?}",
+				err
+			);
+		}
+	}
+
+	// Each tracker prunes its own output, since it holds the lock of the writes to it.
+	shared::retention::spawn_consumption_pruner(Some(args.rpc_index));
+
+	let (request_shutdown, shutdown) = shutdown::channel();
+
 	// Asynchronously subscribes to follow the latest finalized block of each parachain
 	// and continuously fetches the weight consumption.
 	let tasks: Vec<_> = registered_paras()
 		.into_iter()
+		// The registration and history of paused parachains is kept, they are simply not tracked.
+		.filter(|para| !para.paused)
 		.map(|para| {
-			tokio::spawn(async move { track_weight_consumption(para, args.rpc_index).await })
+			let (args, shutdown) = (args.clone(), shutdown.clone());
+			tokio::spawn(
+				async move { track_weight_consumption(para, args, warmup_end, shutdown).await },
+			)
 		})
 		.collect();
 
-	for task in tasks {
-		task.await.expect("Failed to track consumption");
+	let tracking = async {
+		// The parachains are tracked independently, so a panicking task doesn't stop the others.
+		for task in tasks {
+			if let Err(err) = task.await {
+				log::error!(
+					target: LOG_TARGET,
+					"Failed to track consumption: {:?}",
+					err
+				);
+			}
+		}
+
+		#[cfg(feature = "kafka")]
+		publisher::flush().await;
+	};
+	let mut tracking = std::pin::pin!(tracking);
+
+	tokio::select! {
+		_ = &mut tracking => {},
+		_ = shutdown::signal() => {
+			log::info!(target: LOG_TARGET, "Shutting down, waiting for the pending writes");
+			let _ = request_shutdown.send(true);
+
+			let timeout = Duration::from_secs(config().shutdown_timeout);
+			if tokio::time::timeout(timeout, &mut tracking).await.is_err() {
+				log::warn!(
+					target: LOG_TARGET,
+					"Not all the pending writes finished within {} seconds",
+					timeout.as_secs()
+				);
+			}
+		},
 	}
 
 	Ok(())
 }
 
-async fn track_weight_consumption(para: Parachain, rpc_index: usize) {
+/// Tracks the consumption of a parachain, reconnecting whenever its connection is dropped.
+///
+/// Consecutive failed attempts are retried with an exponentially increasing delay, which is reset
+/// once consumption is recorded again. Tracking stops once the shutdown is requested.
+async fn track_weight_consumption(
+	para: Parachain,
+	args: cli::Args,
+	warmup_end: Timestamp,
+	mut shutdown: Shutdown,
+) {
+	if has_chaindata(para.relay_chain.clone(), para.para_id) == Some(false) {
+		log::warn!(
+			target: LOG_TARGET,
+			"{}-{} - Missing from the chaindata, tracking it through its registered rpcs",
+			para.relay_chain, para.para_id,
+		);
+	}
+
+	let rpc_index = args.rpc_index;
 	let Some(rpc) = para.rpcs.get(rpc_index) else {
 		log::error!(
 			target: LOG_TARGET,
@@ -85,34 +234,115 @@ async fn track_weight_consumption(para: Parachain, rpc_index: usize) {
 		return;
 	};
 
-	log::info!("{}-{} - Starting to track consumption.", para.relay_chain, para.para_id);
-	let result = OnlineClient::<PolkadotConfig>::from_url(rpc).await;
+	let mut reconnect_delay = INITIAL_RECONNECT_DELAY;
+	loop {
+		log::info!(
+			target: LOG_TARGET,
+			"{}-{} - Starting to track consumption.",
+			para.relay_chain,
+			para.para_id
+		);
+
+		let client = tokio::select! {
+			_ = shutdown.requested() => break,
+			client = OnlineClient::<PolkadotConfig>::from_url(rpc) => client,
+		};
+
+		let mut recorded = false;
+		let error = match client {
+			Ok(api) => match track_blocks(
+				api,
+				rpc,
+				para.clone(),
+				&args,
+				warmup_end,
+				&mut recorded,
+				&mut shutdown,
+			)
+			.await
+			{
+				Ok(()) => "The finalized block subscription ended".to_string(),
+				Err(err) => format!("Failed to track new block: {:?}", err),
+			},
+			Err(err) => format!("Failed to create online client: {:?}", err),
+		};
+
+		if shutdown.is_requested() {
+			break;
+		}
+
+		log::error!(
+			target: LOG_TARGET,
+			"{}-{} - {}",
+			para.relay_chain,
+			para.para_id,
+			error
+		);
 
-	if let Ok(api) = result {
-		if let Err(err) = track_blocks(api, para.clone(), rpc_index).await {
+		if let Err(err) = note_tracker_error(rpc_index, &para, error) {
 			log::error!(
 				target: LOG_TARGET,
-				"{}-{} - Failed to track new block: {:?}",
+				"{}-{} - Failed to persist the tracker error: {:?}",
 				para.relay_chain,
 				para.para_id,
 				err
 			);
 		}
-	} else {
-		log::error!(
+
+		if recorded {
+			reconnect_delay = INITIAL_RECONNECT_DELAY;
+		}
+
+		log::info!(
 			target: LOG_TARGET,
-			"{}-{} - Failed to create online client: {:?}",
+			"{}-{} - Reconnecting in {} seconds",
 			para.relay_chain,
 			para.para_id,
-			result
+			reconnect_delay.as_secs()
 		);
+		tokio::select! {
+			_ = shutdown.requested() => break,
+			_ = tokio::time::sleep(reconnect_delay) => {},
+		}
+		reconnect_delay = reconnect_delay.saturating_mul(2).min(MAX_RECONNECT_DELAY);
+
+		#[cfg(feature = "metrics")]
+		if let Err(err) = shared::tracker::note_reconnect(rpc_index, &para) {
+			log::error!(
+				target: LOG_TARGET,
+				"{}-{} - Failed to update the tracker metrics: {:?}",
+				para.relay_chain,
+				para.para_id,
+				err
+			);
+		}
 	}
+
+	log::info!(
+		target: LOG_TARGET,
+		"{}-{} - Stopped tracking consumption.",
+		para.relay_chain,
+		para.para_id
+	);
 }
 
+/// Tracks the consumption of the finalized blocks of the parachain.
+///
+/// If the parachain is to be backfilled, the consumption of the blocks preceding the first
+/// finalized block received through the subscription is recorded first.
+///
+/// Returns once the shutdown is requested, in which case the consumption of the blocks that are
+/// still being fetched is discarded.
+///
+/// Fails if the watchdog considers the subscription stalled, so that the tracker reconnects.
 async fn track_blocks(
 	api: OnlineClient<PolkadotConfig>,
+	rpc: &str,
 	para: Parachain,
-	rpc_index: usize,
+	args: &cli::Args,
+	warmup_end: Timestamp,
+	recorded: &mut bool,
+	shutdown: &mut Shutdown,
 ) -> Result<(), Box<dyn std::error::Error>> {
 	log::info!(
 		target: LOG_TARGET,
@@ -127,40 +357,289 @@ async fn track_blocks(
 		.await
 		.map_err(|_| "Failed to subscribe to finalized blocks")?;
 
+	let rpc_index = args.rpc_index;
+	let dimensions = recorded_dimensions(&para);
+	let sampling_interval = sampling_interval(&para);
+	let mut weight_monitor =
+		weight_threshold(&para).map(|threshold| WeightMonitor::new(para.clone(), threshold));
+	let record_block_hashes = config().record_block_hashes;
+	let record_inherent_split = config().record_inherent_split;
+	let stop_tracking_expired = config().stop_tracking_expired;
+
+	let first_block = tokio::select! {
+		biased;
+		_ = shutdown.requested() => return Ok(()),
+		block = blocks_sub.next() => block,
+		_ = watchdog::stalled(&para) => return Err(STALLED_SUBSCRIPTION.into()),
+	};
+	let Some(first_block) = first_block else { return Ok(()) };
+	let first_block = first_block?;
+
+	if let Some(backfill_from) = para.backfill_from {
+		// The consumption recorded before the tracker got restarted isn't recorded again, and the
+		// subscription continues with the first block, so it isn't backfilled.
+		let blocks = backfill_range(
+			backfill_from,
+			latest_consumption(&para).map(|latest| latest.consumption.block_number),
+			first_block.number(),
+			config().max_backfill_blocks,
+		);
+
+		backfill(
+			api.clone(),
+			rpc,
+			&para,
+			blocks,
+			args,
+			&dimensions,
+			sampling_interval,
+			record_block_hashes,
+			record_inherent_split,
+			recorded,
+			shutdown,
+		)
+		.await?;
+
+		if shutdown.is_requested() {
+			return Ok(());
+		}
+	}
+
 	// Wait for new finalized blocks, then fetch and output the weight consumption accordingly.
-	while let Some(Ok(block)) = blocks_sub.next().await {
-		note_new_block(api.clone(), para.clone(), rpc_index, block).await?;
+	//
+	// The consumption of up to `prefetch_depth` blocks is fetched concurrently, while `buffered`
+	// ensures it is still written in the order of the blocks.
+	let consumptions = stream::once(ready(Ok(first_block)))
+		.chain(blocks_sub)
+		.take_while(|block| ready(block.is_ok()))
+		.filter_map(|block| ready(block.ok()))
+		// The consumption of the blocks which aren't sampled in the tier of the parachain isn't
+		// even fetched.
+		.filter(|block| ready(is_sampled(block.number(), sampling_interval)))
+		.map(|block| {
+			fetch_consumption(
+				api.clone(),
+				&dimensions,
+				record_block_hashes,
+				record_inherent_split,
+				block,
+			)
+		})
+		.buffered(args.prefetch_depth.max(1));
+	let mut consumptions = std::pin::pin!(consumptions);
+
+	loop {
+		// The shutdown is only handled between two blocks, so that the consumption of a block is
+		// never partially written.
+		let consumption = tokio::select! {
+			biased;
+			_ = shutdown.requested() => break,
+			consumption = consumptions.next() => consumption,
+			_ = watchdog::stalled(&para) => return Err(STALLED_SUBSCRIPTION.into()),
+		};
+		let Some(consumption) = consumption else { break };
+		let consumption = consumption.map_err(|err| err as Box<dyn std::error::Error>)?;
+
+		if current_timestamp() < warmup_end {
+			log::debug!(
+				target: LOG_TARGET,
+				"{}-{} - Warming up, discarding consumption of block {}",
+				para.relay_chain,
+				para.para_id,
+				consumption.block_number
+			);
+			continue;
+		}
+
+		// The registry update of a renewal restarts the tracker, so the expiry is kept up to date.
+		if stop_tracking_expired && subscription_state(&para) == SubscriptionState::Expired {
+			log::debug!(
+				target: LOG_TARGET,
+				"{}-{} - Subscription expired, discarding consumption of block {}",
+				para.relay_chain,
+				para.para_id,
+				consumption.block_number
+			);
+			continue;
+		}
+
+		if let Some(alert) = weight_monitor.as_mut().and_then(|m| m.observe(&consumption)) {
+			// Posting the alert shouldn't hold up the tracking.
+			tokio::spawn(async move { fire_weight_alert(&alert).await });
+		}
+
+		record_consumption(&para, consumption, rpc_index)?;
+		*recorded = true;
 	}
 
 	Ok(())
 }
 
-async fn note_new_block(
+/// Records the consumption of the blocks within `blocks`, which precede the blocks received
+/// through the finalized block subscription.
+///
+/// The consumption of up to `backfill_parallelism` blocks is fetched concurrently, while still
+/// being recorded in the order of the blocks.
+///
+/// The backfill is abandoned if the consumption of a block can't be fetched, e.g. because the
+/// node pruned the state, without affecting the tracking of the new blocks.
+#[allow(clippy::too_many_arguments)]
+async fn backfill(
 	api: OnlineClient<PolkadotConfig>,
-	para: Parachain,
+	rpc: &str,
+	para: &Parachain,
+	blocks: std::ops::Range<u32>,
+	args: &cli::Args,
+	dimensions: &[WeightDimension],
+	sampling_interval: u32,
+	record_block_hashes: bool,
+	record_inherent_split: bool,
+	recorded: &mut bool,
+	shutdown: &mut Shutdown,
+) -> Result<(), Box<dyn std::error::Error>> {
+	if blocks.is_empty() {
+		return Ok(());
+	}
+
+	log::info!(
+		target: LOG_TARGET,
+		"{}-{} - Backfilling the consumption of blocks #{} to #{}",
+		para.relay_chain,
+		para.para_id,
+		blocks.start,
+		blocks.end - 1
+	);
+
+	let rpc_client = match RpcClient::from_url(rpc).await {
+		Ok(rpc_client) => rpc_client,
+		Err(err) => {
+			log::error!(
+				target: LOG_TARGET,
+				"{}-{} - Failed to connect for the backfill, abandoning it: {:?}",
+				para.relay_chain,
+				para.para_id,
+				err
+			);
+			return Ok(());
+		},
+	};
+	let consumptions = stream::iter(sampled_blocks(blocks, sampling_interval))
+		.map(|block_number| {
+			let (api, rpc_client) = (api.clone(), rpc_client.clone());
+			async move {
+				let consumption = async move {
+					let block_hash: Option<H256> = rpc_client
+						.request("chain_getBlockHash", rpc_params![Some(block_number)])
+						.await?;
+					let block_hash = block_hash.ok_or("Failed to query block hash")?;
+					let block = api.blocks().at(block_hash).await?;
+
+					fetch_consumption(
+						api,
+						dimensions,
+						record_block_hashes,
+						record_inherent_split,
+						block,
+					)
+					.await
+				};
+				(block_number, consumption.await)
+			}
+		})
+		.buffered(args.backfill_parallelism.max(1));
+	let mut consumptions = std::pin::pin!(consumptions);
+
+	loop {
+		// Same as for the subscription, the shutdown is only handled between two blocks.
+		let consumption = tokio::select! {
+			biased;
+			_ = shutdown.requested() => break,
+			consumption = consumptions.next() => consumption,
+		};
+		let Some((block_number, consumption)) = consumption else { break };
+		let consumption = match consumption {
+			Ok(consumption) => consumption,
+			Err(err) => {
+				// The remaining blocks most likely can't be fetched either, e.g. if the state of
+				// the older blocks was pruned.
+				log::error!(
+					target: LOG_TARGET,
+					"{}-{} - Failed to backfill block #{}, abandoning the backfill: {:?}",
+					para.relay_chain,
+					para.para_id,
+					block_number,
+					err
+				);
+				break;
+			},
+		};
+
+		// The historical consumption isn't affected by the warmup, nor does it fire alerts.
+		record_consumption(para, consumption, args.rpc_index)?;
+		*recorded = true;
+	}
+
+	Ok(())
+}
+
+fn record_consumption(
+	para: &Parachain,
+	consumption: WeightConsumption,
 	rpc_index: usize,
+) -> Result<(), std::io::Error> {
+	#[cfg(feature = "kafka")]
+	publisher::publish(para, &consumption);
+
+	write_consumption(para.clone(), consumption, Some(rpc_index))?;
+
+	#[cfg(feature = "metrics")]
+	if let Err(err) = shared::tracker::note_processed_block(rpc_index, para) {
+		log::error!(
+			target: LOG_TARGET,
+			"{}-{} - Failed to update the tracker metrics: {:?}",
+			para.relay_chain,
+			para.para_id,
+			err
+		);
+	}
+
+	Ok(())
+}
+
+async fn fetch_consumption(
+	api: OnlineClient<PolkadotConfig>,
+	dimensions: &[WeightDimension],
+	record_block_hash: bool,
+	record_inherent_split: bool,
 	block: Block<PolkadotConfig, OnlineClient<PolkadotConfig>>,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<WeightConsumption, Box<dyn std::error::Error + Send + Sync>> {
 	let block_number = block.header().number;
 
 	let timestamp = timestamp_at(api.clone(), block.hash()).await?;
-	let consumption = weight_consumption(api, block_number, timestamp).await?;
+	let consumption =
+		weight_consumption(api.clone(), block_number, block.hash(), timestamp, dimensions).await?;
 
-	write_consumption(para, consumption, Some(rpc_index))?;
+	let inherent_split =
+		if record_inherent_split { Some(inherent_split(api, &block).await?) } else { None };
 
-	Ok(())
+	Ok(WeightConsumption {
+		block_hash: record_block_hash.then(|| format!("{:?}", block.hash())),
+		inherent_split,
+		..consumption
+	})
 }
 
 async fn weight_consumption(
 	api: OnlineClient<PolkadotConfig>,
 	block_number: u32,
+	block_hash: H256,
 	timestamp: Timestamp,
-) -> Result<WeightConsumption, Box<dyn std::error::Error>> {
+	dimensions: &[WeightDimension],
+) -> Result<WeightConsumption, Box<dyn std::error::Error + Send + Sync>> {
 	let weight_query = polkadot::storage().system().block_weight();
 	let weight_consumed = api
 		.storage()
-		.at_latest()
-		.await?
+		.at(block_hash)
 		.fetch(&weight_query)
 		.await?
 		.ok_or("Failed to query consumption")?;
@@ -181,30 +660,80 @@ async fn weight_consumption(
 	let operational_proof_size = weight_consumed.operational.proof_size;
 	let mandatory_proof_size = weight_consumed.mandatory.proof_size;
 
+	// Dimensions that aren't recorded for the parachain are skipped to save storage.
 	let consumption = WeightConsumption {
 		block_number,
 		timestamp,
-		ref_time: (
-			round_to(normal_ref_time as f32 / ref_time_limit as f32, 3),
-			round_to(operational_ref_time as f32 / ref_time_limit as f32, 3),
-			round_to(mandatory_ref_time as f32 / ref_time_limit as f32, 3),
-		)
-			.into(),
-		proof_size: (
-			round_to(normal_proof_size as f32 / proof_limit as f32, 3),
-			round_to(operational_proof_size as f32 / proof_limit as f32, 3),
-			round_to(mandatory_proof_size as f32 / proof_limit as f32, 3),
-		)
-			.into(),
+		ref_time: dimensions.contains(&WeightDimension::RefTime).then(|| {
+			(
+				round_to(normal_ref_time as f32 / ref_time_limit as f32, 3),
+				round_to(operational_ref_time as f32 / ref_time_limit as f32, 3),
+				round_to(mandatory_ref_time as f32 / ref_time_limit as f32, 3),
+			)
+				.into()
+		}),
+		proof_size: dimensions.contains(&WeightDimension::ProofSize).then(|| {
+			(
+				round_to(normal_proof_size as f32 / proof_limit as f32, 3),
+				round_to(operational_proof_size as f32 / proof_limit as f32, 3),
+				round_to(mandatory_proof_size as f32 / proof_limit as f32, 3),
+			)
+				.into()
+		}),
+		block_hash: None,
+		inherent_split: None,
 	};
 
 	Ok(consumption)
 }
 
+/// Computes the weight consumed by the inherents of the block, compared to the weight consumed by
+/// its other extrinsics.
+///
+/// The weight of each extrinsic is read from the event emitted after its dispatch, and the
+/// extrinsics of the mandatory dispatch class are considered to be the inherents.
+async fn inherent_split(
+	api: OnlineClient<PolkadotConfig>,
+	block: &Block<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+) -> Result<InherentSplit, Box<dyn std::error::Error + Send + Sync>> {
+	let weight_limit_query = polkadot::constants().system().block_weights();
+	let max_block = api.constants().at(&weight_limit_query)?.max_block;
+
+	let (mut inherent, mut non_inherent) = ((0u64, 0u64), (0u64, 0u64));
+	for event in block.events().await?.iter() {
+		let event = event?;
+		let dispatch_info = if let Some(success) = event.as_event::<ExtrinsicSuccess>()? {
+			success.dispatch_info
+		} else if let Some(failed) = event.as_event::<ExtrinsicFailed>()? {
+			failed.dispatch_info
+		} else {
+			continue;
+		};
+
+		let totals = if matches!(dispatch_info.class, DispatchClass::Mandatory) {
+			&mut inherent
+		} else {
+			&mut non_inherent
+		};
+		totals.0 = totals.0.saturating_add(dispatch_info.weight.ref_time);
+		totals.1 = totals.1.saturating_add(dispatch_info.weight.proof_size);
+	}
+
+	let ref_time = |weight: u64| round_to(weight as f32 / max_block.ref_time as f32, 3);
+	let proof_size = |weight: u64| round_to(weight as f32 / max_block.proof_size as f32, 3);
+
+	Ok(InherentSplit {
+		inherent_ref_time: ref_time(inherent.0),
+		non_inherent_ref_time: ref_time(non_inherent.0),
+		inherent_proof_size: proof_size(inherent.1),
+		non_inherent_proof_size: proof_size(non_inherent.1),
+	})
+}
+
 async fn timestamp_at(
 	api: OnlineClient<PolkadotConfig>,
 	block_hash: H256,
-) -> Result<Timestamp, Box<dyn std::error::Error>> {
+) -> Result<Timestamp, Box<dyn std::error::Error + Send + Sync>> {
 	let timestamp_query = polkadot::storage().timestamp().now();
 
 	let timestamp = api
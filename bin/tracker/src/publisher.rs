@@ -0,0 +1,104 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Publishes the recorded consumption to a Kafka topic.
+//!
+//! Publishing is fire-and-forget: records are handed over to a background task through a bounded
+//! buffer, and are dropped if the buffer is full so that the tracker is never blocked. The records
+//! still in the buffer are published before the tracker shuts down.
+
+use crate::LOG_TARGET;
+use rdkafka::{
+	error::KafkaError,
+	producer::{FutureProducer, FutureRecord},
+	ClientConfig,
+};
+use shared::config::KafkaConfig;
+use std::{sync::Mutex, time::Duration};
+use tokio::{
+	sync::mpsc::{self, error::TrySendError, Sender},
+	task::JoinHandle,
+};
+use types::{Parachain, WeightConsumption};
+
+/// The buffer through which the records are handed over, along with the task publishing them.
+static PUBLISHER: Mutex<Option<(Sender<(Parachain, WeightConsumption)>, JoinHandle<()>)>> =
+	Mutex::new(None);
+
+/// Starts the background task which publishes the records to the configured topic.
+pub fn init(config: KafkaConfig) -> Result<(), KafkaError> {
+	let producer: FutureProducer =
+		ClientConfig::new().set("bootstrap.servers", &config.brokers).create()?;
+
+	let (sender, mut receiver) =
+		mpsc::channel::<(Parachain, WeightConsumption)>(config.buffer_size);
+
+	let task = tokio::spawn(async move {
+		while let Some((para, consumption)) = receiver.recv().await {
+			let key = format!("{}-{}", para.relay_chain, para.para_id);
+			let payload = serde_json::json!({
+				"relay_chain": para.relay_chain,
+				"para_id": para.para_id,
+				"consumption": consumption,
+			})
+			.to_string();
+
+			let record = FutureRecord::to(&config.topic).key(&key).payload(&payload);
+			if let Err((err, _)) = producer.send(record, Duration::from_secs(0)).await {
+				log::warn!(
+					target: LOG_TARGET,
+					"{} - Failed to publish consumption: {:?}",
+					key,
+					err
+				);
+			}
+		}
+	});
+
+	*PUBLISHER.lock().unwrap() = Some((sender, task));
+
+	Ok(())
+}
+
+/// Stops accepting new records and waits until the buffered ones are published.
+pub async fn flush() {
+	let Some((sender, task)) = PUBLISHER.lock().unwrap().take() else { return };
+
+	// The background task stops once the buffer is drained after its sender is dropped.
+	drop(sender);
+	if let Err(err) = task.await {
+		log::error!(
+			target: LOG_TARGET,
+			"Failed to flush the published consumption: {:?}",
+			err
+		);
+	}
+}
+
+/// Queues the consumption record for publishing.
+pub fn publish(para: &Parachain, consumption: &WeightConsumption) {
+	let publisher = PUBLISHER.lock().unwrap();
+	let Some((sender, _)) = publisher.as_ref() else { return };
+
+	if let Err(TrySendError::Full(_)) = sender.try_send((para.clone(), consumption.clone())) {
+		log::warn!(
+			target: LOG_TARGET,
+			"{}-{} - Publish buffer is full, dropping consumption of block {}",
+			para.relay_chain,
+			para.para_id,
+			consumption.block_number
+		);
+	}
+}
@@ -0,0 +1,108 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Reconciliation of the registry against the parachains onboarded on the relay chains.
+
+use crate::{polkadot, LOG_TARGET};
+use polkadot::runtime_types::polkadot_parachain::primitives::Id;
+use shared::{
+	config::{relay_rpc_url, ReconciliationMode},
+	registry::{registered_paras, update_registry},
+};
+use std::collections::HashMap;
+use subxt::{OnlineClient, PolkadotConfig};
+use types::{ParaId, Parachain, RelayChain};
+
+/// Returns the registered parachains which are no longer onboarded on their relay chain.
+///
+/// In `Pause` mode the tracking of these parachains is also paused.
+pub async fn reconcile_registry(mode: ReconciliationMode) -> Vec<(RelayChain, ParaId)> {
+	let mut paras = registered_paras();
+	let mut clients: HashMap<RelayChain, Option<OnlineClient<PolkadotConfig>>> = HashMap::new();
+
+	let mut offboarded = vec![];
+	for para in paras.iter().filter(|para| !para.paused) {
+		if !clients.contains_key(&para.relay_chain) {
+			let client = relay_client(&para.relay_chain).await;
+			clients.insert(para.relay_chain.clone(), client);
+		}
+		let Some(Some(client)) = clients.get(&para.relay_chain) else { continue };
+
+		match is_onboarded(client, para).await {
+			Ok(false) => {
+				log::warn!(
+					target: LOG_TARGET,
+					"{}-{} - Para is no longer onboarded on the relay chain",
+					para.relay_chain,
+					para.para_id
+				);
+				offboarded.push((para.relay_chain.clone(), para.para_id));
+			},
+			Ok(true) => {},
+			Err(err) => log::error!(
+				target: LOG_TARGET,
+				"{}-{} - Failed to check whether the para is onboarded: {:?}",
+				para.relay_chain,
+				para.para_id,
+				err
+			),
+		}
+	}
+
+	if mode == ReconciliationMode::Pause && !offboarded.is_empty() {
+		paras
+			.iter_mut()
+			.filter(|para| offboarded.contains(&(para.relay_chain.clone(), para.para_id)))
+			.for_each(|para| para.paused = true);
+
+		if let Err(err) = update_registry(paras) {
+			log::error!(
+				target: LOG_TARGET,
+				"Failed to pause the offboarded paras: {:?}",
+				err
+			);
+		}
+	}
+
+	offboarded
+}
+
+async fn relay_client(relay_chain: &RelayChain) -> Option<OnlineClient<PolkadotConfig>> {
+	let rpc_url = relay_rpc_url(relay_chain)?;
+
+	match OnlineClient::<PolkadotConfig>::from_url(rpc_url).await {
+		Ok(client) => Some(client),
+		Err(err) => {
+			log::error!(
+				target: LOG_TARGET,
+				"{} - Failed to connect to the relay chain: {:?}",
+				relay_chain,
+				err
+			);
+			None
+		},
+	}
+}
+
+/// Returns whether the relay chain still has a head stored for the parachain.
+async fn is_onboarded(
+	client: &OnlineClient<PolkadotConfig>,
+	para: &Parachain,
+) -> Result<bool, subxt::Error> {
+	let head_query = polkadot::storage().paras().heads(Id(para.para_id));
+	let head = client.storage().at_latest().await?.fetch(&head_query).await?;
+
+	Ok(head.is_some())
+}
@@ -0,0 +1,66 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Graceful shutdown of the tracker.
+//!
+//! Once the process receives `SIGTERM` or `Ctrl-C`, all the tracking tasks are signalled to stop.
+//! Each task stops at a block boundary, i.e. only after the consumption of the block it is
+//! processing is fully written, so that no partial records are left behind.
+
+use tokio::sync::watch;
+
+/// Notifies the tracking tasks about the requested shutdown.
+#[derive(Clone)]
+pub struct Shutdown(watch::Receiver<bool>);
+
+impl Shutdown {
+	/// Resolves once the shutdown is requested.
+	pub async fn requested(&mut self) {
+		// The sender is kept alive until the tracker exits, so this can't fail.
+		let _ = self.0.wait_for(|requested| *requested).await;
+	}
+
+	/// Returns whether the shutdown was requested.
+	pub fn is_requested(&self) -> bool {
+		*self.0.borrow()
+	}
+}
+
+/// Creates the sender through which the shutdown is requested, along with its receiving end.
+pub fn channel() -> (watch::Sender<bool>, Shutdown) {
+	let (sender, receiver) = watch::channel(false);
+	(sender, Shutdown(receiver))
+}
+
+/// Resolves once the process receives `SIGTERM` or `Ctrl-C`.
+pub async fn signal() {
+	#[cfg(unix)]
+	{
+		use tokio::signal::unix::{signal, SignalKind};
+
+		match signal(SignalKind::terminate()) {
+			Ok(mut terminate) => tokio::select! {
+				_ = terminate.recv() => {},
+				_ = tokio::signal::ctrl_c() => {},
+			},
+			Err(_) => {
+				let _ = tokio::signal::ctrl_c().await;
+			},
+		}
+	}
+
+	#[cfg(not(unix))]
+	let _ = tokio::signal::ctrl_c().await;
+}
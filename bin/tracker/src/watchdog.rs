@@ -0,0 +1,37 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Detection of stalled block subscriptions.
+//!
+//! A connection can silently stop delivering finalized blocks while the socket stays open, in
+//! which case it is never dropped and the tracker doesn't reconnect by itself. When the watchdog
+//! is enabled, the subscription of a parachain is considered stalled once no new block is
+//! received within its staleness threshold, after which the tracker reconnects to it.
+
+use shared::config::{config, staleness_threshold};
+use std::time::Duration;
+use types::Parachain;
+
+/// Resolves once no new block of the parachain has been received for longer than its staleness
+/// threshold.
+///
+/// Never resolves if the watchdog isn't enabled.
+pub async fn stalled(para: &Parachain) {
+	if !config().watchdog {
+		return std::future::pending().await;
+	}
+
+	tokio::time::sleep(Duration::from_secs(staleness_threshold(para))).await
+}
@@ -0,0 +1,43 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Benchmarks reading the registry through its in-memory cache, which only checks the metadata of
+//! the registry file, against reading it from the registry file as done when `cache_registry` is
+//! disabled.
+//!
+//! Run with `cargo bench -p routes`, which reads the registry of the mock config.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use shared::{
+	config::config,
+	registry::registered_paras,
+	storage::{FileStorage, RegistryStorage},
+};
+
+fn registry_reads(c: &mut Criterion) {
+	let mut group = c.benchmark_group("registry");
+	// The first read populates the cache.
+	assert_eq!(registered_paras(), FileStorage::new(config().registry).read().unwrap());
+
+	group.bench_function("cached", |b| b.iter(registered_paras));
+	// Without the cache, every read parses the config to find the registry file and then reads it.
+	group.bench_function("uncached", |b| {
+		b.iter(|| FileStorage::new(config().registry).read().unwrap())
+	});
+	group.finish();
+}
+
+criterion_group!(benches, registry_reads);
+criterion_main!(benches);
@@ -0,0 +1,180 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Operations reserved for the operator of the service.
+//!
+//! All admin requests need to provide the configured admin key in the `X-Admin-Key` header.
+
+use crate::*;
+use rocket::{
+	get,
+	outcome::Outcome,
+	post,
+	request::{self, FromRequest},
+	serde::json::Json,
+};
+use shared::{
+	chaindata,
+	config::config,
+	migration::{link_migration, migrations, previous_identities, ParaMigration},
+	registry::{registered_paras, update_registry},
+	tracker::{tracker_errors, TrackerError},
+};
+use subtle::ConstantTimeEq;
+use types::{ParaId, RelayChain};
+
+/// The header containing the admin key.
+pub const ADMIN_KEY_HEADER: &str = "X-Admin-Key";
+
+/// The admin key provided with the request, if any.
+pub struct AdminKey(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminKey {
+	type Error = ();
+
+	async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+		Outcome::Success(AdminKey(request.headers().get_one(ADMIN_KEY_HEADER).map(String::from)))
+	}
+}
+
+impl AdminKey {
+	/// Ensures that the provided key matches the configured admin key.
+	pub fn authorize(&self) -> Result<(), Error> {
+		let path = config().admin_key.ok_or(Error::AdminDisabled)?;
+		let admin_key = std::fs::read_to_string(&path).map_err(|_| {
+			log::error!(
+				target: LOG_TARGET,
+				"Failed to load the admin key from {}",
+				path
+			);
+			Error::AdminDisabled
+		})?;
+
+		// The keys are compared in constant time, so that the admin key can't be guessed from the
+		// time it takes to reject a wrong key.
+		match &self.0 {
+			Some(key) if bool::from(key.trim().as_bytes().ct_eq(admin_key.trim().as_bytes())) =>
+				Ok(()),
+			_ => Err(Error::Unauthorized),
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PauseData {
+	/// The parachain for which the tracking is paused or resumed.
+	pub para: (RelayChain, ParaId),
+}
+
+/// Pause the tracking of a parachain, while keeping its registration and history intact.
+#[post("/admin/pause", data = "<pause_data>")]
+pub fn pause(key: AdminKey, pause_data: Json<PauseData>) -> Result<(), Error> {
+	key.authorize()?;
+	set_paused(pause_data.into_inner().para, true)
+}
+
+/// Resume the tracking of a paused parachain.
+#[post("/admin/resume", data = "<pause_data>")]
+pub fn resume(key: AdminKey, pause_data: Json<PauseData>) -> Result<(), Error> {
+	key.authorize()?;
+	set_paused(pause_data.into_inner().para, false)
+}
+
+fn set_paused((relay_chain, para_id): (RelayChain, ParaId), paused: bool) -> Result<(), Error> {
+	let mut paras = registered_paras();
+	let para = paras
+		.iter_mut()
+		.find(|para| para.relay_chain == relay_chain && para.para_id == para_id)
+		.ok_or(Error::NotRegistered)?;
+	para.paused = paused;
+
+	if let Err(err) = update_registry(paras) {
+		log::error!(
+			target: LOG_TARGET,
+			"{}-{} - Failed to update the paused state of the para: {:?}",
+			relay_chain,
+			para_id,
+			err
+		);
+		return Err(Error::InvalidData);
+	}
+
+	// The tracker is restarted so that it picks up the change.
+	#[cfg(not(debug_assertions))]
+	shared::init_tracker();
+
+	Ok(())
+}
+
+/// Link the old and new identities of a parachain that migrated to a different relay chain.
+///
+/// Once linked, the consumption recorded under the old identity can be queried along with the
+/// consumption of the new one.
+#[post("/admin/link-migration", data = "<migration>")]
+pub fn link_para_migration(key: AdminKey, migration: Json<ParaMigration>) -> Result<(), Error> {
+	key.authorize()?;
+
+	let migration = migration.into_inner();
+	let (relay_chain, para_id) = migration.from.clone();
+
+	// Each identity can only be migrated from once, and linking must not introduce a cycle.
+	if migration.from == migration.to ||
+		migrations().iter().any(|m| m.from == migration.from) ||
+		previous_identities(relay_chain, para_id).contains(&migration.to)
+	{
+		return Err(Error::InvalidMigration);
+	}
+
+	link_migration(migration).map_err(|err| {
+		log::error!(
+			target: LOG_TARGET,
+			"Failed to link para migration: {:?}",
+			err
+		);
+		Error::InvalidData
+	})
+}
+
+/// Query the recent errors which caused the tracking of parachains to stop, oldest first.
+///
+/// Only available if `tracker_error_history` is configured.
+#[get("/admin/tracker_errors")]
+pub fn list_tracker_errors(key: AdminKey) -> Result<String, Error> {
+	key.authorize()?;
+
+	let mut errors: Vec<TrackerError> = (0..config().outputs).flat_map(tracker_errors).collect();
+	errors.sort_by_key(|error| error.timestamp);
+
+	serde_json::to_string(&errors).map_err(|_| Error::InvalidData)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ChainDataRefresh {
+	/// The number of parachains contained in the refreshed chaindata.
+	pub paras: usize,
+}
+
+/// Refresh the chaindata from the configured `chaindata_url`, so that newly onboarded parachains
+/// can be registered without restarting the service.
+#[post("/admin/refresh-chaindata")]
+pub async fn refresh_chaindata(key: AdminKey) -> Result<String, Error> {
+	key.authorize()?;
+
+	let paras = chaindata::refresh_chaindata().await.map_err(Error::ChainDataError)?;
+	serde_json::to_string(&ChainDataRefresh { paras }).map_err(|_| Error::InvalidData)
+}
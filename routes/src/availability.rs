@@ -0,0 +1,78 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::*;
+use rocket::get;
+use shared::{
+	config::block_time, consumption::consumption_iter, current_timestamp, registry::registered_para,
+};
+use types::{ParaId, Timestamp};
+
+/// The default window, in seconds, over which the availability is computed.
+pub const DEFAULT_AVAILABILITY_WINDOW: Timestamp = 3600;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Availability {
+	/// The window in seconds, ending now, over which the availability was computed.
+	pub window: Timestamp,
+	/// The number of blocks expected to be produced within the window, based on the expected
+	/// block time of the parachain.
+	pub expected_blocks: u64,
+	/// The number of blocks recorded within the window.
+	pub recorded_blocks: u64,
+	/// The percentage of the expected blocks that were recorded, capped at 100.
+	pub availability: f32,
+	/// The number of gaps in the block numbers recorded within the window.
+	pub gaps: u32,
+}
+
+/// Query the fraction of the expected blocks of a parachain that were recorded within the
+/// specified `window` in seconds.
+///
+/// This combines the detection of gaps in the recorded consumption with the liveness of the
+/// parachain, since blocks that weren't produced aren't recorded either.
+#[get("/consumption/<relay>/<para_id>/availability?<window>")]
+pub fn availability(
+	relay: &str,
+	para_id: ParaId,
+	window: Option<Timestamp>,
+) -> Result<String, Error> {
+	let para = registered_para(relay.into(), para_id).ok_or(Error::NotRegistered)?;
+	let window = window.unwrap_or(DEFAULT_AVAILABILITY_WINDOW);
+
+	// The consumption timestamps are in milliseconds.
+	let since = current_timestamp().saturating_sub(window).saturating_mul(1000);
+	let mut block_numbers: Vec<u32> = consumption_iter(para.clone(), None)
+		.map_err(|_| Error::ConsumptionDataNotFound)?
+		.filter(|consumption| consumption.timestamp >= since)
+		.map(|consumption| consumption.block_number)
+		.collect();
+	block_numbers.sort_unstable();
+	block_numbers.dedup();
+
+	let expected_blocks = window / block_time(&para).max(1);
+	let recorded_blocks = block_numbers.len() as u64;
+	let availability = if expected_blocks == 0 {
+		100.0
+	} else {
+		(recorded_blocks as f32 / expected_blocks as f32 * 100.0).min(100.0)
+	};
+	let gaps = block_numbers.windows(2).filter(|pair| pair[1] - pair[0] > 1).count() as u32;
+
+	let availability =
+		Availability { window, expected_blocks, recorded_blocks, availability, gaps };
+	serde_json::to_string(&availability).map_err(|_| Error::InvalidData)
+}
@@ -0,0 +1,181 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Server-side rendering of consumption charts.
+//!
+//! This is only available with the `chart` feature since it pulls in the plotting dependencies.
+
+use crate::Error;
+use plotters::{coord::Shift, prelude::*};
+use rocket::{
+	form,
+	form::{FromFormField, ValueField},
+	get,
+	http::ContentType,
+};
+use shared::{consumption::get_consumption, registry::registered_para};
+use std::io::Cursor;
+use types::{DispatchClassConsumption, ParaId, Timestamp, WeightConsumption};
+
+/// The default width of a chart in pixels.
+pub const DEFAULT_CHART_WIDTH: u32 = 800;
+/// The default height of a chart in pixels.
+pub const DEFAULT_CHART_HEIGHT: u32 = 400;
+/// The maximum width or height of a chart in pixels.
+pub const MAX_CHART_DIMENSION: u32 = 4096;
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Default)]
+pub enum ChartFormat {
+	#[default]
+	Svg,
+	Png,
+}
+
+#[rocket::async_trait]
+impl<'r> FromFormField<'r> for ChartFormat {
+	fn from_value(field: ValueField<'r>) -> form::Result<'r, Self> {
+		match field.value {
+			"svg" => Ok(ChartFormat::Svg),
+			"png" => Ok(ChartFormat::Png),
+			_ => Err(form::Error::validation("invalid ChartFormat").into()),
+		}
+	}
+}
+
+/// The weight dimension that is plotted, summed over all the dispatch classes.
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Default)]
+pub enum ChartMetric {
+	#[default]
+	RefTime,
+	ProofSize,
+}
+
+#[rocket::async_trait]
+impl<'r> FromFormField<'r> for ChartMetric {
+	fn from_value(field: ValueField<'r>) -> form::Result<'r, Self> {
+		match field.value {
+			"ref_time" => Ok(ChartMetric::RefTime),
+			"proof_size" => Ok(ChartMetric::ProofSize),
+			_ => Err(form::Error::validation("invalid ChartMetric").into()),
+		}
+	}
+}
+
+impl ChartMetric {
+	fn label(self) -> &'static str {
+		match self {
+			ChartMetric::RefTime => "ref_time",
+			ChartMetric::ProofSize => "proof_size",
+		}
+	}
+
+	fn value(self, consumption: &WeightConsumption) -> Option<f32> {
+		let dimension = match self {
+			ChartMetric::RefTime => consumption.ref_time.as_ref(),
+			ChartMetric::ProofSize => consumption.proof_size.as_ref(),
+		};
+
+		dimension.map(|DispatchClassConsumption { normal, operational, mandatory }| {
+			normal + operational + mandatory
+		})
+	}
+}
+
+/// Render the consumption of a parachain within the specified window as a line chart.
+///
+/// The chart plots the consumption of the selected `metric` per block, and is returned as an SVG
+/// by default or as a PNG. The `width` and `height` are in pixels, and are capped at
+/// `MAX_CHART_DIMENSION`.
+#[get("/consumption/<relay>/<para_id>/chart?<start>&<end>&<metric>&<format>&<width>&<height>")]
+#[allow(clippy::too_many_arguments)]
+pub fn consumption_chart(
+	relay: &str,
+	para_id: ParaId,
+	start: Option<Timestamp>,
+	end: Option<Timestamp>,
+	metric: Option<ChartMetric>,
+	format: Option<ChartFormat>,
+	width: Option<u32>,
+	height: Option<u32>,
+) -> Result<(ContentType, Vec<u8>), Error> {
+	let para = registered_para(relay.into(), para_id).ok_or(Error::NotRegistered)?;
+	let metric = metric.unwrap_or_default();
+	let (start, end) = (start.unwrap_or_default(), end.unwrap_or(Timestamp::MAX));
+
+	let points: Vec<(u32, f32)> = get_consumption(para.clone(), None)
+		.map_err(|_| Error::ConsumptionDataNotFound)?
+		.into_iter()
+		.filter(|consumption| consumption.timestamp >= start && consumption.timestamp <= end)
+		.filter_map(|consumption| Some((consumption.block_number, metric.value(&consumption)?)))
+		.collect();
+
+	let size = (
+		width.unwrap_or(DEFAULT_CHART_WIDTH).clamp(1, MAX_CHART_DIMENSION),
+		height.unwrap_or(DEFAULT_CHART_HEIGHT).clamp(1, MAX_CHART_DIMENSION),
+	);
+	let title = format!("{} - {}", para.name, metric.label());
+
+	match format.unwrap_or_default() {
+		ChartFormat::Svg => {
+			let mut svg = String::new();
+			draw(SVGBackend::with_string(&mut svg, size).into_drawing_area(), &title, &points)
+				.map_err(|_| Error::ChartRenderingFailed)?;
+
+			Ok((ContentType::SVG, svg.into_bytes()))
+		},
+		ChartFormat::Png => {
+			let mut buffer = vec![0; (size.0 * size.1 * 3) as usize];
+			draw(
+				BitMapBackend::with_buffer(&mut buffer, size).into_drawing_area(),
+				&title,
+				&points,
+			)
+			.map_err(|_| Error::ChartRenderingFailed)?;
+
+			let image = image::RgbImage::from_raw(size.0, size.1, buffer)
+				.ok_or(Error::ChartRenderingFailed)?;
+			let mut png = vec![];
+			image
+				.write_to(&mut Cursor::new(&mut png), image::ImageOutputFormat::Png)
+				.map_err(|_| Error::ChartRenderingFailed)?;
+
+			Ok((ContentType::PNG, png))
+		},
+	}
+}
+
+fn draw<DB: DrawingBackend>(
+	root: DrawingArea<DB, Shift>,
+	title: &str,
+	points: &[(u32, f32)],
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+	root.fill(&WHITE)?;
+
+	let first_block = points.first().map_or(0, |(block, _)| *block);
+	let last_block = points.last().map_or(0, |(block, _)| *block).max(first_block + 1);
+	let max_value = points.iter().map(|(_, value)| *value).fold(0.0, f32::max).max(f32::EPSILON);
+
+	let mut chart = ChartBuilder::on(&root)
+		.caption(title, ("sans-serif", 20))
+		.margin(10)
+		.x_label_area_size(30)
+		.y_label_area_size(50)
+		.build_cartesian_2d(first_block..last_block, 0f32..max_value)?;
+
+	chart.configure_mesh().x_desc("Block number").draw()?;
+	chart.draw_series(LineSeries::new(points.iter().copied(), &BLUE))?;
+
+	root.present()
+}
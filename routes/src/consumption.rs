@@ -13,16 +13,30 @@
 // You should have received a copy of the GNU General Public License
 // along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::Error;
-use chrono::NaiveDateTime;
+use crate::{signature::Signed, Error};
+use chrono::{TimeZone, Utc};
+use chrono_tz::Tz;
 use rocket::{
 	form,
 	form::{FromFormField, ValueField},
 	get,
+	http::{Accept, ContentType, Header},
+	response::{self, stream::TextStream, Responder},
+	tokio::{sync::mpsc, task::spawn_blocking},
+	Request, Response,
+};
+use shared::{
+	chaindata::{get_para_metadata, ParaMetadata},
+	config::config,
+	consumption::{consumption_iter, get_consumption, latest_consumption},
+	current_timestamp,
+	migration::previous_identities,
+	registry::registered_para,
+};
+use std::collections::{BTreeMap, BTreeSet};
+use types::{
+	DispatchClassConsumption, ParaId, Parachain, RelayChain, Timestamp, WeightConsumption,
 };
-use shared::{consumption::get_consumption, registry::registered_para};
-use std::collections::BTreeMap;
-use types::{DispatchClassConsumption, ParaId, Timestamp, WeightConsumption};
 
 #[derive(Clone, Debug, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 #[serde(crate = "rocket::serde")]
@@ -49,21 +63,244 @@ impl<'r> FromFormField<'r> for Grouping {
 	}
 }
 
+/// The function used for aggregating the consumption of the records within a group.
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Default)]
+pub enum AggregationFn {
+	#[default]
+	Sum,
+	Avg,
+	Max,
+	Min,
+	/// The 95th percentile, using the nearest-rank method.
+	P95,
+}
+
+impl TryFrom<&str> for AggregationFn {
+	type Error = Error;
+
+	fn try_from(value: &str) -> Result<Self, Self::Error> {
+		match value {
+			"sum" => Ok(AggregationFn::Sum),
+			"avg" => Ok(AggregationFn::Avg),
+			"max" => Ok(AggregationFn::Max),
+			"min" => Ok(AggregationFn::Min),
+			"p95" => Ok(AggregationFn::P95),
+			_ => Err(Error::UnsupportedAggregationFn),
+		}
+	}
+}
+
+impl AggregationFn {
+	fn apply(self, mut values: Vec<f32>) -> f32 {
+		match self {
+			AggregationFn::Sum => values.iter().sum(),
+			AggregationFn::Avg => values.iter().sum::<f32>() / values.len() as f32,
+			AggregationFn::Max => values.into_iter().fold(f32::MIN, f32::max),
+			AggregationFn::Min => values.into_iter().fold(f32::MAX, f32::min),
+			AggregationFn::P95 => {
+				values.sort_by(f32::total_cmp);
+				let rank = (values.len() as f32 * 0.95).ceil() as usize;
+				values[rank.saturating_sub(1)]
+			},
+		}
+	}
+}
+
+/// Which of the recorded weight dimensions are returned.
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum Weighting {
+	/// Only the ref_time, i.e. the time spent executing the blocks.
+	Execution,
+	/// Only the proof size, i.e. the size of the PoV which needs to be validated.
+	Validation,
+	/// Both dimensions along with the ratio of the ref_time to the proof size.
+	Ratio,
+}
+
+#[rocket::async_trait]
+impl<'r> FromFormField<'r> for Weighting {
+	fn from_value(field: ValueField<'r>) -> form::Result<'r, Self> {
+		match field.value {
+			"execution" => Ok(Weighting::Execution),
+			"validation" => Ok(Weighting::Validation),
+			"ratio" => Ok(Weighting::Ratio),
+			_ => Err(form::Error::validation("invalid Weighting").into()),
+		}
+	}
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+	/// A single JSON array containing all the records.
+	Json,
+	/// Newline delimited JSON, with one record per line.
+	Ndjson,
+	/// CSV with a header line.
+	Csv,
+}
+
+#[rocket::async_trait]
+impl<'r> FromFormField<'r> for ExportFormat {
+	fn from_value(field: ValueField<'r>) -> form::Result<'r, Self> {
+		match field.value {
+			"json" => Ok(ExportFormat::Json),
+			"ndjson" => Ok(ExportFormat::Ndjson),
+			"csv" => Ok(ExportFormat::Csv),
+			_ => Err(form::Error::validation("invalid ExportFormat").into()),
+		}
+	}
+}
+
+const CSV_HEADER: &str = "block_number,timestamp,normal_dispatch_ref_time,\
+	operational_dispatch_ref_time,mandatory_dispatch_ref_time,normal_proof_size,\
+	operational_proof_size,mandatory_proof_size,block_hash,inherent_ref_time,\
+	non_inherent_ref_time,inherent_proof_size,non_inherent_proof_size\n";
+
+const CONSUMPTION_CSV_HEADER: &str = "group,timestamp,count,normal_ref_time,operational_ref_time,\
+	mandatory_ref_time,normal_proof_size,operational_proof_size,mandatory_proof_size,normal_ratio,\
+	operational_ratio,mandatory_ratio,block_hash\n";
+
+/// The header containing the block number up to which the consumption data was returned.
+///
+/// This can be provided as `since_block` in the next query to only fetch new data.
+pub const CURSOR_HEADER: &str = "X-Cursor";
+
+/// The header containing the total number of records within the queried range, over all pages.
+pub const TOTAL_COUNT_HEADER: &str = "X-Total-Count";
+
+/// The number of records returned per page if no `page_size` is specified.
+pub const DEFAULT_PAGE_SIZE: u32 = 1000;
+
+/// The maximum number of parachains that can be queried in a single `/consumption/latest` request.
+pub const MAX_LATEST_PARAS: usize = 50;
+
+/// The number of records sent to the response of a consumption stream at once.
+pub const STREAM_CHUNK_RECORDS: usize = 256;
+
+/// The number of chunks of a consumption stream which are read ahead of the response. Reading
+/// waits for the response to catch up once these are buffered, which bounds the memory used by a
+/// stream regardless of the size of the consumption history.
+pub const STREAM_BUFFERED_CHUNKS: usize = 16;
+
+/// The consumption data along with any additional headers.
+pub struct ConsumptionResponse {
+	data: Signed,
+	/// Overrides the content type of the data if set.
+	content_type: Option<ContentType>,
+	headers: Vec<Header<'static>>,
+}
+
+impl<'r> Responder<'r, 'static> for ConsumptionResponse {
+	fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+		let mut response = Response::build_from(self.data.respond_to(request)?);
+		if let Some(content_type) = self.content_type {
+			response.header(content_type);
+		}
+		self.headers.into_iter().for_each(|header| {
+			response.header(header);
+		});
+
+		response.ok()
+	}
+}
+
 #[derive(Default, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 #[serde(crate = "rocket::serde")]
 pub struct AggregatedData {
 	pub group: String,
 	/// The aggregated ref_time consumption over all the dispatch classes.
-	pub ref_time: DispatchClassConsumption,
+	///
+	/// `None` if the ref_time dimension isn't recorded for the parachain.
+	pub ref_time: Option<DispatchClassConsumption>,
 	/// The aggregated proof size over all dispatch classes.
-	pub proof_size: DispatchClassConsumption,
+	///
+	/// `None` if the proof size dimension isn't recorded for the parachain.
+	pub proof_size: Option<DispatchClassConsumption>,
+	/// The ratio of the aggregated ref_time to the aggregated proof size. A ratio above 1 means
+	/// that the parachain is rather compute-bound, while a ratio below 1 means that it is rather
+	/// bandwidth-bound.
+	///
+	/// Only returned when querying with `weighting=ratio`, and only if both dimensions are
+	/// recorded for the parachain.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub ratio: Option<DispatchClassConsumption>,
 	pub count: usize,
 }
 
+/// The consumption data along with the descriptive metadata of the parachain.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct EnrichedConsumption<T = AggregatedData> {
+	/// `None` if the parachain is no longer part of the chaindata.
+	pub metadata: Option<ParaMetadata>,
+	pub data: Vec<T>,
+}
+
+/// The aggregated consumption along with the hash of the block it belongs to.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct HashedAggregatedData {
+	#[serde(flatten)]
+	pub data: AggregatedData,
+	/// `None` if the hash wasn't recorded, or if the data isn't grouped by block number.
+	pub block_hash: Option<String>,
+}
+
 /// Query the consumption data of a parachain.
 ///
 /// This will return an error in case there is no data associated with the specific parachain.
-#[get("/consumption/<relay>/<para_id>?<start>&<end>&<page>&<page_size>&<grouping>")]
+///
+/// When signing is enabled the response contains the signature of the returned data.
+///
+/// When `since_block` is provided only the consumption recorded after the specified block is
+/// returned. The response contains a cursor which can be used as `since_block` for the next query.
+///
+/// The consumption is stored relative to the per-block limits of the parachain. When `as_percent`
+/// is set the values are returned as percentages of the limits instead of fractions.
+///
+/// When `enrich` is set the data is returned along with the name, logo and category of the
+/// parachain.
+///
+/// `agg_fn` selects how the consumption within each group is aggregated, and is one of `sum`
+/// (default), `avg`, `max`, `min` or `p95`.
+///
+/// When `include_hash` is set the data grouped by block number contains the hash of each block,
+/// if it was recorded.
+///
+/// `tz` is the IANA name of the timezone, e.g. `Europe/Berlin`, to whose boundaries the groups
+/// are aligned. Defaults to UTC, which is also used if the timezone is invalid.
+///
+/// The records are ordered by their timestamp and returned in pages of `page_size` records, which
+/// defaults to `DEFAULT_PAGE_SIZE`. The response contains the total number of records within the
+/// queried range, which can be used for building pagers. When grouping by a time period the
+/// groups are paged instead of the records, so that each group aggregates all of its records.
+///
+/// `start` and `end` are timestamps in milliseconds. Reversed ranges, and ranges starting in the
+/// future, are rejected with an `InvalidRange` error.
+///
+/// Queries covering a wider time range than the configured `max_query_range` are rejected, in
+/// which case the data should be queried over multiple narrower ranges or exported through the
+/// stream instead.
+///
+/// Grouping by a time period returns an `InsufficientData` error if there are fewer records within
+/// the queried window than the configured `min_aggregation_records`.
+///
+/// When `follow_migration` is set the consumption recorded under the previous identities of a
+/// parachain that migrated between relay chains is returned as well.
+///
+/// `weighting` selects whether only the `execution` time (ref_time), only the `validation` cost
+/// (proof size), or both along with their `ratio` are returned. Both dimensions are returned
+/// without a ratio by default.
+///
+/// `format` selects whether the data is returned as `json` (default), `ndjson` or `csv`. CSV is
+/// also returned when it is the preferred media type of the `Accept` header and no `format` is
+/// provided. Each CSV row contains the group, the timestamp of the block when grouping by block
+/// number, the number of aggregated records and a column per dispatch class of each dimension.
+/// The metadata requested through `enrich` is only returned in the JSON format.
+#[get(
+	"/consumption/<relay>/<para_id>?<start>&<end>&<page>&<page_size>&<grouping>&<since_block>&<as_percent>&<enrich>&<agg_fn>&<include_hash>&<follow_migration>&<tz>&<weighting>&<format>"
+)]
+#[allow(clippy::too_many_arguments)]
 pub fn consumption(
 	relay: &str,
 	para_id: ParaId,
@@ -72,69 +309,505 @@ pub fn consumption(
 	page: Option<u32>,
 	page_size: Option<u32>,
 	grouping: Option<Grouping>,
-) -> Result<String, Error> {
+	since_block: Option<u32>,
+	as_percent: Option<bool>,
+	enrich: Option<bool>,
+	agg_fn: Option<&str>,
+	include_hash: Option<bool>,
+	follow_migration: Option<bool>,
+	tz: Option<&str>,
+	weighting: Option<Weighting>,
+	format: Option<ExportFormat>,
+	accept: Option<&Accept>,
+) -> Result<ConsumptionResponse, Error> {
 	let para = registered_para(relay.into(), para_id).ok_or(Error::NotRegistered)?;
+	let format = format
+		.or_else(|| accept.filter(|accept| accept.preferred().is_csv()).map(|_| ExportFormat::Csv))
+		.unwrap_or(ExportFormat::Json);
+	let agg_fn = agg_fn.map(AggregationFn::try_from).transpose()?.unwrap_or_default();
 
-	let (page, page_size) = (page.unwrap_or_default(), page_size.unwrap_or(u32::MAX));
+	let (page, page_size) = (page.unwrap_or_default(), page_size.unwrap_or(DEFAULT_PAGE_SIZE));
 	let (start, end) = (start.unwrap_or_default(), end.unwrap_or(Timestamp::MAX));
+	// The consumption timestamps are in milliseconds.
+	let now = current_timestamp().saturating_mul(1000);
+	ensure_valid_range(start, end, now)?;
+	ensure_within_query_range(start, end, config().max_query_range, now)?;
 
-	// By default query the consumption that was collected from rpc index 0.
-	let weight_consumptions: Vec<WeightConsumption> = get_consumption(para, None)
-		.map_err(|_| Error::ConsumptionDataNotFound)?
+	let mut weight_consumptions: Vec<WeightConsumption> =
+		consumption_history(para, follow_migration.unwrap_or_default())?
+			.into_iter()
+			.filter(|consumption| consumption.timestamp >= start && consumption.timestamp <= end)
+			.filter(|consumption| {
+				since_block.map_or(true, |since| consumption.block_number > since)
+			})
+			.collect();
+	// The ordering needs to be deterministic for the pagination to be stable.
+	weight_consumptions
+		.sort_by_key(|consumption| (consumption.timestamp, consumption.block_number));
+
+	let grouping = grouping.unwrap_or(Grouping::BlockNumber);
+	let tz = tz.and_then(|tz| tz.parse::<Tz>().ok()).unwrap_or(Tz::UTC);
+
+	// The threshold covers all the records within the queried window, rather than the ones of
+	// the requested page.
+	if grouping != Grouping::BlockNumber &&
+		weight_consumptions.len() < config().min_aggregation_records
+	{
+		return Err(Error::InsufficientData);
+	}
+
+	let (total, weight_consumptions) = if grouping == Grouping::BlockNumber {
+		let total = weight_consumptions.len();
+		let page: Vec<WeightConsumption> = weight_consumptions
+			.into_iter()
+			.skip(page.saturating_mul(page_size) as usize)
+			.take(page_size as usize)
+			.collect();
+		(total, page)
+	} else {
+		paginate_groups(weight_consumptions, grouping, tz, page, page_size)
+	};
+	let mut headers = vec![Header::new(TOTAL_COUNT_HEADER, total.to_string())];
+
+	if let Some(cursor) = weight_consumptions.last().map(|c| c.block_number).or(since_block) {
+		headers.push(Header::new(CURSOR_HEADER, cursor.to_string()));
+	}
+
+	let weight_consumptions = if as_percent.unwrap_or_default() {
+		weight_consumptions.into_iter().map(to_percent).collect()
+	} else {
+		weight_consumptions
+	};
+
+	let block_hashes: BTreeMap<String, Option<String>> = weight_consumptions
+		.iter()
+		.map(|c| (c.block_number.to_string(), c.block_hash.clone()))
+		.collect();
+	let timestamps: BTreeMap<String, Timestamp> = weight_consumptions
+		.iter()
+		.map(|c| (c.block_number.to_string(), c.timestamp))
+		.collect();
+
+	let grouped = aggregate_consumption_in(weight_consumptions, grouping, agg_fn, tz);
+	let grouped = match weighting {
+		Some(weighting) => apply_weighting(grouped, weighting),
+		None => grouped,
+	};
+
+	if format == ExportFormat::Csv {
+		let include_hash = include_hash.unwrap_or_default() && grouping == Grouping::BlockNumber;
+		let rows: String = grouped
+			.iter()
+			.map(|data| {
+				let (timestamp, block_hash) = if grouping == Grouping::BlockNumber {
+					(timestamps.get(&data.group).copied(), block_hashes.get(&data.group).cloned())
+				} else {
+					(None, None)
+				};
+				aggregated_csv_row(data, timestamp, block_hash.flatten().filter(|_| include_hash))
+			})
+			.collect();
+
+		let data = Signed(format!("{}{}", CONSUMPTION_CSV_HEADER, rows));
+		return Ok(ConsumptionResponse { data, content_type: Some(ContentType::CSV), headers })
+	}
+
+	let metadata = (enrich.unwrap_or_default() && format == ExportFormat::Json)
+		.then(|| get_para_metadata(relay.into(), para_id).ok());
+
+	let data = if include_hash.unwrap_or_default() {
+		let hashed: Vec<HashedAggregatedData> = grouped
+			.into_iter()
+			.map(|data| HashedAggregatedData {
+				block_hash: (grouping == Grouping::BlockNumber)
+					.then(|| block_hashes.get(&data.group).cloned().flatten())
+					.flatten(),
+				data,
+			})
+			.collect();
+		serialize_consumption(metadata, hashed, format)
+	} else {
+		serialize_consumption(metadata, grouped, format)
+	}
+	.map(Signed)
+	.map_err(|_| Error::InvalidData)?;
+
+	let content_type =
+		(format == ExportFormat::Ndjson).then(|| ContentType::new("application", "x-ndjson"));
+	Ok(ConsumptionResponse { data, content_type, headers })
+}
+
+/// Returns the total number of groups the records fall into, along with the records of the groups
+/// within the requested page.
+fn paginate_groups(
+	weight_consumptions: Vec<WeightConsumption>,
+	grouping: Grouping,
+	tz: Tz,
+	page: u32,
+	page_size: u32,
+) -> (usize, Vec<WeightConsumption>) {
+	let keyed: Vec<((u32, String), WeightConsumption)> = weight_consumptions
+		.into_iter()
+		.map(|datum| (get_aggregation_key(datum.clone(), grouping, tz), datum))
+		.collect();
+
+	// The groups are ordered the same way as in the aggregated data.
+	let groups: BTreeSet<&(u32, String)> = keyed.iter().map(|(key, _)| key).collect();
+	let total = groups.len();
+	let paged: BTreeSet<(u32, String)> = groups
 		.into_iter()
-		.filter(|consumption| consumption.timestamp >= start && consumption.timestamp <= end)
 		.skip(page.saturating_mul(page_size) as usize)
 		.take(page_size as usize)
+		.cloned()
 		.collect();
 
-	let grouping = grouping.unwrap_or(Grouping::BlockNumber);
+	let weight_consumptions = keyed
+		.into_iter()
+		.filter(|(key, _)| paged.contains(key))
+		.map(|(_, datum)| datum)
+		.collect();
+	(total, weight_consumptions)
+}
+
+/// Ensures that the queried time range isn't reversed and doesn't start in the future, since there
+/// can't be any consumption recorded within such a range.
+pub fn ensure_valid_range(start: Timestamp, end: Timestamp, now: Timestamp) -> Result<(), Error> {
+	if start > end || start > now {
+		return Err(Error::InvalidRange);
+	}
+
+	Ok(())
+}
+
+/// Ensures that the queried time range doesn't exceed the maximum range.
+///
+/// The range is capped at `now`, since there is no consumption recorded after it.
+pub fn ensure_within_query_range(
+	start: Timestamp,
+	end: Timestamp,
+	max_range: Option<Timestamp>,
+	now: Timestamp,
+) -> Result<(), Error> {
+	let Some(max_range) = max_range else { return Ok(()) };
+
+	if end.min(now).saturating_sub(start) > max_range {
+		return Err(Error::QueryRangeTooWide(max_range));
+	}
+
+	Ok(())
+}
+
+/// Returns the consumption of a parachain, optionally preceded by the consumption recorded under
+/// its previous identities.
+fn consumption_history(
+	para: Parachain,
+	follow_migration: bool,
+) -> Result<Vec<WeightConsumption>, Error> {
+	let mut history = vec![];
+	if follow_migration {
+		for (relay_chain, para_id) in previous_identities(para.relay_chain.clone(), para.para_id) {
+			let previous = Parachain { relay_chain, para_id, ..para.clone() };
+			// There might be no consumption recorded under some of the previous identities.
+			history.extend(get_consumption(previous, None).unwrap_or_default());
+		}
+	}
+
+	// By default query the consumption that was collected from rpc index 0.
+	history.extend(get_consumption(para, None).map_err(|_| Error::ConsumptionDataNotFound)?);
+
+	Ok(history)
+}
+
+fn serialize_consumption<T: serde::Serialize>(
+	metadata: Option<Option<ParaMetadata>>,
+	data: Vec<T>,
+	format: ExportFormat,
+) -> serde_json::Result<String> {
+	match (format, metadata) {
+		(ExportFormat::Ndjson, _) => data
+			.iter()
+			.map(|row| serde_json::to_string(row).map(|row| row + "\n"))
+			.collect(),
+		(_, Some(metadata)) => serde_json::to_string(&EnrichedConsumption { metadata, data }),
+		(_, None) => serde_json::to_string(&data),
+	}
+}
+
+fn aggregated_csv_row(
+	data: &AggregatedData,
+	timestamp: Option<Timestamp>,
+	block_hash: Option<String>,
+) -> String {
+	let mut columns = vec![
+		data.group.clone(),
+		timestamp.map(|t| t.to_string()).unwrap_or_default(),
+		data.count.to_string(),
+	];
+	columns.extend(csv_columns(&data.ref_time));
+	columns.extend(csv_columns(&data.proof_size));
+	columns.extend(csv_columns(&data.ratio));
+	columns.push(block_hash.unwrap_or_default());
+
+	format!("{}\n", columns.join(","))
+}
+
+/// Query the latest consumption of multiple parachains.
+///
+/// The parachains are provided as a comma separated list of `<relay>:<para_id>` identifiers. The
+/// response maps each identifier to the latest consumption of the parachain, or `null` if there is
+/// no consumption recorded for it.
+#[get("/consumption/latest?<paras>")]
+pub fn consumption_latest(paras: &str) -> Result<Signed, Error> {
+	let identifiers: Vec<&str> = paras.split(',').map(str::trim).collect();
+	if identifiers.len() > MAX_LATEST_PARAS {
+		return Err(Error::TooManyParas);
+	}
+
+	let latest = identifiers
+		.into_iter()
+		.map(|identifier| {
+			let (relay, para_id) =
+				parse_para_identifier(identifier).ok_or(Error::InvalidParaIdentifier)?;
+			let consumption = registered_para(relay, para_id)
+				.and_then(|para| latest_consumption(&para))
+				.map(|latest| latest.consumption);
+
+			Ok((identifier.to_string(), consumption))
+		})
+		.collect::<Result<BTreeMap<String, Option<WeightConsumption>>, Error>>()?;
+
+	serde_json::to_string(&latest).map(Signed).map_err(|_| Error::InvalidData)
+}
+
+/// Parses a `<relay>:<para_id>` parachain identifier.
+pub(crate) fn parse_para_identifier(identifier: &str) -> Option<(RelayChain, ParaId)> {
+	let (relay, para_id) = identifier.split_once(':')?;
+	Some((parse_relay_chain(relay)?, para_id.parse().ok()?))
+}
+
+/// Parses a relay chain name, ignoring its case.
+pub(crate) fn parse_relay_chain(relay: &str) -> Option<RelayChain> {
+	match relay.to_lowercase().as_str() {
+		"polkadot" => Some(RelayChain::Polkadot),
+		"kusama" => Some(RelayChain::Kusama),
+		"paseo" => Some(RelayChain::Paseo),
+		_ => None,
+	}
+}
+
+/// Stream all the consumption data of a parachain.
+///
+/// The records are written to the response as they are read from the storage, so exporting the
+/// full consumption history doesn't require buffering it in memory. Reading the storage is
+/// blocking, so the records are read on a blocking task, at most `STREAM_BUFFERED_CHUNKS` chunks
+/// ahead of the response.
+#[get("/consumption/<relay>/<para_id>/stream?<format>")]
+pub fn consumption_stream(
+	relay: &str,
+	para_id: ParaId,
+	format: Option<ExportFormat>,
+) -> Result<(ContentType, TextStream![String]), Error> {
+	let para = registered_para(relay.into(), para_id).ok_or(Error::NotRegistered)?;
+	let records = consumption_iter(para, None).map_err(|_| Error::ConsumptionDataNotFound)?;
+
+	let format = format.unwrap_or(ExportFormat::Json);
+	let (content_type, prefix, suffix) = match format {
+		ExportFormat::Json => (ContentType::JSON, "[", "]"),
+		ExportFormat::Ndjson => (ContentType::new("application", "x-ndjson"), "", ""),
+		ExportFormat::Csv => (ContentType::CSV, CSV_HEADER, ""),
+	};
+
+	let (sender, mut receiver) = mpsc::channel(STREAM_BUFFERED_CHUNKS);
+	spawn_blocking(move || {
+		let mut chunk = String::new();
+		for (index, record) in records.enumerate() {
+			chunk.push_str(&export_record(&record, format, index));
+			if (index + 1) % STREAM_CHUNK_RECORDS == 0 {
+				// Sending fails once the client disconnected, in which case reading stops.
+				if sender.blocking_send(std::mem::take(&mut chunk)).is_err() {
+					return;
+				}
+			}
+		}
+		if !chunk.is_empty() {
+			let _ = sender.blocking_send(chunk);
+		}
+	});
 
-	let grouped = group_consumption(weight_consumptions, grouping);
+	let stream = TextStream! {
+		yield prefix.to_string();
+		while let Some(chunk) = receiver.recv().await {
+			yield chunk;
+		}
+		yield suffix.to_string();
+	};
 
-	serde_json::to_string(&grouped).map_err(|_| Error::InvalidData)
+	Ok((content_type, stream))
 }
 
+fn to_percent(consumption: WeightConsumption) -> WeightConsumption {
+	WeightConsumption {
+		ref_time: consumption.ref_time.as_ref().map(DispatchClassConsumption::as_percent),
+		proof_size: consumption.proof_size.as_ref().map(DispatchClassConsumption::as_percent),
+		..consumption
+	}
+}
+
+fn export_record(record: &WeightConsumption, format: ExportFormat, index: usize) -> String {
+	match format {
+		ExportFormat::Json => {
+			let separator = if index == 0 { "" } else { "," };
+			format!("{}{}", separator, serde_json::to_string(record).unwrap_or_default())
+		},
+		ExportFormat::Ndjson => format!("{}\n", serde_json::to_string(record).unwrap_or_default()),
+		ExportFormat::Csv => {
+			let mut columns = vec![record.block_number.to_string(), record.timestamp.to_string()];
+			columns.extend(csv_columns(&record.ref_time));
+			columns.extend(csv_columns(&record.proof_size));
+			columns.push(record.block_hash.clone().unwrap_or_default());
+			columns.extend(match &record.inherent_split {
+				Some(split) => vec![
+					split.inherent_ref_time.to_string(),
+					split.non_inherent_ref_time.to_string(),
+					split.inherent_proof_size.to_string(),
+					split.non_inherent_proof_size.to_string(),
+				],
+				None => vec![String::new(); 4],
+			});
+
+			format!("{}\n", columns.join(","))
+		},
+	}
+}
+
+fn csv_columns(consumption: &Option<DispatchClassConsumption>) -> Vec<String> {
+	// Unlike in the storage, the exported CSV always has a column per dispatch class.
+	match consumption {
+		Some(consumption) => vec![
+			consumption.normal.to_string(),
+			consumption.operational.to_string(),
+			consumption.mandatory.to_string(),
+		],
+		None => vec![String::new(); 3],
+	}
+}
+
+/// Groups the consumption and sums it up within each group.
 pub fn group_consumption(
 	weight_consumptions: Vec<WeightConsumption>,
 	grouping: Grouping,
 ) -> Vec<AggregatedData> {
-	let grouped = weight_consumptions.iter().fold(BTreeMap::new(), |mut acc, datum| {
-		let key = get_aggregation_key(datum.clone(), grouping);
-		let entry: &mut AggregatedData = acc.entry(key).or_default();
-
-		entry.ref_time.normal += datum.ref_time.normal;
-		entry.ref_time.operational += datum.ref_time.operational;
-		entry.ref_time.mandatory += datum.ref_time.mandatory;
-
-		entry.proof_size.normal += datum.proof_size.normal;
-		entry.proof_size.operational += datum.proof_size.operational;
-		entry.proof_size.mandatory += datum.proof_size.mandatory;
+	aggregate_consumption(weight_consumptions, grouping, AggregationFn::Sum)
+}
 
-		entry.count += 1;
+/// Groups the consumption and aggregates it within each group using `agg_fn`.
+pub fn aggregate_consumption(
+	weight_consumptions: Vec<WeightConsumption>,
+	grouping: Grouping,
+	agg_fn: AggregationFn,
+) -> Vec<AggregatedData> {
+	aggregate_consumption_in(weight_consumptions, grouping, agg_fn, Tz::UTC)
+}
 
-		acc
-	});
+/// Same as `aggregate_consumption`, except that the groups are aligned to the boundaries of the
+/// specified timezone.
+pub fn aggregate_consumption_in(
+	weight_consumptions: Vec<WeightConsumption>,
+	grouping: Grouping,
+	agg_fn: AggregationFn,
+	tz: Tz,
+) -> Vec<AggregatedData> {
+	let grouped = weight_consumptions.into_iter().fold(
+		BTreeMap::<(u32, String), Vec<WeightConsumption>>::new(),
+		|mut acc, datum| {
+			let key = get_aggregation_key(datum.clone(), grouping, tz);
+			acc.entry(key).or_default().push(datum);
+			acc
+		},
+	);
 
 	grouped
 		.into_iter()
-		.map(|(key, entry)| {
-			let mut entry = entry;
-			entry.group = key;
-			entry
+		.map(|((_, group), records)| AggregatedData {
+			ref_time: aggregate(records.iter().filter_map(|r| r.ref_time.as_ref()), agg_fn),
+			proof_size: aggregate(records.iter().filter_map(|r| r.proof_size.as_ref()), agg_fn),
+			ratio: None,
+			count: records.len(),
+			group,
+		})
+		.collect()
+}
+
+/// Leaves only the weight dimensions selected by the `weighting` in the aggregated data.
+pub fn apply_weighting(data: Vec<AggregatedData>, weighting: Weighting) -> Vec<AggregatedData> {
+	data.into_iter()
+		.map(|data| match weighting {
+			Weighting::Execution => AggregatedData { proof_size: None, ..data },
+			Weighting::Validation => AggregatedData { ref_time: None, ..data },
+			Weighting::Ratio => AggregatedData {
+				ratio: data.ref_time.clone().zip(data.proof_size.clone()).map(
+					|(ref_time, proof_size)| DispatchClassConsumption {
+						normal: ratio(ref_time.normal, proof_size.normal),
+						operational: ratio(ref_time.operational, proof_size.operational),
+						mandatory: ratio(ref_time.mandatory, proof_size.mandatory),
+					},
+				),
+				..data
+			},
 		})
 		.collect()
 }
 
-fn get_aggregation_key(datum: WeightConsumption, grouping: Grouping) -> String {
-	let datetime =
-		NaiveDateTime::from_timestamp_opt((datum.timestamp / 1000) as i64, 0).unwrap_or_default();
+/// Returns the ratio of the ref_time to the proof size, which is zero when nothing was consumed.
+fn ratio(ref_time: f32, proof_size: f32) -> f32 {
+	// Infinite ratios can't be represented in JSON.
+	if proof_size == 0.0 {
+		return 0.0;
+	}
+
+	ref_time / proof_size
+}
+
+fn aggregate<'a>(
+	consumptions: impl Iterator<Item = &'a DispatchClassConsumption>,
+	agg_fn: AggregationFn,
+) -> Option<DispatchClassConsumption> {
+	let consumptions: Vec<&DispatchClassConsumption> = consumptions.collect();
 
-	match grouping {
-		Grouping::BlockNumber => datum.block_number.to_string(),
-		Grouping::Minute => datetime.format("%Y-%m-%dT%H:%M").to_string(),
-		Grouping::Hour => datetime.format("%Y-%m-%dT%H:00").to_string(),
-		Grouping::Day => datetime.format("%Y-%m-%d").to_string(),
-		Grouping::Month => datetime.format("%Y-%m").to_string(),
-		Grouping::Year => datetime.format("%Y").to_string(),
+	// Dimensions that aren't recorded are left out of the aggregation.
+	if consumptions.is_empty() {
+		return None;
 	}
+
+	let values = |class: fn(&DispatchClassConsumption) -> f32| {
+		agg_fn.apply(consumptions.iter().map(|c| class(c)).collect())
+	};
+
+	Some(DispatchClassConsumption {
+		normal: values(|c| c.normal),
+		operational: values(|c| c.operational),
+		mandatory: values(|c| c.mandatory),
+	})
+}
+
+/// Returns the key of the group the record falls into, along with the block number for the
+/// grouping by block number, so that the blocks are ordered by their numbers rather than as
+/// strings.
+fn get_aggregation_key(datum: WeightConsumption, grouping: Grouping, tz: Tz) -> (u32, String) {
+	let datetime = Utc
+		.timestamp_opt((datum.timestamp / 1000) as i64, 0)
+		.single()
+		.unwrap_or_default()
+		.with_timezone(&tz);
+
+	let group = match grouping {
+		Grouping::BlockNumber => return (datum.block_number, datum.block_number.to_string()),
+		Grouping::Minute => datetime.format("%Y-%m-%dT%H:%M"),
+		Grouping::Hour => datetime.format("%Y-%m-%dT%H:00"),
+		Grouping::Day => datetime.format("%Y-%m-%d"),
+		Grouping::Month => datetime.format("%Y-%m"),
+		Grouping::Year => datetime.format("%Y"),
+	};
+
+	(0, group.to_string())
 }
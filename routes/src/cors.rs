@@ -0,0 +1,63 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Cross-origin resource sharing, so that frontends hosted on other origins can call the web API.
+//!
+//! The allowed origins, methods and headers are read from `cors` in the config file. Without any
+//! allowed origin no CORS headers are sent, so browsers only allow same-origin requests.
+
+use rocket::http::Method;
+use rocket_cors::{AllowedHeaders, AllowedOrigins, Cors, CorsOptions};
+use shared::config::{config, CorsConfig};
+use std::str::FromStr;
+
+/// Returns the CORS fairing configured in the config file, or `None` if no cross-origin requests
+/// are allowed.
+pub fn cors() -> Option<Cors> {
+	let config = config().cors;
+	if config.allowed_origins.is_empty() {
+		return None;
+	}
+
+	// The config is validated on startup.
+	Some(cors_options(&config).to_cors().expect("Invalid CORS configuration"))
+}
+
+/// Returns the CORS options corresponding to the configuration.
+pub fn cors_options(config: &CorsConfig) -> CorsOptions {
+	let allowed_origins = if config.allowed_origins.iter().any(|origin| origin == "*") {
+		AllowedOrigins::all()
+	} else {
+		AllowedOrigins::some_exact(&config.allowed_origins)
+	};
+
+	let allowed_headers = if config.allowed_headers.is_empty() {
+		AllowedHeaders::all()
+	} else {
+		AllowedHeaders::some(&config.allowed_headers.iter().map(String::as_str).collect::<Vec<_>>())
+	};
+
+	CorsOptions {
+		allowed_origins,
+		allowed_methods: config
+			.allowed_methods
+			.iter()
+			.filter_map(|method| Method::from_str(&method.to_uppercase()).ok())
+			.map(From::from)
+			.collect(),
+		allowed_headers,
+		..Default::default()
+	}
+}
@@ -0,0 +1,62 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::*;
+use rocket::{get, Orbit, Rocket};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct RouteInfo {
+	/// The HTTP method of the route.
+	pub method: String,
+	/// The URI of the route, as defined in the route attribute.
+	pub uri: String,
+	/// The dynamic parameters in the path of the route.
+	pub path_params: Vec<String>,
+	/// The query parameters accepted by the route.
+	pub query_params: Vec<String>,
+}
+
+/// Query all the routes mounted on the server.
+///
+/// The list is derived from the mounted routes themselves, so it always reflects the actual API.
+#[get("/routes")]
+pub fn list_routes(rocket: &Rocket<Orbit>) -> Result<String, Error> {
+	let mut routes: Vec<RouteInfo> = rocket
+		.routes()
+		.map(|route| {
+			let uri = route.uri.to_string();
+			let (path, query) = uri.split_once('?').unwrap_or((&uri, ""));
+
+			RouteInfo {
+				method: route.method.to_string(),
+				path_params: dynamic_params(path),
+				query_params: dynamic_params(query),
+				uri,
+			}
+		})
+		.collect();
+	routes.sort_by(|a, b| (&a.uri, &a.method).cmp(&(&b.uri, &b.method)));
+
+	serde_json::to_string(&routes).map_err(|_| Error::InvalidData)
+}
+
+fn dynamic_params(uri_part: &str) -> Vec<String> {
+	uri_part
+		.split(['/', '&'])
+		.filter_map(|segment| segment.strip_prefix('<')?.strip_suffix('>'))
+		.map(|param| param.trim_end_matches("..").to_string())
+		.collect()
+}
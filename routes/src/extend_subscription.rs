@@ -13,16 +13,21 @@
 // You should have received a copy of the GNU General Public License
 // along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::*;
+use crate::{
+	idempotency::{body_hash, Idempotency},
+	register::{register, validate_subscription_payment},
+	*,
+};
 use polkadot_core_primitives::BlockNumber;
 use rocket::{post, serde::json::Json};
 use shared::{
-	config::config,
+	config::{config, PaymentInfo},
 	current_timestamp,
-	payment::validate_registration_payment,
+	payment::para_payment_info,
 	registry::{registered_para, registered_paras, update_registry},
+	subscription::{extension, renewal},
 };
-use types::{ParaId, RelayChain};
+use types::{Balance, ParaId, Parachain, RelayChain, Timestamp};
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
@@ -30,12 +35,33 @@ pub struct ExtendSubscriptionData {
 	/// The parachain which is getting its subscription extended.
 	pub para: (RelayChain, ParaId),
 	/// The block in which the payment occurred for the specific parachain.
-	pub payment_block_number: BlockNumber,
+	///
+	/// If `None`, the renewal is paid from the credit accumulated through micro-payments.
+	pub payment_block_number: Option<BlockNumber>,
+	/// The number of blocks following `payment_block_number` in which the payment is also
+	/// searched for, in case the exact block isn't known.
+	///
+	/// This is capped at `MAX_PAYMENT_SEARCH_WINDOW`.
+	#[serde(default)]
+	pub payment_search_window: BlockNumber,
+	/// The number of seconds by which the subscription is extended, at a prorated cost.
+	///
+	/// Unlike full renewals, these extensions can be made at any time. If `None`, the
+	/// subscription is renewed for a full subscription period.
+	#[serde(default)]
+	pub duration: Option<Timestamp>,
 }
 
 /// Extend the subscription of a parachain for resource utilization tracking.
 #[post("/extend-subscription", data = "<data>")]
-pub async fn extend_subscription(data: Json<ExtendSubscriptionData>) -> Result<(), Error> {
+pub async fn extend_subscription(
+	data: Json<ExtendSubscriptionData>,
+	idempotency: Idempotency<'_>,
+) -> Result<(), Error> {
+	idempotency.run(body_hash(&*data), extend(data)).await
+}
+
+async fn extend(data: Json<ExtendSubscriptionData>) -> Result<(), Error> {
 	let (relay_chain, para_id) = data.para.clone();
 
 	log::info!(
@@ -44,31 +70,71 @@ pub async fn extend_subscription(data: Json<ExtendSubscriptionData>) -> Result<(
 		relay_chain, para_id
 	);
 
-	let para = registered_para(relay_chain.clone(), para_id).ok_or(Error::NotRegistered)?;
+	let Some(para) = registered_para(relay_chain.clone(), para_id) else {
+		if config().register_on_extend {
+			// The payment is validated as a registration payment.
+			return register(
+				relay_chain,
+				para_id,
+				data.payment_block_number,
+				data.payment_search_window,
+				None,
+				None,
+				None,
+				false,
+			)
+			.await
+			.map(|_| ());
+		}
+
+		return Err(Error::NotRegistered);
+	};
+
+	let (expiry_timestamp, drawn_credit) = if let Some(payment_info) = config().payment_info {
+		let payment_info = para_payment_info(&para, &payment_info).map_err(Error::from)?;
+		let now = current_timestamp();
+		let renewal = match data.duration {
+			Some(0) => return Err(Error::InvalidDuration),
+			Some(duration) => extension(para.expiry_timestamp, &payment_info, now, duration),
+			None => {
+				if para.expiry_timestamp.saturating_sub(payment_info.renewal_period) > now {
+					// Cannot renew yet.
+					return Err(Error::AlreadyRegistered);
+				}
 
-	let subscription_duration = if let Some(payment_info) = config().payment_info {
-		if para.expiry_timestamp.saturating_sub(payment_info.renewal_period) > current_timestamp() {
-			// Cannot renew yet.
-			return Err(Error::AlreadyRegistered);
+				renewal(para.expiry_timestamp, &payment_info, now)
+			},
 		}
+		.map_err(Error::from)?;
 
-		validate_registration_payment(
-			para.clone(),
-			payment_info.clone(),
-			data.payment_block_number,
-		)
-		.await
-		.map_err(Error::PaymentValidationError)?;
+		let drawn_credit = if let Some(payment_block_number) = data.payment_block_number {
+			// The cost of renewing a lapsed subscription can differ from the base cost.
+			let payment_info = PaymentInfo { cost: renewal.cost.to_string(), ..payment_info };
 
-		payment_info.subscription_duration
+			validate_subscription_payment(
+				para.clone(),
+				payment_info,
+				payment_block_number,
+				data.payment_search_window,
+				false,
+			)
+			.await?;
+
+			0
+		} else {
+			draw_credit(&para, &payment_info, renewal.cost)?
+		};
+
+		(renewal.expiry_timestamp, drawn_credit)
 	} else {
-		Default::default()
+		(para.expiry_timestamp, 0)
 	};
 
 	let mut paras = registered_paras();
 
 	if let Some(para) = paras.iter_mut().find(|p| **p == para) {
-		para.expiry_timestamp += subscription_duration;
+		para.expiry_timestamp = expiry_timestamp;
+		para.credit -= drawn_credit;
 	} else {
 		return Err(Error::NotRegistered);
 	}
@@ -88,3 +154,23 @@ pub async fn extend_subscription(data: Json<ExtendSubscriptionData>) -> Result<(
 
 	Ok(())
 }
+
+/// Returns the credit drawn from the parachain for a renewal costing `cost` which is made without
+/// a payment.
+///
+/// The renewal requires a payment if the credit can't be accumulated, i.e. if micro-payments
+/// aren't enabled.
+pub fn draw_credit(
+	para: &Parachain,
+	payment_info: &PaymentInfo,
+	cost: Balance,
+) -> Result<Balance, Error> {
+	if !payment_info.micro_payments {
+		return Err(Error::PaymentRequired);
+	}
+	if para.credit < cost {
+		return Err(Error::InsufficientCredit);
+	}
+
+	Ok(cost)
+}
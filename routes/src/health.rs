@@ -0,0 +1,130 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::*;
+use rocket::get;
+use shared::{
+	chaindata::ensure_rpc_reachable,
+	config::config,
+	consumption::tracking_activity,
+	payment::{metadata_mismatch_suspected, rpc_latency},
+	registry::{ensure_registry_readable, registered_paras},
+	tracker::{tracker_state, TrackerState},
+};
+use types::{ParaId, RelayChain};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Health {
+	/// Set while the tracker is within its warmup period, during which no consumption is
+	/// recorded.
+	pub warming_up: bool,
+	/// Set when no blocks are being recorded for any of the registered parachains.
+	pub tracker_stalled: bool,
+	/// The parachains for which no blocks were recorded recently, while other parachains are
+	/// still being tracked.
+	pub dormant: Vec<(RelayChain, ParaId)>,
+	/// Set when payments consistently aren't found even though transfers to the receiver exist,
+	/// which suggests that the metadata is outdated after a runtime upgrade.
+	pub metadata_mismatch_suspected: bool,
+	/// The latest measured latency of the payment RPC in milliseconds.
+	pub rpc_latency: Option<u64>,
+	/// The parachains for which the tracking is paused.
+	pub paused: Vec<(RelayChain, ParaId)>,
+	/// The registered parachains which were found to no longer be onboarded on their relay chain.
+	pub offboarded: Vec<(RelayChain, ParaId)>,
+}
+
+/// Query the health of the consumption tracking.
+#[get("/health")]
+pub fn health() -> Result<String, Error> {
+	let paras = registered_paras();
+	let activity = tracking_activity(&paras);
+
+	let tracker_states: Vec<TrackerState> =
+		(0..config().outputs).filter_map(tracker_state).collect();
+	let warming_up = tracker_states.iter().any(|state| state.warming_up());
+
+	let mut offboarded: Vec<(RelayChain, ParaId)> = vec![];
+	for para in tracker_states.into_iter().flat_map(|state| state.offboarded) {
+		if !offboarded.contains(&para) {
+			offboarded.push(para);
+		}
+	}
+
+	let health = Health {
+		warming_up,
+		// Nothing is recorded during the warmup, which doesn't mean that the tracker stalled.
+		tracker_stalled: activity.stalled && !warming_up,
+		dormant: activity.dormant.into_iter().map(|p| (p.relay_chain, p.para_id)).collect(),
+		metadata_mismatch_suspected: metadata_mismatch_suspected(),
+		rpc_latency: rpc_latency().map(|latency| latency.millis),
+		paused: paras
+			.into_iter()
+			.filter(|p| p.paused)
+			.map(|p| (p.relay_chain, p.para_id))
+			.collect(),
+		offboarded,
+	};
+
+	serde_json::to_string(&health).map_err(|_| Error::InvalidData)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct DependencyStatus {
+	/// The dependency, i.e. `registry` or the rpc url of a relay chain.
+	pub dependency: String,
+	/// Set when the dependency is available.
+	pub healthy: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Readiness {
+	/// Set when all the dependencies are available.
+	pub ready: bool,
+	pub dependencies: Vec<DependencyStatus>,
+}
+
+/// Query whether the server is ready to serve requests.
+///
+/// Responds with `503 Service Unavailable` if any of the dependencies is unavailable.
+#[get("/health/ready")]
+pub async fn readiness() -> (Status, String) {
+	let mut dependencies = vec![DependencyStatus {
+		dependency: "registry".to_string(),
+		healthy: ensure_registry_readable().is_ok(),
+	}];
+
+	let mut rpc_urls: Vec<String> =
+		config().relay_chains.into_iter().filter_map(|relay| relay.rpc_url).collect();
+	rpc_urls.sort();
+	rpc_urls.dedup();
+
+	for rpc_url in rpc_urls {
+		let healthy = ensure_rpc_reachable(&rpc_url).await.is_ok();
+		dependencies.push(DependencyStatus { dependency: rpc_url, healthy });
+	}
+
+	let ready = dependencies.iter().all(|dependency| dependency.healthy);
+	let status = if ready { Status::Ok } else { Status::ServiceUnavailable };
+	let readiness = Readiness { ready, dependencies };
+
+	match serde_json::to_string(&readiness) {
+		Ok(body) => (status, body),
+		Err(_) => (Status::InternalServerError, String::new()),
+	}
+}
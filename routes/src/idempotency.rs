@@ -0,0 +1,225 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Replaying the outcome of retried requests to the endpoints which modify the registry.
+//!
+//! A client can attach an `Idempotency-Key` header to a request, in which case the successful
+//! outcome of the request is cached under the key for `idempotency_ttl` seconds. Retries made with
+//! the same key within that time get the cached outcome instead of being handled again, so that
+//! e.g. a retried registration doesn't fail with `AlreadyRegistered`.
+//!
+//! Failed requests don't modify the registry, so their outcome isn't cached and they can be
+//! retried with the same key.
+//!
+//! Reusing a key for a request with a different body is rejected, since the cached outcome
+//! wouldn't be the outcome of that request.
+
+use crate::*;
+use rocket::{
+	fairing::AdHoc,
+	outcome::Outcome,
+	request::{self, FromRequest},
+};
+use shared::config::config;
+use std::{
+	any::Any,
+	collections::{hash_map::DefaultHasher, HashMap},
+	future::Future,
+	hash::{Hash, Hasher},
+	sync::{Arc, Mutex},
+	time::{Duration, Instant},
+};
+
+/// The header through which the client provides the idempotency key of a request.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// The maximum length of an idempotency key.
+pub const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+
+/// The number of cached outcomes above which the expired ones are dropped.
+const MAX_CACHED_OUTCOMES: usize = 10_000;
+
+/// The outcome of a request made with an idempotency key.
+enum CachedOutcome {
+	/// The request is still being handled.
+	Pending,
+	/// The request succeeded, and its outcome is replayed until the contained instant.
+	Succeeded(Instant, Arc<dyn Any + Send + Sync>),
+}
+
+/// The outcome of a request along with the hash of its body.
+struct CachedEntry {
+	body_hash: u64,
+	outcome: CachedOutcome,
+}
+
+/// Keeps track of the outcomes of the requests made with an idempotency key.
+pub struct IdempotencyCache {
+	/// The duration for which the outcome of a request is replayed.
+	ttl: Duration,
+	outcomes: Mutex<HashMap<String, CachedEntry>>,
+}
+
+impl IdempotencyCache {
+	pub fn new(ttl: Duration) -> Self {
+		Self { ttl, outcomes: Default::default() }
+	}
+
+	/// Returns the cached outcome of the request with the specified key, if any.
+	///
+	/// Otherwise, the key is reserved until the request completes, so that a concurrent retry
+	/// isn't handled twice.
+	///
+	/// Fails if the key was used for a request with a different body.
+	fn begin(
+		&self,
+		key: &str,
+		body_hash: u64,
+		now: Instant,
+	) -> Result<Option<Arc<dyn Any + Send + Sync>>, Error> {
+		let mut outcomes = self.outcomes.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+		if outcomes.len() > MAX_CACHED_OUTCOMES {
+			outcomes.retain(|_, entry| !entry.is_expired(now));
+		}
+
+		match outcomes.get(key) {
+			Some(entry) if !entry.is_expired(now) && entry.body_hash != body_hash =>
+				Err(Error::IdempotencyKeyReused),
+			Some(CachedEntry { outcome: CachedOutcome::Pending, .. }) =>
+				Err(Error::RequestInProgress),
+			Some(CachedEntry {
+				outcome: CachedOutcome::Succeeded(expires_at, outcome), ..
+			}) if *expires_at > now => Ok(Some(outcome.clone())),
+			_ => {
+				let entry = CachedEntry { body_hash, outcome: CachedOutcome::Pending };
+				outcomes.insert(key.to_string(), entry);
+				Ok(None)
+			},
+		}
+	}
+
+	/// Caches the outcome of a succeeded request.
+	fn succeed(
+		&self,
+		key: &str,
+		body_hash: u64,
+		outcome: Arc<dyn Any + Send + Sync>,
+		now: Instant,
+	) {
+		let mut outcomes = self.outcomes.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+		let outcome = CachedOutcome::Succeeded(now + self.ttl, outcome);
+		outcomes.insert(key.to_string(), CachedEntry { body_hash, outcome });
+	}
+
+	/// Releases the key of a request which didn't succeed.
+	fn release(&self, key: &str) {
+		let mut outcomes = self.outcomes.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+		if matches!(outcomes.get(key), Some(CachedEntry { outcome: CachedOutcome::Pending, .. })) {
+			outcomes.remove(key);
+		}
+	}
+}
+
+impl CachedEntry {
+	fn is_expired(&self, now: Instant) -> bool {
+		matches!(self.outcome, CachedOutcome::Succeeded(expires_at, _) if expires_at <= now)
+	}
+}
+
+/// Returns the hash of the body of a request, with which retries are matched to the original
+/// request.
+///
+/// The outcomes are only cached in memory, so the hash doesn't need to be stable across restarts.
+pub fn body_hash(body: &impl Serialize) -> u64 {
+	let mut hasher = DefaultHasher::new();
+	serde_json::to_vec(body).unwrap_or_default().hash(&mut hasher);
+	hasher.finish()
+}
+
+/// Releases the key of a request once it completes, unless its outcome was cached.
+struct PendingRequest<'a> {
+	cache: &'a IdempotencyCache,
+	key: &'a str,
+}
+
+impl Drop for PendingRequest<'_> {
+	fn drop(&mut self) {
+		self.cache.release(self.key);
+	}
+}
+
+/// Enables the caching of outcomes if `idempotency_ttl` is not zero.
+pub fn idempotency_cache() -> AdHoc {
+	AdHoc::on_ignite("Idempotency keys", |rocket| async {
+		match config().idempotency_ttl {
+			0 => rocket,
+			ttl => rocket.manage(IdempotencyCache::new(Duration::from_secs(ttl))),
+		}
+	})
+}
+
+/// The idempotency key of the request, if the client provided one.
+///
+/// Requests without a key, and all requests when the caching isn't enabled, are always handled.
+pub struct Idempotency<'r>(Result<Option<(&'r IdempotencyCache, String)>, Error>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Idempotency<'r> {
+	type Error = ();
+
+	async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+		let Some(key) = request.headers().get_one(IDEMPOTENCY_KEY_HEADER) else {
+			return Outcome::Success(Idempotency(Ok(None)))
+		};
+		if key.is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LENGTH {
+			return Outcome::Success(Idempotency(Err(Error::InvalidIdempotencyKey)))
+		}
+
+		// The key is scoped to the request, so that it can't replay the outcome of a different
+		// endpoint, nor that of a dry run.
+		let key = format!("{} {} {}", request.method(), request.uri(), key);
+		let entry = request.rocket().state::<IdempotencyCache>().map(|cache| (cache, key));
+
+		Outcome::Success(Idempotency(Ok(entry)))
+	}
+}
+
+impl Idempotency<'_> {
+	/// Handles the request, unless it is a retry of a succeeded request, in which case the
+	/// outcome of the latter is returned.
+	///
+	/// `body_hash` is the `body_hash` of the body of the request.
+	pub async fn run<T, F>(self, body_hash: u64, handler: F) -> Result<T, Error>
+	where
+		T: Clone + Send + Sync + 'static,
+		F: Future<Output = Result<T, Error>>,
+	{
+		let Some((cache, key)) = self.0? else { return handler.await };
+
+		if let Some(outcome) = cache.begin(&key, body_hash, Instant::now())? {
+			// The key is scoped to the endpoint, so the outcome is always of the same type.
+			return outcome.downcast_ref::<T>().cloned().ok_or(Error::InvalidData)
+		}
+
+		// Releases the key even if the handler doesn't complete.
+		let _pending = PendingRequest { cache, key: &key };
+		let outcome = handler.await;
+		if let Ok(outcome) = &outcome {
+			cache.succeed(&key, body_hash, Arc::new(outcome.clone()), Instant::now());
+		}
+
+		outcome
+	}
+}
@@ -17,13 +17,53 @@
 //!
 //! This API exposes the following endpoints:
 //! - `/consumption`: Used to query consumption data associated with a parachain.
+//! - `/consumption/<relay>/<para_id>/stream`: Used to export all the consumption data of a
+//!   parachain as JSON, NDJSON or CSV.
+//! - `/consumption/<relay>/<para_id>/chart`: Used to render the consumption of a parachain as a PNG
+//!   or SVG chart. Only available with the `chart` feature.
+//! - `/consumption/<relay>/<para_id>/availability`: Used to query the fraction of the expected
+//!   blocks of a parachain that were recorded.
 //! - `/register`: Used to register a parachain for consumption tracking.
-//! - `/registry`: Used for querying all the registered parachains.
+//! - `/register_paras`: Used to register multiple parachains of a relay chain with a single
+//!   payment.
+//! - `/unregister_para`: Used to stop tracking a registered parachain.
+//! - `/registry`: Used for querying all the registered parachains, optionally of a single relay
+//!   chain.
 //! - `/extend-subscription`: For extending the subscription of a parachain.
+//! - `/reactivation-cost`: For querying the cost of bringing a lapsed subscription current.
+//! - `/consumption/latest`: For querying the latest consumption of multiple parachains at once.
+//! - `/pubkey`: For querying the public key used to sign the consumption responses.
+//! - `/top-up`: For accumulating credit towards the subscription through micro-payments.
+//! - `/subscription`: For querying the subscription status of a parachain.
+//! - `/subscription/status`: For querying the subscription statuses of multiple parachains at once.
+//! - `/health`: For querying the health of the consumption tracking.
+//! - `/health/ready`: Readiness probe, checking that the registry and the relay chain rpcs are
+//!   available.
+//! - `/metrics`: For exporting Prometheus metrics of the consumption tracking. Only available with
+//!   the `metrics` feature.
+//! - `/admin/link-migration`: For linking the identities of a parachain that migrated to a
+//!   different relay chain.
+//! - `/admin/pause` and `/admin/resume`: For pausing and resuming the tracking of a parachain.
+//! - `/admin/tracker_errors`: For querying the recent errors which stopped the tracking.
+//! - `/admin/refresh-chaindata`: For refreshing the chaindata without restarting the service.
+//! - `/order`: For registering a parachain through a signed order of a trusted partner, with the
+//!   payment settled later through `/order/settle`.
+//! - `/registration/<relay>/<para_id>/payment`: For querying the payment with which a parachain was
+//!   registered.
+//! - `/registration/<relay>/<para_id>`: For updating the mutable fields of a registration.
+//! - `/routes`: For listing all the available routes along with their parameters.
 
-use rocket::{http::Status, response::Responder, Request, Response};
+use rocket::{
+	http::{ContentType, Status},
+	response::Responder,
+	Request, Response,
+};
 use serde::{Deserialize, Serialize};
-use shared::{chaindata::ChainDataError, payment::PaymentError};
+use shared::{
+	chaindata::ChainDataError,
+	payment::{PaymentError, RPC_DEGRADATION_PERIOD},
+};
+use types::Timestamp;
 
 const LOG_TARGET: &str = "server";
 
@@ -46,15 +86,175 @@ pub enum Error {
 	ChainDataError(ChainDataError),
 	/// An error occured when trying to validate the payment.
 	PaymentValidationError(PaymentError),
+	/// Signing of the responses is not enabled.
+	SigningDisabled,
+	/// The provided parachain identifier is not in the `<relay>:<para_id>` format.
+	InvalidParaIdentifier,
+	/// The number of queried parachains exceeds the maximum allowed per request.
+	TooManyParas,
+	/// Registrations are temporarily rejected because the payment RPC is degraded.
+	RpcDegraded,
+	/// Micro-payments are not enabled.
+	MicroPaymentsDisabled,
+	/// The accumulated credit doesn't cover the cost of extending the subscription.
+	InsufficientCredit,
+	/// The requested aggregation function is not supported.
+	UnsupportedAggregationFn,
+	/// Failed to render the consumption chart.
+	ChartRenderingFailed,
+	/// Admin operations are not enabled.
+	AdminDisabled,
+	/// The provided admin key is missing or invalid.
+	Unauthorized,
+	/// The migration is either already linked or would introduce a cycle.
+	InvalidMigration,
+	/// There are too few records within the queried window for the aggregates to be meaningful.
+	InsufficientData,
+	/// The order was placed by a partner that isn't trusted.
+	UnknownPartner,
+	/// The signature of the order is invalid.
+	InvalidSignature,
+	/// The order was created too long ago, or was already placed.
+	OrderExpired,
+	/// There is no pending order for the parachain.
+	OrderNotFound,
+	/// The parachain was registered without a payment.
+	PaymentNotFound,
+	/// The queried time range exceeds the contained maximum range in milliseconds.
+	QueryRangeTooWide(Timestamp),
+	/// The queried time range is reversed or starts in the future.
+	InvalidRange,
+	/// The relay chain and the `ParaId` of a registration cannot be changed.
+	ImmutableField,
+	/// Updating a registration requires a proof of ownership in payment mode.
+	OwnershipProofRequired,
+	/// The registration has no known payer which could prove its ownership.
+	OwnerUnknown,
+	/// The ownership proof doesn't succeed the payment of the registration, or its latest update,
+	/// so it might have been made for a previous registration or update of the parachain.
+	StaleOwnershipProof,
+	/// The provided relay chain is not supported.
+	UnknownRelayChain,
+	/// The payment was already used for a registration or an extension.
+	PaymentAlreadyUsed,
+	/// The requested duration of a subscription extension is zero, or too long for the expiry of
+	/// the subscription to be represented.
+	InvalidDuration,
+	/// Payments are not configured for the relay chain of the parachain.
+	PaymentNotConfigured,
+	/// The batch of parachains to register is empty or contains the same parachain twice.
+	InvalidBatch,
+	/// The client made too many requests, and can retry after the contained number of seconds.
+	RateLimited(Timestamp),
+	/// The message sent over the live consumption WebSocket is not a valid request.
+	InvalidLiveRequest,
+	/// The payment was made by an account which isn't authorized to manage the parachain.
+	UnauthorizedPayer,
+	/// The requested subscription tier isn't configured.
+	UnknownTier,
+	/// The `Idempotency-Key` header is empty or too long.
+	InvalidIdempotencyKey,
+	/// A request with the same idempotency key is still being handled.
+	RequestInProgress,
+	/// The idempotency key was already used for a request with a different body.
+	IdempotencyKeyReused,
+}
+
+/// The JSON body of an error response.
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+pub struct ErrorResponse {
+	/// The name of the error, e.g. `NotRegistered`.
+	pub error: String,
+	/// The HTTP status code of the response.
+	pub code: u16,
+}
+
+impl Error {
+	/// The HTTP status with which the error is returned.
+	pub fn status(&self) -> Status {
+		match self {
+			Error::AlreadyRegistered | Error::PaymentAlreadyUsed | Error::RequestInProgress =>
+				Status::Conflict,
+			Error::NotRegistered |
+			Error::ConsumptionDataNotFound |
+			Error::OrderNotFound |
+			Error::PaymentNotFound |
+			Error::ChainDataError(ChainDataError::ParaNotFound) => Status::NotFound,
+			Error::ChainDataError(ChainDataError::RefreshFailed) => Status::BadGateway,
+			Error::PaymentRequired | Error::InsufficientCredit => Status::PaymentRequired,
+			// The payment might still be valid, so the request can be retried.
+			Error::PaymentValidationError(PaymentError::Unreachable) => Status::ServiceUnavailable,
+			Error::InvalidParaIdentifier |
+			Error::TooManyParas |
+			Error::UnsupportedAggregationFn |
+			Error::InvalidMigration |
+			Error::QueryRangeTooWide(_) |
+			Error::InvalidRange |
+			Error::ImmutableField |
+			Error::OwnershipProofRequired |
+			Error::StaleOwnershipProof |
+			Error::UnknownRelayChain |
+			Error::InvalidDuration |
+			Error::InvalidBatch |
+			Error::InvalidLiveRequest |
+			Error::UnknownTier |
+			Error::InvalidIdempotencyKey |
+			Error::ChainDataError(ChainDataError::RpcUnreachable) |
+			Error::ChainDataError(ChainDataError::InvalidRpcUrl) |
+			Error::PaymentValidationError(_) => Status::BadRequest,
+			Error::Unauthorized | Error::InvalidSignature => Status::Unauthorized,
+			Error::SigningDisabled |
+			Error::MicroPaymentsDisabled |
+			Error::AdminDisabled |
+			Error::UnknownPartner |
+			Error::ChainDataError(ChainDataError::RefreshDisabled) |
+			Error::PaymentNotConfigured |
+			Error::UnauthorizedPayer => Status::Forbidden,
+			Error::OrderExpired => Status::Gone,
+			Error::InsufficientData | Error::OwnerUnknown | Error::IdempotencyKeyReused =>
+				Status::UnprocessableEntity,
+			Error::RateLimited(_) => Status::TooManyRequests,
+			Error::RpcDegraded => Status::ServiceUnavailable,
+			Error::InvalidData | Error::ChartRenderingFailed => Status::InternalServerError,
+		}
+	}
+}
+
+impl From<PaymentError> for Error {
+	fn from(err: PaymentError) -> Self {
+		match err {
+			PaymentError::AlreadyUsed => Error::PaymentAlreadyUsed,
+			PaymentError::NotConfigured => Error::PaymentNotConfigured,
+			PaymentError::UnauthorizedPayer => Error::UnauthorizedPayer,
+			PaymentError::DurationOverflow => Error::InvalidDuration,
+			err => Error::PaymentValidationError(err),
+		}
+	}
 }
 
 impl<'r> Responder<'r, 'static> for Error {
 	fn respond_to(self, _: &'r Request<'_>) -> Result<Response<'static>, Status> {
-		let body = format!("{:?}", self);
-		Response::build()
-			.status(Status::InternalServerError)
-			.sized_body(body.len(), std::io::Cursor::new(body))
-			.ok()
+		let status = self.status();
+		let body = serde_json::to_string(&ErrorResponse {
+			error: format!("{:?}", self),
+			code: status.code,
+		})
+		.map_err(|_| Status::InternalServerError)?;
+
+		let mut response = Response::build();
+		response.status(status).header(ContentType::JSON);
+
+		match self {
+			Error::RpcDegraded => {
+				response.raw_header("Retry-After", RPC_DEGRADATION_PERIOD.to_string());
+			},
+			Error::RateLimited(retry_after) => {
+				response.raw_header("Retry-After", retry_after.to_string());
+			},
+			_ => {},
+		}
+
+		response.sized_body(body.len(), std::io::Cursor::new(body)).ok()
 	}
 }
 
@@ -66,6 +266,50 @@ impl From<String> for Error {
 			"ConsumptionDataNotFound" => Self::ConsumptionDataNotFound,
 			"InvalidData" => Self::InvalidData,
 			"PaymentRequired" => Self::PaymentRequired,
+			"SigningDisabled" => Self::SigningDisabled,
+			"InvalidParaIdentifier" => Self::InvalidParaIdentifier,
+			"TooManyParas" => Self::TooManyParas,
+			"RpcDegraded" => Self::RpcDegraded,
+			"MicroPaymentsDisabled" => Self::MicroPaymentsDisabled,
+			"InsufficientCredit" => Self::InsufficientCredit,
+			"UnsupportedAggregationFn" => Self::UnsupportedAggregationFn,
+			"ChartRenderingFailed" => Self::ChartRenderingFailed,
+			"AdminDisabled" => Self::AdminDisabled,
+			"Unauthorized" => Self::Unauthorized,
+			"InvalidMigration" => Self::InvalidMigration,
+			"InsufficientData" => Self::InsufficientData,
+			"UnknownPartner" => Self::UnknownPartner,
+			"InvalidSignature" => Self::InvalidSignature,
+			"OrderExpired" => Self::OrderExpired,
+			"OrderNotFound" => Self::OrderNotFound,
+			"PaymentNotFound" => Self::PaymentNotFound,
+			"InvalidRange" => Self::InvalidRange,
+			"ImmutableField" => Self::ImmutableField,
+			"OwnershipProofRequired" => Self::OwnershipProofRequired,
+			"OwnerUnknown" => Self::OwnerUnknown,
+			"StaleOwnershipProof" => Self::StaleOwnershipProof,
+			"UnknownRelayChain" => Self::UnknownRelayChain,
+			"PaymentAlreadyUsed" => Self::PaymentAlreadyUsed,
+			"InvalidDuration" => Self::InvalidDuration,
+			"PaymentNotConfigured" => Self::PaymentNotConfigured,
+			"InvalidBatch" => Self::InvalidBatch,
+			"InvalidLiveRequest" => Self::InvalidLiveRequest,
+			"UnauthorizedPayer" => Self::UnauthorizedPayer,
+			"UnknownTier" => Self::UnknownTier,
+			"InvalidIdempotencyKey" => Self::InvalidIdempotencyKey,
+			"RequestInProgress" => Self::RequestInProgress,
+			"IdempotencyKeyReused" => Self::IdempotencyKeyReused,
+			_ if v.starts_with("QueryRangeTooWide(") => {
+				let max_range =
+					v.trim_start_matches("QueryRangeTooWide(").trim_end_matches(')').trim();
+
+				Error::QueryRangeTooWide(max_range.parse().expect("Invalid range"))
+			},
+			_ if v.starts_with("RateLimited(") => {
+				let retry_after = v.trim_start_matches("RateLimited(").trim_end_matches(')').trim();
+
+				Error::RateLimited(retry_after.parse().expect("Invalid retry after"))
+			},
 			_ if v.starts_with("ChainDataError(") => {
 				let chaindata_error =
 					v.trim_start_matches("ChainDataError(").trim_end_matches(')').trim();
@@ -83,7 +327,27 @@ impl From<String> for Error {
 	}
 }
 
+pub mod admin;
+pub mod availability;
+#[cfg(feature = "chart")]
+pub mod chart;
 pub mod consumption;
+pub mod cors;
+pub mod endpoints;
 pub mod extend_subscription;
+pub mod health;
+pub mod idempotency;
+pub mod live;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod order;
+pub mod payment;
+pub mod rate_limit;
+pub mod reactivation;
 pub mod register;
 pub mod registry;
+pub mod signature;
+pub mod subscription;
+pub mod top_up;
+pub mod unregister;
+pub mod update;
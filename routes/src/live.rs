@@ -0,0 +1,352 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Streams the consumption of parachains over WebSocket as it gets recorded.
+//!
+//! The trackers run as separate processes, so the server follows the latest consumption they
+//! record for each parachain and broadcasts every new record to the connected clients, including
+//! the ones recorded in between two polls. Each client only receives the records of the
+//! parachains it subscribed to.
+
+use crate::{consumption::MAX_LATEST_PARAS, Error, ErrorResponse, LOG_TARGET};
+use rocket::{
+	fairing::AdHoc,
+	futures::{SinkExt, StreamExt},
+	get,
+	tokio::{
+		self,
+		sync::broadcast::{self, error::RecvError},
+	},
+	Shutdown,
+};
+use rocket_ws::{Channel, Message, WebSocket};
+use serde::{Deserialize, Serialize};
+use shared::{
+	config::config,
+	consumption::{consumption_appended, latest_consumption},
+	registry::{registered_para, registered_paras},
+};
+use std::{
+	collections::{BTreeMap, HashMap},
+	sync::OnceLock,
+	time::Duration,
+};
+use types::{ParaId, Parachain, RelayChain, WeightConsumption};
+
+/// The interval in milliseconds at which the recorded consumption is checked for new records.
+pub const LIVE_POLL_INTERVAL: u64 = 1000;
+
+static LIVE_CONSUMPTION: OnceLock<broadcast::Sender<LiveConsumption>> = OnceLock::new();
+
+/// A consumption record of a parachain, as pushed to the subscribed clients.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct LiveConsumption {
+	/// The parachain the consumption belongs to.
+	pub para: (RelayChain, ParaId),
+	/// The recorded consumption.
+	pub consumption: WeightConsumption,
+}
+
+/// Sent to a client which fell behind, in place of the records it missed.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(crate = "rocket::serde")]
+pub struct LiveLagged {
+	/// The number of records the client missed.
+	pub skipped: u64,
+}
+
+/// A message sent by a client to choose the parachains it receives the consumption of.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum LiveRequest {
+	/// Start receiving the consumption of the parachain.
+	Subscribe(LiveSubscription),
+	/// Stop receiving the consumption of the parachain.
+	Unsubscribe((RelayChain, ParaId)),
+}
+
+/// The parachain a client subscribes to, optionally along with how often it receives its
+/// consumption.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(crate = "rocket::serde", untagged)]
+pub enum LiveSubscription {
+	/// Receive every record of the parachain, e.g. `["Polkadot",2000]`.
+	Para((RelayChain, ParaId)),
+	/// Receive records which are at least `sampling_interval` blocks apart, e.g.
+	/// `{"para":["Polkadot",2000],"sampling_interval":10}`.
+	Sampled { para: (RelayChain, ParaId), sampling_interval: u32 },
+}
+
+/// The consumption a client receives of a parachain it subscribed to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subscription {
+	/// The minimum number of blocks between two records sent to the client.
+	pub sampling_interval: u32,
+	/// The number of the latest block sent to the client.
+	last_sent: Option<u32>,
+}
+
+impl Subscription {
+	/// Returns whether the record is sent to the client, in which case it is noted as the
+	/// latest sent one.
+	pub fn sample(&mut self, consumption: &WeightConsumption) -> bool {
+		let due = self.last_sent.map_or(true, |last_sent| {
+			consumption.block_number >= last_sent.saturating_add(self.sampling_interval)
+		});
+		if due {
+			self.last_sent = Some(consumption.block_number);
+		}
+
+		due
+	}
+}
+
+/// Returns a receiver of all the consumption broadcasted from now on.
+pub fn subscribe_live_consumption() -> broadcast::Receiver<LiveConsumption> {
+	live_sender().subscribe()
+}
+
+fn live_sender() -> &'static broadcast::Sender<LiveConsumption> {
+	// A channel can't be created without any capacity.
+	LIVE_CONSUMPTION.get_or_init(|| broadcast::channel(config().live_channel_capacity.max(1)).0)
+}
+
+/// Follows the consumption recorded for the registered parachains.
+#[derive(Debug, Default)]
+pub struct LiveFeed {
+	/// The number of the latest block broadcasted for each parachain.
+	last_pushed: HashMap<(RelayChain, ParaId), u32>,
+	/// The offset up to which each consumption file of a parachain got read.
+	offsets: HashMap<(RelayChain, ParaId, Option<usize>), u64>,
+}
+
+impl LiveFeed {
+	/// Creates a feed which only broadcasts the consumption recorded after its creation.
+	pub fn new() -> Self {
+		let mut feed = Self::default();
+		feed.updates();
+		feed
+	}
+
+	/// Broadcasts the consumption recorded since the last poll, and returns the number of
+	/// broadcasted records.
+	pub fn poll(&mut self) -> usize {
+		let updates = self.updates();
+		let count = updates.len();
+		for update in updates {
+			// Sending only fails when there are no clients, in which case there is no one to
+			// miss the record.
+			let _ = live_sender().send(update);
+		}
+
+		count
+	}
+
+	fn updates(&mut self) -> Vec<LiveConsumption> {
+		let mut updates = vec![];
+		for para in registered_paras() {
+			// The latest consumption is cheap to read, so the recorded consumption is only read
+			// once there is something new.
+			let Some(latest) = latest_consumption(&para) else { continue };
+			let key = (para.relay_chain.clone(), para.para_id);
+			let records = match self.last_pushed.get(&key) {
+				Some(&last_pushed) if latest.consumption.block_number <= last_pushed => continue,
+				Some(&last_pushed) => self.recorded_after(&para, last_pushed),
+				// The consumption recorded before the parachain was followed isn't broadcasted.
+				None => {
+					// Skips the files past what is already recorded.
+					self.recorded_after(&para, u32::MAX);
+					vec![latest.consumption]
+				},
+			};
+
+			if let Some(last) = records.iter().map(|record| record.block_number).max() {
+				self.last_pushed.insert(key.clone(), last);
+			}
+			updates.extend(
+				records
+					.into_iter()
+					.map(|consumption| LiveConsumption { para: key.clone(), consumption }),
+			);
+		}
+
+		updates
+	}
+
+	/// Returns the consumption of a parachain recorded after the provided block, in the order of
+	/// the blocks.
+	///
+	/// Each tracker records the consumption separately, so the records of all of them, as well as
+	/// the processed ones, are merged. Only what got appended to the files since the last read is
+	/// read.
+	fn recorded_after(&mut self, para: &Parachain, block_number: u32) -> Vec<WeightConsumption> {
+		let mut records = BTreeMap::new();
+		let rpc_indexes = std::iter::once(None).chain((0..para.rpcs.len()).map(Some));
+		for rpc_index in rpc_indexes {
+			let key = (para.relay_chain.clone(), para.para_id, rpc_index);
+			let offset = self.offsets.get(&key).copied().unwrap_or_default();
+			let Ok((consumption, offset)) = consumption_appended(para.clone(), rpc_index, offset)
+			else {
+				continue
+			};
+			self.offsets.insert(key, offset);
+			for record in
+				consumption.into_iter().filter(|record| record.block_number > block_number)
+			{
+				records.entry(record.block_number).or_insert(record);
+			}
+		}
+
+		records.into_values().collect()
+	}
+}
+
+/// Starts following the recorded consumption once the server is launched, until it shuts down.
+pub fn live_feed() -> AdHoc {
+	AdHoc::on_liftoff("Live consumption", |rocket| {
+		let mut shutdown = rocket.shutdown();
+		Box::pin(async move {
+			tokio::spawn(async move {
+				let mut feed = LiveFeed::new();
+				let mut interval = tokio::time::interval(Duration::from_millis(LIVE_POLL_INTERVAL));
+				loop {
+					tokio::select! {
+						_ = &mut shutdown => break,
+						_ = interval.tick() => {
+							// Reading the recorded consumption is blocking.
+							feed = match tokio::task::spawn_blocking(move || {
+								feed.poll();
+								feed
+							})
+							.await
+							{
+								Ok(feed) => feed,
+								Err(err) => {
+									log::error!(
+										target: LOG_TARGET,
+										"Failed to follow the recorded consumption: {:?}",
+										err
+									);
+									LiveFeed::new()
+								},
+							};
+						},
+					}
+				}
+			});
+		})
+	})
+}
+
+/// Stream the consumption of parachains over WebSocket as it gets recorded.
+///
+/// Clients choose the parachains by sending `{"subscribe":["Polkadot",2000]}` and
+/// `{"unsubscribe":["Polkadot",2000]}` messages, and receive each new record as a
+/// `LiveConsumption`. Invalid requests are answered with an `ErrorResponse`, and clients falling
+/// behind are sent a `LiveLagged` with the number of records they missed.
+#[get("/consumption/live")]
+pub fn consumption_live(ws: WebSocket, mut shutdown: Shutdown) -> Channel<'static> {
+	ws.channel(move |mut stream| {
+		Box::pin(async move {
+			let mut receiver = subscribe_live_consumption();
+			let mut subscribed = HashMap::new();
+
+			loop {
+				tokio::select! {
+					_ = &mut shutdown => break,
+					message = stream.next() => {
+						let text = match message {
+							Some(Ok(Message::Text(text))) => text,
+							Some(Ok(Message::Close(_))) | None => break,
+							Some(Ok(_)) => continue,
+							Some(Err(err)) => return Err(err),
+						};
+
+						if let Err(err) = handle_live_request(&text, &mut subscribed) {
+							let response = ErrorResponse {
+								error: format!("{:?}", err),
+								code: err.status().code,
+							};
+							let body = serde_json::to_string(&response).unwrap_or_default();
+							stream.send(Message::Text(body)).await?;
+						}
+					},
+					record = receiver.recv() => match record {
+						Ok(record) => {
+							let Some(subscription) = subscribed.get_mut(&record.para) else {
+								continue
+							};
+							if subscription.sample(&record.consumption) {
+								let body = serde_json::to_string(&record).unwrap_or_default();
+								stream.send(Message::Text(body)).await?;
+							}
+						},
+						Err(RecvError::Lagged(skipped)) => {
+							log::warn!(
+								target: LOG_TARGET,
+								"Live consumption client missed {} records",
+								skipped
+							);
+							let body =
+								serde_json::to_string(&LiveLagged { skipped }).unwrap_or_default();
+							stream.send(Message::Text(body)).await?;
+						},
+						Err(RecvError::Closed) => break,
+					},
+				}
+			}
+
+			Ok(())
+		})
+	})
+}
+
+/// Applies a request of a client to the parachains it is subscribed to.
+pub fn handle_live_request(
+	request: &str,
+	subscribed: &mut HashMap<(RelayChain, ParaId), Subscription>,
+) -> Result<(), Error> {
+	match serde_json::from_str(request).map_err(|_| Error::InvalidLiveRequest)? {
+		LiveRequest::Subscribe(subscription) => {
+			let ((relay_chain, para_id), sampling_interval) = match subscription {
+				LiveSubscription::Para(para) => (para, 1),
+				LiveSubscription::Sampled { para, sampling_interval } => (para, sampling_interval),
+			};
+			if sampling_interval == 0 {
+				return Err(Error::InvalidLiveRequest);
+			}
+			if registered_para(relay_chain.clone(), para_id).is_none() {
+				return Err(Error::NotRegistered);
+			}
+			if !subscribed.contains_key(&(relay_chain.clone(), para_id)) &&
+				subscribed.len() >= MAX_LATEST_PARAS
+			{
+				return Err(Error::TooManyParas);
+			}
+
+			// Subscribing again changes the sampling interval of the subscription.
+			subscribed
+				.entry((relay_chain, para_id))
+				.and_modify(|subscription| subscription.sampling_interval = sampling_interval)
+				.or_insert(Subscription { sampling_interval, last_sent: None });
+		},
+		LiveRequest::Unsubscribe(para) => {
+			subscribed.remove(&para);
+		},
+	}
+
+	Ok(())
+}
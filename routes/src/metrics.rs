@@ -0,0 +1,127 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Prometheus metrics of the consumption tracking.
+//!
+//! This is only available with the `metrics` feature. The counters are only updated by trackers
+//! which are also built with the `metrics` feature.
+
+use rocket::{get, http::ContentType};
+use shared::{
+	config::config,
+	consumption::latest_consumption,
+	registry::registered_paras,
+	tracker::{tracker_metrics, ParaMetrics},
+};
+use std::fmt::Write;
+use types::{ParaId, RelayChain};
+
+/// Export the metrics of the consumption tracking in the Prometheus text format.
+#[get("/metrics")]
+pub fn metrics() -> (ContentType, String) {
+	let paras = registered_paras();
+	let tracker_metrics: Vec<(usize, ParaMetrics)> = (0..config().outputs)
+		.flat_map(|rpc_index| {
+			tracker_metrics(rpc_index).into_iter().map(move |metrics| (rpc_index, metrics))
+		})
+		.collect();
+
+	let mut out = String::new();
+
+	write_header(
+		&mut out,
+		"weigher_registered_paras",
+		"gauge",
+		"The number of registered parachains.",
+	);
+	let _ = writeln!(out, "weigher_registered_paras {}", paras.len());
+
+	write_header(
+		&mut out,
+		"weigher_blocks_processed_total",
+		"counter",
+		"The number of blocks processed by the tracker since it started.",
+	);
+	for (rpc_index, metrics) in &tracker_metrics {
+		let labels = labels(&metrics.para, Some(*rpc_index));
+		let _ = writeln!(
+			out,
+			"weigher_blocks_processed_total{{{}}} {}",
+			labels, metrics.blocks_processed
+		);
+	}
+
+	write_header(
+		&mut out,
+		"weigher_reconnects_total",
+		"counter",
+		"The number of times the tracker reconnected to a parachain since it started.",
+	);
+	for (rpc_index, metrics) in &tracker_metrics {
+		let labels = labels(&metrics.para, Some(*rpc_index));
+		let _ = writeln!(out, "weigher_reconnects_total{{{}}} {}", labels, metrics.reconnects);
+	}
+
+	write_header(
+		&mut out,
+		"weigher_last_block_timestamp_seconds",
+		"gauge",
+		"The timestamp of the latest recorded block of a parachain.",
+	);
+	for para in &paras {
+		let Some(latest) = latest_consumption(para) else { continue };
+		let labels = labels(&(para.relay_chain.clone(), para.para_id), None);
+		// The block timestamps are in milliseconds.
+		let _ = writeln!(
+			out,
+			"weigher_last_block_timestamp_seconds{{{}}} {}",
+			labels,
+			latest.consumption.timestamp as f64 / 1000.0
+		);
+	}
+
+	write_header(
+		&mut out,
+		"weigher_latest_consumption_stale",
+		"gauge",
+		"Whether the latest consumption of a parachain is older than its staleness threshold.",
+	);
+	for para in &paras {
+		let Some(latest) = latest_consumption(para) else { continue };
+		let labels = labels(&(para.relay_chain.clone(), para.para_id), None);
+		let _ = writeln!(
+			out,
+			"weigher_latest_consumption_stale{{{}}} {}",
+			labels,
+			u8::from(latest.is_stale(para))
+		);
+	}
+
+	(ContentType::new("text", "plain").with_params(("version", "0.0.4")), out)
+}
+
+fn write_header(out: &mut String, name: &str, kind: &str, help: &str) {
+	let _ = writeln!(out, "# HELP {} {}", name, help);
+	let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn labels((relay_chain, para_id): &(RelayChain, ParaId), rpc_index: Option<usize>) -> String {
+	let mut labels = format!("relay_chain=\"{}\",para_id=\"{}\"", relay_chain, para_id);
+	if let Some(rpc_index) = rpc_index {
+		let _ = write!(labels, ",rpc_index=\"{}\"", rpc_index);
+	}
+
+	labels
+}
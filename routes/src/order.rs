@@ -0,0 +1,327 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Registration through orders of trusted partners, with deferred settlement of the payment.
+//!
+//! The lifecycle of an order is the following:
+//! - The partner places a signed order, which registers the parachain straight away.
+//! - The partner settles the payment within its settlement window through a signed settlement.
+//! - Otherwise, once the window passes the order expires and the parachain is unregistered.
+
+use crate::{
+	idempotency::{body_hash, Idempotency},
+	*,
+};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use polkadot_core_primitives::BlockNumber;
+use rocket::{fairing::AdHoc, post, serde::json::Json, tokio};
+use shared::{
+	chaindata,
+	config::{config, partner},
+	current_timestamp,
+	order::{orders, write_orders, Order, OrderState},
+	payment::validate_registration_payment,
+	registry::{registered_para, registered_paras, update_registry},
+};
+use std::time::Duration;
+use types::{ParaId, RelayChain, Timestamp};
+
+/// The maximum number of seconds between the creation and the placement of an order.
+pub const MAX_ORDER_AGE: Timestamp = 300;
+
+/// The interval in seconds at which the settlement of the pending orders is checked.
+pub const SETTLEMENT_CHECK_INTERVAL: u64 = 60;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct OrderData {
+	/// The name of the partner placing the order.
+	pub partner: String,
+	/// The parachain to register.
+	pub para: (RelayChain, ParaId),
+	/// The timestamp at which the order was created.
+	pub created_at: Timestamp,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SignedOrder {
+	pub order: OrderData,
+	/// The hex encoded Ed25519 signature of the JSON encoded order, signed by the partner.
+	pub signature: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SettlementData {
+	/// The parachain for which the payment is settled.
+	pub para: (RelayChain, ParaId),
+	/// The block in which the payment occurred.
+	///
+	/// In free mode, this is ignored and can be `None`.
+	pub payment_block_number: Option<BlockNumber>,
+	/// The number of blocks following `payment_block_number` in which the payment is also
+	/// searched for, in case the exact block isn't known.
+	///
+	/// This is capped at `MAX_PAYMENT_SEARCH_WINDOW`.
+	#[serde(default)]
+	pub payment_search_window: BlockNumber,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SignedSettlement {
+	pub settlement: SettlementData,
+	/// The hex encoded Ed25519 signature of the JSON encoded settlement, signed by the partner
+	/// which placed the order.
+	pub signature: String,
+}
+
+/// Register a parachain through a signed order of a trusted partner.
+///
+/// The payment has to be settled through `/order/settle` within the settlement window of the
+/// partner, otherwise the parachain is unregistered.
+#[post("/order", data = "<signed_order>")]
+pub async fn place_order(
+	signed_order: Json<SignedOrder>,
+	idempotency: Idempotency<'_>,
+) -> Result<(), Error> {
+	idempotency
+		.run(body_hash(&*signed_order), async move { place(signed_order) })
+		.await
+}
+
+fn place(signed_order: Json<SignedOrder>) -> Result<(), Error> {
+	let SignedOrder { order, signature } = signed_order.into_inner();
+	let partner = partner(&order.partner).ok_or(Error::UnknownPartner)?;
+
+	verify_signature(&order, &order.partner, &partner.public_key, &signature)?;
+
+	let now = current_timestamp();
+	if now.abs_diff(order.created_at) > MAX_ORDER_AGE {
+		return Err(Error::OrderExpired);
+	}
+
+	let (relay_chain, para_id) = order.para.clone();
+	let mut orders = orders();
+	// Prevents a placed order from being replayed once the parachain gets unregistered.
+	if orders.iter().any(|o| {
+		o.para == order.para && o.partner == order.partner && o.created_at == order.created_at
+	}) {
+		return Err(Error::OrderExpired);
+	}
+
+	if registered_para(relay_chain.clone(), para_id).is_some() {
+		return Err(Error::AlreadyRegistered);
+	}
+
+	let mut para = chaindata::get_para(relay_chain, para_id).map_err(Error::ChainDataError)?;
+	para.expiry_timestamp =
+		now + config().payment_info.map(|info| info.subscription_duration).unwrap_or_default();
+
+	// The order is stored first, so that a registered parachain always has an order to settle.
+	orders.push(Order {
+		para: order.para,
+		partner: order.partner,
+		created_at: order.created_at,
+		settlement_deadline: now + partner.settlement_window,
+		state: OrderState::Pending,
+	});
+	write_orders(&orders).map_err(|err| {
+		log::error!(
+			target: LOG_TARGET,
+			"{}-{} - Failed to store order: {:?}",
+			para.relay_chain,
+			para.para_id,
+			err
+		);
+		Error::InvalidData
+	})?;
+
+	let mut paras = registered_paras();
+	paras.push(para.clone());
+	if let Err(err) = update_registry(paras) {
+		log::error!(
+			target: LOG_TARGET,
+			"{}-{} - Failed to register para: {:?}",
+			para.relay_chain,
+			para.para_id,
+			err
+		);
+		// The order can be placed again once nothing got registered.
+		orders.pop();
+		let _ = write_orders(&orders);
+		return Err(Error::InvalidData);
+	}
+
+	#[cfg(not(debug_assertions))]
+	shared::init_tracker();
+
+	Ok(())
+}
+
+/// Settle the payment of a pending order.
+///
+/// The settlement has to be signed by the partner which placed the order.
+#[post("/order/settle", data = "<settlement>")]
+pub async fn settle_order(
+	settlement: Json<SignedSettlement>,
+	idempotency: Idempotency<'_>,
+) -> Result<(), Error> {
+	idempotency.run(body_hash(&*settlement), settle(settlement)).await
+}
+
+async fn settle(settlement: Json<SignedSettlement>) -> Result<(), Error> {
+	let SignedSettlement { settlement, signature } = settlement.into_inner();
+
+	let mut orders = orders();
+	let order = orders
+		.iter_mut()
+		.find(|o| o.para == settlement.para && o.state == OrderState::Pending)
+		.ok_or(Error::OrderNotFound)?;
+
+	let partner = partner(&order.partner).ok_or(Error::UnknownPartner)?;
+	verify_signature(&settlement, &order.partner, &partner.public_key, &signature)?;
+
+	let (relay_chain, para_id) = settlement.para.clone();
+	let para = registered_para(relay_chain, para_id).ok_or(Error::NotRegistered)?;
+
+	if let Some(payment_info) = config().payment_info {
+		let payment_block_number = settlement.payment_block_number.ok_or(Error::PaymentRequired)?;
+
+		let payment = validate_registration_payment(
+			para.clone(),
+			payment_info,
+			payment_block_number,
+			settlement.payment_search_window,
+		)
+		.await
+		.map_err(Error::from)?;
+
+		let mut paras = registered_paras();
+		if let Some(registered) = paras.iter_mut().find(|p| **p == para) {
+			registered.payment = Some(payment);
+		}
+		if let Err(err) = update_registry(paras) {
+			log::error!(
+				target: LOG_TARGET,
+				"{}-{} - Failed to store the settled payment: {:?}",
+				para.relay_chain,
+				para.para_id,
+				err
+			);
+		}
+	}
+
+	order.state = OrderState::Settled;
+	write_orders(&orders).map_err(|err| {
+		log::error!(
+			target: LOG_TARGET,
+			"Failed to store order settlement: {:?}",
+			err
+		);
+		Error::InvalidData
+	})
+}
+
+/// Unregisters the parachains of all the pending orders whose settlement deadline passed.
+pub fn expire_unsettled_orders() {
+	let now = current_timestamp();
+	let mut orders = orders();
+
+	let expired: Vec<(RelayChain, ParaId)> = orders
+		.iter_mut()
+		.filter(|o| o.state == OrderState::Pending && o.settlement_deadline < now)
+		.map(|o| {
+			o.state = OrderState::Expired;
+			o.para.clone()
+		})
+		.collect();
+
+	if expired.is_empty() {
+		return;
+	}
+
+	log::info!(
+		target: LOG_TARGET,
+		"Unregistering paras with unsettled orders: {:?}",
+		expired
+	);
+
+	let mut paras = registered_paras();
+	paras.retain(|para| !expired.contains(&(para.relay_chain.clone(), para.para_id)));
+
+	if let Err(err) = update_registry(paras).and_then(|_| write_orders(&orders)) {
+		log::error!(
+			target: LOG_TARGET,
+			"Failed to expire unsettled orders: {:?}",
+			err
+		);
+	} else {
+		#[cfg(not(debug_assertions))]
+		shared::init_tracker();
+	}
+}
+
+/// Periodically expires the orders that weren't settled in time.
+pub fn settlement_job() -> AdHoc {
+	AdHoc::on_liftoff("Order settlement", |rocket| {
+		let mut shutdown = rocket.shutdown();
+		Box::pin(async move {
+			tokio::spawn(async move {
+				let mut interval =
+					tokio::time::interval(Duration::from_secs(SETTLEMENT_CHECK_INTERVAL));
+				loop {
+					tokio::select! {
+						_ = &mut shutdown => break,
+						_ = interval.tick() => expire_unsettled_orders(),
+					}
+				}
+			});
+		})
+	})
+}
+
+/// Verifies the signature of the partner over the JSON encoded `message`.
+fn verify_signature<T: Serialize>(
+	message: &T,
+	partner: &str,
+	public_key: &str,
+	signature: &str,
+) -> Result<(), Error> {
+	let public_key = decode(public_key)
+		.and_then(|key| <[u8; 32]>::try_from(key).ok())
+		.and_then(|key| VerifyingKey::from_bytes(&key).ok())
+		.ok_or_else(|| {
+			log::error!(
+				target: LOG_TARGET,
+				"Invalid public key configured for partner {}",
+				partner
+			);
+			Error::InvalidSignature
+		})?;
+
+	let signature = decode(signature)
+		.and_then(|signature| <[u8; 64]>::try_from(signature).ok())
+		.map(|signature| Signature::from_bytes(&signature))
+		.ok_or(Error::InvalidSignature)?;
+
+	let message = serde_json::to_vec(message).map_err(|_| Error::InvalidData)?;
+	public_key.verify(&message, &signature).map_err(|_| Error::InvalidSignature)
+}
+
+fn decode(value: &str) -> Option<Vec<u8>> {
+	hex::decode(value.trim().trim_start_matches("0x")).ok()
+}
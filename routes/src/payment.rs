@@ -0,0 +1,73 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::{admin::AdminKey, *};
+use rocket::get;
+use shared::{config::config, payment::payment_extrinsic, registry::registered_para};
+use types::ParaId;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PaymentReceipt {
+	/// The block in which the payment occurred.
+	pub block_number: u32,
+	/// The hash of the block in which the payment occurred.
+	pub block_hash: Option<String>,
+	/// The index of the payment extrinsic within the block.
+	pub extrinsic_index: u32,
+	/// The hex encoded address of the account that made the payment.
+	///
+	/// Only returned to admins.
+	pub payer: Option<String>,
+	/// The hex encoded payment extrinsic, re-fetched from the payment chain.
+	///
+	/// Only returned if requested.
+	pub extrinsic: Option<String>,
+}
+
+/// Query the payment with which a parachain was registered.
+///
+/// The address of the payer is only returned when a valid admin key is provided. When
+/// `include_extrinsic` is set the encoded payment extrinsic is re-fetched from the payment chain.
+#[get("/registration/<relay>/<para_id>/payment?<include_extrinsic>")]
+pub async fn registration_payment(
+	key: AdminKey,
+	relay: &str,
+	para_id: ParaId,
+	include_extrinsic: Option<bool>,
+) -> Result<String, Error> {
+	let para = registered_para(relay.into(), para_id).ok_or(Error::NotRegistered)?;
+	// Parachains registered in free mode have no payment.
+	let payment = para.payment.clone().ok_or(Error::PaymentNotFound)?;
+
+	let extrinsic = match (include_extrinsic.unwrap_or_default(), config().payment_info) {
+		(true, Some(payment_info)) => {
+			let extrinsic =
+				payment_extrinsic(&para, &payment_info, &payment).await.map_err(Error::from)?;
+			Some(format!("0x{}", hex::encode(extrinsic)))
+		},
+		_ => None,
+	};
+
+	let receipt = PaymentReceipt {
+		block_number: payment.block_number,
+		block_hash: payment.block_hash,
+		extrinsic_index: payment.extrinsic_index,
+		payer: key.authorize().ok().and(payment.payer),
+		extrinsic,
+	};
+
+	serde_json::to_string(&receipt).map_err(|_| Error::InvalidData)
+}
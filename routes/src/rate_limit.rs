@@ -0,0 +1,162 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Per-IP rate limiting of the endpoints which modify the registry.
+//!
+//! Every client IP has a token bucket holding up to `rate_limit` requests, which is refilled at
+//! a rate of `rate_limit` requests per minute. Read-only endpoints, i.e. `GET`, `HEAD` and
+//! `OPTIONS` requests, and the admin endpoints aren't rate limited.
+//!
+//! The buckets are implemented as a generic cell rate algorithm, which only needs to keep track of
+//! the time at which the bucket of each client will be full again.
+//!
+//! The limit is enforced by a fairing, which reroutes the requests exceeding it to a route
+//! responding with `RateLimited`, so that these never reach the endpoints.
+
+use crate::*;
+use rocket::{
+	fairing::{self, AdHoc, Fairing, Info, Kind},
+	get,
+	http::{uri::Origin, Method},
+	routes, Build, Data, Rocket,
+};
+use shared::config::config;
+use std::{
+	collections::HashMap,
+	net::IpAddr,
+	sync::Mutex,
+	time::{Duration, Instant},
+};
+
+/// The number of tracked clients above which the ones with a full bucket are dropped.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// The base path of the route to which the rate limited requests are rerouted.
+const RATE_LIMITED_PATH: &str = "/rate-limited";
+
+/// Keeps track of the requests made by each client IP.
+pub struct RateLimiter {
+	/// The time it takes to refill a single token.
+	interval: Duration,
+	/// The time it takes to refill all the tokens but one.
+	tolerance: Duration,
+	/// The proxies whose forwarded client IP is trusted.
+	trusted_proxies: Vec<IpAddr>,
+	/// The time at which the bucket of each client is full again, as if all the tokens taken from
+	/// it were taken at once.
+	buckets: Mutex<HashMap<IpAddr, Instant>>,
+}
+
+impl RateLimiter {
+	pub fn new(requests_per_minute: u32, trusted_proxies: Vec<IpAddr>) -> Self {
+		let requests_per_minute = requests_per_minute.max(1);
+		let interval = Duration::from_secs(60) / requests_per_minute;
+
+		Self {
+			interval,
+			tolerance: interval * (requests_per_minute - 1),
+			trusted_proxies,
+			buckets: Default::default(),
+		}
+	}
+
+	/// Returns the IP of the client making the request.
+	///
+	/// This is the IP of the remote peer, unless the peer is one of the trusted proxies, in which
+	/// case it is the client IP forwarded by the proxy through the configured `ip_header`.
+	pub fn client_ip(&self, request: &Request<'_>) -> Option<IpAddr> {
+		let remote = request.remote()?.ip();
+		if self.trusted_proxies.contains(&remote) {
+			return Some(request.real_ip().unwrap_or(remote));
+		}
+
+		Some(remote)
+	}
+
+	/// Takes a token from the bucket of the client.
+	///
+	/// Returns the number of seconds after which the client can retry if its bucket is empty.
+	pub fn acquire(&self, ip: IpAddr, now: Instant) -> Result<(), Timestamp> {
+		let mut buckets = self.buckets.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+		if buckets.len() > MAX_TRACKED_CLIENTS {
+			// Full buckets are the same as newly created ones.
+			buckets.retain(|_, full_at| *full_at > now);
+		}
+
+		let full_at = buckets.get(&ip).copied().unwrap_or(now).max(now);
+		let wait = full_at.duration_since(now);
+		if wait > self.tolerance {
+			let retry_after = wait - self.tolerance;
+			return Err(retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0));
+		}
+
+		buckets.insert(ip, full_at + self.interval);
+		Ok(())
+	}
+}
+
+/// Enables the rate limiting if `rate_limit` is configured.
+pub fn rate_limiter() -> AdHoc {
+	AdHoc::on_ignite("Rate limiting", |rocket| async {
+		let config = config();
+		match config.rate_limit {
+			Some(requests_per_minute) =>
+				rocket.attach(RateLimiter::new(requests_per_minute, config.trusted_proxies)),
+			None => rocket,
+		}
+	})
+}
+
+/// Returns whether the request is subject to the rate limiting.
+fn is_rate_limited(request: &Request<'_>) -> bool {
+	!matches!(request.method(), Method::Get | Method::Head | Method::Options) &&
+		!request.uri().path().starts_with("/admin")
+}
+
+#[rocket::async_trait]
+impl Fairing for RateLimiter {
+	fn info(&self) -> Info {
+		Info { name: "Rate limiting", kind: Kind::Ignite | Kind::Request }
+	}
+
+	async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+		Ok(rocket.mount(RATE_LIMITED_PATH, routes![rate_limited]))
+	}
+
+	/// Takes a token from the bucket of the client making the request, and reroutes the request
+	/// if the bucket is empty.
+	///
+	/// Requests of clients whose IP is unknown are let through.
+	async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+		if !is_rate_limited(request) {
+			return;
+		}
+
+		let Some(ip) = self.client_ip(request) else { return };
+		if let Err(retry_after) = self.acquire(ip, Instant::now()) {
+			let uri = format!("{}/{}", RATE_LIMITED_PATH, retry_after);
+			if let Ok(uri) = Origin::parse_owned(uri) {
+				request.set_method(Method::Get);
+				request.set_uri(uri);
+			}
+		}
+	}
+}
+
+/// Responds to the requests which exceeded the rate limit.
+#[get("/<retry_after>")]
+fn rate_limited(retry_after: Timestamp) -> Error {
+	Error::RateLimited(retry_after)
+}
@@ -0,0 +1,91 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::*;
+use rocket::get;
+use shared::{
+	config::{config, PaymentInfo},
+	current_timestamp,
+	payment::{estimate_payment_fee, para_payment_info, payment_call_data},
+	registry::registered_para,
+	subscription::renewal,
+};
+use types::{Balance, ParaId, Timestamp};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReactivationCost {
+	/// The payment required to bring the subscription of the parachain current.
+	pub cost: Balance,
+	/// The hex encoded call which needs to be executed on the payment chain.
+	///
+	/// This is `None` in free mode where no payment is required.
+	pub call_data: Option<String>,
+	/// The expiry timestamp of the subscription once the payment is made.
+	pub expiry_timestamp: Timestamp,
+	/// The estimated fee of the payment extrinsic, which is paid on top of the cost.
+	///
+	/// Only estimated if enabled in the config.
+	pub estimated_fee: Option<Balance>,
+	/// Set when the fee estimation is enabled, but the payment node couldn't provide an estimate.
+	pub fee_estimation_unavailable: bool,
+}
+
+/// Query the cost of reactivating the subscription of a parachain.
+///
+/// Depending on the configured lapse policy the time during which the subscription was lapsed is
+/// either forgiven or has to be paid for.
+///
+/// When fee estimation is enabled the response also contains the estimated fee of the payment
+/// extrinsic.
+#[get("/reactivation-cost/<relay>/<para_id>")]
+pub async fn reactivation_cost(relay: &str, para_id: ParaId) -> Result<String, Error> {
+	let para = registered_para(relay.into(), para_id).ok_or(Error::NotRegistered)?;
+
+	let reactivation_cost = if let Some(payment_info) = config().payment_info {
+		let payment_info = para_payment_info(&para, &payment_info).map_err(Error::from)?;
+		let renewal = renewal(para.expiry_timestamp, &payment_info, current_timestamp())
+			.map_err(Error::PaymentValidationError)?;
+
+		let payment_info = PaymentInfo { cost: renewal.cost.to_string(), ..payment_info };
+		let call_data = payment_call_data(para.clone(), payment_info.clone())
+			.await
+			.map_err(Error::PaymentValidationError)?;
+
+		let estimated_fee = if payment_info.estimate_fees {
+			estimate_payment_fee(para, payment_info.clone()).await
+		} else {
+			None
+		};
+
+		ReactivationCost {
+			cost: renewal.cost,
+			call_data: Some(format!("0x{}", hex::encode(call_data))),
+			expiry_timestamp: renewal.expiry_timestamp,
+			estimated_fee,
+			fee_estimation_unavailable: payment_info.estimate_fees && estimated_fee.is_none(),
+		}
+	} else {
+		ReactivationCost {
+			cost: 0,
+			call_data: None,
+			expiry_timestamp: para.expiry_timestamp,
+			estimated_fee: None,
+			fee_estimation_unavailable: false,
+		}
+	};
+
+	serde_json::to_string(&reactivation_cost).map_err(|_| Error::InvalidData)
+}
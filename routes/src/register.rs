@@ -13,17 +13,23 @@
 // You should have received a copy of the GNU General Public License
 // along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::*;
+use crate::{
+	idempotency::{body_hash, Idempotency},
+	*,
+};
 use polkadot_core_primitives::BlockNumber;
 use rocket::{post, serde::json::Json};
 use shared::{
 	chaindata,
-	config::config,
+	config::{config, payment_exempt, PaymentInfo},
 	current_timestamp,
-	payment::validate_registration_payment,
+	payment::{
+		check_registration_payment, rpc_degraded, validate_batch_payment,
+		validate_registration_payment,
+	},
 	registry::{registered_para, registered_paras, update_registry},
 };
-use types::{ParaId, RelayChain};
+use types::{ParaId, Parachain, PaymentDetails, RelayChain, Timestamp};
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
@@ -32,16 +38,237 @@ pub struct RegistrationData {
 	pub para: (RelayChain, ParaId),
 	/// The block in which the payment occurred for the specific parachain.
 	///
-	/// In free mode (where payment is not required), this is ignored and can be `None`.
-	/// Otherwise, it should contain a valid block number.
+	/// In free mode (where payment is not required), or for parachains exempt from payment, this
+	/// is ignored and can be `None`. Otherwise, it should contain a valid block number.
+	pub payment_block_number: Option<BlockNumber>,
+	/// The number of blocks following `payment_block_number` in which the payment is also
+	/// searched for, in case the exact block isn't known.
+	///
+	/// This is capped at `MAX_PAYMENT_SEARCH_WINDOW`.
+	#[serde(default)]
+	pub payment_search_window: BlockNumber,
+	/// The rpc url of the parachain.
+	///
+	/// Only used when registering a parachain that isn't part of the chaindata, which is only
+	/// allowed if `allow_unknown_paras` is enabled.
+	#[serde(default)]
+	pub rpc_url: Option<String>,
+	/// The block from which the consumption of the parachain is backfilled once it gets
+	/// registered. If `None`, the consumption is only recorded from the latest finalized block
+	/// onwards.
+	#[serde(default)]
+	pub backfill_from: Option<BlockNumber>,
+	/// The subscription tier the parachain is registered for, which defines the cost of the
+	/// registration and how often the consumption is recorded.
+	///
+	/// If `None`, the parachain is registered for the default tier.
+	#[serde(default)]
+	pub tier: Option<String>,
+}
+
+/// The maximum number of parachains that can be registered in a single batch.
+pub const MAX_BATCH_PARAS: usize = 50;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct BatchRegistrationData {
+	/// The relay chain of the parachains getting registered.
+	pub relay_chain: RelayChain,
+	/// The parachains getting registered.
+	pub para_ids: Vec<ParaId>,
+	/// The block in which the payment occurred for all the parachains.
+	///
+	/// In free mode, or if all the parachains are exempt from payment, this is ignored and can be
+	/// `None`.
 	pub payment_block_number: Option<BlockNumber>,
+	/// The number of blocks following `payment_block_number` in which the payment is also
+	/// searched for, in case the exact block isn't known.
+	///
+	/// This is capped at `MAX_PAYMENT_SEARCH_WINDOW`.
+	#[serde(default)]
+	pub payment_search_window: BlockNumber,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct RegistrationOutcome {
+	/// The registered parachain.
+	pub para: (RelayChain, ParaId),
+	/// The name of the parachain.
+	pub name: String,
+	/// The timestamp at which the subscription of the parachain expires.
+	pub expiry_timestamp: Timestamp,
+	/// The payment with which the parachain is registered.
+	///
+	/// `None` in free mode.
+	pub payment: Option<PaymentDetails>,
+	/// Whether the registration was only validated, without being committed.
+	pub dry_run: bool,
 }
 
 /// Register a parachain for resource utilization tracking.
-#[post("/register_para", data = "<registration_data>")]
-pub async fn register_para(registration_data: Json<RegistrationData>) -> Result<(), Error> {
-	let (relay_chain, para_id) = registration_data.para.clone();
+///
+/// When `dry_run` is set the registration, including its payment, is only validated. Neither the
+/// parachain gets registered, nor the payment gets used, so that the registration can still be
+/// submitted afterwards.
+#[post("/register_para?<dry_run>", data = "<registration_data>")]
+pub async fn register_para(
+	registration_data: Json<RegistrationData>,
+	dry_run: Option<bool>,
+	idempotency: Idempotency<'_>,
+) -> Result<String, Error> {
+	idempotency
+		.run(body_hash(&*registration_data), async move {
+			let (relay_chain, para_id) = registration_data.para.clone();
+			let outcome = register(
+				relay_chain,
+				para_id,
+				registration_data.payment_block_number,
+				registration_data.payment_search_window,
+				registration_data.rpc_url.clone(),
+				registration_data.backfill_from,
+				registration_data.tier.clone(),
+				dry_run.unwrap_or_default(),
+			)
+			.await?;
+
+			serde_json::to_string(&outcome).map_err(|_| Error::InvalidData)
+		})
+		.await
+}
+
+/// Register multiple parachains of the same relay chain, which are paid for with a single
+/// payment.
+///
+/// Either all the parachains get registered, or none of them does, e.g. if any of them is already
+/// registered.
+#[post("/register_paras", data = "<batch>")]
+pub async fn register_paras(
+	batch: Json<BatchRegistrationData>,
+	idempotency: Idempotency<'_>,
+) -> Result<String, Error> {
+	idempotency.run(body_hash(&*batch), register_batch(batch)).await
+}
+
+async fn register_batch(batch: Json<BatchRegistrationData>) -> Result<String, Error> {
+	let BatchRegistrationData {
+		relay_chain,
+		para_ids,
+		payment_block_number,
+		payment_search_window,
+	} = batch.into_inner();
+
+	log::info!(
+		target: LOG_TARGET,
+		"{} - Attempting to register paras {:?}",
+		relay_chain, para_ids
+	);
+
+	if para_ids.len() > MAX_BATCH_PARAS {
+		return Err(Error::TooManyParas);
+	}
+
+	let mut unique_ids = para_ids.clone();
+	unique_ids.sort();
+	unique_ids.dedup();
+	if para_ids.is_empty() || unique_ids.len() != para_ids.len() {
+		return Err(Error::InvalidBatch);
+	}
+
+	let mut paras = registered_paras();
+
+	let mut batch = vec![];
+	for para_id in para_ids {
+		if registered_para(relay_chain.clone(), para_id).is_some() {
+			return Err(Error::AlreadyRegistered);
+		}
+
+		batch.push(
+			chaindata::get_para(relay_chain.clone(), para_id).map_err(Error::ChainDataError)?,
+		);
+	}
+
+	// Only the parachains which aren't exempt from payment are paid for.
+	let paying: Vec<Parachain> =
+		batch.iter().filter(|para| !payment_exempt(para)).cloned().collect();
+	let payment_info = config().payment_info.filter(|_| !paying.is_empty());
+	let (payment, subscription_duration) = if let Some(payment_info) = payment_info {
+		if rpc_degraded() {
+			return Err(Error::RpcDegraded);
+		}
+
+		let payment_block_number = payment_block_number.ok_or(Error::PaymentRequired)?;
+
+		let payment = validate_batch_payment(
+			&paying,
+			payment_info.clone(),
+			payment_block_number,
+			payment_search_window,
+		)
+		.await
+		.map_err(Error::from)?;
+
+		(Some(payment), payment_info.subscription_duration)
+	} else {
+		(None, Default::default())
+	};
+
+	let now = current_timestamp();
+	batch.iter_mut().for_each(|para| {
+		// The parachains exempt from payment are registered the same way as in free mode.
+		if payment_exempt(para) {
+			para.expiry_timestamp = now;
+		} else {
+			para.payment = payment.clone();
+			para.expiry_timestamp = now + subscription_duration;
+		}
+	});
+
+	let outcomes: Vec<RegistrationOutcome> = batch
+		.iter()
+		.map(|para| RegistrationOutcome {
+			para: (para.relay_chain.clone(), para.para_id),
+			name: para.name.clone(),
+			expiry_timestamp: para.expiry_timestamp,
+			payment: para.payment.clone(),
+			dry_run: false,
+		})
+		.collect();
+
+	paras.extend(batch);
+
+	// All the parachains are written at once, so that the batch is either registered as a whole
+	// or not at all.
+	if let Err(err) = update_registry(paras) {
+		log::error!(
+			target: LOG_TARGET,
+			"{} - Failed to register the batch of paras: {:?}",
+			relay_chain,
+			err
+		);
+		// Nothing got registered, even though the payment might be consumed already.
+		return Err(Error::InvalidData);
+	}
+
+	#[cfg(not(debug_assertions))]
+	shared::init_tracker();
 
+	serde_json::to_string(&outcomes).map_err(|_| Error::InvalidData)
+}
+
+/// Registers the parachain after validating its registration payment.
+///
+/// When `dry_run` is set nothing is committed.
+pub(crate) async fn register(
+	relay_chain: RelayChain,
+	para_id: ParaId,
+	payment_block_number: Option<BlockNumber>,
+	payment_search_window: BlockNumber,
+	rpc_url: Option<String>,
+	backfill_from: Option<BlockNumber>,
+	tier: Option<String>,
+	dry_run: bool,
+) -> Result<RegistrationOutcome, Error> {
 	log::info!(
 		target: LOG_TARGET,
 		"{}-{} - Attempting to register para",
@@ -54,15 +281,37 @@ pub async fn register_para(registration_data: Json<RegistrationData>) -> Result<
 		return Err(Error::AlreadyRegistered);
 	}
 
-	let mut para = chaindata::get_para(relay_chain, para_id).map_err(Error::ChainDataError)?;
+	let mut para = match (chaindata::get_para(relay_chain.clone(), para_id), rpc_url) {
+		(Ok(para), _) => para,
+		(Err(ChainDataError::ParaNotFound), Some(rpc_url)) if config().allow_unknown_paras =>
+			chaindata::get_unknown_para(relay_chain, para_id, rpc_url)
+				.await
+				.map_err(Error::ChainDataError)?,
+		(Err(err), _) => return Err(Error::ChainDataError(err)),
+	};
 
-	let subscription_duration = if let Some(payment_info) = config().payment_info {
-		let payment_block_number =
-			registration_data.payment_block_number.ok_or(Error::PaymentRequired)?;
+	if let Some(tier) = &tier {
+		// The payment is validated against the cost of the tier.
+		config()
+			.payment_info
+			.and_then(|info| info.tier(tier))
+			.ok_or(Error::UnknownTier)?;
+	}
+	para.tier = tier;
 
-		validate_registration_payment(para.clone(), payment_info.clone(), payment_block_number)
-			.await
-			.map_err(Error::PaymentValidationError)?;
+	let payment_info = config().payment_info.filter(|_| !payment_exempt(&para));
+	let subscription_duration = if let Some(payment_info) = payment_info {
+		let payment_block_number = payment_block_number.ok_or(Error::PaymentRequired)?;
+
+		let payment = validate_subscription_payment(
+			para.clone(),
+			payment_info.clone(),
+			payment_block_number,
+			payment_search_window,
+			dry_run,
+		)
+		.await?;
+		para.payment = Some(payment);
 
 		payment_info.subscription_duration
 	} else {
@@ -70,6 +319,18 @@ pub async fn register_para(registration_data: Json<RegistrationData>) -> Result<
 	};
 
 	para.expiry_timestamp = current_timestamp() + subscription_duration;
+	para.backfill_from = backfill_from;
+
+	let outcome = RegistrationOutcome {
+		para: (para.relay_chain.clone(), para.para_id),
+		name: para.name.clone(),
+		expiry_timestamp: para.expiry_timestamp,
+		payment: para.payment.clone(),
+		dry_run,
+	};
+	if dry_run {
+		return Ok(outcome);
+	}
 
 	paras.push(para.clone());
 
@@ -81,10 +342,42 @@ pub async fn register_para(registration_data: Json<RegistrationData>) -> Result<
 			para.para_id,
 			err
 		);
-	} else {
-		#[cfg(not(debug_assertions))]
-		shared::init_tracker();
+		// Nothing got registered, even though the payment might be consumed already.
+		return Err(Error::InvalidData);
 	}
 
-	Ok(())
+	#[cfg(not(debug_assertions))]
+	shared::init_tracker();
+
+	Ok(outcome)
+}
+
+/// Validates the payment for the subscription of a parachain, be it a registration or an
+/// extension, and notes it as consumed unless `dry_run` is set.
+pub(crate) async fn validate_subscription_payment(
+	para: Parachain,
+	payment_info: PaymentInfo,
+	payment_block_number: BlockNumber,
+	payment_search_window: BlockNumber,
+	dry_run: bool,
+) -> Result<PaymentDetails, Error> {
+	// Shed the load while the RPC is degraded. Tracking of the registered parachains
+	// continues regardless.
+	if rpc_degraded() {
+		return Err(Error::RpcDegraded);
+	}
+
+	if dry_run {
+		check_registration_payment(para, payment_info, payment_block_number, payment_search_window)
+			.await
+	} else {
+		validate_registration_payment(
+			para,
+			payment_info,
+			payment_block_number,
+			payment_search_window,
+		)
+		.await
+	}
+	.map_err(Error::from)
 }
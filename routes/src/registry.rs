@@ -13,14 +13,98 @@
 // You should have received a copy of the GNU General Public License
 // along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::Error;
+use crate::{consumption::parse_relay_chain, *};
 use rocket::get;
-use shared::registry::registered_paras;
+use shared::{
+	chaindata::{chaindata_paras, get_para_metadata},
+	registry::registered_paras,
+	subscription::{subscription_state, SubscriptionState},
+};
+use types::Parachain;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ListedParachain {
+	#[serde(flatten)]
+	pub para: Parachain,
+	/// The state of the subscription of the parachain.
+	pub status: SubscriptionState,
+	/// Whether the parachain is missing from the chaindata, e.g. after the chaindata was
+	/// refreshed. It is still tracked through the rpcs it was registered with.
+	///
+	/// `None` if the chaindata can't be read, in which case it is unknown.
+	#[serde(default)]
+	pub chaindata_missing: Option<bool>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct EnrichedParachain {
+	#[serde(flatten)]
+	pub para: Parachain,
+	/// The state of the subscription of the parachain.
+	pub status: SubscriptionState,
+	/// Whether the parachain is missing from the chaindata, in which case it has no metadata.
+	///
+	/// `None` if the chaindata can't be read, in which case it is unknown.
+	#[serde(default)]
+	pub chaindata_missing: Option<bool>,
+	/// URL of the parachain's logo.
+	pub logo: Option<String>,
+	/// The category of the parachain, e.g. `defi`.
+	pub category: Option<String>,
+}
 
 /// Query all the registered parachains.
-#[get("/registry")]
-pub fn registry() -> Result<String, Error> {
-	let registered_paras = registered_paras();
+///
+/// When `enrich` is set the parachains also contain their logo and category from the chaindata.
+/// When `relay_chain` is set only the parachains of that relay chain are returned.
+///
+/// Each parachain contains the `status` of its subscription, i.e. whether it is `Active`, in its
+/// `GracePeriod` or `Expired`, and whether it is missing from the chaindata.
+#[get("/registry?<enrich>&<relay_chain>")]
+pub fn registry(enrich: Option<bool>, relay_chain: Option<&str>) -> Result<String, Error> {
+	let relay_chain = relay_chain
+		.map(|relay| parse_relay_chain(relay).ok_or(Error::UnknownRelayChain))
+		.transpose()?;
+
+	let registered_paras: Vec<Parachain> = registered_paras()
+		.into_iter()
+		.filter(|para| relay_chain.as_ref().map_or(true, |relay| para.relay_chain == *relay))
+		.collect();
+	let chaindata = chaindata_paras();
+	let chaindata_missing = |para: &Parachain| {
+		chaindata
+			.as_ref()
+			.map(|paras| !paras.contains(&(para.relay_chain.clone(), para.para_id)))
+	};
+
+	if !enrich.unwrap_or_default() {
+		let listed: Vec<ListedParachain> = registered_paras
+			.into_iter()
+			.map(|para| ListedParachain {
+				status: subscription_state(&para),
+				chaindata_missing: chaindata_missing(&para),
+				para,
+			})
+			.collect();
+
+		return serde_json::to_string(&listed).map_err(|_| Error::InvalidData);
+	}
+
+	let enriched: Vec<EnrichedParachain> = registered_paras
+		.into_iter()
+		.map(|para| {
+			let metadata = get_para_metadata(para.relay_chain.clone(), para.para_id).ok();
+			EnrichedParachain {
+				status: subscription_state(&para),
+				chaindata_missing: chaindata_missing(&para),
+				logo: metadata.as_ref().and_then(|m| m.logo.clone()),
+				category: metadata.and_then(|m| m.category),
+				para,
+			}
+		})
+		.collect();
 
-	serde_json::to_string(&registered_paras).map_err(|_| Error::InvalidData)
+	serde_json::to_string(&enriched).map_err(|_| Error::InvalidData)
 }
@@ -0,0 +1,67 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::*;
+use ed25519_dalek::{Signer, SigningKey};
+use rocket::{get, response};
+use shared::config::config;
+
+/// The header containing the hex encoded Ed25519 signature of the response body.
+pub const SIGNATURE_HEADER: &str = "X-Signature";
+
+/// A response body which gets signed with the server key when signing is enabled.
+pub struct Signed(pub String);
+
+impl<'r> Responder<'r, 'static> for Signed {
+	fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+		let signature = signing_key().map(|key| key.sign(self.0.as_bytes()));
+
+		let mut response = Response::build_from(self.0.respond_to(request)?);
+		if let Some(signature) = signature {
+			response
+				.raw_header(SIGNATURE_HEADER, format!("0x{}", hex::encode(signature.to_bytes())));
+		}
+
+		response.ok()
+	}
+}
+
+/// Query the public key which can be used to verify the signed responses.
+#[get("/pubkey")]
+pub fn pubkey() -> Result<String, Error> {
+	let key = signing_key().ok_or(Error::SigningDisabled)?;
+	let public_key = format!("0x{}", hex::encode(key.verifying_key().to_bytes()));
+
+	serde_json::to_string(&public_key).map_err(|_| Error::InvalidData)
+}
+
+fn signing_key() -> Option<SigningKey> {
+	let path = config().signing_key?;
+
+	let seed = std::fs::read_to_string(&path)
+		.ok()
+		.and_then(|content| hex::decode(content.trim().trim_start_matches("0x")).ok())
+		.and_then(|seed| <[u8; 32]>::try_from(seed).ok());
+
+	if seed.is_none() {
+		log::error!(
+			target: LOG_TARGET,
+			"Failed to load the signing key from {}",
+			path
+		);
+	}
+
+	seed.map(|seed| SigningKey::from_bytes(&seed))
+}
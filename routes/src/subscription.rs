@@ -0,0 +1,99 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::{consumption::parse_para_identifier, *};
+use rocket::get;
+use shared::{
+	consumption::{tracking_activity, TrackingActivity},
+	registry::{registered_para, registered_paras},
+	subscription::{subscription_state, SubscriptionState},
+};
+use std::collections::BTreeMap;
+use types::{Balance, ParaId, Parachain, RelayChain, Timestamp};
+
+/// The maximum number of parachains that can be queried in a single `/subscription/status`
+/// request.
+pub const MAX_STATUS_PARAS: usize = 50;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SubscriptionStatus {
+	/// The parachain to which the status belongs.
+	pub para: (RelayChain, ParaId),
+	/// The timestamp when the subscription expires.
+	pub expiry_timestamp: Timestamp,
+	/// Whether the subscription is still active.
+	///
+	/// Subscriptions never expire in free mode, nor for the parachains exempt from payment.
+	pub active: bool,
+	/// Whether no blocks were recorded for the parachain recently, while other parachains are
+	/// still being tracked.
+	pub dormant: bool,
+	/// The credit accumulated through micro-payments.
+	pub credit: Balance,
+}
+
+impl SubscriptionStatus {
+	pub fn new(para: &Parachain, activity: &TrackingActivity) -> Self {
+		SubscriptionStatus {
+			para: (para.relay_chain.clone(), para.para_id),
+			expiry_timestamp: para.expiry_timestamp,
+			active: subscription_state(para) == SubscriptionState::Active,
+			dormant: activity.dormant.contains(para),
+			credit: para.credit,
+		}
+	}
+}
+
+/// Query the subscription status of a parachain.
+#[get("/subscription/<relay>/<para_id>")]
+pub fn subscription_status(relay: &str, para_id: ParaId) -> Result<String, Error> {
+	let para = registered_para(relay.into(), para_id).ok_or(Error::NotRegistered)?;
+	let activity = tracking_activity(&registered_paras());
+
+	serde_json::to_string(&SubscriptionStatus::new(&para, &activity))
+		.map_err(|_| Error::InvalidData)
+}
+
+/// Query the subscription status of multiple parachains.
+///
+/// The parachains are provided as a comma separated list of `<relay>:<para_id>` identifiers. The
+/// response maps each identifier to the status of the parachain, or `null` if it isn't registered.
+#[get("/subscription/status?<paras>")]
+pub fn subscription_statuses(paras: &str) -> Result<String, Error> {
+	let identifiers: Vec<&str> = paras.split(',').map(str::trim).collect();
+	if identifiers.len() > MAX_STATUS_PARAS {
+		return Err(Error::TooManyParas);
+	}
+
+	let registered = registered_paras();
+	let activity = tracking_activity(&registered);
+
+	let statuses = identifiers
+		.into_iter()
+		.map(|identifier| {
+			let (relay, para_id) =
+				parse_para_identifier(identifier).ok_or(Error::InvalidParaIdentifier)?;
+			let status = registered
+				.iter()
+				.find(|para| para.relay_chain == relay && para.para_id == para_id)
+				.map(|para| SubscriptionStatus::new(para, &activity));
+
+			Ok((identifier.to_string(), status))
+		})
+		.collect::<Result<BTreeMap<String, Option<SubscriptionStatus>>, Error>>()?;
+
+	serde_json::to_string(&statuses).map_err(|_| Error::InvalidData)
+}
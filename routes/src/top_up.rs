@@ -0,0 +1,87 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::{
+	idempotency::{body_hash, Idempotency},
+	*,
+};
+use polkadot_core_primitives::BlockNumber;
+use rocket::{post, serde::json::Json};
+use shared::{
+	config::config,
+	payment::validate_micro_payment,
+	registry::{registered_para, registered_paras, update_registry},
+	subscription::add_credit,
+};
+use types::{ParaId, RelayChain};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct TopUpData {
+	/// The parachain for which the credit is getting topped up.
+	pub para: (RelayChain, ParaId),
+	/// The block in which the micro-payments occurred for the specific parachain.
+	pub payment_block_number: BlockNumber,
+}
+
+/// Add the micro-payments made for a parachain to its credit.
+///
+/// The accumulated credit can be drawn from when extending the subscription.
+#[post("/top-up", data = "<data>")]
+pub async fn top_up(data: Json<TopUpData>, idempotency: Idempotency<'_>) -> Result<(), Error> {
+	idempotency.run(body_hash(&*data), add_micro_payments(data)).await
+}
+
+async fn add_micro_payments(data: Json<TopUpData>) -> Result<(), Error> {
+	let (relay_chain, para_id) = data.para.clone();
+
+	log::info!(
+		target: LOG_TARGET,
+		"{}-{} - Attempting to top up the credit of para",
+		relay_chain, para_id
+	);
+
+	let payment_info = config()
+		.payment_info
+		.filter(|payment_info| payment_info.micro_payments)
+		.ok_or(Error::MicroPaymentsDisabled)?;
+
+	let para = registered_para(relay_chain, para_id).ok_or(Error::NotRegistered)?;
+
+	let amount = validate_micro_payment(para.clone(), payment_info, data.payment_block_number)
+		.await
+		.map_err(Error::from)?;
+
+	let mut paras = registered_paras();
+
+	let Some(registered) = paras.iter_mut().find(|p| **p == para) else {
+		return Err(Error::NotRegistered);
+	};
+	add_credit(registered, amount, data.payment_block_number)
+		.map_err(Error::PaymentValidationError)?;
+
+	if let Err(err) = update_registry(paras) {
+		log::error!(
+			target: LOG_TARGET,
+			"{}-{} Failed to top up the credit of para: {:?}",
+			para.relay_chain,
+			para.para_id,
+			err
+		);
+		return Err(Error::InvalidData);
+	}
+
+	Ok(())
+}
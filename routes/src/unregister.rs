@@ -0,0 +1,119 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::{
+	idempotency::{body_hash, Idempotency},
+	update::ensure_ownership,
+	*,
+};
+use polkadot_core_primitives::BlockNumber;
+use rocket::{post, serde::json::Json};
+use shared::{
+	config::{config, payment_exempt},
+	registry::{registered_para, registered_paras, update_registry},
+};
+use types::{ParaId, Parachain, RelayChain};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct UnregistrationData {
+	/// The parachain getting unregistered.
+	pub para: (RelayChain, ParaId),
+	/// The block containing the ownership proof of the registration.
+	///
+	/// Only required in payment mode, unless `allow_unproven_unregistration` is enabled. The block
+	/// should contain a remark made by the account which paid for the registration, with the
+	/// content returned by `unregister_remark`. The block has to succeed the block containing the
+	/// payment of the registration, so that a proof made for a previous registration of the
+	/// parachain can't be replayed.
+	#[serde(default)]
+	pub proof_block_number: Option<BlockNumber>,
+}
+
+/// Unregister a parachain, which stops its consumption from being tracked.
+#[post("/unregister_para", data = "<data>")]
+pub async fn unregister_para(
+	data: Json<UnregistrationData>,
+	idempotency: Idempotency<'_>,
+) -> Result<(), Error> {
+	idempotency.run(body_hash(&*data), unregister(data)).await
+}
+
+async fn unregister(data: Json<UnregistrationData>) -> Result<(), Error> {
+	let (relay_chain, para_id) = data.para.clone();
+
+	log::info!(
+		target: LOG_TARGET,
+		"{}-{} - Attempting to unregister para",
+		relay_chain, para_id
+	);
+
+	let para = registered_para(relay_chain.clone(), para_id).ok_or(Error::NotRegistered)?;
+
+	// Same as for their registration, payment exempt parachains don't require a payment, so they
+	// have no owner which could prove the ownership.
+	match config().payment_info.filter(|_| !payment_exempt(&para)) {
+		Some(payment_info) if !payment_info.allow_unproven_unregistration => {
+			ensure_proof_succeeds_payment(&para, data.proof_block_number)?;
+
+			let remark = unregister_remark(&para);
+			ensure_ownership(&para, payment_info, data.proof_block_number, remark).await?;
+		},
+		_ => {},
+	}
+
+	let mut paras = registered_paras();
+	paras.retain(|p| !(p.relay_chain == relay_chain && p.para_id == para_id));
+
+	if let Err(err) = update_registry(paras) {
+		log::error!(
+			target: LOG_TARGET,
+			"{}-{} - Failed to unregister para: {:?}",
+			relay_chain,
+			para_id,
+			err
+		);
+		return Err(Error::InvalidData);
+	}
+
+	#[cfg(not(debug_assertions))]
+	shared::init_tracker();
+
+	Ok(())
+}
+
+/// Ensures that the ownership proof isn't contained in a block preceding the payment of the
+/// registration.
+///
+/// The remark doesn't change across registrations, so this binds the proof to the blocks after the
+/// payment, up to the `payment_max_age` enforced for all the proofs.
+fn ensure_proof_succeeds_payment(
+	para: &Parachain,
+	proof_block_number: Option<BlockNumber>,
+) -> Result<(), Error> {
+	match (&para.payment, proof_block_number) {
+		(Some(payment), Some(proof_block_number)) if proof_block_number <= payment.block_number =>
+			Err(Error::StaleOwnershipProof),
+		_ => Ok(()),
+	}
+}
+
+/// Returns the content of the remark proving the ownership of the registration when
+/// unregistering the parachain.
+pub fn unregister_remark(para: &Parachain) -> Vec<u8> {
+	format!("regionx-weigher::unregister:{}:{}", para.relay_chain, para.para_id)
+		.as_bytes()
+		.to_vec()
+}
@@ -0,0 +1,182 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::*;
+use polkadot_core_primitives::BlockNumber;
+use rocket::{patch, serde::json::Json};
+use shared::{
+	chaindata::ensure_websocket_url,
+	config::{config, PaymentInfo},
+	payment::{rpc_degraded, validate_ownership_proof},
+	registry::{registered_paras, update_registry},
+};
+use types::{ParaId, Parachain, RelayChain};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", deny_unknown_fields)]
+pub struct RegistrationChanges {
+	/// The new name of the parachain.
+	pub name: Option<String>,
+	/// The new rpc urls of the parachain.
+	pub rpcs: Option<Vec<String>>,
+	/// The relay chain of a registration is immutable, so this can only be set to its current
+	/// value.
+	pub relay_chain: Option<RelayChain>,
+	/// The `ParaId` of a registration is immutable, so this can only be set to its current value.
+	pub para_id: Option<ParaId>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "rocket::serde", deny_unknown_fields)]
+pub struct RegistrationUpdate {
+	/// The fields of the registration getting updated. Fields which are `None` are left as is.
+	pub changes: RegistrationChanges,
+	/// The block containing the ownership proof of the registration.
+	///
+	/// In free mode this is ignored and can be `None`. Otherwise, the block should contain a
+	/// remark made by the account which paid for the registration, with the content returned by
+	/// `update_remark`.
+	#[serde(default)]
+	pub proof_block_number: Option<BlockNumber>,
+}
+
+/// Update the mutable fields of a registration, without affecting its subscription or tracking.
+#[patch("/registration/<relay>/<para_id>", data = "<update>")]
+pub async fn update_registration(
+	relay: &str,
+	para_id: ParaId,
+	update: Json<RegistrationUpdate>,
+) -> Result<(), Error> {
+	let relay_chain: RelayChain = relay.into();
+	let RegistrationUpdate { changes, proof_block_number } = update.into_inner();
+
+	let mut paras = registered_paras();
+	let para = paras
+		.iter_mut()
+		.find(|para| para.relay_chain == relay_chain && para.para_id == para_id)
+		.ok_or(Error::NotRegistered)?;
+
+	if changes
+		.relay_chain
+		.as_ref()
+		.map_or(false, |relay_chain| *relay_chain != para.relay_chain) ||
+		changes.para_id.map_or(false, |para_id| para_id != para.para_id)
+	{
+		return Err(Error::ImmutableField);
+	}
+
+	if let Some(rpcs) = &changes.rpcs {
+		if rpcs.is_empty() {
+			return Err(Error::InvalidData);
+		}
+		// The trackers connect to the rpcs, so they have to be WebSocket urls as well.
+		rpcs.iter()
+			.try_for_each(|rpc| ensure_websocket_url(rpc))
+			.map_err(Error::ChainDataError)?;
+	}
+
+	if let Some(payment_info) = config().payment_info {
+		ensure_proof_succeeds_last_update(para, proof_block_number)?;
+
+		let remark = update_remark(para, &changes);
+		ensure_ownership(para, payment_info, proof_block_number, remark).await?;
+		para.last_update_block = proof_block_number;
+	}
+
+	log::info!(
+		target: LOG_TARGET,
+		"{}-{} - Updating registration: {:?}",
+		para.relay_chain,
+		para.para_id,
+		changes
+	);
+
+	if let Some(name) = changes.name {
+		para.name = name;
+	}
+	if let Some(rpcs) = changes.rpcs {
+		para.rpcs = rpcs;
+	}
+
+	if let Err(err) = update_registry(paras) {
+		log::error!(
+			target: LOG_TARGET,
+			"{}-{} - Failed to update the registration: {:?}",
+			relay_chain,
+			para_id,
+			err
+		);
+		return Err(Error::InvalidData);
+	}
+
+	// The tracker is restarted so that it picks up the new rpc urls.
+	#[cfg(not(debug_assertions))]
+	shared::init_tracker();
+
+	Ok(())
+}
+
+/// Ensures that the specified block contains a remark with the given content, made by the account
+/// which paid for the registration of the parachain.
+pub(crate) async fn ensure_ownership(
+	para: &Parachain,
+	payment_info: PaymentInfo,
+	proof_block_number: Option<BlockNumber>,
+	remark: Vec<u8>,
+) -> Result<(), Error> {
+	let proof_block_number = proof_block_number.ok_or(Error::OwnershipProofRequired)?;
+	// The owner is the account which paid for the registration.
+	let payer = para.payment.clone().and_then(|p| p.payer).ok_or(Error::OwnerUnknown)?;
+
+	if rpc_degraded() {
+		return Err(Error::RpcDegraded);
+	}
+
+	validate_ownership_proof(para.clone(), payment_info, proof_block_number, payer, remark)
+		.await
+		.map_err(Error::from)
+}
+
+/// Ensures that the ownership proof was made after the payment of the registration, as well as
+/// after the proof of its latest update.
+///
+/// The remark only commits to the changes, so this prevents replaying the proof of an update,
+/// e.g. to revert the rpcs to the ones of a previous update.
+fn ensure_proof_succeeds_last_update(
+	para: &Parachain,
+	proof_block_number: Option<BlockNumber>,
+) -> Result<(), Error> {
+	let last_proven = para
+		.payment
+		.as_ref()
+		.map(|payment| payment.block_number)
+		.max(para.last_update_block);
+	match (last_proven, proof_block_number) {
+		(Some(last_proven), Some(proof_block_number)) if proof_block_number <= last_proven =>
+			Err(Error::StaleOwnershipProof),
+		_ => Ok(()),
+	}
+}
+
+/// Returns the content of the remark proving the ownership of the registration for the specified
+/// changes.
+///
+/// The remark commits to the changes, so that a proof can't be reused for a different update.
+pub fn update_remark(para: &Parachain, changes: &RegistrationChanges) -> Vec<u8> {
+	let changes = serde_json::to_string(changes).unwrap_or_default();
+	format!("regionx-weigher::update:{}:{}:{}", para.relay_chain, para.para_id, changes)
+		.as_bytes()
+		.to_vec()
+}
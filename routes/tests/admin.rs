@@ -0,0 +1,271 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use rocket::{
+	http::{ContentType, Header, Status},
+	local::blocking::{Client, LocalResponse},
+	routes,
+};
+use routes::{
+	admin::{
+		link_para_migration, list_tracker_errors, pause, refresh_chaindata, resume, PauseData,
+		ADMIN_KEY_HEADER,
+	},
+	consumption::{consumption, group_consumption, AggregatedData, Grouping},
+	health::{health, Health},
+	Error, ErrorResponse,
+};
+use shared::{
+	chaindata::{get_para, read_chaindata_paras, write_chaindata, ChainDataError},
+	config::config,
+	migration::ParaMigration,
+	registry::registered_para,
+	tracker::{note_tracker_error, TrackerError},
+};
+use types::RelayChain::*;
+
+mod mock;
+use mock::{mock_consumption, MockEnvironment};
+
+const ADMIN_KEY: &str = "mock-admin-key-secret";
+
+#[test]
+fn linking_migration_requires_admin_key() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![link_para_migration]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let migration = ParaMigration { from: (Polkadot, 2004), to: (Kusama, 2004) };
+
+		let response = client
+			.post("/admin/link-migration")
+			.header(ContentType::JSON)
+			.body(serde_json::to_string(&migration).unwrap())
+			.dispatch();
+		assert_eq!(response.status(), Status::Unauthorized);
+		assert_eq!(parse_err_response(response), Error::Unauthorized);
+
+		let response = client
+			.post("/admin/link-migration")
+			.header(ContentType::JSON)
+			.header(Header::new(ADMIN_KEY_HEADER, "invalid-key"))
+			.body(serde_json::to_string(&migration).unwrap())
+			.dispatch();
+		assert_eq!(response.status(), Status::Unauthorized);
+		assert_eq!(parse_err_response(response), Error::Unauthorized);
+
+		// Nor is a key only differing in its last character:
+		let response = client
+			.post("/admin/link-migration")
+			.header(ContentType::JSON)
+			.header(Header::new(ADMIN_KEY_HEADER, "mock-admin-key-secrer"))
+			.body(serde_json::to_string(&migration).unwrap())
+			.dispatch();
+		assert_eq!(response.status(), Status::Unauthorized);
+	});
+}
+
+#[test]
+fn following_migration_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![link_para_migration, consumption]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let old_para = get_para(Polkadot, 2004).unwrap();
+		let para = get_para(Polkadot, 2000).unwrap();
+
+		let migration = ParaMigration { from: (Polkadot, 2004), to: (Polkadot, 2000) };
+		let response = link(&client, &migration);
+		assert_eq!(response.status(), Status::Ok);
+
+		// The history is only merged when requested:
+		let response = client.get("/consumption/polkadot/2000").dispatch();
+		assert_eq!(
+			parse_ok_response(response),
+			group_consumption(
+				mock_consumption().get(&para).unwrap().clone(),
+				Grouping::BlockNumber
+			)
+		);
+
+		let response = client.get("/consumption/polkadot/2000?follow_migration=true").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		let mut history = mock_consumption().get(&old_para).unwrap().clone();
+		history.extend(mock_consumption().get(&para).unwrap().clone());
+		assert_eq!(parse_ok_response(response), group_consumption(history, Grouping::BlockNumber));
+	});
+}
+
+#[test]
+fn invalid_migrations_are_rejected() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![link_para_migration]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		// A para cannot migrate to itself:
+		let response =
+			link(&client, &ParaMigration { from: (Polkadot, 2000), to: (Polkadot, 2000) });
+		assert_eq!(parse_err_response(response), Error::InvalidMigration);
+
+		let response = link(&client, &ParaMigration { from: (Polkadot, 2000), to: (Kusama, 2000) });
+		assert_eq!(response.status(), Status::Ok);
+
+		// An identity can only be migrated from once:
+		let response = link(&client, &ParaMigration { from: (Polkadot, 2000), to: (Kusama, 3000) });
+		assert_eq!(parse_err_response(response), Error::InvalidMigration);
+
+		// Linking must not introduce a cycle:
+		let response = link(&client, &ParaMigration { from: (Kusama, 2000), to: (Polkadot, 2000) });
+		assert_eq!(parse_err_response(response), Error::InvalidMigration);
+	});
+}
+
+#[test]
+fn pausing_and_resuming_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![pause, resume, health]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let pause_data = PauseData { para: (Polkadot, 2000) };
+
+		// Requires the admin key:
+		let response = client
+			.post("/admin/pause")
+			.header(ContentType::JSON)
+			.body(serde_json::to_string(&pause_data).unwrap())
+			.dispatch();
+		assert_eq!(response.status(), Status::Unauthorized);
+
+		let response = admin_post(&client, "/admin/pause", &pause_data);
+		assert_eq!(response.status(), Status::Ok);
+		assert!(registered_para(Polkadot, 2000).unwrap().paused);
+
+		let health: Health =
+			serde_json::from_str(&client.get("/health").dispatch().into_string().unwrap()).unwrap();
+		assert_eq!(health.paused, vec![(Polkadot, 2000)]);
+
+		let response = admin_post(&client, "/admin/resume", &pause_data);
+		assert_eq!(response.status(), Status::Ok);
+		assert!(!registered_para(Polkadot, 2000).unwrap().paused);
+
+		// Only registered paras can be paused:
+		let response = admin_post(&client, "/admin/pause", &PauseData { para: (Kusama, 2000) });
+		assert_eq!(parse_err_response(response), Error::NotRegistered);
+	});
+}
+
+#[test]
+fn tracker_errors_are_persisted() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![list_tracker_errors]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let para = get_para(Polkadot, 2000).unwrap();
+		let other = get_para(Polkadot, 2004).unwrap();
+
+		for message in ["first", "second", "third"] {
+			note_tracker_error(0, &para, message.to_string()).unwrap();
+		}
+		note_tracker_error(0, &other, "other".to_string()).unwrap();
+
+		// Requires the admin key:
+		let response = client.get("/admin/tracker_errors").dispatch();
+		assert_eq!(response.status(), Status::Unauthorized);
+
+		let response = client
+			.get("/admin/tracker_errors")
+			.header(Header::new(ADMIN_KEY_HEADER, ADMIN_KEY))
+			.dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		let errors: Vec<TrackerError> =
+			serde_json::from_str(&response.into_string().unwrap()).unwrap();
+
+		// The mock config keeps the three most recent errors:
+		let summary: Vec<(&str, u32)> =
+			errors.iter().map(|e| (e.message.as_str(), e.reconnect_attempts)).collect();
+		assert_eq!(summary, vec![("second", 1), ("third", 2), ("other", 0)]);
+		assert_eq!(errors[2].para, (Polkadot, 2004));
+	});
+}
+
+#[test]
+fn refreshing_chaindata_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![refresh_chaindata]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let response = client.post("/admin/refresh-chaindata").dispatch();
+		assert_eq!(response.status(), Status::Unauthorized);
+
+		// The mock config has no `chaindata_url` to refresh from:
+		let response = client
+			.post("/admin/refresh-chaindata")
+			.header(Header::new(ADMIN_KEY_HEADER, ADMIN_KEY))
+			.dispatch();
+		assert_eq!(response.status(), Status::Forbidden);
+		assert_eq!(
+			parse_err_response(response),
+			Error::ChainDataError(ChainDataError::RefreshDisabled)
+		);
+
+		// The chaindata of the repository is left untouched by replacing a copy of it:
+		let dir = std::env::temp_dir().join(format!("refresh-chaindata-{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let path = dir.join("chaindata.json");
+		let path = path.to_str().unwrap();
+		let content = std::fs::read_to_string(config().chaindata).unwrap();
+		std::fs::write(path, &content).unwrap();
+
+		// Invalid chaindata is never stored:
+		assert_eq!(write_chaindata(path, "invalid"), Err(ChainDataError::RefreshFailed));
+		assert_eq!(std::fs::read_to_string(path).unwrap(), content);
+
+		let paras: Vec<serde_json::Value> = serde_json::from_str(&content).unwrap();
+		assert_eq!(write_chaindata(path, &content), Ok(paras.len()));
+		assert_eq!(std::fs::read_to_string(path).unwrap(), content);
+		assert!(read_chaindata_paras(path).unwrap().contains(&(Polkadot, 2000)));
+		std::fs::remove_dir_all(dir).unwrap();
+	});
+}
+
+fn admin_post<'a>(client: &'a Client, uri: &'static str, data: &PauseData) -> LocalResponse<'a> {
+	client
+		.post(uri)
+		.header(ContentType::JSON)
+		.header(Header::new(ADMIN_KEY_HEADER, ADMIN_KEY))
+		.body(serde_json::to_string(data).unwrap())
+		.dispatch()
+}
+
+fn link<'a>(client: &'a Client, migration: &ParaMigration) -> LocalResponse<'a> {
+	client
+		.post("/admin/link-migration")
+		.header(ContentType::JSON)
+		.header(Header::new(ADMIN_KEY_HEADER, ADMIN_KEY))
+		.body(serde_json::to_string(migration).unwrap())
+		.dispatch()
+}
+
+fn parse_ok_response<'a>(response: LocalResponse<'a>) -> Vec<AggregatedData> {
+	let body = response.into_string().unwrap();
+	serde_json::from_str(&body).expect("can't parse value")
+}
+
+fn parse_err_response<'a>(response: LocalResponse<'a>) -> Error {
+	let body = response.into_string().unwrap();
+	let response: ErrorResponse = serde_json::from_str(&body).expect("can't parse error");
+	response.error.into()
+}
@@ -0,0 +1,77 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use shared::{
+	alert::{WeightAlert, WeightMonitor},
+	chaindata::get_para,
+	config::{weight_threshold, WeightThreshold},
+};
+use types::{RelayChain::*, WeightConsumption};
+
+#[test]
+fn weight_thresholds_are_configured_per_para() {
+	let para = get_para(Polkadot, 2000).unwrap();
+	assert_eq!(
+		weight_threshold(&para),
+		Some(WeightThreshold { para: (Polkadot, 2000), threshold: 0.9, consecutive_blocks: 3 })
+	);
+
+	assert_eq!(weight_threshold(&get_para(Polkadot, 2004).unwrap()), None);
+}
+
+#[test]
+fn weight_alert_requires_consecutive_blocks() {
+	let para = get_para(Polkadot, 2000).unwrap();
+	let threshold = weight_threshold(&para).unwrap();
+	let mut monitor = WeightMonitor::new(para, threshold);
+
+	let consumption = |block_number: u32, ref_time: f32, proof_size: f32| WeightConsumption {
+		block_number,
+		timestamp: 0,
+		ref_time: Some((ref_time, 0.0, 0.25).into()),
+		proof_size: Some((proof_size, 0.0, 0.0).into()),
+		block_hash: None,
+		inherent_split: None,
+	};
+
+	// The streak is reset by a block below the threshold:
+	assert_eq!(monitor.observe(&consumption(1, 0.75, 0.1)), None);
+	assert_eq!(monitor.observe(&consumption(2, 0.75, 0.1)), None);
+	assert_eq!(monitor.observe(&consumption(3, 0.25, 0.1)), None);
+
+	// Either dimension exceeding the threshold counts, with the dispatch classes summed up:
+	assert_eq!(monitor.observe(&consumption(4, 0.75, 0.1)), None);
+	assert_eq!(monitor.observe(&consumption(5, 0.25, 0.95)), None);
+	assert_eq!(
+		monitor.observe(&consumption(6, 0.75, 0.1)),
+		Some(WeightAlert {
+			relay_chain: Polkadot,
+			para_id: 2000,
+			block_number: 6,
+			ref_time: Some(1.0),
+			proof_size: Some(0.1),
+			threshold: 0.9,
+			consecutive_blocks: 3,
+		})
+	);
+
+	// The alert isn't repeated while the streak continues, but is once a new one is long enough:
+	assert_eq!(monitor.observe(&consumption(7, 0.75, 0.1)), None);
+	assert_eq!(monitor.observe(&consumption(8, 0.25, 0.1)), None);
+	for block_number in 9..11 {
+		assert_eq!(monitor.observe(&consumption(block_number, 0.75, 0.1)), None);
+	}
+	assert!(monitor.observe(&consumption(11, 0.75, 0.1)).is_some());
+}
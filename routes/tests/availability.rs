@@ -0,0 +1,101 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use rocket::{
+	http::Status,
+	local::blocking::{Client, LocalResponse},
+	routes,
+};
+use routes::{
+	availability::{availability, Availability},
+	Error, ErrorResponse,
+};
+use shared::{chaindata::get_para, consumption::write_consumption, current_timestamp};
+use types::{RelayChain::*, WeightConsumption};
+
+mod mock;
+use mock::MockEnvironment;
+
+#[test]
+fn availability_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![availability]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		// The mock consumption was recorded long ago, so it is outside of the window:
+		let response = client.get("/consumption/polkadot/2000/availability?window=60").dispatch();
+		assert_eq!(
+			parse_ok_response(response),
+			Availability {
+				window: 60,
+				expected_blocks: 10,
+				recorded_blocks: 0,
+				availability: 0.0,
+				gaps: 0
+			}
+		);
+
+		// Record the last 10 blocks, except for block 105:
+		let para = get_para(Polkadot, 2000).unwrap();
+		let now = current_timestamp() * 1000;
+		for block_number in (100..110).filter(|block_number| *block_number != 105) {
+			let consumption = WeightConsumption {
+				block_number,
+				timestamp: now - (110 - block_number as u64) * 5000,
+				ref_time: Some((0.1, 0.1, 0.1).into()),
+				proof_size: Some((0.1, 0.1, 0.1).into()),
+				block_hash: None,
+				inherent_split: None,
+			};
+			write_consumption(para.clone(), consumption, None).unwrap();
+		}
+
+		// The mock config has a block time of 6 seconds for Polkadot:
+		let response = client.get("/consumption/polkadot/2000/availability?window=60").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(
+			parse_ok_response(response),
+			Availability {
+				window: 60,
+				expected_blocks: 10,
+				recorded_blocks: 9,
+				availability: 90.0,
+				gaps: 1
+			}
+		);
+	});
+}
+
+#[test]
+fn availability_of_unregistered_para_fails() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![availability]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let response = client.get("/consumption/polkadot/2006/availability").dispatch();
+		assert_eq!(parse_err_response(response), Error::NotRegistered);
+	});
+}
+
+fn parse_ok_response<'a>(response: LocalResponse<'a>) -> Availability {
+	let body = response.into_string().unwrap();
+	serde_json::from_str(&body).expect("can't parse value")
+}
+
+fn parse_err_response<'a>(response: LocalResponse<'a>) -> Error {
+	let body = response.into_string().unwrap();
+	let response: ErrorResponse = serde_json::from_str(&body).expect("can't parse error");
+	response.error.into()
+}
@@ -0,0 +1,75 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+#![cfg(feature = "chart")]
+
+use rocket::{
+	http::{ContentType, Status},
+	local::blocking::Client,
+	routes,
+};
+use routes::{chart::consumption_chart, ErrorResponse};
+
+mod mock;
+use mock::MockEnvironment;
+
+#[test]
+fn rendering_svg_chart_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![consumption_chart]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let response =
+			client.get("/consumption/polkadot/2000/chart?width=300&height=200").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(response.content_type(), Some(ContentType::SVG));
+
+		let svg = response.into_string().unwrap();
+		assert!(svg.contains("<svg"));
+		assert!(svg.contains("width=\"300\""));
+	});
+}
+
+#[test]
+fn rendering_png_chart_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![consumption_chart]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let response = client
+			.get("/consumption/polkadot/2000/chart?format=png&metric=proof_size")
+			.dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(response.content_type(), Some(ContentType::PNG));
+
+		let png = response.into_bytes().unwrap();
+		assert!(png.starts_with(b"\x89PNG"));
+	});
+}
+
+#[test]
+fn chart_of_unregistered_para_fails() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![consumption_chart]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let response = client.get("/consumption/polkadot/42/chart").dispatch();
+		assert_eq!(response.status(), Status::NotFound);
+		assert_eq!(response.content_type(), Some(ContentType::JSON));
+
+		let body: ErrorResponse = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+		assert_eq!(body, ErrorResponse { error: "NotRegistered".to_string(), code: 404 });
+	});
+}
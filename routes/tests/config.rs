@@ -0,0 +1,106 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use shared::config::{config, validate, ConfigError};
+use types::RelayChain::*;
+
+#[test]
+fn config_validation_works() {
+	// The mock config is valid:
+	assert_eq!(validate(), Ok(()));
+
+	let invalid = |update: fn(&mut shared::config::Config)| {
+		let mut config = config();
+		update(&mut config);
+		config.validate()
+	};
+
+	assert_eq!(
+		invalid(|config| config.payment_info.as_mut().unwrap().cost = "1.5 DOT".into()),
+		Err(ConfigError::InvalidCost("payment_info.cost".into()))
+	);
+	assert_eq!(
+		invalid(|config| config.payment_info.as_mut().unwrap().rpc_url = "localhost:9944".into()),
+		Err(ConfigError::InvalidRpcUrl("localhost:9944".into()))
+	);
+	assert_eq!(
+		invalid(|config| config.payment_info.as_mut().unwrap().subscription_duration = 0),
+		Err(ConfigError::ZeroDuration("payment_info.subscription_duration".into()))
+	);
+	assert_eq!(
+		invalid(|config| config.payment_info.as_mut().unwrap().renewal_period = 0),
+		Err(ConfigError::ZeroDuration("payment_info.renewal_period".into()))
+	);
+
+	assert_eq!(
+		invalid(|config| {
+			config.payment_info.as_mut().unwrap().ca_bundle = Some("mock-ca-bundle.pem".into())
+		}),
+		Err(ConfigError::InvalidCaBundle("mock-ca-bundle.pem".into()))
+	);
+
+	assert_eq!(
+		invalid(|config| config.cors.allowed_origins = vec!["https://app.example/path".into()]),
+		Err(ConfigError::InvalidCors("https://app.example/path".into()))
+	);
+	assert_eq!(
+		invalid(|config| config.cors.allowed_methods = vec!["FETCH".into()]),
+		Err(ConfigError::InvalidCors("FETCH".into()))
+	);
+	assert_eq!(
+		invalid(|config| {
+			config.cors.allowed_origins = vec!["*".into(), "http://localhost:3000".into()]
+		}),
+		Ok(())
+	);
+
+	// The per relay chain payment configuration is validated as well:
+	assert_eq!(
+		invalid(|config| {
+			let payment_info = config.payment_info.as_mut().unwrap();
+			payment_info.relay_chains = vec![shared::config::RelayChainPaymentInfo {
+				relay_chain: Kusama,
+				rpc_url: payment_info.rpc_url.clone(),
+				receiver: payment_info.receiver.clone(),
+				cost: "-1".into(),
+				payment_chain: None,
+				call_indices: None,
+				tier_costs: vec![],
+			}];
+		}),
+		Err(ConfigError::InvalidCost("payment_info.relay_chains.Kusama.cost".into()))
+	);
+	// Each tier needs a cost on the payment chain of each relay chain:
+	assert_eq!(
+		invalid(|config| {
+			let payment_info = config.payment_info.as_mut().unwrap();
+			payment_info.relay_chains = vec![shared::config::RelayChainPaymentInfo {
+				relay_chain: Kusama,
+				rpc_url: payment_info.rpc_url.clone(),
+				receiver: payment_info.receiver.clone(),
+				cost: "42".into(),
+				payment_chain: None,
+				call_indices: None,
+				tier_costs: vec![],
+			}];
+		}),
+		Err(ConfigError::MissingTierCost(
+			"payment_info.relay_chains.Kusama.tier_costs.basic".into()
+		))
+	);
+
+	// The payment configuration isn't validated in free mode:
+	assert_eq!(invalid(|config| config.payment_info = None), Ok(()));
+}
@@ -14,16 +14,30 @@
 // along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
 
 use rocket::{
-	http::Status,
+	http::{Accept, ContentType, Status},
 	local::blocking::{Client, LocalResponse},
 	routes,
 };
 use routes::{
-	consumption::{consumption, group_consumption, AggregatedData, Grouping},
-	Error,
+	consumption::{
+		aggregate_consumption, apply_weighting, consumption, consumption_latest,
+		consumption_stream, ensure_within_query_range, group_consumption, AggregatedData,
+		AggregationFn, EnrichedConsumption, Grouping, HashedAggregatedData, Weighting,
+		CURSOR_HEADER, DEFAULT_PAGE_SIZE, MAX_LATEST_PARAS, TOTAL_COUNT_HEADER,
+	},
+	Error, ErrorResponse,
 };
-use shared::{chaindata::get_para, registry::update_registry, reset_mock_environment};
-use types::{RelayChain::*, WeightConsumption};
+use shared::{
+	chaindata::{get_para, get_para_metadata},
+	consumption::{
+		get_consumption, latest_consumption, prune_consumption, write_batch_consumption,
+		write_consumption,
+	},
+	registry::update_registry,
+	reset_mock_environment, set_mock_timestamp,
+};
+use std::collections::BTreeMap;
+use types::{InherentSplit, RelayChain::*, WeightConsumption};
 
 mod mock;
 use mock::{mock_consumption, MockEnvironment};
@@ -54,7 +68,7 @@ fn parachain_not_found_handled() {
 		let client = Client::tracked(rocket).expect("valid rocket instance");
 
 		let response = client.get("/consumption/polkadot/42").dispatch();
-		assert_eq!(response.status(), Status::InternalServerError);
+		assert_eq!(response.status(), Status::NotFound);
 
 		let err = parse_err_response(response);
 		assert_eq!(err, Error::NotRegistered);
@@ -73,7 +87,7 @@ fn consumption_data_not_found_handled() {
 	assert!(update_registry(vec![get_para(Polkadot, 2000).unwrap()]).is_ok());
 
 	let response = client.get("/consumption/polkadot/2000").dispatch();
-	assert_eq!(response.status(), Status::InternalServerError);
+	assert_eq!(response.status(), Status::NotFound);
 
 	let err = parse_err_response(response);
 	assert_eq!(err, Error::ConsumptionDataNotFound);
@@ -197,6 +211,201 @@ fn timestamp_based_filtering_works() {
 	});
 }
 
+#[test]
+fn time_window_filtering_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![consumption]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let para = get_para(Kusama, 2000).unwrap();
+		update_registry(vec![para.clone()]).unwrap();
+
+		let seeded: Vec<WeightConsumption> = (1..=5)
+			.map(|block_number| WeightConsumption {
+				block_number,
+				timestamp: block_number as u64 * 1000,
+				ref_time: Some((0.1, 0.1, 0.1).into()),
+				proof_size: Some((0.2, 0.2, 0.2).into()),
+				block_hash: None,
+				inherent_split: None,
+			})
+			.collect();
+		seeded.iter().for_each(|consumption| {
+			write_consumption(para.clone(), consumption.clone(), None).unwrap()
+		});
+
+		let response = client.get("/consumption/kusama/2000?start=2000&end=4000").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		let groups: Vec<String> =
+			parse_ok_response(response).into_iter().map(|data| data.group).collect();
+		assert_eq!(groups, vec!["2", "3", "4"]);
+
+		// Reversed ranges are rejected rather than returning nothing:
+		let response = client.get("/consumption/kusama/2000?start=4000&end=2000").dispatch();
+		assert_eq!(response.status(), Status::BadRequest);
+		assert_eq!(parse_err_response(response), Error::InvalidRange);
+
+		// So are ranges starting in the future:
+		let response =
+			client.get(format!("/consumption/kusama/2000?start={}", u64::MAX)).dispatch();
+		assert_eq!(parse_err_response(response), Error::InvalidRange);
+	});
+}
+
+#[test]
+fn paging_returns_the_total_count() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![consumption]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let para = get_para(Kusama, 2000).unwrap();
+		update_registry(vec![para.clone()]).unwrap();
+
+		// Seed more records than fit on a single page, out of order:
+		let total = DEFAULT_PAGE_SIZE + 5;
+		(1..=total).rev().for_each(|block_number| {
+			let consumption = WeightConsumption {
+				block_number,
+				timestamp: block_number as u64,
+				ref_time: Some((0.1, 0.1, 0.1).into()),
+				proof_size: Some((0.2, 0.2, 0.2).into()),
+				block_hash: None,
+				inherent_split: None,
+			};
+			write_consumption(para.clone(), consumption, None).unwrap()
+		});
+
+		let response = client.get("/consumption/kusama/2000").dispatch();
+		assert_eq!(
+			response.headers().get_one(TOTAL_COUNT_HEADER),
+			Some(total.to_string().as_str())
+		);
+		let data = parse_ok_response(response);
+		assert_eq!(data.len(), DEFAULT_PAGE_SIZE as usize);
+		// The records are ordered by their timestamp:
+		assert_eq!(data[0].group, "1");
+
+		let response = client.get("/consumption/kusama/2000?page=1").dispatch();
+		assert_eq!(
+			response.headers().get_one(TOTAL_COUNT_HEADER),
+			Some(total.to_string().as_str())
+		);
+		let groups: Vec<String> =
+			parse_ok_response(response).into_iter().map(|data| data.group).collect();
+		let expected: Vec<String> = (DEFAULT_PAGE_SIZE + 1..=total)
+			.map(|block_number| block_number.to_string())
+			.collect();
+		assert_eq!(groups, expected);
+
+		// The total count only covers the records within the queried range:
+		let response =
+			client.get("/consumption/kusama/2000?start=11&end=20&page_size=5").dispatch();
+		assert_eq!(response.headers().get_one(TOTAL_COUNT_HEADER), Some("10"));
+	});
+}
+
+#[test]
+fn paging_groups_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![consumption]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let para = get_para(Kusama, 2000).unwrap();
+		update_registry(vec![para.clone()]).unwrap();
+
+		// Seed more records than fit on a single page, spread over three days:
+		const DAY: u64 = 24 * 60 * 60 * 1000;
+		let per_day = DEFAULT_PAGE_SIZE / 2;
+		(0..per_day * 3).for_each(|index| {
+			let consumption = WeightConsumption {
+				block_number: index + 1,
+				timestamp: (index / per_day) as u64 * DAY + index as u64,
+				ref_time: Some((0.1, 0.1, 0.1).into()),
+				proof_size: Some((0.2, 0.2, 0.2).into()),
+				block_hash: None,
+				inherent_split: None,
+			};
+			write_consumption(para.clone(), consumption, None).unwrap()
+		});
+
+		// None of the records get cut off by the default page size:
+		let response = client.get("/consumption/kusama/2000?grouping=day").dispatch();
+		assert_eq!(response.headers().get_one(TOTAL_COUNT_HEADER), Some("3"));
+		let data = parse_ok_response(response);
+		let groups: Vec<(&str, usize)> =
+			data.iter().map(|data| (data.group.as_str(), data.count)).collect();
+		let per_day = per_day as usize;
+		assert_eq!(
+			groups,
+			vec![("1970-01-01", per_day), ("1970-01-02", per_day), ("1970-01-03", per_day)]
+		);
+
+		// The groups are paged:
+		let response = client
+			.get("/consumption/kusama/2000?grouping=day&page=1&page_size=2")
+			.dispatch();
+		assert_eq!(response.headers().get_one(TOTAL_COUNT_HEADER), Some("3"));
+		let groups: Vec<(String, usize)> = parse_ok_response(response)
+			.into_iter()
+			.map(|data| (data.group, data.count))
+			.collect();
+		assert_eq!(groups, vec![("1970-01-03".to_string(), per_day)]);
+	});
+}
+
+#[test]
+fn grouping_by_block_number_keeps_the_block_order() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![consumption]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let para = get_para(Kusama, 2000).unwrap();
+		update_registry(vec![para.clone()]).unwrap();
+
+		// The block numbers cross a digit boundary:
+		let records: Vec<WeightConsumption> = (998..=1001)
+			.map(|block_number| WeightConsumption {
+				block_number,
+				timestamp: block_number as u64 * 6000,
+				ref_time: Some((0.1, 0.1, 0.1).into()),
+				proof_size: Some((0.2, 0.2, 0.2).into()),
+				block_hash: None,
+				inherent_split: None,
+			})
+			.collect();
+		records
+			.iter()
+			.for_each(|record| write_consumption(para.clone(), record.clone(), None).unwrap());
+
+		let groups = |data: Vec<AggregatedData>| -> Vec<String> {
+			data.into_iter().map(|data| data.group).collect()
+		};
+		let expected = vec!["998", "999", "1000", "1001"];
+		assert_eq!(
+			groups(aggregate_consumption(records, Grouping::BlockNumber, AggregationFn::Sum)),
+			expected
+		);
+
+		let response = client.get("/consumption/kusama/2000").dispatch();
+		assert_eq!(groups(parse_ok_response(response)), expected);
+
+		let response = client.get("/consumption/kusama/2000?page=1&page_size=2").dispatch();
+		assert_eq!(groups(parse_ok_response(response)), vec!["1000", "1001"]);
+	});
+}
+
+#[test]
+fn range_validation_works() {
+	let now = 10_000;
+
+	assert_eq!(ensure_valid_range(0, u64::MAX, now), Ok(()));
+	assert_eq!(ensure_valid_range(5_000, 5_000, now), Ok(()));
+	assert_eq!(ensure_valid_range(now, u64::MAX, now), Ok(()));
+
+	assert_eq!(ensure_valid_range(5_001, 5_000, now), Err(Error::InvalidRange));
+	assert_eq!(ensure_valid_range(now + 1, u64::MAX, now), Err(Error::InvalidRange));
+}
+
 #[test]
 fn pagination_and_timestamp_filtering_works() {
 	MockEnvironment::new().execute_with(|| {
@@ -234,6 +443,35 @@ fn pagination_and_timestamp_filtering_works() {
 	});
 }
 
+#[test]
+fn since_block_cursor_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![consumption]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let para = get_para(Polkadot, 2000).unwrap();
+		let mock_data = mock_consumption().get(&para).unwrap().clone();
+
+		// Only the consumption after the specified block is returned:
+		let response = client.get("/consumption/polkadot/2000?since_block=2").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(response.headers().get_one(CURSOR_HEADER), Some("4"));
+
+		let response_data = parse_ok_response(response);
+		let expected_data = group_consumption(
+			mock_data.into_iter().filter(|c| c.block_number > 2).collect(),
+			Grouping::BlockNumber,
+		);
+		assert_eq!(response_data, expected_data);
+
+		// Polling with the returned cursor doesn't return any data since nothing new was recorded:
+		let response = client.get("/consumption/polkadot/2000?since_block=4").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(response.headers().get_one(CURSOR_HEADER), Some("4"));
+		assert!(parse_ok_response(response).is_empty());
+	});
+}
+
 #[test]
 fn grouping_works() {
 	MockEnvironment::new().execute_with(|| {
@@ -299,6 +537,652 @@ fn grouping_works() {
 	});
 }
 
+#[test]
+fn as_percent_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![consumption]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let para = get_para(Polkadot, 2000).unwrap();
+		let response = client.get("/consumption/polkadot/2000?as_percent=true").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		let percentages = mock_consumption()
+			.get(&para)
+			.unwrap()
+			.iter()
+			.map(|c| WeightConsumption {
+				ref_time: c.ref_time.as_ref().map(|ref_time| ref_time.as_percent()),
+				proof_size: c.proof_size.as_ref().map(|proof_size| proof_size.as_percent()),
+				..c.clone()
+			})
+			.collect();
+		assert_eq!(
+			parse_ok_response(response),
+			group_consumption(percentages, Grouping::BlockNumber)
+		);
+
+		// Returns fractions when not set:
+		let response = client.get("/consumption/polkadot/2000?as_percent=false").dispatch();
+		assert_eq!(
+			parse_ok_response(response),
+			group_consumption(
+				mock_consumption().get(&para).unwrap().clone(),
+				Grouping::BlockNumber
+			)
+		);
+	});
+}
+
+#[test]
+fn aggregation_functions_work() {
+	let record = |block_number, normal: f32| WeightConsumption {
+		block_number,
+		timestamp: 0,
+		ref_time: Some((normal, 0.0, 0.1).into()),
+		proof_size: None,
+		block_hash: None,
+		inherent_split: None,
+	};
+	// 20 records with a normal ref_time of 0.05, 0.1, ..., 1.0
+	let records: Vec<WeightConsumption> = (1..=20).map(|i| record(i, i as f32 * 0.05)).collect();
+
+	let aggregated = |agg_fn| {
+		let aggregated = aggregate_consumption(records.clone(), Grouping::Day, agg_fn);
+		assert_eq!(aggregated.len(), 1);
+		assert_eq!(aggregated[0].count, 20);
+		assert_eq!(aggregated[0].proof_size, None);
+		aggregated[0].ref_time.clone().unwrap()
+	};
+
+	assert!((aggregated(AggregationFn::Sum).normal - 10.5).abs() < 1e-4);
+	assert!((aggregated(AggregationFn::Avg).normal - 0.525).abs() < 1e-4);
+	assert_eq!(aggregated(AggregationFn::Max).normal, 1.0);
+	assert_eq!(aggregated(AggregationFn::Min).normal, 0.05);
+	// The 19th of the 20 values:
+	assert_eq!(aggregated(AggregationFn::P95).normal, 19.0 * 0.05);
+	assert_eq!(aggregated(AggregationFn::Max).mandatory, 0.1);
+
+	// Summing is the default:
+	assert_eq!(
+		aggregate_consumption(records.clone(), Grouping::Day, AggregationFn::Sum),
+		group_consumption(records, Grouping::Day)
+	);
+}
+
+#[test]
+fn agg_fn_query_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![consumption]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let para = get_para(Polkadot, 2000).unwrap();
+		let response = client.get("/consumption/polkadot/2000?grouping=day&agg_fn=max").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(
+			parse_ok_response(response),
+			aggregate_consumption(
+				mock_consumption().get(&para).unwrap().clone(),
+				Grouping::Day,
+				AggregationFn::Max
+			)
+		);
+
+		let response = client.get("/consumption/polkadot/2000?agg_fn=median").dispatch();
+		assert_eq!(parse_err_response(response), Error::UnsupportedAggregationFn);
+	});
+}
+
+#[test]
+fn querying_hourly_mean_and_max_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![consumption]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let para = get_para(Kusama, 2000).unwrap();
+		update_registry(vec![para.clone()]).unwrap();
+
+		// Two hours of records, more than fit on a single page. The consumption alternates between
+		// 0.2 and 0.6 in the first hour and is 0.1 throughout the second one.
+		const HOUR: u64 = 60 * 60 * 1000;
+		let per_hour = DEFAULT_PAGE_SIZE as u64 / 2 + 100;
+		(0..per_hour * 2).for_each(|index| {
+			let normal: f32 = match (index / per_hour, index % 2) {
+				(0, 0) => 0.2,
+				(0, _) => 0.6,
+				_ => 0.1,
+			};
+			let consumption = WeightConsumption {
+				block_number: index as u32 + 1,
+				timestamp: (index / per_hour) * HOUR + index,
+				ref_time: Some((normal, 0.0, 0.0).into()),
+				proof_size: None,
+				block_hash: None,
+				inherent_split: None,
+			};
+			write_consumption(para.clone(), consumption, None).unwrap()
+		});
+
+		let query = |agg_fn: &str| -> Vec<f32> {
+			let response = client
+				.get(format!("/consumption/kusama/2000?grouping=hour&agg_fn={}", agg_fn))
+				.dispatch();
+			assert_eq!(response.status(), Status::Ok);
+			parse_ok_response(response)
+				.into_iter()
+				.map(|data| {
+					assert_eq!(data.count, per_hour as usize);
+					data.ref_time.unwrap().normal
+				})
+				.collect()
+		};
+
+		let mean = query("avg");
+		assert_eq!(mean.len(), 2);
+		assert!((mean[0] - 0.4).abs() < 1e-3);
+		assert!((mean[1] - 0.1).abs() < 1e-3);
+
+		assert_eq!(query("max"), vec![0.6, 0.1]);
+	});
+}
+
+#[test]
+fn including_block_hashes_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![consumption]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		// The mock records were stored without hashes:
+		let para = get_para(Polkadot, 2004).unwrap();
+		let block_hash = format!("0x{}", "ab".repeat(32));
+		let hashed = WeightConsumption {
+			block_number: 2,
+			timestamp: 6,
+			ref_time: Some((0.4, 0.1, 0.1).into()),
+			proof_size: None,
+			block_hash: Some(block_hash.clone()),
+			inherent_split: None,
+		};
+		write_consumption(para.clone(), hashed.clone(), None).unwrap();
+		assert_eq!(get_consumption(para, None).unwrap().last(), Some(&hashed));
+
+		let response = client.get("/consumption/polkadot/2004?include_hash=true").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		let data: Vec<HashedAggregatedData> =
+			serde_json::from_str(&response.into_string().unwrap()).unwrap();
+		let hashes: Vec<Option<String>> = data.into_iter().map(|d| d.block_hash).collect();
+		assert_eq!(hashes, vec![None, Some(block_hash)]);
+
+		// Not included by default:
+		let response = client.get("/consumption/polkadot/2004").dispatch();
+		assert!(!response.into_string().unwrap().contains("block_hash"));
+	});
+}
+
+#[test]
+fn enriching_consumption_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![consumption]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let para = get_para(Polkadot, 2000).unwrap();
+		let response = client.get("/consumption/polkadot/2000?enrich=true").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		let enriched: EnrichedConsumption =
+			serde_json::from_str(&response.into_string().unwrap()).unwrap();
+		let metadata = enriched.metadata.clone().unwrap();
+		assert_eq!(metadata, get_para_metadata(Polkadot, 2000).unwrap());
+		assert_eq!(metadata.name, "Acala");
+		assert_eq!(metadata.category, Some("defi".to_string()));
+		assert_eq!(
+			enriched.data,
+			group_consumption(
+				mock_consumption().get(&para).unwrap().clone(),
+				Grouping::BlockNumber
+			)
+		);
+	});
+}
+
+#[test]
+fn unrecorded_dimensions_are_null() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![consumption]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let para = get_para(Polkadot, 2004).unwrap();
+		let unrecorded_proof_size = WeightConsumption {
+			block_number: 2,
+			timestamp: 6,
+			ref_time: Some((0.4, 0.1, 0.1).into()),
+			proof_size: None,
+			block_hash: None,
+			inherent_split: None,
+		};
+		write_consumption(para.clone(), unrecorded_proof_size.clone(), None).unwrap();
+
+		let response = client.get("/consumption/polkadot/2004").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		let consumption_data = parse_ok_response(response);
+		let mut expected_data = mock_consumption().get(&para).unwrap().clone();
+		expected_data.push(unrecorded_proof_size);
+
+		assert_eq!(consumption_data, group_consumption(expected_data, Grouping::BlockNumber));
+		assert!(consumption_data[0].proof_size.is_some());
+		assert!(consumption_data[1].proof_size.is_none());
+	});
+}
+
+#[test]
+fn latest_consumption_is_cached() {
+	MockEnvironment::new().execute_with(|| {
+		let para = get_para(Polkadot, 2000).unwrap();
+		let mock_data = mock_consumption().get(&para).unwrap().clone();
+
+		let latest = latest_consumption(&para).unwrap();
+		assert_eq!(&latest.consumption, mock_data.last().unwrap());
+		assert!(!latest.is_stale(&para));
+
+		// No consumption was recorded for unregistered parachains:
+		assert!(latest_consumption(&get_para(Polkadot, 2006).unwrap()).is_none());
+	});
+}
+
+#[test]
+fn querying_latest_consumption_of_multiple_paras_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![consumption_latest]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let para_2000 = get_para(Polkadot, 2000).unwrap();
+		let para_2004 = get_para(Polkadot, 2004).unwrap();
+
+		let response = client
+			.get("/consumption/latest?paras=polkadot:2000,polkadot:2004,kusama:2000")
+			.dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		let latest: BTreeMap<String, Option<WeightConsumption>> =
+			serde_json::from_str(&response.into_string().unwrap()).unwrap();
+		let last_recorded = |para| mock_consumption().get(para).unwrap().last().cloned();
+		assert_eq!(
+			latest,
+			BTreeMap::from([
+				("polkadot:2000".to_string(), last_recorded(&para_2000)),
+				("polkadot:2004".to_string(), last_recorded(&para_2004)),
+				// Not registered:
+				("kusama:2000".to_string(), None),
+			])
+		);
+
+		// Invalid identifiers are rejected:
+		let response = client.get("/consumption/latest?paras=polkadot:2000,rococo:2000").dispatch();
+		assert_eq!(parse_err_response(response), Error::InvalidParaIdentifier);
+
+		// The number of parachains per request is capped:
+		let paras = vec!["polkadot:2000"; MAX_LATEST_PARAS + 1].join(",");
+		let response = client.get(format!("/consumption/latest?paras={}", paras)).dispatch();
+		assert_eq!(parse_err_response(response), Error::TooManyParas);
+	});
+}
+
+#[test]
+fn streaming_consumption_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![consumption_stream]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		// Extend the mock data with a large synthetic dataset:
+		let para = get_para(Polkadot, 2004).unwrap();
+		let synthetic = (2..10_000)
+			.map(|block_number| WeightConsumption {
+				block_number,
+				timestamp: block_number as u64 * 6,
+				ref_time: Some((0.1, 0.2, 0.3).into()),
+				proof_size: Some((0.3, 0.2, 0.1).into()),
+				block_hash: None,
+				inherent_split: None,
+			})
+			.collect();
+		write_batch_consumption(para.clone(), synthetic).unwrap();
+		let expected_data = get_consumption(para, None).unwrap();
+
+		// Streaming as a JSON array:
+		let response = client.get("/consumption/polkadot/2004/stream").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		let streamed: Vec<WeightConsumption> =
+			serde_json::from_str(&response.into_string().unwrap()).unwrap();
+		assert_eq!(streamed, expected_data);
+
+		// Streaming as NDJSON:
+		let response = client.get("/consumption/polkadot/2004/stream?format=ndjson").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		let streamed: Vec<WeightConsumption> = response
+			.into_string()
+			.unwrap()
+			.lines()
+			.map(|line| serde_json::from_str(line).unwrap())
+			.collect();
+		assert_eq!(streamed, expected_data);
+
+		// Streaming as CSV:
+		let response = client.get("/consumption/polkadot/2004/stream?format=csv").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		let body = response.into_string().unwrap();
+		let mut lines = body.lines();
+		assert!(lines.next().unwrap().starts_with("block_number,timestamp,"));
+		assert_eq!(lines.count(), expected_data.len());
+	});
+}
+
+#[test]
+fn inherent_split_is_stored() {
+	MockEnvironment::new().execute_with(|| {
+		let para = get_para(Polkadot, 2004).unwrap();
+
+		let split = WeightConsumption {
+			block_number: 2,
+			timestamp: 6,
+			ref_time: Some((0.4, 0.1, 0.1).into()),
+			proof_size: None,
+			// The block hash column is left empty when only the split is recorded.
+			block_hash: None,
+			inherent_split: Some(InherentSplit {
+				inherent_ref_time: 0.1,
+				non_inherent_ref_time: 0.4,
+				inherent_proof_size: 0.05,
+				non_inherent_proof_size: 0.2,
+			}),
+		};
+		write_consumption(para.clone(), split.clone(), None).unwrap();
+
+		let stored = get_consumption(para, None).unwrap();
+		assert_eq!(stored.last(), Some(&split));
+	});
+}
+
+#[test]
+fn timezone_aware_grouping_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![consumption]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let groups = |uri: &str| -> Vec<String> {
+			let response = client.get(uri).dispatch();
+			assert_eq!(response.status(), Status::Ok);
+			parse_ok_response(response).into_iter().map(|data| data.group).collect()
+		};
+
+		// The mock consumption was recorded right after the UNIX epoch:
+		assert_eq!(groups("/consumption/polkadot/2000?grouping=day"), vec!["1970-01-01"]);
+		assert_eq!(
+			groups("/consumption/polkadot/2000?grouping=day&tz=America/New_York"),
+			vec!["1969-12-31"]
+		);
+		assert_eq!(
+			groups("/consumption/polkadot/2000?grouping=hour&tz=Asia/Kolkata"),
+			vec!["1970-01-01T05:00"]
+		);
+
+		// Invalid timezones fall back to UTC:
+		assert_eq!(
+			groups("/consumption/polkadot/2000?grouping=day&tz=Invalid/Zone"),
+			vec!["1970-01-01"]
+		);
+	});
+}
+
+#[test]
+fn max_query_range_works() {
+	let now = 10_000;
+
+	// Unlimited by default:
+	assert_eq!(ensure_within_query_range(0, u64::MAX, None, now), Ok(()));
+
+	assert_eq!(ensure_within_query_range(5_000, 6_000, Some(1_000), now), Ok(()));
+	assert_eq!(
+		ensure_within_query_range(5_000, 6_001, Some(1_000), now),
+		Err(Error::QueryRangeTooWide(1_000))
+	);
+	// Ranges extending into the future are capped at now:
+	assert_eq!(ensure_within_query_range(9_000, u64::MAX, Some(1_000), now), Ok(()));
+	assert_eq!(
+		ensure_within_query_range(0, u64::MAX, Some(1_000), now),
+		Err(Error::QueryRangeTooWide(1_000))
+	);
+
+	// The error contains the allowed maximum:
+	assert_eq!(Error::from("QueryRangeTooWide(1000)".to_string()), Error::QueryRangeTooWide(1_000));
+}
+
+#[test]
+fn querying_too_wide_range_fails() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![consumption]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		// Move the clock past the range allowed by the mock config:
+		let max_range = 100_000_000_000_000;
+		let now = 2 * max_range;
+		set_mock_timestamp(Some(now / 1000));
+
+		// Without a `start`, the whole history up to now is queried:
+		let response = client.get("/consumption/polkadot/2000").dispatch();
+		assert_eq!(response.status(), Status::BadRequest);
+		assert_eq!(parse_err_response(response), Error::QueryRangeTooWide(max_range));
+
+		let response = client
+			.get(format!("/consumption/polkadot/2000?end={}", max_range + 1))
+			.dispatch();
+		assert_eq!(response.status(), Status::BadRequest);
+		assert_eq!(parse_err_response(response), Error::QueryRangeTooWide(max_range));
+
+		// Narrower ranges are still served:
+		let response =
+			client.get(format!("/consumption/polkadot/2000?end={}", max_range)).dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(parse_ok_response(response).len(), 4);
+
+		let response = client
+			.get(format!("/consumption/polkadot/2000?start={}", now - max_range))
+			.dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		assert!(parse_ok_response(response).is_empty());
+	});
+}
+
+#[test]
+fn weighting_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![consumption]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let para = get_para(Polkadot, 2000).unwrap();
+		let grouped = group_consumption(
+			mock_consumption().get(&para).unwrap().clone(),
+			Grouping::BlockNumber,
+		);
+
+		let response = client.get("/consumption/polkadot/2000?weighting=execution").dispatch();
+		let execution = parse_ok_response(response);
+		assert_eq!(execution, apply_weighting(grouped.clone(), Weighting::Execution));
+		assert!(execution
+			.iter()
+			.all(|data| data.ref_time.is_some() && data.proof_size.is_none()));
+
+		let response = client.get("/consumption/polkadot/2000?weighting=validation").dispatch();
+		let validation = parse_ok_response(response);
+		assert!(validation
+			.iter()
+			.all(|data| data.ref_time.is_none() && data.proof_size.is_some()));
+
+		let response = client.get("/consumption/polkadot/2000?weighting=ratio").dispatch();
+		let ratio = parse_ok_response(response);
+		// The first mock block consumed the same amount of both dimensions:
+		assert_eq!(ratio[0].ratio, Some((1.0, 1.0, 1.0).into()));
+		assert_eq!(ratio[0].ref_time, grouped[0].ref_time);
+		assert_eq!(ratio[0].proof_size, grouped[0].proof_size);
+
+		// The ratio is only returned when requested:
+		assert!(grouped.iter().all(|data| data.ratio.is_none()));
+	});
+}
+
+#[test]
+fn csv_export_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![consumption]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let para = get_para(Polkadot, 2000).unwrap();
+		let records = mock_consumption().get(&para).unwrap().clone();
+
+		let response = client.get("/consumption/polkadot/2000?format=csv").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(response.content_type(), Some(ContentType::CSV));
+
+		let body = response.into_string().unwrap();
+		let mut lines = body.lines();
+		assert!(lines.next().unwrap().starts_with("group,timestamp,count,normal_ref_time,"));
+
+		let first: Vec<&str> = lines.next().unwrap().split(',').collect();
+		assert_eq!(first[0], records[0].block_number.to_string());
+		assert_eq!(first[1], records[0].timestamp.to_string());
+		assert_eq!(first[2], "1");
+		assert_eq!(first[3], records[0].ref_time.as_ref().unwrap().normal.to_string());
+		assert_eq!(lines.count(), records.len() - 1);
+
+		// CSV can also be requested through the `Accept` header:
+		let response = client.get("/consumption/polkadot/2000").header(Accept::CSV).dispatch();
+		assert_eq!(response.content_type(), Some(ContentType::CSV));
+		assert_eq!(response.into_string().unwrap(), body);
+
+		// JSON is returned by default:
+		let response = client.get("/consumption/polkadot/2000").dispatch();
+		assert_eq!(parse_ok_response(response), group_consumption(records, Grouping::BlockNumber));
+	});
+}
+
+#[test]
+fn pruning_consumption_works() {
+	MockEnvironment::new().execute_with(|| {
+		let para = get_para(Polkadot, 2000).unwrap();
+		let mock_data = mock_consumption().get(&para).unwrap().clone();
+
+		// Only the records before the specified timestamp are removed:
+		assert_eq!(prune_consumption(&para, None, 12).unwrap(), 2);
+		assert_eq!(get_consumption(para.clone(), None), Ok(mock_data[2..].to_vec()));
+
+		// Pruning again doesn't remove anything else:
+		assert_eq!(prune_consumption(&para, None, 12).unwrap(), 0);
+
+		// New consumption is still appended after pruning:
+		write_consumption(para.clone(), mock_data[0].clone(), None).unwrap();
+		assert_eq!(get_consumption(para.clone(), None).unwrap().len(), 3);
+
+		// Parachains without consumption are skipped:
+		let unrecorded = get_para(Polkadot, 2006).unwrap();
+		assert_eq!(prune_consumption(&unrecorded, None, 12).unwrap(), 0);
+	});
+}
+
+#[test]
+fn records_written_while_pruning_are_kept() {
+	MockEnvironment::new().execute_with(|| {
+		let para = get_para(Polkadot, 2000).unwrap();
+		let mock_data = mock_consumption().get(&para).unwrap().clone();
+		// A record which isn't pruned:
+		let record = mock_data[2].clone();
+
+		std::thread::scope(|scope| {
+			for _ in 0..4 {
+				scope.spawn(|| {
+					for _ in 0..25 {
+						write_consumption(para.clone(), record.clone(), None).unwrap();
+					}
+				});
+			}
+			scope.spawn(|| {
+				for _ in 0..25 {
+					prune_consumption(&para, None, 12).unwrap();
+				}
+			});
+		});
+
+		// Only the records recorded before the timestamp were removed:
+		prune_consumption(&para, None, 12).unwrap();
+		assert_eq!(get_consumption(para.clone(), None).unwrap().len(), mock_data.len() - 2 + 100);
+	});
+}
+
+#[test]
+fn aggregating_too_few_records_fails() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![consumption]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		// The mock config requires at least two records:
+		let response = client.get("/consumption/polkadot/2000?grouping=day&end=6").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		let response = client.get("/consumption/polkadot/2000?grouping=day&end=0").dispatch();
+		assert_eq!(parse_err_response(response), Error::InsufficientData);
+
+		let response = client.get("/consumption/polkadot/2004?grouping=day").dispatch();
+		assert_eq!(parse_err_response(response), Error::InsufficientData);
+
+		// The threshold doesn't apply when the consumption isn't aggregated:
+		let response = client.get("/consumption/polkadot/2004").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+	});
+}
+
+#[test]
+fn aggregation_threshold_applies_to_the_whole_window() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![consumption]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let para = get_para(Kusama, 2000).unwrap();
+		update_registry(vec![para.clone()]).unwrap();
+
+		// Three records on the first day and a single one on the second:
+		const DAY: u64 = 24 * 60 * 60 * 1000;
+		[0, 1, 2, DAY].into_iter().enumerate().for_each(|(index, timestamp)| {
+			let consumption = WeightConsumption {
+				block_number: index as u32 + 1,
+				timestamp,
+				ref_time: Some((0.1, 0.1, 0.1).into()),
+				proof_size: Some((0.2, 0.2, 0.2).into()),
+				block_hash: None,
+				inherent_split: None,
+			};
+			write_consumption(para.clone(), consumption, None).unwrap()
+		});
+
+		// The page only holds a single record, but the window holds enough of them:
+		let response = client
+			.get("/consumption/kusama/2000?grouping=day&page=1&page_size=1")
+			.dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		let data = parse_ok_response(response);
+		assert_eq!(data.len(), 1);
+		assert_eq!((data[0].group.as_str(), data[0].count), ("1970-01-02", 1));
+
+		// Whereas a window with a single record doesn't:
+		let response = client
+			.get(format!("/consumption/kusama/2000?grouping=day&start={}", DAY))
+			.dispatch();
+		assert_eq!(parse_err_response(response), Error::InsufficientData);
+	});
+}
+
 fn parse_ok_response<'a>(response: LocalResponse<'a>) -> Vec<AggregatedData> {
 	let body = response.into_string().unwrap();
 	serde_json::from_str(&body).expect("can't parse value")
@@ -306,5 +1190,6 @@ fn parse_ok_response<'a>(response: LocalResponse<'a>) -> Vec<AggregatedData> {
 
 fn parse_err_response<'a>(response: LocalResponse<'a>) -> Error {
 	let body = response.into_string().unwrap();
-	body.into()
+	let response: ErrorResponse = serde_json::from_str(&body).expect("can't parse error");
+	response.error.into()
 }
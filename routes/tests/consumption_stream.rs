@@ -0,0 +1,106 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+//! The streaming of the consumption has its own test binary, since measuring the memory it uses
+//! requires a global allocator which keeps track of the allocated memory.
+
+use rocket::{http::Status, local::blocking::Client, routes};
+use routes::consumption::consumption_stream;
+use shared::{chaindata::get_para, consumption::write_batch_consumption};
+use std::{
+	alloc::{GlobalAlloc, Layout, System},
+	io::Read,
+	sync::atomic::{AtomicUsize, Ordering},
+};
+use types::{RelayChain::*, WeightConsumption};
+
+mod mock;
+use mock::MockEnvironment;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK_ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+/// Keeps track of the allocated memory, as well as of its peak since it was last reset.
+struct TrackingAllocator;
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		let ptr = System.alloc(layout);
+		if !ptr.is_null() {
+			let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+			PEAK_ALLOCATED.fetch_max(allocated, Ordering::Relaxed);
+		}
+		ptr
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		System.dealloc(ptr, layout);
+		ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+	}
+}
+
+#[global_allocator]
+static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+
+#[test]
+fn streaming_consumption_uses_bounded_memory() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![consumption_stream]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		// A consumption history much larger than the memory the stream may use:
+		let para = get_para(Polkadot, 2004).unwrap();
+		let records = 100_000;
+		for blocks in (0..records).collect::<Vec<u32>>().chunks(10_000) {
+			let synthetic = blocks
+				.iter()
+				.map(|&block_number| WeightConsumption {
+					block_number: block_number + 2,
+					timestamp: block_number as u64 * 6,
+					ref_time: Some((0.1, 0.2, 0.3).into()),
+					proof_size: Some((0.3, 0.2, 0.1).into()),
+					block_hash: Some(format!("0x{:064x}", block_number)),
+					inherent_split: None,
+				})
+				.collect();
+			write_batch_consumption(para.clone(), synthetic).unwrap();
+		}
+
+		let baseline = ALLOCATED.load(Ordering::Relaxed);
+		PEAK_ALLOCATED.store(baseline, Ordering::Relaxed);
+
+		let mut response = client.get("/consumption/polkadot/2004/stream?format=ndjson").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		// The response is read in small pieces, rather than buffered by the test itself:
+		let mut buffer = [0u8; 8192];
+		let (mut streamed, mut lines) = (0, 0);
+		loop {
+			let read = response.read(&mut buffer).unwrap();
+			if read == 0 {
+				break;
+			}
+			streamed += read;
+			lines += buffer[..read].iter().filter(|byte| **byte == b'\n').count();
+		}
+
+		// Each record took up a line, along with the records of the mock data:
+		assert!(lines >= records as usize);
+		// The stream only held a fraction of the streamed data in memory at any time:
+		let peak = PEAK_ALLOCATED.load(Ordering::Relaxed).saturating_sub(baseline);
+		assert!(streamed > 20_000_000, "streamed {} bytes", streamed);
+		assert!(peak < streamed / 4, "used {} bytes to stream {} bytes", peak, streamed);
+	});
+}
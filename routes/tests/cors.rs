@@ -0,0 +1,91 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use rocket::{
+	http::{Header, Status},
+	local::blocking::Client,
+	routes,
+};
+use routes::{
+	cors::{cors, cors_options},
+	registry::registry,
+};
+use shared::config::{config, CorsConfig};
+
+mod mock;
+use mock::MockEnvironment;
+
+const ALLOW_ORIGIN: &str = "Access-Control-Allow-Origin";
+
+#[test]
+fn only_same_origin_requests_are_allowed_by_default() {
+	MockEnvironment::new().execute_with(|| {
+		assert_eq!(config().cors, CorsConfig::default());
+		assert!(cors().is_none());
+
+		let rocket = rocket::build().mount("/", routes![registry]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let response = client
+			.get("/registry")
+			.header(Header::new("Origin", "https://app.example"))
+			.dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(response.headers().get_one(ALLOW_ORIGIN), None);
+	});
+}
+
+#[test]
+fn configured_origins_are_allowed() {
+	MockEnvironment::new().execute_with(|| {
+		let config = CorsConfig {
+			allowed_origins: vec!["https://app.example".into()],
+			..Default::default()
+		};
+		let rocket = rocket::build()
+			.attach(cors_options(&config).to_cors().unwrap())
+			.mount("/", routes![registry]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let response = client
+			.get("/registry")
+			.header(Header::new("Origin", "https://app.example"))
+			.dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(response.headers().get_one(ALLOW_ORIGIN), Some("https://app.example"));
+
+		// Other origins aren't allowed:
+		let response = client
+			.get("/registry")
+			.header(Header::new("Origin", "https://other.example"))
+			.dispatch();
+		assert_eq!(response.headers().get_one(ALLOW_ORIGIN), None);
+
+		// Preflight requests are answered for the allowed methods:
+		let response = client
+			.options("/registry")
+			.header(Header::new("Origin", "https://app.example"))
+			.header(Header::new("Access-Control-Request-Method", "POST"))
+			.dispatch();
+		assert_eq!(response.headers().get_one(ALLOW_ORIGIN), Some("https://app.example"));
+
+		let response = client
+			.options("/registry")
+			.header(Header::new("Origin", "https://app.example"))
+			.header(Header::new("Access-Control-Request-Method", "DELETE"))
+			.dispatch();
+		assert_eq!(response.headers().get_one(ALLOW_ORIGIN), None);
+	});
+}
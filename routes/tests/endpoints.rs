@@ -0,0 +1,63 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use rocket::{http::Status, local::blocking::Client, routes};
+use routes::{
+	consumption::consumption,
+	endpoints::{list_routes, RouteInfo},
+	register::register_para,
+};
+
+#[test]
+fn listing_routes_works() {
+	let rocket = rocket::build().mount("/", routes![consumption, register_para, list_routes]);
+	let client = Client::tracked(rocket).expect("valid rocket instance");
+
+	let response = client.get("/routes").dispatch();
+	assert_eq!(response.status(), Status::Ok);
+
+	let routes: Vec<RouteInfo> = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+	assert_eq!(routes.len(), 3);
+
+	let consumption = routes.iter().find(|r| r.uri.starts_with("/consumption")).unwrap();
+	assert_eq!(consumption.method, "GET");
+	assert_eq!(consumption.path_params, vec!["relay", "para_id"]);
+	assert_eq!(
+		consumption.query_params,
+		vec![
+			"start",
+			"end",
+			"page",
+			"page_size",
+			"grouping",
+			"since_block",
+			"as_percent",
+			"enrich",
+			"agg_fn",
+			"include_hash",
+			"follow_migration",
+			"tz",
+			"weighting",
+			"format"
+		]
+	);
+
+	let register = routes.iter().find(|r| r.uri.starts_with("/register_para")).unwrap();
+	assert_eq!(register.method, "POST");
+	assert!(register.path_params.is_empty());
+	assert_eq!(register.query_params, vec!["dry_run"]);
+
+	assert!(routes.iter().any(|r| r.uri == "/routes" && r.method == "GET"));
+}
@@ -20,15 +20,19 @@ use rocket::{
 	routes,
 };
 use routes::{
-	extend_subscription::{extend_subscription, ExtendSubscriptionData},
-	Error,
+	extend_subscription::{draw_credit, extend_subscription, ExtendSubscriptionData},
+	Error, ErrorResponse,
 };
 use shared::{
 	chaindata::get_para,
-	payment::PaymentError,
+	config::{config, PaymentInfo},
+	current_timestamp,
+	payment::{note_rpc_latency, PaymentError, RPC_DEGRADATION_PERIOD},
 	registry::{registered_para, update_registry},
+	set_mock_timestamp,
+	subscription::{add_credit, renewal},
 };
-use types::RelayChain::*;
+use types::{Balance, RelayChain::*};
 
 mod mock;
 use mock::MockEnvironment;
@@ -44,7 +48,9 @@ fn extend_subscription_works() {
 		let para = get_para(Polkadot, 2000).unwrap();
 		let extend_subscription = ExtendSubscriptionData {
 			para: (para.relay_chain.clone(), para.para_id),
-			payment_block_number: PARA_2000_PAYMENT,
+			payment_block_number: Some(PARA_2000_PAYMENT),
+			payment_search_window: 0,
+			duration: None,
 		};
 
 		let response = client
@@ -67,9 +73,13 @@ fn cannot_extend_subscription_for_unregistered() {
 		let rocket = rocket::build().mount("/", routes![extend_subscription]);
 		let client = Client::tracked(rocket).expect("valid rocket instance");
 
+		// The mock config treats the extension as a registration, which has to be paid for
+		// separately, so it can't be paid from credit:
 		let extend_subscription = ExtendSubscriptionData {
 			para: (Polkadot, 2006),
-			payment_block_number: PARA_2000_PAYMENT,
+			payment_block_number: None,
+			payment_search_window: 0,
+			duration: None,
 		};
 
 		let response = client
@@ -78,7 +88,45 @@ fn cannot_extend_subscription_for_unregistered() {
 			.body(serde_json::to_string(&extend_subscription).unwrap())
 			.dispatch();
 
-		assert_eq!(parse_err_response(response), Error::NotRegistered);
+		assert_eq!(parse_err_response(response), Error::PaymentRequired);
+		assert!(registered_para(Polkadot, 2006).is_none());
+	});
+}
+
+#[test]
+fn extending_unregistered_para_registers_it() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![extend_subscription]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		// The mock config exempts the Polkadot Asset Hub from payment, so its registration
+		// doesn't require one either:
+		let extend_subscription = ExtendSubscriptionData {
+			para: (Polkadot, 1000),
+			payment_block_number: None,
+			payment_search_window: 0,
+			duration: None,
+		};
+
+		let response = client
+			.post("/extend-subscription")
+			.header(ContentType::JSON)
+			.body(serde_json::to_string(&extend_subscription).unwrap())
+			.dispatch();
+
+		assert_eq!(response.status(), Status::Ok);
+		let asset_hub = registered_para(Polkadot, 1000).unwrap();
+		assert_eq!(asset_hub.payment, None);
+
+		// Once registered, the subscription is renewed instead, which the credit doesn't cover:
+		let response = client
+			.post("/extend-subscription")
+			.header(ContentType::JSON)
+			.body(serde_json::to_string(&extend_subscription).unwrap())
+			.dispatch();
+
+		assert_eq!(parse_err_response(response), Error::InsufficientCredit);
+		assert_eq!(registered_para(Polkadot, 1000), Some(asset_hub));
 	});
 }
 
@@ -90,7 +138,9 @@ fn cannot_extend_subscription_before_renewal_period() {
 
 		let extend_subscription = ExtendSubscriptionData {
 			para: (Polkadot, 2000),
-			payment_block_number: PARA_2000_PAYMENT,
+			payment_block_number: Some(PARA_2000_PAYMENT),
+			payment_search_window: 0,
+			duration: None,
 		};
 
 		let mut para = get_para(Polkadot, 2000).unwrap();
@@ -107,6 +157,54 @@ fn cannot_extend_subscription_before_renewal_period() {
 	});
 }
 
+#[test]
+fn renewal_period_is_respected() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![extend_subscription]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let payment_info = config().payment_info.unwrap();
+		let cost: Balance = payment_info.cost.parse().unwrap();
+		let now = 1_700_000_000;
+		set_mock_timestamp(Some(now));
+
+		// The renewal period starts one second from now:
+		let mut para = get_para(Polkadot, 2000).unwrap();
+		para.expiry_timestamp = now + payment_info.renewal_period + 1;
+		add_credit(&mut para, 2 * cost, 1).unwrap();
+		update_registry(vec![para.clone()]).unwrap();
+
+		let extend_subscription = ExtendSubscriptionData {
+			para: (Polkadot, 2000),
+			payment_block_number: None,
+			payment_search_window: 0,
+			duration: None,
+		};
+		let extend = || {
+			client
+				.post("/extend-subscription")
+				.header(ContentType::JSON)
+				.body(serde_json::to_string(&extend_subscription).unwrap())
+				.dispatch()
+		};
+
+		// Cannot renew yet:
+		assert_eq!(parse_err_response(extend()), Error::AlreadyRegistered);
+		assert_eq!(registered_para(Polkadot, 2000).unwrap(), para);
+
+		// Once the renewal period starts the subscription can be renewed:
+		set_mock_timestamp(Some(now + 1));
+		assert_eq!(extend().status(), Status::Ok);
+
+		let registered = registered_para(Polkadot, 2000).unwrap();
+		assert_eq!(
+			registered.expiry_timestamp,
+			para.expiry_timestamp + payment_info.subscription_duration
+		);
+		assert_eq!(registered.credit, cost);
+	});
+}
+
 #[test]
 fn providing_non_finalized_payment_block_number_fails() {
 	MockEnvironment::new().execute_with(|| {
@@ -116,7 +214,9 @@ fn providing_non_finalized_payment_block_number_fails() {
 		let para = get_para(Polkadot, 2000).unwrap();
 		let extend_subscription = ExtendSubscriptionData {
 			para: (para.relay_chain.clone(), para.para_id),
-			payment_block_number: 99999999,
+			payment_block_number: Some(99999999),
+			payment_search_window: 0,
+			duration: None,
 		};
 
 		let response = client
@@ -142,7 +242,9 @@ fn payment_not_found_works() {
 		// We are extending the subscription for para 2004, but the payment is for para 2000.
 		let extend_subscription = ExtendSubscriptionData {
 			para: (para.relay_chain.clone(), para.para_id),
-			payment_block_number: PARA_2000_PAYMENT,
+			payment_block_number: Some(PARA_2000_PAYMENT),
+			payment_search_window: 0,
+			duration: None,
 		};
 
 		let response = client
@@ -158,7 +260,163 @@ fn payment_not_found_works() {
 	});
 }
 
+#[test]
+fn extending_subscription_sheds_load_while_rpc_is_degraded() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![extend_subscription]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		// Above the `max_rpc_latency` from the mock config.
+		note_rpc_latency(60_000);
+
+		let para = registered_para(Polkadot, 2000).unwrap();
+		let extend_subscription = ExtendSubscriptionData {
+			para: (para.relay_chain.clone(), para.para_id),
+			payment_block_number: Some(PARA_2000_PAYMENT),
+			payment_search_window: 0,
+			duration: None,
+		};
+
+		let response = client
+			.post("/extend-subscription")
+			.header(ContentType::JSON)
+			.body(serde_json::to_string(&extend_subscription).unwrap())
+			.dispatch();
+
+		assert_eq!(response.status(), Status::ServiceUnavailable);
+		assert_eq!(
+			response.headers().get_one("Retry-After"),
+			Some(RPC_DEGRADATION_PERIOD.to_string().as_str())
+		);
+		assert_eq!(parse_err_response(response), Error::RpcDegraded);
+		assert_eq!(
+			registered_para(Polkadot, 2000).unwrap().expiry_timestamp,
+			para.expiry_timestamp
+		);
+	});
+}
+
+#[test]
+fn credit_accumulation_works() {
+	let mut para = get_para(Polkadot, 2000).unwrap();
+
+	// Multiple micro-payments accumulate credit:
+	assert_eq!(add_credit(&mut para, 100, 10), Ok(()));
+	assert_eq!(add_credit(&mut para, 50, 11), Ok(()));
+	assert_eq!(add_credit(&mut para, 25, 20), Ok(()));
+	assert_eq!(para.credit, 175);
+	assert_eq!(para.last_credited_block, Some(20));
+
+	// The same payment can't be credited twice:
+	assert_eq!(add_credit(&mut para, 25, 20), Err(PaymentError::AlreadyCredited));
+	assert_eq!(add_credit(&mut para, 100, 15), Err(PaymentError::AlreadyCredited));
+	assert_eq!(para.credit, 175);
+}
+
+#[test]
+fn extend_subscription_with_credit_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![extend_subscription]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let payment_info = config().payment_info.unwrap();
+		let cost: Balance = payment_info.cost.parse().unwrap();
+
+		let mut para = get_para(Polkadot, 2000).unwrap();
+		para.expiry_timestamp = current_timestamp() + 100;
+		add_credit(&mut para, cost / 2, 1).unwrap();
+		update_registry(vec![para.clone()]).unwrap();
+
+		let extend_subscription = ExtendSubscriptionData {
+			para: (Polkadot, 2000),
+			payment_block_number: None,
+			payment_search_window: 0,
+			duration: None,
+		};
+		let extend = || {
+			client
+				.post("/extend-subscription")
+				.header(ContentType::JSON)
+				.body(serde_json::to_string(&extend_subscription).unwrap())
+				.dispatch()
+		};
+
+		// The credit doesn't cover the renewal yet:
+		assert_eq!(parse_err_response(extend()), Error::InsufficientCredit);
+
+		add_credit(&mut para, cost, 2).unwrap();
+		update_registry(vec![para.clone()]).unwrap();
+
+		let expected = renewal(para.expiry_timestamp, &payment_info, current_timestamp()).unwrap();
+		assert_eq!(extend().status(), Status::Ok);
+
+		let registered = registered_para(Polkadot, 2000).unwrap();
+		assert_eq!(registered.credit, cost / 2 + cost - expected.cost);
+		assert_eq!(registered.expiry_timestamp, expected.expiry_timestamp);
+	});
+}
+
+#[test]
+fn extending_without_payment_requires_micro_payments() {
+	let payment_info = config().payment_info.unwrap();
+	let mut para = get_para(Polkadot, 2000).unwrap();
+	add_credit(&mut para, 100, 1).unwrap();
+
+	assert_eq!(draw_credit(&para, &payment_info, 100), Ok(100));
+	assert_eq!(draw_credit(&para, &payment_info, 101), Err(Error::InsufficientCredit));
+
+	// Without micro-payments the credit can't be accumulated, so a payment is required instead:
+	let disabled = PaymentInfo { micro_payments: false, ..payment_info };
+	assert_eq!(draw_credit(&para, &disabled, 100), Err(Error::PaymentRequired));
+}
+
+#[test]
+fn partial_extension_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![extend_subscription]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let payment_info = config().payment_info.unwrap();
+		let cost: Balance = payment_info.cost.parse().unwrap();
+		let half_period = payment_info.subscription_duration / 2;
+
+		// Partial extensions aren't limited to the renewal period:
+		let mut para = get_para(Polkadot, 2000).unwrap();
+		para.expiry_timestamp = current_timestamp() + 10 * payment_info.renewal_period;
+		add_credit(&mut para, cost / 2, 1).unwrap();
+		update_registry(vec![para.clone()]).unwrap();
+
+		let extend = |duration| {
+			let extend_subscription = ExtendSubscriptionData {
+				para: (Polkadot, 2000),
+				payment_block_number: None,
+				payment_search_window: 0,
+				duration: Some(duration),
+			};
+
+			client
+				.post("/extend-subscription")
+				.header(ContentType::JSON)
+				.body(serde_json::to_string(&extend_subscription).unwrap())
+				.dispatch()
+		};
+
+		assert_eq!(parse_err_response(extend(0)), Error::InvalidDuration);
+		// Durations which would overflow the expiry are rejected as well:
+		assert_eq!(parse_err_response(extend(u64::MAX)), Error::InvalidDuration);
+		// The credit only covers half of a subscription period:
+		assert_eq!(parse_err_response(extend(half_period + 1)), Error::InsufficientCredit);
+
+		assert_eq!(extend(half_period).status(), Status::Ok);
+
+		let registered = registered_para(Polkadot, 2000).unwrap();
+		assert_eq!(registered.credit, 0);
+		assert_eq!(registered.expiry_timestamp, para.expiry_timestamp + half_period);
+	});
+}
+
 fn parse_err_response<'a>(response: LocalResponse<'a>) -> Error {
 	let body = response.into_string().unwrap();
-	body.into()
+	let response: ErrorResponse = serde_json::from_str(&body).expect("can't parse error");
+	response.error.into()
 }
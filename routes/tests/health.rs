@@ -0,0 +1,289 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use rocket::{
+	http::Status,
+	local::blocking::{Client, LocalResponse},
+	routes,
+};
+use routes::health::{health, readiness, DependencyStatus, Health, Readiness};
+use shared::{
+	chaindata::get_para,
+	config::{block_time, dormancy_threshold, sampled_threshold, staleness_threshold},
+	current_timestamp,
+	payment::{note_payment_lookup, note_rpc_latency},
+	registry::{registered_paras, update_registry},
+	reset_mock_environment,
+	tracker::{write_tracker_state, TrackerState},
+};
+use types::{Parachain, RelayChain::*};
+
+mod mock;
+use mock::MockEnvironment;
+
+#[test]
+fn health_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![health]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let response = client.get("/health").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(
+			parse_ok_response(response),
+			Health {
+				warming_up: false,
+				tracker_stalled: false,
+				dormant: vec![],
+				metadata_mismatch_suspected: false,
+				rpc_latency: None,
+				paused: vec![],
+				offboarded: vec![]
+			}
+		);
+	});
+}
+
+#[test]
+fn dormant_paras_are_detected() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![health]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		// Register a parachain for which no blocks were recorded.
+		let dormant = get_para(Kusama, 2000).unwrap();
+		let mut paras = registered_paras();
+		paras.push(dormant.clone());
+		update_registry(paras).unwrap();
+
+		let response = client.get("/health").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(
+			parse_ok_response(response),
+			Health {
+				warming_up: false,
+				tracker_stalled: false,
+				dormant: vec![(dormant.relay_chain, dormant.para_id)],
+				metadata_mismatch_suspected: false,
+				rpc_latency: None,
+				paused: vec![],
+				offboarded: vec![]
+			}
+		);
+	});
+}
+
+#[test]
+fn tracker_stall_is_detected() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![health]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		// No blocks were recorded for any of the registered parachains.
+		let paras = registered_paras();
+		reset_mock_environment();
+		update_registry(paras).unwrap();
+
+		let response = client.get("/health").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(
+			parse_ok_response(response),
+			Health {
+				warming_up: false,
+				tracker_stalled: true,
+				dormant: vec![],
+				metadata_mismatch_suspected: false,
+				rpc_latency: None,
+				paused: vec![],
+				offboarded: vec![]
+			}
+		);
+	});
+}
+
+#[test]
+fn warmup_is_detected() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![health]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		// No blocks are recorded during the warmup:
+		let paras = registered_paras();
+		reset_mock_environment();
+		update_registry(paras).unwrap();
+
+		// The mock config has a warmup of 60 seconds.
+		write_tracker_state(
+			0,
+			&TrackerState { started_at: current_timestamp(), offboarded: vec![] },
+		)
+		.unwrap();
+
+		let health = parse_ok_response(client.get("/health").dispatch());
+		assert!(health.warming_up);
+		// Not recording anything during the warmup is expected.
+		assert!(!health.tracker_stalled);
+
+		write_tracker_state(
+			0,
+			&TrackerState { started_at: current_timestamp() - 120, offboarded: vec![] },
+		)
+		.unwrap();
+
+		let health = parse_ok_response(client.get("/health").dispatch());
+		assert!(!health.warming_up);
+		assert!(health.tracker_stalled);
+	});
+}
+
+#[test]
+fn metadata_mismatch_is_detected() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![health]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let mismatch_suspected = |client: &Client| {
+			let response = client.get("/health").dispatch();
+			parse_ok_response(response).metadata_mismatch_suspected
+		};
+
+		// Payments that aren't found without any transfers to the receiver are user errors:
+		(0..5).for_each(|_| note_payment_lookup(false, false));
+		assert!(!mismatch_suspected(&client));
+
+		note_payment_lookup(false, true);
+		note_payment_lookup(false, true);
+		assert!(!mismatch_suspected(&client));
+		note_payment_lookup(false, true);
+		assert!(mismatch_suspected(&client));
+
+		// Resolved as soon as a payment is found again:
+		note_payment_lookup(true, true);
+		assert!(!mismatch_suspected(&client));
+	});
+}
+
+#[test]
+fn rpc_latency_is_exposed() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![health]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let rpc_latency = |client: &Client| {
+			let response = client.get("/health").dispatch();
+			parse_ok_response(response).rpc_latency
+		};
+
+		assert_eq!(rpc_latency(&client), None);
+
+		note_rpc_latency(1000);
+		assert_eq!(rpc_latency(&client), Some(1000));
+
+		// The measurements are smoothed out:
+		note_rpc_latency(5000);
+		assert_eq!(rpc_latency(&client), Some(2000));
+	});
+}
+
+#[test]
+fn offboarded_paras_are_exposed() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![health]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let state = TrackerState {
+			started_at: current_timestamp() - 120,
+			offboarded: vec![(Polkadot, 2004)],
+		};
+		write_tracker_state(0, &state).unwrap();
+
+		let health = parse_ok_response(client.get("/health").dispatch());
+		assert_eq!(health.offboarded, vec![(Polkadot, 2004)]);
+	});
+}
+
+#[test]
+fn block_time_based_thresholds_work() {
+	let para = get_para(Polkadot, 2000).unwrap();
+	let slow_para = get_para(Polkadot, 2004).unwrap();
+	let kusama_para = get_para(Kusama, 2000).unwrap();
+
+	// The block time of Polkadot is configured, while the one of Kusama is the default:
+	assert_eq!(block_time(&para), 6);
+	assert_eq!(block_time(&kusama_para), 6);
+	// Para specific block times take precedence:
+	assert_eq!(block_time(&slow_para), 24);
+
+	// The mock config allows 50 blocks before staleness and 100 before dormancy:
+	assert_eq!(staleness_threshold(&para), 300);
+	assert_eq!(staleness_threshold(&slow_para), 1200);
+	assert_eq!(dormancy_threshold(&para), 600);
+	assert_eq!(dormancy_threshold(&slow_para), 2400);
+
+	// Only every 10th block is recorded in the basic tier of the mock config:
+	let sampled_para = Parachain { tier: Some("basic".into()), ..para };
+	assert_eq!(staleness_threshold(&sampled_para), 3000);
+	assert_eq!(dormancy_threshold(&sampled_para), 6000);
+
+	// The time based thresholds are scaled by the sampling interval as well:
+	assert_eq!(sampled_threshold(None, 300, 1), 300);
+	assert_eq!(sampled_threshold(None, 300, 10), 3000);
+	assert_eq!(sampled_threshold(Some(600), 300, 10), 6000);
+}
+
+#[test]
+fn readiness_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![readiness]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		// The mock config has no relay chain rpcs configured, so only the registry is checked.
+		let response = client.get("/health/ready").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		let registry = DependencyStatus { dependency: "registry".to_string(), healthy: true };
+		assert_eq!(
+			parse_readiness(response),
+			Readiness { ready: true, dependencies: vec![registry.clone()] }
+		);
+
+		// The server isn't ready once neither the registry nor its backup can be read:
+		#[cfg(not(feature = "sqlite"))]
+		{
+			let storage = shared::storage::FileStorage::new(shared::config::config().registry);
+			std::fs::write(shared::config::config().registry, "invalid").unwrap();
+			std::fs::write(storage.backup_path(), "invalid").unwrap();
+
+			let response = client.get("/health/ready").dispatch();
+			assert_eq!(response.status(), Status::ServiceUnavailable);
+			assert_eq!(
+				parse_readiness(response),
+				Readiness {
+					ready: false,
+					dependencies: vec![DependencyStatus { healthy: false, ..registry }]
+				}
+			);
+		}
+	});
+}
+
+fn parse_readiness<'a>(response: LocalResponse<'a>) -> Readiness {
+	let body = response.into_string().unwrap();
+	serde_json::from_str(&body).expect("can't parse value")
+}
+
+fn parse_ok_response<'a>(response: LocalResponse<'a>) -> Health {
+	let body = response.into_string().unwrap();
+	serde_json::from_str(&body).expect("can't parse value")
+}
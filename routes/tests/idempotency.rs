@@ -0,0 +1,200 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use polkadot_core_primitives::BlockNumber;
+use rocket::{
+	http::{ContentType, Header, Status},
+	local::{
+		asynchronous,
+		blocking::{Client, LocalResponse},
+	},
+	post, routes,
+	serde::json::Json,
+	tokio,
+};
+use routes::{
+	idempotency::{
+		body_hash, idempotency_cache, Idempotency, IDEMPOTENCY_KEY_HEADER,
+		MAX_IDEMPOTENCY_KEY_LENGTH,
+	},
+	register::{register_para, RegistrationData},
+	Error, ErrorResponse,
+};
+use shared::{config::config, registry::registered_paras};
+use std::time::Duration;
+use types::RelayChain::*;
+
+mod mock;
+use mock::MockEnvironment;
+
+const PARA_2000_PAYMENT: BlockNumber = 9145403;
+
+#[test]
+fn retried_requests_get_the_cached_outcome() {
+	MockEnvironment::default().execute_with(|| {
+		assert_eq!(config().idempotency_ttl, 600);
+
+		let rocket = rocket::build().attach(idempotency_cache()).mount("/", routes![register_para]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let response = register(&client, Some(PARA_2000_PAYMENT), Some("registration-1"));
+		assert_eq!(response.status(), Status::Ok);
+		let outcome = response.into_string().unwrap();
+
+		// The retry gets the outcome of the first request instead of being registered again:
+		let response = register(&client, Some(PARA_2000_PAYMENT), Some("registration-1"));
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(response.into_string().unwrap(), outcome);
+		assert_eq!(registered_paras().len(), 1);
+
+		// Requests with a different key, or without a key, are handled again:
+		let response = register(&client, Some(PARA_2000_PAYMENT), Some("registration-2"));
+		assert_eq!(parse_err_response(response), Error::AlreadyRegistered);
+
+		let response = register(&client, Some(PARA_2000_PAYMENT), None);
+		assert_eq!(parse_err_response(response), Error::AlreadyRegistered);
+	});
+}
+
+#[test]
+fn reusing_a_key_for_a_different_request_fails() {
+	MockEnvironment::default().execute_with(|| {
+		let rocket = rocket::build().attach(idempotency_cache()).mount("/", routes![register_para]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let response = register(&client, Some(PARA_2000_PAYMENT), Some("registration"));
+		assert_eq!(response.status(), Status::Ok);
+
+		// The cached outcome isn't the outcome of a request with a different body:
+		let response = register(&client, Some(PARA_2000_PAYMENT + 1), Some("registration"));
+		assert_eq!(response.status(), Status::UnprocessableEntity);
+		assert_eq!(parse_err_response(response), Error::IdempotencyKeyReused);
+	});
+}
+
+#[rocket::async_test]
+async fn concurrent_retries_are_rejected() {
+	let rocket = rocket::build().attach(idempotency_cache()).mount("/", routes![slow_echo]);
+	let client = asynchronous::Client::tracked(rocket).await.expect("valid rocket instance");
+
+	let echo = |value: u32| {
+		client
+			.post("/slow_echo")
+			.header(ContentType::JSON)
+			.header(Header::new(IDEMPOTENCY_KEY_HEADER, "echo"))
+			.body(value.to_string())
+			.dispatch()
+	};
+
+	// The retry is made while the original request is still being handled:
+	let (original, retry) = tokio::join!(echo(1), echo(1));
+	assert_eq!(original.status(), Status::Ok);
+	assert_eq!(retry.status(), Status::Conflict);
+	let body = retry.into_string().await.unwrap();
+	let response: ErrorResponse = serde_json::from_str(&body).expect("can't parse error");
+	assert_eq!(Error::from(response.error), Error::RequestInProgress);
+
+	// Once the original request completes, the retry gets its outcome:
+	let response = echo(1).await;
+	assert_eq!(response.into_string().await.unwrap(), "1");
+}
+
+#[test]
+fn failed_requests_are_not_cached() {
+	MockEnvironment::default().execute_with(|| {
+		let rocket = rocket::build().attach(idempotency_cache()).mount("/", routes![register_para]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let response = register(&client, None, Some("registration"));
+		assert_eq!(parse_err_response(response), Error::PaymentRequired);
+
+		// The failed request didn't register the parachain, so it can be retried with the same key:
+		let response = register(&client, Some(PARA_2000_PAYMENT), Some("registration"));
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(registered_paras().len(), 1);
+	});
+}
+
+#[test]
+fn invalid_idempotency_key_is_rejected() {
+	MockEnvironment::default().execute_with(|| {
+		let rocket = rocket::build().attach(idempotency_cache()).mount("/", routes![register_para]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let response = register(&client, Some(PARA_2000_PAYMENT), Some(""));
+		assert_eq!(parse_err_response(response), Error::InvalidIdempotencyKey);
+
+		let key = "k".repeat(MAX_IDEMPOTENCY_KEY_LENGTH + 1);
+		let response = register(&client, Some(PARA_2000_PAYMENT), Some(&key));
+		assert_eq!(parse_err_response(response), Error::InvalidIdempotencyKey);
+		assert!(registered_paras().is_empty());
+	});
+}
+
+#[test]
+fn idempotency_keys_are_ignored_without_the_cache() {
+	MockEnvironment::default().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![register_para]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let response = register(&client, Some(PARA_2000_PAYMENT), Some("registration"));
+		assert_eq!(response.status(), Status::Ok);
+
+		let response = register(&client, Some(PARA_2000_PAYMENT), Some("registration"));
+		assert_eq!(parse_err_response(response), Error::AlreadyRegistered);
+	});
+}
+
+/// Echoes the value after a delay, so that requests can be made while it is being handled.
+#[post("/slow_echo", data = "<value>")]
+async fn slow_echo(value: Json<u32>, idempotency: Idempotency<'_>) -> Result<String, Error> {
+	idempotency
+		.run(body_hash(&*value), async move {
+			tokio::time::sleep(Duration::from_millis(100)).await;
+			Ok(value.into_inner().to_string())
+		})
+		.await
+}
+
+fn register<'a>(
+	client: &'a Client,
+	payment_block_number: Option<BlockNumber>,
+	idempotency_key: Option<&str>,
+) -> LocalResponse<'a> {
+	let registration_data = RegistrationData {
+		para: (Polkadot, 2000),
+		payment_block_number,
+		payment_search_window: 0,
+		rpc_url: None,
+		backfill_from: None,
+		tier: None,
+	};
+
+	let mut request = client
+		.post("/register_para")
+		.header(ContentType::JSON)
+		.body(serde_json::to_string(&registration_data).unwrap());
+	if let Some(key) = idempotency_key {
+		request = request.header(Header::new(IDEMPOTENCY_KEY_HEADER, key.to_string()));
+	}
+
+	request.dispatch()
+}
+
+fn parse_err_response<'a>(response: LocalResponse<'a>) -> Error {
+	let body = response.into_string().unwrap();
+	let response: ErrorResponse = serde_json::from_str(&body).expect("can't parse error");
+	response.error.into()
+}
@@ -0,0 +1,204 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use routes::{
+	live::{
+		handle_live_request, subscribe_live_consumption, LiveConsumption, LiveFeed, LiveLagged,
+	},
+	Error,
+};
+use shared::{
+	chaindata::get_para,
+	consumption::{prune_consumption, write_consumption},
+};
+use std::collections::{HashMap, HashSet};
+use types::{RelayChain::*, WeightConsumption};
+
+mod mock;
+use mock::MockEnvironment;
+
+#[test]
+fn live_feed_broadcasts_new_consumption() {
+	MockEnvironment::new().execute_with(|| {
+		let mut feed = LiveFeed::new();
+		let mut receiver = subscribe_live_consumption();
+
+		// The consumption recorded before the feed was created isn't broadcasted:
+		assert_eq!(feed.poll(), 0);
+		assert!(receiver.try_recv().is_err());
+
+		let para = get_para(Polkadot, 2000).unwrap();
+		let consumption = WeightConsumption {
+			block_number: 5,
+			timestamp: 24,
+			ref_time: Some((0.2, 0.1, 0.3).into()),
+			proof_size: Some((0.1, 0.2, 0.3).into()),
+			block_hash: None,
+			inherent_split: None,
+		};
+		write_consumption(para.clone(), consumption.clone(), None).unwrap();
+
+		assert_eq!(feed.poll(), 1);
+		assert_eq!(
+			receiver.try_recv().unwrap(),
+			LiveConsumption { para: (Polkadot, 2000), consumption }
+		);
+
+		// Each record is only broadcasted once:
+		assert_eq!(feed.poll(), 0);
+		assert!(receiver.try_recv().is_err());
+	});
+}
+
+#[test]
+fn live_feed_broadcasts_all_records_since_the_last_poll() {
+	MockEnvironment::new().execute_with(|| {
+		let mut feed = LiveFeed::new();
+		let mut receiver = subscribe_live_consumption();
+
+		let para = get_para(Polkadot, 2000).unwrap();
+		let records: Vec<WeightConsumption> = (5..8)
+			.map(|block_number| WeightConsumption {
+				block_number,
+				timestamp: block_number as u64 * 6,
+				ref_time: Some((0.2, 0.1, 0.3).into()),
+				proof_size: Some((0.1, 0.2, 0.3).into()),
+				block_hash: None,
+				inherent_split: None,
+			})
+			.collect();
+		for record in &records {
+			write_consumption(para.clone(), record.clone(), None).unwrap();
+		}
+
+		// All the records written in between two polls are broadcasted once, in order:
+		assert_eq!(feed.poll(), 3);
+		for consumption in records {
+			assert_eq!(
+				receiver.try_recv().unwrap(),
+				LiveConsumption { para: (Polkadot, 2000), consumption }
+			);
+		}
+		assert!(receiver.try_recv().is_err());
+
+		assert_eq!(feed.poll(), 0);
+	});
+}
+
+#[test]
+fn live_feed_follows_pruned_consumption() {
+	MockEnvironment::new().execute_with(|| {
+		let mut feed = LiveFeed::new();
+		let mut receiver = subscribe_live_consumption();
+
+		let para = get_para(Polkadot, 2000).unwrap();
+		let record = |block_number: u32| WeightConsumption {
+			block_number,
+			timestamp: block_number as u64 * 6,
+			ref_time: Some((0.2, 0.1, 0.3).into()),
+			proof_size: Some((0.1, 0.2, 0.3).into()),
+			block_hash: None,
+			inherent_split: None,
+		};
+		write_consumption(para.clone(), record(5), None).unwrap();
+		assert_eq!(feed.poll(), 1);
+		assert_eq!(receiver.try_recv().unwrap().consumption, record(5));
+
+		// The file gets shorter once pruned, after which it is read from the start:
+		prune_consumption(&para, None, 36).unwrap();
+		write_consumption(para.clone(), record(6), None).unwrap();
+		assert_eq!(feed.poll(), 1);
+		assert_eq!(receiver.try_recv().unwrap().consumption, record(6));
+		assert!(receiver.try_recv().is_err());
+	});
+}
+
+#[test]
+fn sampled_subscriptions_work() {
+	MockEnvironment::new().execute_with(|| {
+		let mut subscribed = HashMap::new();
+
+		let request = r#"{"subscribe":{"para":["Polkadot",2000],"sampling_interval":3}}"#;
+		assert_eq!(handle_live_request(request, &mut subscribed), Ok(()));
+		let subscription = subscribed.get_mut(&(Polkadot, 2000)).unwrap();
+		assert_eq!(subscription.sampling_interval, 3);
+
+		// Only records at least three blocks apart are sent:
+		let sent: Vec<u32> = [1, 2, 4, 5, 6, 7, 10]
+			.into_iter()
+			.filter(|&block_number| {
+				subscription.sample(&WeightConsumption {
+					block_number,
+					timestamp: 0,
+					ref_time: None,
+					proof_size: None,
+					block_hash: None,
+					inherent_split: None,
+				})
+			})
+			.collect();
+		assert_eq!(sent, vec![1, 4, 7, 10]);
+
+		let request = r#"{"subscribe":{"para":["Polkadot",2000],"sampling_interval":0}}"#;
+		assert_eq!(handle_live_request(request, &mut subscribed), Err(Error::InvalidLiveRequest));
+
+		// Clients falling behind are told how many records they missed:
+		assert_eq!(serde_json::to_string(&LiveLagged { skipped: 3 }).unwrap(), r#"{"skipped":3}"#);
+	});
+}
+
+#[test]
+fn handling_live_requests_works() {
+	MockEnvironment::new().execute_with(|| {
+		let mut subscribed = HashMap::new();
+
+		assert_eq!(
+			handle_live_request(r#"{"subscribe":["Polkadot",2000]}"#, &mut subscribed),
+			Ok(())
+		);
+		assert_eq!(
+			handle_live_request(r#"{"subscribe":["Polkadot",2004]}"#, &mut subscribed),
+			Ok(())
+		);
+		assert_eq!(
+			subscribed.keys().cloned().collect::<HashSet<_>>(),
+			HashSet::from([(Polkadot, 2000), (Polkadot, 2004)])
+		);
+
+		// Only registered parachains can be subscribed to:
+		assert_eq!(
+			handle_live_request(r#"{"subscribe":["Polkadot",2006]}"#, &mut subscribed),
+			Err(Error::NotRegistered)
+		);
+
+		assert_eq!(
+			handle_live_request(r#"{"unsubscribe":["Polkadot",2000]}"#, &mut subscribed),
+			Ok(())
+		);
+		assert_eq!(
+			subscribed.keys().cloned().collect::<HashSet<_>>(),
+			HashSet::from([(Polkadot, 2004)])
+		);
+
+		assert_eq!(
+			handle_live_request(r#"{"subscribe":2000}"#, &mut subscribed),
+			Err(Error::InvalidLiveRequest)
+		);
+		assert_eq!(
+			subscribed.keys().cloned().collect::<HashSet<_>>(),
+			HashSet::from([(Polkadot, 2004)])
+		);
+	});
+}
@@ -0,0 +1,64 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use log::Level;
+use serde_json::json;
+use shared::{
+	config::{config, log_format, LogFormat},
+	logger::json_log_line,
+};
+
+mod mock;
+use mock::MockEnvironment;
+
+#[test]
+fn logs_are_text_by_default() {
+	MockEnvironment::new().execute_with(|| {
+		assert_eq!(config().log_format, LogFormat::Text);
+		assert_eq!(log_format(), LogFormat::Text);
+	});
+}
+
+#[test]
+fn json_log_line_carries_the_parachain() {
+	assert_eq!(
+		json_log_line(Level::Info, "processor", "Polkadot-2000 - Processing consumption."),
+		json!({
+			"level": "INFO",
+			"target": "processor",
+			"relay_chain": "Polkadot",
+			"para_id": 2000,
+			"message": "Processing consumption.",
+		})
+	);
+
+	// Messages which don't concern a specific parachain are logged as is.
+	assert_eq!(
+		json_log_line(Level::Warn, "tracker", "Shutting down - waiting for the pending writes"),
+		json!({
+			"level": "WARN",
+			"target": "tracker",
+			"message": "Shutting down - waiting for the pending writes",
+		})
+	);
+	assert_eq!(
+		json_log_line(Level::Error, "tracker", "Kusama-abc - Failed to subscribe"),
+		json!({
+			"level": "ERROR",
+			"target": "tracker",
+			"message": "Kusama-abc - Failed to subscribe",
+		})
+	);
+}
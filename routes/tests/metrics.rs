@@ -0,0 +1,67 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+#![cfg(feature = "metrics")]
+
+use rocket::{http::Status, local::blocking::Client, routes};
+use routes::metrics::metrics;
+use shared::{
+	chaindata::get_para,
+	config::staleness_threshold,
+	current_timestamp, set_mock_timestamp,
+	tracker::{note_processed_block, note_reconnect, tracker_metrics},
+};
+use types::RelayChain::*;
+
+mod mock;
+use mock::MockEnvironment;
+
+#[test]
+fn metrics_are_exported() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![metrics]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let para = get_para(Polkadot, 2000).unwrap();
+		note_processed_block(0, &para).unwrap();
+		note_processed_block(0, &para).unwrap();
+		note_reconnect(0, &para).unwrap();
+		assert_eq!(tracker_metrics(0).len(), 1);
+
+		let response = client.get("/metrics").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		let body = response.into_string().unwrap();
+		let labels = "relay_chain=\"Polkadot\",para_id=\"2000\"";
+		assert!(body.contains("weigher_registered_paras 2\n"));
+		assert!(body.contains(&format!(
+			"weigher_blocks_processed_total{{{},rpc_index=\"0\"}} 2\n",
+			labels
+		)));
+		assert!(
+			body.contains(&format!("weigher_reconnects_total{{{},rpc_index=\"0\"}} 1\n", labels))
+		);
+		// The latest block of the mock consumption has a timestamp of 18 milliseconds:
+		assert!(
+			body.contains(&format!("weigher_last_block_timestamp_seconds{{{}}} 0.018\n", labels))
+		);
+		assert!(body.contains(&format!("weigher_latest_consumption_stale{{{}}} 0\n", labels)));
+
+		// The consumption goes stale once it isn't updated within the staleness threshold:
+		set_mock_timestamp(Some(current_timestamp() + staleness_threshold(&para) + 1));
+		let body = client.get("/metrics").dispatch().into_string().unwrap();
+		assert!(body.contains(&format!("weigher_latest_consumption_stale{{{}}} 1\n", labels)));
+	});
+}
@@ -64,34 +64,44 @@ pub fn mock_consumption() -> HashMap<Parachain, Vec<WeightConsumption>> {
 			WeightConsumption {
 				block_number: 1,
 				timestamp: 0,
-				ref_time: (0.5, 0.3, 0.2).into(),
-				proof_size: (0.5, 0.3, 0.2).into(),
+				ref_time: Some((0.5, 0.3, 0.2).into()),
+				proof_size: Some((0.5, 0.3, 0.2).into()),
+				block_hash: None,
+				inherent_split: None,
 			},
 			WeightConsumption {
 				block_number: 2,
 				timestamp: 6,
-				ref_time: (0.1, 0.4, 0.2).into(),
-				proof_size: (0.2, 0.3, 0.3).into(),
+				ref_time: Some((0.1, 0.4, 0.2).into()),
+				proof_size: Some((0.2, 0.3, 0.3).into()),
+				block_hash: None,
+				inherent_split: None,
 			},
 			WeightConsumption {
 				block_number: 3,
 				timestamp: 12,
-				ref_time: (0.0, 0.2, 0.4).into(),
-				proof_size: (0.1, 0.0, 0.3).into(),
+				ref_time: Some((0.0, 0.2, 0.4).into()),
+				proof_size: Some((0.1, 0.0, 0.3).into()),
+				block_hash: None,
+				inherent_split: None,
 			},
 			WeightConsumption {
 				block_number: 4,
 				timestamp: 18,
-				ref_time: (0.1, 0.0, 0.4).into(),
-				proof_size: (0.2, 0.1, 0.3).into(),
+				ref_time: Some((0.1, 0.0, 0.4).into()),
+				proof_size: Some((0.2, 0.1, 0.3).into()),
+				block_hash: None,
+				inherent_split: None,
 			},
 		],
 		get_para(Polkadot, 2004).unwrap() => vec![
 			WeightConsumption {
 				block_number: 1,
 				timestamp: 0,
-				ref_time: (0.8, 0.0, 0.1).into(),
-				proof_size: (0.6, 0.2, 0.1).into(),
+				ref_time: Some((0.8, 0.0, 0.1).into()),
+				proof_size: Some((0.6, 0.2, 0.1).into()),
+				block_hash: None,
+				inherent_split: None,
 			},
 		],
 	}
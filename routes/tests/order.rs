@@ -0,0 +1,218 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use ed25519_dalek::{Signer, SigningKey};
+use rocket::{
+	http::{ContentType, Status},
+	local::blocking::{Client, LocalResponse},
+	routes,
+};
+use routes::{
+	order::{
+		expire_unsettled_orders, place_order, settle_order, OrderData, SettlementData, SignedOrder,
+		SignedSettlement, MAX_ORDER_AGE,
+	},
+	Error, ErrorResponse,
+};
+use shared::{
+	current_timestamp,
+	order::{orders, orders_path, write_orders, OrderState},
+	registry::{registered_para, registered_paras},
+};
+use types::RelayChain::*;
+
+mod mock;
+use mock::MockEnvironment;
+
+// The seed of the key configured for `mock-partner`.
+const PARTNER_SEED: [u8; 32] = [7; 32];
+
+#[test]
+fn placing_order_works() {
+	MockEnvironment::default().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![place_order]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let order = mock_order(current_timestamp());
+		let response = place(&client, &sign(order.clone(), &PARTNER_SEED));
+		assert_eq!(response.status(), Status::Ok);
+
+		// The para is registered straight away:
+		assert!(registered_para(Polkadot, 2000).is_some());
+
+		let orders = orders();
+		assert_eq!(orders.len(), 1);
+		assert_eq!(orders[0].para, (Polkadot, 2000));
+		assert_eq!(orders[0].state, OrderState::Pending);
+
+		// The same order cannot be placed again:
+		let response = place(&client, &sign(order, &PARTNER_SEED));
+		assert_eq!(parse_err_response(response), Error::OrderExpired);
+	});
+}
+
+#[test]
+fn para_is_not_registered_when_order_cannot_be_stored() {
+	MockEnvironment::default().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![place_order]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		// The orders can't be written in place of a directory:
+		std::fs::create_dir(orders_path()).unwrap();
+		let response = place(&client, &sign(mock_order(current_timestamp()), &PARTNER_SEED));
+		std::fs::remove_dir(orders_path()).unwrap();
+
+		assert_eq!(parse_err_response(response), Error::InvalidData);
+		assert!(registered_para(Polkadot, 2000).is_none());
+	});
+}
+
+#[test]
+fn invalid_orders_are_rejected() {
+	MockEnvironment::default().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![place_order]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let now = current_timestamp();
+
+		// Untrusted partner:
+		let mut order = mock_order(now);
+		order.partner = "unknown".to_string();
+		let response = place(&client, &sign(order, &PARTNER_SEED));
+		assert_eq!(parse_err_response(response), Error::UnknownPartner);
+
+		// Signed with the wrong key:
+		let response = place(&client, &sign(mock_order(now), &[8; 32]));
+		assert_eq!(parse_err_response(response), Error::InvalidSignature);
+
+		// Tampered order:
+		let mut signed_order = sign(mock_order(now), &PARTNER_SEED);
+		signed_order.order.para = (Polkadot, 2004);
+		let response = place(&client, &signed_order);
+		assert_eq!(parse_err_response(response), Error::InvalidSignature);
+
+		// Created too long ago:
+		let response = place(&client, &sign(mock_order(now - MAX_ORDER_AGE - 1), &PARTNER_SEED));
+		assert_eq!(parse_err_response(response), Error::OrderExpired);
+
+		assert!(registered_paras().is_empty());
+		assert!(orders().is_empty());
+	});
+}
+
+#[test]
+fn unsettled_orders_expire() {
+	MockEnvironment::default().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![place_order]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let response = place(&client, &sign(mock_order(current_timestamp()), &PARTNER_SEED));
+		assert_eq!(response.status(), Status::Ok);
+
+		// Orders within the settlement window are kept:
+		expire_unsettled_orders();
+		assert!(registered_para(Polkadot, 2000).is_some());
+		assert_eq!(orders()[0].state, OrderState::Pending);
+
+		// Move the deadline to the past:
+		let mut placed = orders();
+		placed[0].settlement_deadline = current_timestamp() - 1;
+		write_orders(&placed).unwrap();
+
+		expire_unsettled_orders();
+		assert!(registered_para(Polkadot, 2000).is_none());
+		assert_eq!(orders()[0].state, OrderState::Expired);
+	});
+}
+
+#[test]
+fn settling_unknown_order_fails() {
+	MockEnvironment::default().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![settle_order]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let response = settle(&client, &sign_settlement(mock_settlement(), &PARTNER_SEED));
+		assert_eq!(parse_err_response(response), Error::OrderNotFound);
+	});
+}
+
+#[test]
+fn settlement_requires_partner_signature() {
+	MockEnvironment::default().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![place_order, settle_order]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let response = place(&client, &sign(mock_order(current_timestamp()), &PARTNER_SEED));
+		assert_eq!(response.status(), Status::Ok);
+
+		// Signed with the wrong key:
+		let response = settle(&client, &sign_settlement(mock_settlement(), &[8; 32]));
+		assert_eq!(parse_err_response(response), Error::InvalidSignature);
+
+		// Tampered settlement:
+		let mut signed_settlement = sign_settlement(mock_settlement(), &PARTNER_SEED);
+		signed_settlement.settlement.payment_search_window = 10;
+		let response = settle(&client, &signed_settlement);
+		assert_eq!(parse_err_response(response), Error::InvalidSignature);
+
+		// The order remains pending:
+		assert_eq!(orders()[0].state, OrderState::Pending);
+		assert!(registered_para(Polkadot, 2000).is_some());
+	});
+}
+
+fn mock_order(created_at: u64) -> OrderData {
+	OrderData { partner: "mock-partner".to_string(), para: (Polkadot, 2000), created_at }
+}
+
+fn sign(order: OrderData, seed: &[u8; 32]) -> SignedOrder {
+	let signature = SigningKey::from_bytes(seed).sign(&serde_json::to_vec(&order).unwrap());
+	SignedOrder { order, signature: format!("0x{}", hex::encode(signature.to_bytes())) }
+}
+
+fn mock_settlement() -> SettlementData {
+	SettlementData {
+		para: (Polkadot, 2000),
+		payment_block_number: Some(1),
+		payment_search_window: 0,
+	}
+}
+
+fn sign_settlement(settlement: SettlementData, seed: &[u8; 32]) -> SignedSettlement {
+	let signature = SigningKey::from_bytes(seed).sign(&serde_json::to_vec(&settlement).unwrap());
+	SignedSettlement { settlement, signature: format!("0x{}", hex::encode(signature.to_bytes())) }
+}
+
+fn place<'a>(client: &'a Client, signed_order: &SignedOrder) -> LocalResponse<'a> {
+	client
+		.post("/order")
+		.header(ContentType::JSON)
+		.body(serde_json::to_string(signed_order).unwrap())
+		.dispatch()
+}
+
+fn settle<'a>(client: &'a Client, signed_settlement: &SignedSettlement) -> LocalResponse<'a> {
+	client
+		.post("/order/settle")
+		.header(ContentType::JSON)
+		.body(serde_json::to_string(signed_settlement).unwrap())
+		.dispatch()
+}
+
+fn parse_err_response<'a>(response: LocalResponse<'a>) -> Error {
+	let body = response.into_string().unwrap();
+	let response: ErrorResponse = serde_json::from_str(&body).expect("can't parse error");
+	response.error.into()
+}
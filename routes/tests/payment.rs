@@ -0,0 +1,658 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use rocket::{
+	http::{Header, Status},
+	local::blocking::Client,
+	routes,
+};
+use routes::{
+	admin::ADMIN_KEY_HEADER,
+	payment::{registration_payment, PaymentReceipt},
+	Error,
+};
+use shared::{
+	chaindata::get_para,
+	config::{
+		config, is_sampled, min_finalized_depth, sampled_blocks, sampling_interval, CallIndices,
+		PaymentChain, PaymentInfo, RelayChainPaymentInfo, TierCost,
+	},
+	payment::{
+		accepted_payment_calls, authorized_payers, batch_payment_call, configured_payers,
+		consumed_payments, consumed_payments_path, derive_sub_account, ensure_confirmed,
+		ensure_valid_payment_block, find_match, is_signed_by, metadata_call_indices,
+		note_consumed_payment, para_payment_info, payment_chain_info, payment_receiver,
+		payment_search_range, relay_chain_call_indices, relay_chain_index, retry_unreachable,
+		utility_derivative, ConsumedPayment, PaymentError, MAX_PAYMENT_SEARCH_WINDOW, RPC_ATTEMPTS,
+	},
+	registry::{registered_paras, update_registry},
+};
+use std::sync::{
+	atomic::{AtomicU32, Ordering},
+	Arc,
+};
+use subxt::{ext::codec::Decode, Metadata};
+use types::{Parachain, PaymentDetails, RelayChain::*};
+
+mod mock;
+use mock::MockEnvironment;
+
+#[test]
+fn unfinalized_payment_is_rejected() {
+	assert_eq!(ensure_valid_payment_block(101, 100, 0, None), Err(PaymentError::Unfinalized));
+	assert_eq!(ensure_valid_payment_block(101, 100, 0, Some(10)), Err(PaymentError::Unfinalized));
+	assert_eq!(ensure_valid_payment_block(100, 100, 0, Some(0)), Ok(()));
+}
+
+#[test]
+fn unconfirmed_payment_is_rejected() {
+	// No confirmations are required by default:
+	assert_eq!(ensure_confirmed(100, 100, 0), Ok(()));
+
+	assert_eq!(ensure_confirmed(100, 104, 5), Err(PaymentError::InsufficientConfirmations));
+	assert_eq!(ensure_confirmed(100, 105, 5), Ok(()));
+}
+
+#[test]
+fn unreachable_rpc_is_retried() {
+	let runtime = rocket::tokio::runtime::Builder::new_current_thread()
+		.enable_time()
+		.build()
+		.unwrap();
+	let attempts = &AtomicU32::new(0);
+
+	// Failing to reach the RPC is retried until the attempts are exhausted:
+	let result: Result<(), _> = runtime.block_on(retry_unreachable(move || async move {
+		attempts.fetch_add(1, Ordering::SeqCst);
+		Err(PaymentError::Unreachable)
+	}));
+	assert_eq!(result, Err(PaymentError::Unreachable));
+	assert_eq!(attempts.swap(0, Ordering::SeqCst), RPC_ATTEMPTS);
+	// The caller is told that it can retry:
+	assert_eq!(Error::from(PaymentError::Unreachable).status(), Status::ServiceUnavailable);
+
+	// A payment which isn't found isn't retried, since that wouldn't change the outcome:
+	let result: Result<(), _> = runtime.block_on(retry_unreachable(move || async move {
+		attempts.fetch_add(1, Ordering::SeqCst);
+		Err(PaymentError::NotFound)
+	}));
+	assert_eq!(result, Err(PaymentError::NotFound));
+	assert_eq!(attempts.swap(0, Ordering::SeqCst), 1);
+
+	// The RPC becoming reachable again within the attempts suffices:
+	let result = runtime.block_on(retry_unreachable(move || async move {
+		match attempts.fetch_add(1, Ordering::SeqCst) {
+			0 => Err(PaymentError::Unreachable),
+			_ => Ok(()),
+		}
+	}));
+	assert_eq!(result, Ok(()));
+	assert_eq!(attempts.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn payment_max_age_works() {
+	// There is no limit by default:
+	assert_eq!(ensure_valid_payment_block(0, 100, 0, None), Ok(()));
+
+	// Payments right at the maximum age are accepted:
+	assert_eq!(ensure_valid_payment_block(90, 100, 0, Some(10)), Ok(()));
+	assert_eq!(ensure_valid_payment_block(95, 100, 0, Some(10)), Ok(()));
+
+	// Anything older is rejected:
+	assert_eq!(ensure_valid_payment_block(89, 100, 0, Some(10)), Err(PaymentError::TooOld));
+	assert_eq!(ensure_valid_payment_block(0, 100, 0, Some(10)), Err(PaymentError::TooOld));
+}
+
+#[test]
+fn payment_search_range_works() {
+	// Only the payment block is searched by default:
+	assert_eq!(payment_search_range(100, 0), 100..=100);
+	assert_eq!(payment_search_range(100, 2), 100..=102);
+
+	// The window is bounded:
+	assert_eq!(payment_search_range(100, 1000), 100..=100 + MAX_PAYMENT_SEARCH_WINDOW);
+	assert_eq!(payment_search_range(u32::MAX, 2), u32::MAX..=u32::MAX);
+}
+
+#[test]
+fn consumed_payments_cannot_be_reused() {
+	MockEnvironment::new().execute_with(|| {
+		let payment =
+			PaymentDetails { block_number: 42, block_hash: None, extrinsic_index: 2, payer: None };
+
+		assert_eq!(note_consumed_payment(&payment), Ok(()));
+		assert_eq!(
+			consumed_payments(),
+			Ok(vec![ConsumedPayment { block_number: 42, extrinsic_index: 2, block_hash: None }])
+		);
+		assert_eq!(note_consumed_payment(&payment), Err(PaymentError::AlreadyUsed));
+
+		// Other extrinsics of the same block can still be used:
+		let other = PaymentDetails { extrinsic_index: 3, ..payment };
+		assert_eq!(note_consumed_payment(&other), Ok(()));
+
+		// The payments of the registered parachains are considered used as well:
+		let mut para = get_para(Polkadot, 2000).unwrap();
+		let registered =
+			PaymentDetails { block_number: 43, block_hash: None, extrinsic_index: 1, payer: None };
+		para.payment = Some(registered.clone());
+		update_registry(vec![para]).unwrap();
+		assert_eq!(note_consumed_payment(&registered), Err(PaymentError::AlreadyUsed));
+	});
+}
+
+#[test]
+fn unreadable_consumed_payments_are_not_overwritten() {
+	MockEnvironment::new().execute_with(|| {
+		let payment =
+			PaymentDetails { block_number: 42, block_hash: None, extrinsic_index: 2, payer: None };
+		assert_eq!(note_consumed_payment(&payment), Ok(()));
+
+		// A corrupted file isn't treated as if no payments were consumed:
+		std::fs::write(consumed_payments_path(), "[{\"block_number\": 42,").unwrap();
+		assert_eq!(consumed_payments(), Err(PaymentError::ValidationFailed));
+		assert_eq!(note_consumed_payment(&payment), Err(PaymentError::ValidationFailed));
+
+		let other = PaymentDetails { extrinsic_index: 3, ..payment };
+		assert_eq!(note_consumed_payment(&other), Err(PaymentError::ValidationFailed));
+		assert_eq!(
+			std::fs::read_to_string(consumed_payments_path()).unwrap(),
+			"[{\"block_number\": 42,"
+		);
+	});
+}
+
+#[test]
+fn consumed_payments_are_told_apart_by_their_block() {
+	MockEnvironment::new().execute_with(|| {
+		let payment = PaymentDetails {
+			block_number: 42,
+			block_hash: Some("0x01".into()),
+			extrinsic_index: 2,
+			payer: None,
+		};
+		assert_eq!(note_consumed_payment(&payment), Ok(()));
+		assert_eq!(note_consumed_payment(&payment), Err(PaymentError::AlreadyUsed));
+
+		// The same extrinsic index at the same block number of another payment chain:
+		let other_chain = PaymentDetails { block_hash: Some("0x02".into()), ..payment.clone() };
+		assert_eq!(note_consumed_payment(&other_chain), Ok(()));
+
+		// Payments without a block hash can't be told apart:
+		let unhashed = PaymentDetails { block_hash: None, ..payment };
+		assert_eq!(note_consumed_payment(&unhashed), Err(PaymentError::AlreadyUsed));
+	});
+}
+
+#[test]
+fn min_finalized_depth_works() {
+	// The mock config requires different depths for the relay chains:
+	let polkadot_depth = min_finalized_depth(&Polkadot);
+	let kusama_depth = min_finalized_depth(&Kusama);
+	assert_eq!((polkadot_depth, kusama_depth), (2, 10));
+
+	// Payments right at the minimum depth are accepted:
+	assert_eq!(ensure_valid_payment_block(98, 100, polkadot_depth, None), Ok(()));
+	assert_eq!(ensure_valid_payment_block(90, 100, kusama_depth, None), Ok(()));
+
+	// Anything shallower is rejected:
+	assert_eq!(
+		ensure_valid_payment_block(99, 100, polkadot_depth, None),
+		Err(PaymentError::Unfinalized)
+	);
+	assert_eq!(
+		ensure_valid_payment_block(98, 100, kusama_depth, None),
+		Err(PaymentError::Unfinalized)
+	);
+
+	// Both limits apply at the same time:
+	assert_eq!(ensure_valid_payment_block(90, 100, kusama_depth, Some(10)), Ok(()));
+	assert_eq!(
+		ensure_valid_payment_block(89, 100, kusama_depth, Some(10)),
+		Err(PaymentError::TooOld)
+	);
+}
+
+#[rocket::async_test]
+async fn parallel_matching_works() {
+	let items: Arc<[u32]> = (0..10_000).collect();
+
+	for threshold in [None, Some(0), Some(100), Some(20_000)] {
+		let find = |predicate: fn(&u32) -> bool| find_match(items.clone(), threshold, predicate);
+		assert_eq!(find(|item| *item == 0).await, Some(0));
+		assert_eq!(find(|item| *item == 9_999).await, Some(9_999));
+		// The position of the first match is returned:
+		assert_eq!(find(|item| *item % 3_000 == 2_999).await, Some(2_999));
+		assert_eq!(find(|item| *item == 10_000).await, None);
+		assert_eq!(find_match(Arc::from(vec![]), threshold, |item: &u32| *item == 0).await, None);
+	}
+}
+
+#[test]
+fn querying_registration_payment_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![registration_payment]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		// The mock paras were registered without a payment:
+		let response = client.get("/registration/polkadot/2000/payment").dispatch();
+		assert_eq!(response.status(), Status::NotFound);
+
+		let payment = PaymentDetails {
+			block_number: 9145403,
+			block_hash: Some("0x01".to_string()),
+			extrinsic_index: 2,
+			payer: Some("0x00".to_string()),
+		};
+		let mut paras = registered_paras();
+		paras.iter_mut().for_each(|para| para.payment = Some(payment.clone()));
+		update_registry(paras).unwrap();
+
+		let response = client.get("/registration/polkadot/2000/payment").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		let receipt: PaymentReceipt =
+			serde_json::from_str(&response.into_string().unwrap()).unwrap();
+		assert_eq!(
+			receipt,
+			PaymentReceipt {
+				block_number: 9145403,
+				block_hash: Some("0x01".to_string()),
+				extrinsic_index: 2,
+				// The payer is only returned to admins.
+				payer: None,
+				extrinsic: None
+			}
+		);
+
+		let response = client
+			.get("/registration/polkadot/2000/payment")
+			.header(Header::new(ADMIN_KEY_HEADER, "mock-admin-key-secret"))
+			.dispatch();
+		let receipt: PaymentReceipt =
+			serde_json::from_str(&response.into_string().unwrap()).unwrap();
+		assert_eq!(receipt.payer, payment.payer);
+	});
+}
+
+#[test]
+fn payment_info_is_selected_per_relay_chain() {
+	let payment_info = config().payment_info.unwrap();
+	let polkadot_para = get_para(Polkadot, 2000).unwrap();
+	let kusama_para = get_para(Kusama, 2000).unwrap();
+
+	// Without a per relay chain configuration the same one applies to all parachains:
+	let selected = para_payment_info(&kusama_para, &payment_info).unwrap();
+	assert_eq!(selected.rpc_url, payment_info.rpc_url);
+	assert_eq!(selected.receiver, payment_info.receiver);
+	assert_eq!(selected.cost, payment_info.cost);
+
+	let kusama = RelayChainPaymentInfo {
+		relay_chain: Kusama,
+		rpc_url: "wss://kusama-asset-hub-rpc.polkadot.io".to_string(),
+		receiver: derive_sub_account(&payment_info.receiver, &kusama_para).unwrap(),
+		cost: "42".to_string(),
+		payment_chain: None,
+		call_indices: None,
+		tier_costs: vec![TierCost { tier: "basic".to_string(), cost: "21".to_string() }],
+	};
+	let payment_info = PaymentInfo { relay_chains: vec![kusama.clone()], ..payment_info };
+
+	let selected = para_payment_info(&kusama_para, &payment_info).unwrap();
+	assert_eq!(selected.rpc_url, kusama.rpc_url);
+	assert_eq!(selected.receiver, kusama.receiver);
+	assert_eq!(selected.cost, kusama.cost);
+	// The rest of the configuration is shared:
+	assert_eq!(selected.subscription_duration, payment_info.subscription_duration);
+	// The tiers are paid with the cost configured for the relay chain:
+	let basic = Parachain { tier: Some("basic".into()), ..kusama_para.clone() };
+	assert_eq!(para_payment_info(&basic, &payment_info).unwrap().cost, "21");
+	// Selecting again doesn't change the selected configuration:
+	assert_eq!(para_payment_info(&kusama_para, &selected).unwrap().cost, kusama.cost);
+
+	// Parachains of relay chains without a configuration can't pay:
+	assert_eq!(
+		para_payment_info(&polkadot_para, &payment_info).err(),
+		Some(PaymentError::NotConfigured)
+	);
+}
+
+#[test]
+fn batch_payment_call_works() {
+	let payment_info = config().payment_info.unwrap();
+	let para = get_para(Polkadot, 2000).unwrap();
+	let other = get_para(Polkadot, 2004).unwrap();
+
+	// A batch of a single parachain is paid for the same way as its regular registration:
+	let regular = accepted_payment_calls(para.clone(), payment_info.clone()).unwrap();
+	assert_eq!(batch_payment_call(&[para.clone()], &payment_info).unwrap(), regular[0]);
+
+	// The batch covers the cost of all the parachains, and lists them in the remark:
+	let batch = batch_payment_call(&[para.clone(), other.clone()], &payment_info).unwrap();
+	assert!(!regular.contains(&batch));
+	let remark = b"regionx-weigher::Polkadot:2000,2004";
+	assert!(batch.windows(remark.len()).any(|window| window == remark));
+	assert_ne!(batch, batch_payment_call(&[other, para], &payment_info).unwrap());
+
+	assert_eq!(batch_payment_call(&[], &payment_info), Err(PaymentError::ValidationFailed));
+}
+
+#[test]
+fn remark_prefix_works() {
+	let payment_info = config().payment_info.unwrap();
+	let para = get_para(Polkadot, 2000).unwrap();
+	let contains = |call: &Vec<u8>, remark: &[u8]| call.windows(remark.len()).any(|w| w == remark);
+
+	// Without a prefix the remark is only namespaced by the service:
+	let calls = accepted_payment_calls(para.clone(), payment_info.clone()).unwrap();
+	assert!(contains(&calls[0], b"regionx-weigher::Polkadot:2000"));
+
+	// The remarks of different deployments differ, and so do the accepted payments:
+	let prefixed = PaymentInfo { remark_prefix: Some("eu-1".to_string()), ..payment_info };
+	let prefixed_calls = accepted_payment_calls(para.clone(), prefixed.clone()).unwrap();
+	assert!(contains(&prefixed_calls[0], b"regionx-weigher::eu-1:Polkadot:2000"));
+	assert!(!prefixed_calls.iter().any(|call| calls.contains(call)));
+
+	let batch = batch_payment_call(&[para], &prefixed).unwrap();
+	assert!(contains(&batch, b"regionx-weigher::eu-1:Polkadot:2000"));
+}
+
+#[test]
+fn subscription_tiers_work() {
+	let payment_info = config().payment_info.unwrap();
+	let para = get_para(Polkadot, 2000).unwrap();
+	let basic = Parachain { tier: Some("basic".into()), ..para.clone() };
+
+	// The default tier is paid with the base cost, and records every block:
+	assert_eq!(para_payment_info(&para, &payment_info).unwrap().cost, "1000000000");
+	assert_eq!(sampling_interval(&para), 1);
+
+	// The tier of the parachain overrides the cost:
+	let resolved = para_payment_info(&basic, &payment_info).unwrap();
+	assert_eq!(resolved.cost, "500000000");
+	assert_eq!(sampling_interval(&basic), 10);
+
+	// Resolving the payment info again keeps overrides of the cost, e.g. for renewals:
+	let renewal = PaymentInfo { cost: "42".into(), ..resolved };
+	assert_eq!(para_payment_info(&basic, &renewal).unwrap().cost, "42");
+
+	// Parachains of tiers which are no longer configured fall back to the default tier:
+	let removed = Parachain { tier: Some("removed".into()), ..para };
+	assert_eq!(para_payment_info(&removed, &payment_info).unwrap().cost, "1000000000");
+	assert_eq!(sampling_interval(&removed), 1);
+
+	assert!(is_sampled(20, 10));
+	assert!(!is_sampled(21, 10));
+	assert!(is_sampled(21, 1));
+
+	// Only the sampled blocks are backfilled by the tracker:
+	assert_eq!(sampled_blocks(15..45, 10).collect::<Vec<_>>(), vec![20, 30, 40]);
+	assert_eq!(sampled_blocks(15..18, 1).collect::<Vec<_>>(), vec![15, 16, 17]);
+	assert_eq!(sampled_blocks(11..20, 10).count(), 0);
+}
+
+#[test]
+fn receiver_is_parsed_regardless_of_the_network_prefix() {
+	let with_receiver = |receiver: &str| -> PaymentInfo {
+		serde_json::from_value(serde_json::json!({
+			"rpc_url": "wss://rococo-rpc.polkadot.io",
+			"receiver": receiver,
+			"cost": "1000000000",
+			"subscription_duration": 2419200,
+			"renewal_period": 604800,
+		}))
+		.unwrap()
+	};
+
+	// The receiver of the mock config, with the generic substrate prefix (42):
+	let generic = with_receiver("5DADsnBXr5DXiEAjdJvruf6c7ZSUR8iXUTATQqJfheGLiEVm");
+	assert_eq!(
+		hex::encode(generic.receiver.0),
+		"307183930b2264c5165f4a210a99520c5f1672b0413d57769fabc19e6866fb25"
+	);
+	assert_eq!(generic.receiver, config().payment_info.unwrap().receiver);
+
+	// The same account with the Polkadot (0) and Kusama (2) prefixes:
+	let polkadot = with_receiver("126X27SbhrV19mBFawys3ovkyBS87SGfYwtwa8J2FjHrtbmA");
+	let kusama = with_receiver("DfqY6XQUSETTszBQ1juocTcG9iiDoXhvq1CoVadBSUqTGJS");
+	assert_eq!(polkadot.receiver, generic.receiver);
+	assert_eq!(kusama.receiver, generic.receiver);
+
+	// The encoded `dest` of the payments is the same regardless of the prefix:
+	let para = get_para(Polkadot, 2000).unwrap();
+	let expected = accepted_payment_calls(para.clone(), generic).unwrap();
+	assert_eq!(accepted_payment_calls(para.clone(), polkadot).unwrap(), expected);
+	assert_eq!(accepted_payment_calls(para, kusama).unwrap(), expected);
+}
+
+#[test]
+fn sub_account_derivation_works() {
+	let payment_info = config().payment_info.unwrap();
+	let base = payment_info.receiver.clone();
+
+	let para = get_para(Polkadot, 2000).unwrap();
+	let sub_account = derive_sub_account(&base, &para).unwrap();
+
+	// The derivation is deterministic:
+	assert_eq!(derive_sub_account(&base, &para), Some(sub_account.clone()));
+	assert_ne!(sub_account, base);
+
+	// The sub-account is the derivative the receiver dispatches from through
+	// `utility.as_derivative(0, utility.as_derivative(2000, ..))`:
+	assert_eq!(relay_chain_index(&Polkadot), 0);
+	assert_eq!(
+		hex::encode(utility_derivative(&base, 0).0),
+		"4353ebfdf339fd1c5a8ce66bc17135acb43d26b8db5558d5d61268729c7eab01"
+	);
+	assert_eq!(utility_derivative(&utility_derivative(&base, 0), 2000), sub_account);
+
+	// Each parachain pays to a unique sub-account:
+	assert_ne!(
+		derive_sub_account(&base, &get_para(Polkadot, 2004).unwrap()),
+		Some(sub_account.clone())
+	);
+	assert_ne!(
+		derive_sub_account(&base, &get_para(Kusama, 2000).unwrap()),
+		Some(sub_account.clone())
+	);
+
+	// The receiver is only derived when configured:
+	assert_eq!(payment_receiver(&para, &payment_info), Ok(base.clone()));
+	let derived = PaymentInfo { derive_receiver: true, ..payment_info };
+	assert_eq!(payment_receiver(&para, &derived), Ok(sub_account));
+
+	// Para ids which don't fit a derivative index can't pay to a sub-account:
+	let unindexable = Parachain { para_id: u16::MAX as u32 + 1, ..para };
+	assert_eq!(derive_sub_account(&base, &unindexable), None);
+	assert_eq!(payment_receiver(&unindexable, &derived), Err(PaymentError::UnderivableReceiver));
+}
+
+#[test]
+fn payer_verification_works() {
+	let payment_info = config().payment_info.unwrap();
+	let para = get_para(Polkadot, 2000).unwrap();
+
+	// Only the managers configured for the parachain itself are authorized:
+	let manager = payment_info.receiver.clone();
+	let other = utility_derivative(&manager, 0);
+	let managed = PaymentInfo {
+		para_managers: vec![(Polkadot, 2000, manager.clone()), (Kusama, 2000, other.clone())],
+		..payment_info.clone()
+	};
+	assert!(configured_payers(&para, &payment_info).is_empty());
+	assert_eq!(configured_payers(&para, &managed), vec![manager.clone()]);
+	assert!(configured_payers(&get_para(Polkadot, 2004).unwrap(), &managed).is_empty());
+
+	// The payer is compared against the encoded address of the extrinsic:
+	let mut address = vec![0];
+	address.extend_from_slice(&manager.0);
+	assert!(is_signed_by(Some(&address), &[manager.clone()]));
+	assert!(!is_signed_by(Some(&address), &[other]));
+	assert!(!is_signed_by(None, &[manager]));
+
+	// Any account can pay unless the payer is verified:
+	let runtime = rocket::tokio::runtime::Builder::new_current_thread().build().unwrap();
+	assert_eq!(runtime.block_on(authorized_payers(&[para.clone()], &payment_info)), Ok(None));
+
+	// The manager is resolved through the relay chain, which the mock config has no rpc url for:
+	let verified = PaymentInfo { verify_payer: true, ..managed };
+	assert_eq!(
+		runtime.block_on(authorized_payers(&[para], &verified)),
+		Err(PaymentError::ManagerUnresolvable)
+	);
+}
+
+#[test]
+fn sub_account_payments_are_matched() {
+	let payment_info = config().payment_info.unwrap();
+	let derived = PaymentInfo { derive_receiver: true, ..payment_info.clone() };
+
+	let para = get_para(Polkadot, 2000).unwrap();
+	let other = get_para(Polkadot, 2004).unwrap();
+
+	// Only the remark-based payments are accepted by default, made with either transfer:
+	let accepted = accepted_payment_calls(para.clone(), payment_info.clone()).unwrap();
+	assert_eq!(accepted.len(), 2);
+
+	// When paying to a sub-account the remark is optional:
+	let accepted_derived = accepted_payment_calls(para.clone(), derived.clone()).unwrap();
+	assert_eq!(accepted_derived.len(), 4);
+	// The transfer without the remark is the one returned as the payment call data:
+	assert!(accepted_derived[0].len() < accepted_derived[1].len());
+	// The payments are sent to the sub-account instead of the base account:
+	assert!(!accepted_derived.contains(&accepted[0]));
+
+	// The payment of one parachain doesn't match the payment of another:
+	let accepted_other = accepted_payment_calls(other, derived).unwrap();
+	assert!(accepted_derived.iter().all(|call| !accepted_other.contains(call)));
+}
+
+#[test]
+fn transfer_allow_death_payments_are_accepted() {
+	let payment_info = config().payment_info.unwrap();
+	let para = get_para(Polkadot, 2000).unwrap();
+	let other = get_para(Polkadot, 2004).unwrap();
+
+	// The batch is followed by the number of calls and the transfer, starting with the index of
+	// the `Balances` pallet and the index of the call.
+	let call_index = |call: &Vec<u8>| call[4];
+
+	// The payment can be made with `transfer_allow_death`, e.g. when emptying the account of the
+	// payer, while `transfer_keep_alive` is still returned as the payment call data:
+	let accepted = accepted_payment_calls(para.clone(), payment_info.clone()).unwrap();
+	let (keep_alive, allow_death) = (&accepted[0], &accepted[1]);
+	assert_ne!(call_index(keep_alive), call_index(allow_death));
+	// Apart from the transfer call, the payments are the same:
+	assert_eq!(keep_alive[..4], allow_death[..4]);
+	assert_eq!(keep_alive[5..], allow_death[5..]);
+
+	// This also applies to the payments of a batch:
+	let paras = [para.clone(), other];
+	let batch = batch_payment_call(&paras, &payment_info).unwrap();
+	assert_eq!(call_index(&batch), call_index(keep_alive));
+
+	// Asset payments are only accepted with the configured transfer:
+	let usdt = PaymentInfo { asset_id: Some(1984), ..payment_info };
+	assert_eq!(accepted_payment_calls(para, usdt).unwrap().len(), 1);
+}
+
+#[test]
+fn asset_payments_work() {
+	let payment_info = config().payment_info.unwrap();
+	let usdt = PaymentInfo { asset_id: Some(1984), ..payment_info.clone() };
+	let usdc = PaymentInfo { asset_id: Some(1337), ..payment_info.clone() };
+
+	let para = get_para(Polkadot, 2000).unwrap();
+
+	let native = accepted_payment_calls(para.clone(), payment_info).unwrap();
+	let usdt_payment = accepted_payment_calls(para.clone(), usdt.clone()).unwrap();
+	let usdc_payment = accepted_payment_calls(para, usdc).unwrap();
+
+	// Each asset is paid with a different transfer:
+	assert_ne!(native, usdt_payment);
+	assert_ne!(usdt_payment, usdc_payment);
+
+	// The batch is followed by the number of calls and the asset transfer, starting with its call
+	// index and the compact encoded asset id.
+	let transfer = &usdt_payment[0][3..];
+	assert_eq!(usdt_payment[0][2], 8);
+	assert_eq!(transfer[..2], usdt.asset_transfer_call);
+	assert_eq!(transfer[2..4], [0x01, 0x1f]);
+}
+
+#[test]
+fn payments_can_be_made_on_a_parachain() {
+	let payment_info = config().payment_info.unwrap();
+	let para = get_para(Polkadot, 2000).unwrap();
+	let runtime = rocket::tokio::runtime::Builder::new_current_thread().build().unwrap();
+
+	// By default the payments are made on the relay chain, with the calls of the static metadata:
+	assert_eq!(payment_info.payment_chain, PaymentChain::RelayChain);
+	assert_eq!(payment_info.call_indices, None);
+	let resolved = runtime.block_on(payment_chain_info(payment_info.clone())).unwrap();
+	assert_eq!(resolved.call_indices, None);
+
+	// On the Asset Hub the same calls have different indices:
+	let asset_hub = CallIndices {
+		transfer_keep_alive: [10, 3],
+		transfer_allow_death: [10, 0],
+		batch_all: [40, 2],
+		remark: [0, 0],
+	};
+	let on_asset_hub = PaymentInfo {
+		payment_chain: PaymentChain::Parachain,
+		call_indices: Some(asset_hub),
+		..payment_info.clone()
+	};
+	// Configured indices aren't resolved from the metadata of the payment chain:
+	let resolved = runtime.block_on(payment_chain_info(on_asset_hub.clone())).unwrap();
+	assert_eq!(resolved.call_indices, Some(asset_hub));
+
+	// The payments only differ in the indices of their calls. Each is a batch followed by the
+	// number of calls, the transfer and the remark.
+	let relay_chain = relay_chain_call_indices();
+	let relay_chain_payments = accepted_payment_calls(para.clone(), payment_info).unwrap();
+	let asset_hub_payments = accepted_payment_calls(para, on_asset_hub).unwrap();
+	let transfers = [
+		(relay_chain.transfer_keep_alive, asset_hub.transfer_keep_alive),
+		(relay_chain.transfer_allow_death, asset_hub.transfer_allow_death),
+	];
+	assert_eq!(asset_hub_payments.len(), transfers.len());
+
+	let remark = b"regionx-weigher::Polkadot:2000";
+	for ((relay_chain_payment, asset_hub_payment), (relay_transfer, asset_hub_transfer)) in
+		relay_chain_payments.iter().zip(&asset_hub_payments).zip(transfers)
+	{
+		let remark_position = relay_chain_payment.len() - remark.len() - 3;
+		assert_eq!(relay_chain_payment[..2], relay_chain.batch_all);
+		assert_eq!(relay_chain_payment[3..5], relay_transfer);
+		assert_eq!(relay_chain_payment[remark_position..remark_position + 2], relay_chain.remark);
+
+		let mut expected = relay_chain_payment.clone();
+		expected[..2].copy_from_slice(&asset_hub.batch_all);
+		expected[3..5].copy_from_slice(&asset_hub_transfer);
+		expected[remark_position..remark_position + 2].copy_from_slice(&asset_hub.remark);
+		assert_eq!(*asset_hub_payment, expected);
+	}
+}
+
+#[test]
+fn call_indices_are_resolved_from_the_metadata() {
+	let metadata = std::fs::read("../artifacts/metadata.scale").unwrap();
+	let metadata = Metadata::decode(&mut metadata.as_slice()).unwrap();
+
+	// The indices resolved from the static metadata are the ones of the relay chain:
+	assert_eq!(metadata_call_indices(&metadata), Some(relay_chain_call_indices()));
+}
@@ -0,0 +1,152 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use rocket::{
+	http::{ContentType, Header, Status},
+	local::blocking::{Client, LocalResponse},
+	routes,
+};
+use routes::{
+	rate_limit::RateLimiter,
+	register::{register_para, RegistrationData},
+	registry::registry,
+	Error, ErrorResponse,
+};
+use std::{
+	net::{IpAddr, SocketAddr},
+	time::{Duration, Instant},
+};
+use types::RelayChain::*;
+
+mod mock;
+use mock::MockEnvironment;
+
+#[test]
+fn token_buckets_work() {
+	let limiter = RateLimiter::new(2, vec![]);
+	let (client, other_client): (IpAddr, IpAddr) =
+		("10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap());
+	let now = Instant::now();
+
+	assert_eq!(limiter.acquire(client, now), Ok(()));
+	assert_eq!(limiter.acquire(client, now), Ok(()));
+	// Two requests per minute refill a token every 30 seconds:
+	assert_eq!(limiter.acquire(client, now), Err(30));
+	assert_eq!(limiter.acquire(client, now + Duration::from_secs(20)), Err(10));
+
+	// Each client has its own bucket:
+	assert_eq!(limiter.acquire(other_client, now), Ok(()));
+
+	assert_eq!(limiter.acquire(client, now + Duration::from_secs(30)), Ok(()));
+	assert!(limiter.acquire(client, now + Duration::from_secs(30)).is_err());
+}
+
+#[test]
+fn mutating_endpoints_are_rate_limited() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build()
+			.mount("/", routes![register_para, registry])
+			.attach(RateLimiter::new(2, vec![]));
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+		let remote: SocketAddr = "10.0.0.1:8000".parse().unwrap();
+
+		let registration_data = RegistrationData {
+			para: (Polkadot, 2000),
+			payment_block_number: None,
+			payment_search_window: 0,
+			rpc_url: None,
+			backfill_from: None,
+			tier: None,
+		};
+		let register = |remote: SocketAddr| {
+			client
+				.post("/register_para")
+				.remote(remote)
+				.header(ContentType::JSON)
+				.body(serde_json::to_string(&registration_data).unwrap())
+				.dispatch()
+		};
+
+		// Failed requests count towards the limit as well:
+		assert_eq!(parse_err_response(register(remote)), Error::AlreadyRegistered);
+		assert_eq!(parse_err_response(register(remote)), Error::AlreadyRegistered);
+
+		let response = register(remote);
+		assert_eq!(response.status(), Status::TooManyRequests);
+		assert_eq!(response.headers().get_one("Retry-After"), Some("30"));
+		assert_eq!(parse_err_response(response), Error::RateLimited(30));
+
+		// Other clients aren't affected:
+		let other: SocketAddr = "10.0.0.2:8000".parse().unwrap();
+		assert_eq!(parse_err_response(register(other)), Error::AlreadyRegistered);
+
+		// Read-only endpoints aren't rate limited:
+		for _ in 0..5 {
+			let response = client.get("/registry").remote(remote).dispatch();
+			assert_eq!(response.status(), Status::Ok);
+		}
+
+		// The forwarded client IP of untrusted peers is ignored:
+		let response = client
+			.post("/register_para")
+			.remote(remote)
+			.header(Header::new("X-Real-IP", "10.0.0.3"))
+			.header(ContentType::JSON)
+			.body(serde_json::to_string(&registration_data).unwrap())
+			.dispatch();
+		assert_eq!(response.status(), Status::TooManyRequests);
+	});
+}
+
+#[test]
+fn forwarded_client_ips_of_trusted_proxies_are_used() {
+	MockEnvironment::new().execute_with(|| {
+		let proxy: SocketAddr = "10.0.0.100:8000".parse().unwrap();
+		let rocket = rocket::build()
+			.mount("/", routes![register_para])
+			.attach(RateLimiter::new(1, vec![proxy.ip()]));
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let registration_data = RegistrationData {
+			para: (Polkadot, 2000),
+			payment_block_number: None,
+			payment_search_window: 0,
+			rpc_url: None,
+			backfill_from: None,
+			tier: None,
+		};
+		let register = |forwarded_ip: &str| {
+			client
+				.post("/register_para")
+				.remote(proxy)
+				.header(Header::new("X-Real-IP", forwarded_ip.to_string()))
+				.header(ContentType::JSON)
+				.body(serde_json::to_string(&registration_data).unwrap())
+				.dispatch()
+		};
+
+		assert_eq!(parse_err_response(register("10.0.0.1")), Error::AlreadyRegistered);
+		assert_eq!(register("10.0.0.1").status(), Status::TooManyRequests);
+
+		// Each client behind the proxy has its own bucket:
+		assert_eq!(parse_err_response(register("10.0.0.2")), Error::AlreadyRegistered);
+	});
+}
+
+fn parse_err_response<'a>(response: LocalResponse<'a>) -> Error {
+	let body = response.into_string().unwrap();
+	let response: ErrorResponse = serde_json::from_str(&body).expect("can't parse error");
+	response.error.into()
+}
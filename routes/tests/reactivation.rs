@@ -0,0 +1,169 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use rocket::{
+	http::Status,
+	local::blocking::{Client, LocalResponse},
+	routes,
+};
+use routes::{
+	reactivation::{reactivation_cost, ReactivationCost},
+	Error, ErrorResponse,
+};
+use shared::{
+	config::{config, LapsePolicy, PaymentInfo},
+	current_timestamp,
+	payment::{cached_fee_estimate, note_fee_estimate, PaymentError},
+	registry::{registered_para, update_registry},
+	set_mock_timestamp,
+	subscription::{extension, prorated_cost, renewal, Renewal},
+};
+use types::{Balance, RelayChain::*};
+
+mod mock;
+use mock::MockEnvironment;
+
+#[test]
+fn reactivation_cost_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![reactivation_cost]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let payment_info = config().payment_info.unwrap();
+		let cost: Balance = payment_info.cost.parse().unwrap();
+		let duration = payment_info.subscription_duration;
+
+		// The subscription of the parachain lapsed half a subscription period ago:
+		let now = current_timestamp();
+		set_mock_timestamp(Some(now));
+		let mut para = registered_para(Polkadot, 2000).unwrap();
+		para.expiry_timestamp = now - duration / 2;
+		update_registry(vec![para.clone()]).unwrap();
+
+		let response = client.get("/reactivation-cost/polkadot/2000").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		let reactivation = parse_ok_response(response);
+		// By default the lapsed period is charged for along with the new one:
+		assert_eq!(reactivation.cost, 2 * cost);
+		assert!(reactivation.call_data.is_some());
+		assert_eq!(reactivation.expiry_timestamp, para.expiry_timestamp + 2 * duration);
+		// Fee estimation isn't enabled in the mock config:
+		assert_eq!(reactivation.estimated_fee, None);
+		assert!(!reactivation.fee_estimation_unavailable);
+	});
+}
+
+#[test]
+fn reactivation_cost_for_unregistered_fails() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![reactivation_cost]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let response = client.get("/reactivation-cost/polkadot/2006").dispatch();
+		assert_eq!(parse_err_response(response), Error::NotRegistered);
+	});
+}
+
+#[test]
+fn lapse_policies_work() {
+	let payment_info = PaymentInfo {
+		cost: "100".to_string(),
+		subscription_duration: 10,
+		..config().payment_info.unwrap()
+	};
+	let now = 100;
+
+	// Active subscriptions are simply extended regardless of the policy:
+	for lapse_policy in [LapsePolicy::Forgive, LapsePolicy::Charge] {
+		let payment_info = PaymentInfo { lapse_policy, ..payment_info.clone() };
+		assert_eq!(
+			renewal(105, &payment_info, now),
+			Ok(Renewal { cost: 100, expiry_timestamp: 115 })
+		);
+	}
+
+	// Forgiving the lapse starts the new subscription period now:
+	let forgive = PaymentInfo { lapse_policy: LapsePolicy::Forgive, ..payment_info.clone() };
+	assert_eq!(renewal(75, &forgive, now), Ok(Renewal { cost: 100, expiry_timestamp: 110 }));
+
+	// Charging for the lapse requires paying for the lapsed periods as well:
+	let charge = PaymentInfo { lapse_policy: LapsePolicy::Charge, ..payment_info };
+	// 25 seconds lapsed, which is covered by three subscription periods.
+	assert_eq!(renewal(75, &charge, now), Ok(Renewal { cost: 400, expiry_timestamp: 115 }));
+	// Exactly two lapsed subscription periods.
+	assert_eq!(renewal(80, &charge, now), Ok(Renewal { cost: 300, expiry_timestamp: 110 }));
+
+	// The lapse is charged for unless configured otherwise:
+	assert_eq!(LapsePolicy::default(), LapsePolicy::Charge);
+}
+
+#[test]
+fn extensions_are_prorated() {
+	assert_eq!(prorated_cost(100, 10, 10), 100);
+	assert_eq!(prorated_cost(100, 5, 10), 50);
+	// The cost is rounded up:
+	assert_eq!(prorated_cost(100, 1, 3), 34);
+
+	let payment_info = PaymentInfo {
+		cost: "100".to_string(),
+		subscription_duration: 10,
+		..config().payment_info.unwrap()
+	};
+	let now = 100;
+
+	// Active subscriptions are extended by the requested duration:
+	assert_eq!(
+		extension(105, &payment_info, now, 5),
+		Ok(Renewal { cost: 50, expiry_timestamp: 110 })
+	);
+	// A full subscription period is the same as a renewal:
+	assert_eq!(extension(105, &payment_info, now, 10), renewal(105, &payment_info, now));
+
+	let forgive = PaymentInfo { lapse_policy: LapsePolicy::Forgive, ..payment_info.clone() };
+	assert_eq!(extension(75, &forgive, now, 5), Ok(Renewal { cost: 50, expiry_timestamp: 105 }));
+
+	// The lapsed periods are paid for in full:
+	let charge = PaymentInfo { lapse_policy: LapsePolicy::Charge, ..payment_info.clone() };
+	assert_eq!(extension(75, &charge, now, 5), Ok(Renewal { cost: 350, expiry_timestamp: 110 }));
+
+	// Durations overflowing the expiry are rejected:
+	assert_eq!(extension(105, &payment_info, now, u64::MAX), Err(PaymentError::DurationOverflow));
+	assert_eq!(extension(75, &forgive, now, u64::MAX), Err(PaymentError::DurationOverflow));
+	assert_eq!(extension(75, &charge, now, u64::MAX), Err(PaymentError::DurationOverflow));
+}
+
+#[test]
+fn fee_estimates_are_cached() {
+	MockEnvironment::new().execute_with(|| {
+		assert_eq!(cached_fee_estimate(b"payment"), None);
+
+		note_fee_estimate(b"payment".to_vec(), 100);
+		assert_eq!(cached_fee_estimate(b"payment"), Some(100));
+		// Estimates are cached per call:
+		assert_eq!(cached_fee_estimate(b"other-payment"), None);
+	});
+}
+
+fn parse_ok_response<'a>(response: LocalResponse<'a>) -> ReactivationCost {
+	let body = response.into_string().unwrap();
+	serde_json::from_str(&body).expect("can't parse value")
+}
+
+fn parse_err_response<'a>(response: LocalResponse<'a>) -> Error {
+	let body = response.into_string().unwrap();
+	let response: ErrorResponse = serde_json::from_str(&body).expect("can't parse error");
+	response.error.into()
+}
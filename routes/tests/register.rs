@@ -20,13 +20,20 @@ use rocket::{
 	routes,
 };
 use routes::{
-	register::{register_para, RegistrationData},
-	Error,
+	register::{
+		register_para, register_paras, BatchRegistrationData, RegistrationData, RegistrationOutcome,
+	},
+	top_up::{top_up, TopUpData},
+	Error, ErrorResponse,
 };
 use shared::{
-	chaindata::get_para,
-	payment::PaymentError,
-	registry::{registered_para, registered_paras},
+	chaindata::{get_para, ChainDataError},
+	config::config,
+	current_timestamp,
+	payment::{note_rpc_latency, PaymentError, RPC_DEGRADATION_PERIOD},
+	registry::{registered_para, registered_paras, update_registry},
+	subscription::subscription_state,
+	tracker::backfill_range,
 };
 use types::RelayChain::*;
 
@@ -45,6 +52,10 @@ fn register_works() {
 		let registration_data = RegistrationData {
 			para: (Polkadot, 2000),
 			payment_block_number: Some(PARA_2000_PAYMENT),
+			payment_search_window: 0,
+			rpc_url: None,
+			backfill_from: None,
+			tier: None,
 		};
 
 		let response = client
@@ -55,14 +66,228 @@ fn register_works() {
 
 		assert_eq!(response.status(), Status::Ok);
 
-		let registered = registered_para(Polkadot, 2000).unwrap();
+		// The response contains the assigned expiry of the subscription:
+		let now = current_timestamp();
+		let outcome = parse_outcome(response);
+		assert_eq!(outcome.para, (Polkadot, 2000));
+		assert!(!outcome.dry_run);
+		let subscription_duration = config().payment_info.unwrap().subscription_duration;
+		assert!(outcome.expiry_timestamp <= now + subscription_duration);
+		assert!(outcome.expiry_timestamp + 60 >= now + subscription_duration);
 
-		// Set the `expiry_timestamp` to the proper value.
-		para.expiry_timestamp = registered.expiry_timestamp;
+		// The block containing the payment is recorded for auditing:
+		let payment = outcome.payment.clone().unwrap();
+		assert_eq!(payment.block_number, PARA_2000_PAYMENT);
+		assert!(payment.block_hash.is_some());
 
 		// Ensure the parachain is properly registered:
-		assert_eq!(registered_paras(), vec![para.clone()]);
-		assert_eq!(registered, para);
+		para.expiry_timestamp = outcome.expiry_timestamp;
+		para.payment = outcome.payment;
+		assert_eq!(registered_paras(), vec![para]);
+	});
+}
+
+#[test]
+fn registering_with_backfill_works() {
+	MockEnvironment::default().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![register_para]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let registration_data = RegistrationData {
+			para: (Polkadot, 2000),
+			payment_block_number: Some(PARA_2000_PAYMENT),
+			payment_search_window: 0,
+			rpc_url: None,
+			backfill_from: Some(9000000),
+			tier: None,
+		};
+
+		let response = client
+			.post("/register_para")
+			.header(ContentType::JSON)
+			.body(serde_json::to_string(&registration_data).unwrap())
+			.dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		// The tracker backfills the consumption from the specified block:
+		let para = registered_para(Polkadot, 2000).unwrap();
+		assert_eq!(para.backfill_from, Some(9000000));
+	});
+}
+
+#[test]
+fn backfilled_blocks_do_not_overlap_the_live_ones() {
+	let max_blocks = config().max_backfill_blocks;
+	assert_eq!(max_blocks, 100_800);
+
+	// The backfill ends right before the first block of the subscription:
+	assert_eq!(backfill_range(900, None, 1000, max_blocks), 900..1000);
+
+	// When resuming, the blocks recorded before the restart aren't backfilled again:
+	assert_eq!(backfill_range(900, Some(950), 1000, max_blocks), 951..1000);
+	assert_eq!(backfill_range(900, Some(800), 1000, max_blocks), 900..1000);
+
+	// Nothing is backfilled once the live blocks were recorded:
+	assert!(backfill_range(900, Some(1005), 1000, max_blocks).is_empty());
+	assert!(backfill_range(1200, None, 1000, max_blocks).is_empty());
+
+	// Only the most recent blocks are backfilled when the range is too wide:
+	assert_eq!(backfill_range(0, None, 20_000_000, max_blocks), 19_899_200..20_000_000);
+	assert_eq!(backfill_range(0, None, 50, max_blocks), 0..50);
+}
+
+#[test]
+fn dry_run_registration_works() {
+	MockEnvironment::default().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![register_para]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let registration_data = RegistrationData {
+			para: (Polkadot, 2000),
+			payment_block_number: Some(PARA_2000_PAYMENT),
+			payment_search_window: 0,
+			rpc_url: None,
+			backfill_from: None,
+			tier: None,
+		};
+		let register = |dry_run: bool| {
+			client
+				.post(format!("/register_para?dry_run={}", dry_run))
+				.header(ContentType::JSON)
+				.body(serde_json::to_string(&registration_data).unwrap())
+				.dispatch()
+		};
+
+		// The registration is validated without being committed:
+		let response = register(true);
+		assert_eq!(response.status(), Status::Ok);
+		let outcome = parse_outcome(response);
+		assert!(outcome.dry_run);
+		assert_eq!(outcome.para, (Polkadot, 2000));
+		assert_eq!(outcome.payment.map(|payment| payment.block_number), Some(PARA_2000_PAYMENT));
+		assert!(registered_paras().is_empty());
+
+		// The payment wasn't used, so the registration can be submitted afterwards:
+		let response = register(false);
+		assert_eq!(response.status(), Status::Ok);
+		assert!(!parse_outcome(response).dry_run);
+		assert!(registered_para(Polkadot, 2000).is_some());
+
+		// Dry runs are validated the same way as actual registrations:
+		assert_eq!(parse_err_response(register(true)), Error::AlreadyRegistered);
+	});
+}
+
+#[test]
+fn register_within_search_window_works() {
+	MockEnvironment::default().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![register_para]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		// The payment was made one block later than the provided one:
+		let registration_data = RegistrationData {
+			para: (Polkadot, 2000),
+			payment_block_number: Some(PARA_2000_PAYMENT - 1),
+			payment_search_window: 1,
+			rpc_url: None,
+			backfill_from: None,
+			tier: None,
+		};
+
+		let response = client
+			.post("/register_para")
+			.header(ContentType::JSON)
+			.body(serde_json::to_string(&registration_data).unwrap())
+			.dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		let registered = registered_para(Polkadot, 2000).unwrap();
+		assert_eq!(registered.payment.map(|payment| payment.block_number), Some(PARA_2000_PAYMENT));
+	});
+}
+
+#[test]
+fn paseo_paras_can_be_registered() {
+	MockEnvironment::default().execute_with(|| {
+		let para = get_para(Paseo, 1000).unwrap();
+		assert_eq!(para.relay_chain, Paseo);
+		assert_eq!(para.para_id, 1000);
+		assert!(!para.rpcs.is_empty());
+
+		// The relay chain is encoded the same way as `Polkadot` and `Kusama`:
+		assert_eq!(serde_json::to_string(&Paseo).unwrap(), "\"Paseo\"");
+		let registration_data: RegistrationData =
+			serde_json::from_str(r#"{ "para": ["paseo", 1000] }"#).unwrap();
+		assert_eq!(registration_data.para, (Paseo, 1000));
+
+		let rocket = rocket::build().mount("/", routes![register_para]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		// The registration is only rejected because the mock config requires a payment:
+		let response = client
+			.post("/register_para")
+			.header(ContentType::JSON)
+			.body(r#"{ "para": ["Paseo", 1000] }"#)
+			.dispatch();
+		assert_eq!(parse_err_response(response), Error::PaymentRequired);
+	});
+}
+
+#[test]
+fn payment_exempt_paras_can_be_registered_without_payment() {
+	MockEnvironment::default().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![register_para, register_paras]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		// The system parachains are part of the chaindata:
+		for relay_chain in [Polkadot, Kusama, Paseo] {
+			assert!(get_para(relay_chain.clone(), 1000).is_ok());
+			assert!(get_para(relay_chain.clone(), 1002).is_ok());
+			assert!(get_para(relay_chain, 1005).is_ok());
+		}
+
+		// The mock config exempts the Polkadot Asset Hub from payment:
+		let response = client
+			.post("/register_para")
+			.header(ContentType::JSON)
+			.body(r#"{ "para": ["Polkadot", 1000] }"#)
+			.dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(parse_outcome(response).payment, None);
+		let asset_hub = registered_para(Polkadot, 1000).unwrap();
+		assert_eq!(subscription_state(&asset_hub), shared::subscription::SubscriptionState::Active);
+
+		// Other system parachains still have to be paid for:
+		let response = client
+			.post("/register_para")
+			.header(ContentType::JSON)
+			.body(r#"{ "para": ["Polkadot", 1005] }"#)
+			.dispatch();
+		assert_eq!(parse_err_response(response), Error::PaymentRequired);
+
+		// So do the non-exempt parachains of a batch:
+		update_registry(vec![]).unwrap();
+		let batch = BatchRegistrationData {
+			relay_chain: Polkadot,
+			para_ids: vec![1000, 1005],
+			payment_block_number: None,
+			payment_search_window: 0,
+		};
+		let response = client
+			.post("/register_paras")
+			.header(ContentType::JSON)
+			.body(serde_json::to_string(&batch).unwrap())
+			.dispatch();
+		assert_eq!(parse_err_response(response), Error::PaymentRequired);
+
+		let batch = BatchRegistrationData { para_ids: vec![1000], ..batch };
+		let response = client
+			.post("/register_paras")
+			.header(ContentType::JSON)
+			.body(serde_json::to_string(&batch).unwrap())
+			.dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		assert!(registered_para(Polkadot, 1000).is_some());
 	});
 }
 
@@ -75,6 +300,10 @@ fn cannot_register_same_para_twice() {
 		let registration_data = RegistrationData {
 			para: (Polkadot, 2000),
 			payment_block_number: Some(PARA_2000_PAYMENT),
+			payment_search_window: 0,
+			rpc_url: None,
+			backfill_from: None,
+			tier: None,
 		};
 
 		let register = client
@@ -84,7 +313,75 @@ fn cannot_register_same_para_twice() {
 
 		// Cannot register the same para twice:
 		assert_eq!(register.clone().dispatch().status(), Status::Ok);
-		assert_eq!(parse_err_response(register.dispatch()), Error::AlreadyRegistered);
+		let response = register.dispatch();
+		assert_eq!(response.status(), Status::Conflict);
+		assert_eq!(parse_err_response(response), Error::AlreadyRegistered);
+	});
+}
+
+#[test]
+fn cannot_reuse_payment() {
+	MockEnvironment::default().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![register_para]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let registration_data = RegistrationData {
+			para: (Polkadot, 2000),
+			payment_block_number: Some(PARA_2000_PAYMENT),
+			payment_search_window: 0,
+			rpc_url: None,
+			backfill_from: None,
+			tier: None,
+		};
+
+		let register = client
+			.post("/register_para")
+			.header(ContentType::JSON)
+			.body(serde_json::to_string(&registration_data).unwrap());
+		assert_eq!(register.clone().dispatch().status(), Status::Ok);
+
+		// The payment can't be reused even after the parachain is removed from the registry:
+		update_registry(vec![]).unwrap();
+
+		let response = register.dispatch();
+		assert_eq!(response.status(), Status::Conflict);
+		assert_eq!(parse_err_response(response), Error::PaymentAlreadyUsed);
+		assert!(registered_paras().is_empty());
+	});
+}
+
+#[test]
+fn registration_payment_cannot_be_credited() {
+	MockEnvironment::default().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![register_para, top_up]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let registration_data = RegistrationData {
+			para: (Polkadot, 2000),
+			payment_block_number: Some(PARA_2000_PAYMENT),
+			payment_search_window: 0,
+			rpc_url: None,
+			backfill_from: None,
+			tier: None,
+		};
+		let response = client
+			.post("/register_para")
+			.header(ContentType::JSON)
+			.body(serde_json::to_string(&registration_data).unwrap())
+			.dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		// The registration payment is a valid micro-payment as well, but was already used:
+		let top_up_data =
+			TopUpData { para: (Polkadot, 2000), payment_block_number: PARA_2000_PAYMENT };
+		let response = client
+			.post("/top-up")
+			.header(ContentType::JSON)
+			.body(serde_json::to_string(&top_up_data).unwrap())
+			.dispatch();
+		assert_eq!(response.status(), Status::Conflict);
+		assert_eq!(parse_err_response(response), Error::PaymentAlreadyUsed);
+		assert_eq!(registered_para(Polkadot, 2000).unwrap().credit, 0);
 	});
 }
 
@@ -94,8 +391,14 @@ fn providing_no_payment_info_fails() {
 		let rocket = rocket::build().mount("/", routes![register_para]);
 		let client = Client::tracked(rocket).expect("valid rocket instance");
 
-		let registration_data =
-			RegistrationData { para: (Polkadot, 2006), payment_block_number: None };
+		let registration_data = RegistrationData {
+			para: (Polkadot, 2006),
+			payment_block_number: None,
+			payment_search_window: 0,
+			rpc_url: None,
+			backfill_from: None,
+			tier: None,
+		};
 
 		let response = client
 			.post("/register_para")
@@ -103,6 +406,7 @@ fn providing_no_payment_info_fails() {
 			.body(serde_json::to_string(&registration_data).unwrap())
 			.dispatch();
 
+		assert_eq!(response.status(), Status::PaymentRequired);
 		assert_eq!(parse_err_response(response), Error::PaymentRequired);
 	});
 }
@@ -113,8 +417,14 @@ fn providing_non_finalized_payment_block_number_fails() {
 		let rocket = rocket::build().mount("/", routes![register_para]);
 		let client = Client::tracked(rocket).expect("valid rocket instance");
 
-		let registration_data =
-			RegistrationData { para: (Polkadot, 2006), payment_block_number: Some(99999999) };
+		let registration_data = RegistrationData {
+			para: (Polkadot, 2006),
+			payment_block_number: Some(99999999),
+			payment_search_window: 0,
+			rpc_url: None,
+			backfill_from: None,
+			tier: None,
+		};
 
 		let response = client
 			.post("/register_para")
@@ -139,6 +449,10 @@ fn payment_not_found_works() {
 		let registration_data = RegistrationData {
 			para: (Polkadot, 2006),
 			payment_block_number: Some(PARA_2000_PAYMENT),
+			payment_search_window: 0,
+			rpc_url: None,
+			backfill_from: None,
+			tier: None,
 		};
 
 		let response = client
@@ -154,7 +468,171 @@ fn payment_not_found_works() {
 	});
 }
 
+#[test]
+fn registration_rejected_during_rpc_degradation() {
+	MockEnvironment::default().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![register_para]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		// Above the `max_rpc_latency` from the mock config.
+		note_rpc_latency(60_000);
+
+		let registration_data = RegistrationData {
+			para: (Polkadot, 2000),
+			payment_block_number: Some(PARA_2000_PAYMENT),
+			payment_search_window: 0,
+			rpc_url: None,
+			backfill_from: None,
+			tier: None,
+		};
+
+		let response = client
+			.post("/register_para")
+			.header(ContentType::JSON)
+			.body(serde_json::to_string(&registration_data).unwrap())
+			.dispatch();
+
+		assert_eq!(response.status(), Status::ServiceUnavailable);
+		assert_eq!(
+			response.headers().get_one("Retry-After"),
+			Some(RPC_DEGRADATION_PERIOD.to_string().as_str())
+		);
+		assert_eq!(parse_err_response(response), Error::RpcDegraded);
+		assert!(registered_paras().is_empty());
+	});
+}
+
+#[test]
+fn registering_with_unknown_tier_fails() {
+	MockEnvironment::default().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![register_para]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let registration_data = RegistrationData {
+			para: (Polkadot, 2000),
+			payment_block_number: Some(PARA_2000_PAYMENT),
+			payment_search_window: 0,
+			rpc_url: None,
+			backfill_from: None,
+			tier: Some("premium".into()),
+		};
+
+		let response = client
+			.post("/register_para")
+			.header(ContentType::JSON)
+			.body(serde_json::to_string(&registration_data).unwrap())
+			.dispatch();
+
+		assert_eq!(response.status(), Status::BadRequest);
+		assert_eq!(parse_err_response(response), Error::UnknownTier);
+		assert!(registered_paras().is_empty());
+	});
+}
+
+#[test]
+fn registering_unknown_para_requires_reachable_rpc() {
+	MockEnvironment::default().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![register_para]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let register = |rpc_url: Option<&str>| {
+			let registration_data = RegistrationData {
+				para: (Polkadot, 9999),
+				payment_block_number: Some(PARA_2000_PAYMENT),
+				payment_search_window: 0,
+				rpc_url: rpc_url.map(ToString::to_string),
+				backfill_from: None,
+				tier: None,
+			};
+
+			client
+				.post("/register_para")
+				.header(ContentType::JSON)
+				.body(serde_json::to_string(&registration_data).unwrap())
+				.dispatch()
+		};
+
+		// The parachain isn't part of the chaindata:
+		assert_eq!(
+			parse_err_response(register(None)),
+			Error::ChainDataError(ChainDataError::ParaNotFound)
+		);
+
+		// Unknown parachains are allowed in the mock config, but only through a WebSocket rpc:
+		for rpc_url in ["http://127.0.0.1:1", "file:///etc/passwd", "127.0.0.1:1", "wss://"] {
+			let response = register(Some(rpc_url));
+			assert_eq!(response.status(), Status::BadRequest);
+			assert_eq!(
+				parse_err_response(response),
+				Error::ChainDataError(ChainDataError::InvalidRpcUrl)
+			);
+		}
+
+		// The rpc also has to be reachable:
+		assert_eq!(
+			parse_err_response(register(Some("ws://127.0.0.1:1"))),
+			Error::ChainDataError(ChainDataError::RpcUnreachable)
+		);
+		assert!(registered_paras().is_empty());
+	});
+}
+
+#[test]
+fn batch_registration_is_atomic() {
+	MockEnvironment::default().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![register_paras]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let register = |para_ids: Vec<u32>, payment_block_number: Option<BlockNumber>| {
+			let batch = BatchRegistrationData {
+				relay_chain: Polkadot,
+				para_ids,
+				payment_block_number,
+				payment_search_window: 0,
+			};
+			client
+				.post("/register_paras")
+				.header(ContentType::JSON)
+				.body(serde_json::to_string(&batch).unwrap())
+				.dispatch()
+		};
+
+		// The batch has to contain distinct parachains:
+		assert_eq!(parse_err_response(register(vec![], None)), Error::InvalidBatch);
+		assert_eq!(parse_err_response(register(vec![2000, 2000], None)), Error::InvalidBatch);
+
+		// All the parachains have to be part of the chaindata:
+		assert_eq!(
+			parse_err_response(register(vec![2000, 4242], None)),
+			Error::ChainDataError(ChainDataError::ParaNotFound)
+		);
+
+		// The batch has to be paid for:
+		assert_eq!(parse_err_response(register(vec![2000, 2004], None)), Error::PaymentRequired);
+
+		// The payment of a single parachain doesn't cover the batch:
+		assert_eq!(
+			parse_err_response(register(vec![2000, 2004], Some(PARA_2000_PAYMENT))),
+			Error::PaymentValidationError(PaymentError::NotFound)
+		);
+
+		// If any of the parachains is already registered, none of them gets registered:
+		update_registry(vec![get_para(Polkadot, 2004).unwrap()]).unwrap();
+		assert_eq!(
+			parse_err_response(register(vec![2000, 2004], Some(PARA_2000_PAYMENT))),
+			Error::AlreadyRegistered
+		);
+		assert_eq!(registered_paras(), vec![get_para(Polkadot, 2004).unwrap()]);
+	});
+}
+
+fn parse_outcome<'a>(response: LocalResponse<'a>) -> RegistrationOutcome {
+	let body = response.into_string().unwrap();
+	serde_json::from_str(&body).expect("can't parse outcome")
+}
+
 fn parse_err_response<'a>(response: LocalResponse<'a>) -> Error {
 	let body = response.into_string().unwrap();
-	body.into()
+	let response: ErrorResponse = serde_json::from_str(&body).expect("can't parse error");
+	response.error.into()
 }
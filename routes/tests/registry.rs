@@ -18,8 +18,16 @@ use rocket::{
 	local::blocking::{Client, LocalResponse},
 	routes,
 };
-use routes::registry::registry;
-use shared::chaindata::get_para;
+use routes::{
+	registry::{registry, EnrichedParachain, ListedParachain},
+	Error, ErrorResponse,
+};
+use shared::{
+	chaindata::{get_para, has_chaindata, read_chaindata_paras},
+	registry::{dedup_registry, registered_paras, update_registry},
+	set_mock_timestamp,
+	subscription::SubscriptionState,
+};
 use types::{Parachain, RelayChain::*};
 
 mod mock;
@@ -44,6 +52,334 @@ fn getting_registry_works() {
 	});
 }
 
+#[test]
+fn filtering_registry_by_relay_chain_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![registry]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let polkadot_para = get_para(Polkadot, 2000).unwrap();
+		let kusama_para = get_para(Kusama, 2000).unwrap();
+		update_registry(vec![polkadot_para.clone(), kusama_para.clone()]).unwrap();
+
+		let response = client.get("/registry?relay_chain=Polkadot").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(parse_ok_response(response), vec![polkadot_para]);
+
+		// The relay chain is case insensitive:
+		let response = client.get("/registry?relay_chain=kusama").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		assert_eq!(parse_ok_response(response), vec![kusama_para]);
+
+		let response = client.get("/registry?relay_chain=westend").dispatch();
+		assert_eq!(response.status(), Status::BadRequest);
+		let body: ErrorResponse = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+		assert_eq!(Error::from(body.error), Error::UnknownRelayChain);
+	});
+}
+
+#[test]
+fn enriching_registry_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![registry]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let response = client.get("/registry?enrich=true").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		let mut registry: Vec<EnrichedParachain> =
+			serde_json::from_str(&response.into_string().unwrap()).unwrap();
+		registry.sort_by_key(|p| p.para.para_id);
+
+		assert_eq!(
+			registry,
+			vec![
+				EnrichedParachain {
+					para: get_para(Polkadot, 2000).unwrap(),
+					status: SubscriptionState::Expired,
+					chaindata_missing: Some(false),
+					logo: Some("https://raw.githubusercontent.com/TalismanSociety/chaindata/main/assets/chains/acala.svg".to_string()),
+					category: Some("defi".to_string()),
+				},
+				// The chaindata doesn't contain any metadata for this parachain.
+				EnrichedParachain {
+					para: get_para(Polkadot, 2004).unwrap(),
+					status: SubscriptionState::Expired,
+					chaindata_missing: Some(false),
+					logo: None,
+					category: None
+				},
+			]
+		);
+	});
+}
+
+#[test]
+fn registry_lists_subscription_status() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![registry]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let now = 1_000_000;
+		set_mock_timestamp(Some(now));
+
+		let mut active = get_para(Polkadot, 2000).unwrap();
+		active.expiry_timestamp = now + 1;
+		// The mock config has a grace period of one day.
+		let mut grace_period = get_para(Polkadot, 2004).unwrap();
+		grace_period.expiry_timestamp = now - 86399;
+		let mut expired = get_para(Kusama, 2000).unwrap();
+		expired.expiry_timestamp = now - 86400;
+		update_registry(vec![active.clone(), grace_period.clone(), expired.clone()]).unwrap();
+
+		let response = client.get("/registry").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		let registry: Vec<ListedParachain> =
+			serde_json::from_str(&response.into_string().unwrap()).unwrap();
+		assert_eq!(
+			registry,
+			vec![
+				ListedParachain {
+					para: active,
+					status: SubscriptionState::Active,
+					chaindata_missing: Some(false)
+				},
+				ListedParachain {
+					para: grace_period,
+					status: SubscriptionState::GracePeriod,
+					chaindata_missing: Some(false)
+				},
+				ListedParachain {
+					para: expired,
+					status: SubscriptionState::Expired,
+					chaindata_missing: Some(false)
+				},
+			]
+		);
+	});
+}
+
+#[test]
+fn registry_flags_paras_missing_from_chaindata() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![registry]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		// A registered parachain which has no chaindata match, e.g. after a chaindata refresh:
+		let known = get_para(Polkadot, 2000).unwrap();
+		let missing = Parachain { para_id: 9999, ..known.clone() };
+		assert_eq!(has_chaindata(Polkadot, 2000), Some(true));
+		assert_eq!(has_chaindata(Polkadot, 9999), Some(false));
+		update_registry(vec![known.clone(), missing.clone()]).unwrap();
+
+		let response = client.get("/registry").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		let mut registry: Vec<ListedParachain> =
+			serde_json::from_str(&response.into_string().unwrap()).unwrap();
+		registry.sort_by_key(|p| p.para.para_id);
+		assert_eq!(
+			registry
+				.iter()
+				.map(|p| (p.para.para_id, p.chaindata_missing))
+				.collect::<Vec<_>>(),
+			vec![(2000, Some(false)), (9999, Some(true))]
+		);
+
+		// The enriched listing is flagged the same way, without any metadata:
+		let response = client.get("/registry?enrich=true").dispatch();
+		let mut registry: Vec<EnrichedParachain> =
+			serde_json::from_str(&response.into_string().unwrap()).unwrap();
+		registry.sort_by_key(|p| p.para.para_id);
+		assert_eq!(registry[1].para, missing);
+		assert_eq!(registry[1].chaindata_missing, Some(true));
+		assert_eq!((registry[1].logo.clone(), registry[1].category.clone()), (None, None));
+	});
+}
+
+#[test]
+fn unreadable_chaindata_is_unknown() {
+	MockEnvironment::new().execute_with(|| {
+		let paras = read_chaindata_paras(&shared::config::config().chaindata).unwrap();
+		assert!(paras.contains(&(Polkadot, 2000)));
+		assert!(!paras.contains(&(Kusama, 2000)));
+
+		// It is unknown whether a parachain is missing if the chaindata can't be read:
+		let dir = std::env::temp_dir().join(format!("chaindata-{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let unparseable = dir.join("chaindata.json");
+		std::fs::write(&unparseable, "invalid").unwrap();
+		assert_eq!(read_chaindata_paras(unparseable.to_str().unwrap()), None);
+		assert_eq!(read_chaindata_paras(dir.join("missing.json").to_str().unwrap()), None);
+		std::fs::remove_dir_all(dir).unwrap();
+	});
+}
+
+#[test]
+fn registry_deduplication_works() {
+	MockEnvironment::new().execute_with(|| {
+		let mut para_2000 = get_para(Polkadot, 2000).unwrap();
+		let para_2004 = get_para(Polkadot, 2004).unwrap();
+
+		let mut duplicate = para_2000.clone();
+		duplicate.expiry_timestamp = 42;
+
+		update_registry(vec![para_2000.clone(), para_2004.clone(), duplicate]).unwrap();
+		assert!(dedup_registry().is_ok());
+
+		// The entry with the latest expiry timestamp is kept:
+		para_2000.expiry_timestamp = 42;
+		assert_eq!(registered_paras(), vec![para_2000, para_2004]);
+	});
+}
+
+#[test]
+fn registry_cache_stays_consistent() {
+	MockEnvironment::new().execute_with(|| {
+		let para_2000 = get_para(Polkadot, 2000).unwrap();
+		let para_2004 = get_para(Polkadot, 2004).unwrap();
+
+		update_registry(vec![para_2000.clone()]).unwrap();
+		assert_eq!(registered_paras(), vec![para_2000.clone()]);
+
+		// Writes invalidate the cached registry:
+		update_registry(vec![para_2004.clone()]).unwrap();
+		assert_eq!(registered_paras(), vec![para_2004]);
+
+		// So does resetting the registry:
+		shared::registry::init_registry();
+		assert!(registered_paras().is_empty());
+	});
+}
+
+#[test]
+fn registry_recovers_from_partial_write() {
+	use shared::{
+		config::config,
+		storage::{FileStorage, RegistryStorage},
+	};
+
+	MockEnvironment::new().execute_with(|| {
+		let storage = FileStorage::new(config().registry);
+		let para_2000 = get_para(Polkadot, 2000).unwrap();
+		let para_2004 = get_para(Polkadot, 2004).unwrap();
+
+		storage.write(&[para_2000.clone()]).unwrap();
+		storage.write(&[para_2000.clone(), para_2004.clone()]).unwrap();
+		assert_eq!(storage.read(), Ok(vec![para_2000.clone(), para_2004.clone()]));
+		assert!(temporary_files(&config().registry).is_empty());
+
+		// A partially written registry falls back to the backup of the previous registry:
+		let content = std::fs::read_to_string(config().registry).unwrap();
+		std::fs::write(config().registry, &content[..content.len() / 2]).unwrap();
+		assert_eq!(storage.read(), Ok(vec![para_2000.clone()]));
+
+		// The corrupt registry doesn't replace the backup:
+		storage.write(&[para_2004.clone()]).unwrap();
+		assert_eq!(storage.read(), Ok(vec![para_2004]));
+		std::fs::write(config().registry, "").unwrap();
+		assert_eq!(storage.read(), Ok(vec![para_2000]));
+
+		// Resetting the registry also drops the backup:
+		storage.init().unwrap();
+		std::fs::write(config().registry, "").unwrap();
+		assert!(storage.read().is_err());
+		storage.init().unwrap();
+	});
+}
+
+#[test]
+fn concurrent_registry_writes_do_not_interfere() {
+	use shared::{
+		config::config,
+		storage::{FileStorage, RegistryStorage},
+	};
+
+	MockEnvironment::new().execute_with(|| {
+		let paras = [get_para(Polkadot, 2000).unwrap(), get_para(Polkadot, 2004).unwrap()];
+
+		std::thread::scope(|scope| {
+			for para in &paras {
+				scope.spawn(move || {
+					let storage = FileStorage::new(config().registry);
+					for _ in 0..20 {
+						storage.write(std::slice::from_ref(para)).unwrap();
+					}
+				});
+			}
+		});
+
+		// The registry is one of the written ones, and the backup is intact:
+		let storage = FileStorage::new(config().registry);
+		let registry = storage.read().unwrap();
+		assert!(paras.iter().any(|para| registry == vec![para.clone()]));
+		let backup = std::fs::read_to_string(storage.backup_path()).unwrap();
+		assert!(serde_json::from_str::<Vec<Parachain>>(&backup).is_ok());
+		assert!(temporary_files(&config().registry).is_empty());
+	});
+}
+
+#[test]
+fn cached_registry_picks_up_external_writes() {
+	use shared::storage::registry_storage;
+
+	MockEnvironment::new().execute_with(|| {
+		let mut para_2000 = get_para(Polkadot, 2000).unwrap();
+		let para_2004 = get_para(Polkadot, 2004).unwrap();
+
+		// The mock config caches the registry, which the first read populates:
+		update_registry(vec![para_2000.clone(), para_2004.clone()]).unwrap();
+		assert_eq!(registered_paras(), vec![para_2000.clone(), para_2004.clone()]);
+
+		// Another process, e.g. the tracker, writes to the storage directly:
+		para_2000.paused = true;
+		registry_storage().write(&[para_2000.clone(), para_2004.clone()]).unwrap();
+		assert_eq!(registered_paras(), vec![para_2000.clone(), para_2004.clone()]);
+
+		// So the next write of the server doesn't undo it:
+		let mut paras = registered_paras();
+		paras.retain(|para| para.para_id != 2004);
+		update_registry(paras).unwrap();
+		assert_eq!(registered_paras(), vec![para_2000]);
+	});
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn registry_is_stored_in_the_database() {
+	use shared::{
+		config::config,
+		storage::{FileStorage, RegistryStorage},
+	};
+
+	MockEnvironment::new().execute_with(|| {
+		let para_2000 = get_para(Polkadot, 2000).unwrap();
+		update_registry(vec![para_2000.clone()]).unwrap();
+		assert_eq!(registered_paras(), vec![para_2000.clone()]);
+
+		// The registry file is left untouched:
+		let file_registry = FileStorage::new(config().registry).read().unwrap_or_default();
+		assert!(!file_registry.contains(&para_2000));
+	});
+}
+
+/// Returns the temporary files left behind by the writes of the registry.
+fn temporary_files(registry: &str) -> Vec<String> {
+	let registry = std::path::Path::new(registry);
+	let dir = match registry.parent() {
+		Some(dir) if !dir.as_os_str().is_empty() => dir,
+		_ => std::path::Path::new("."),
+	};
+	let prefix = registry.file_name().unwrap().to_string_lossy().to_string();
+
+	std::fs::read_dir(dir)
+		.unwrap()
+		.filter_map(|entry| entry.ok())
+		.map(|entry| entry.file_name().to_string_lossy().to_string())
+		.filter(|name| name.starts_with(&prefix) && name.ends_with(".tmp"))
+		.collect()
+}
+
 fn parse_ok_response<'a>(response: LocalResponse<'a>) -> Vec<Parachain> {
 	let body = response.into_string().unwrap();
 	serde_json::from_str(&body).expect("can't parse value")
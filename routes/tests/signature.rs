@@ -0,0 +1,53 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use rocket::{http::Status, local::blocking::Client, routes};
+use routes::{
+	consumption::consumption,
+	signature::{pubkey, SIGNATURE_HEADER},
+};
+
+mod mock;
+use mock::MockEnvironment;
+
+#[test]
+fn signed_consumption_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![consumption, pubkey]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let response = client.get("/pubkey").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		let public_key: String = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+		let public_key =
+			VerifyingKey::from_bytes(&decode(&public_key).try_into().unwrap()).unwrap();
+
+		let response = client.get("/consumption/polkadot/2000").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		let signature = response.headers().get_one(SIGNATURE_HEADER).unwrap().to_string();
+		let signature = Signature::from_bytes(&decode(&signature).try_into().unwrap());
+		let body = response.into_string().unwrap();
+
+		assert!(public_key.verify(body.as_bytes(), &signature).is_ok());
+		// Tampering with the data invalidates the signature:
+		assert!(public_key.verify(format!("{} ", body).as_bytes(), &signature).is_err());
+	});
+}
+
+fn decode(value: &str) -> Vec<u8> {
+	hex::decode(value.trim_start_matches("0x")).unwrap()
+}
@@ -0,0 +1,180 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use rocket::{
+	http::Status,
+	local::blocking::{Client, LocalResponse},
+	routes,
+};
+use routes::{
+	subscription::{
+		subscription_status, subscription_statuses, SubscriptionStatus, MAX_STATUS_PARAS,
+	},
+	Error, ErrorResponse,
+};
+use shared::{
+	chaindata::get_para,
+	current_timestamp,
+	expiry::{pending_expiry_warnings, ExpiryWarning},
+	registry::{registered_paras, update_registry},
+};
+use std::collections::BTreeMap;
+use types::RelayChain::*;
+
+mod mock;
+use mock::MockEnvironment;
+
+#[test]
+fn subscription_status_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![subscription_status]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let response = client.get("/subscription/polkadot/2000").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		// The subscription of the mock parachain has expired.
+		assert_eq!(
+			parse_ok_response(response),
+			SubscriptionStatus {
+				para: (Polkadot, 2000),
+				expiry_timestamp: 0,
+				active: false,
+				dormant: false,
+				credit: 0
+			}
+		);
+
+		// Extend the subscription, accumulate some credit and register a parachain for which no
+		// blocks were recorded.
+		let mut paras = registered_paras();
+		let expiry_timestamp = current_timestamp() + 1000;
+		paras.iter_mut().for_each(|para| {
+			para.expiry_timestamp = expiry_timestamp;
+			para.credit = 500;
+		});
+		paras.push(get_para(Kusama, 2000).unwrap());
+		update_registry(paras).unwrap();
+
+		let response = client.get("/subscription/polkadot/2000").dispatch();
+		assert_eq!(
+			parse_ok_response(response),
+			SubscriptionStatus {
+				para: (Polkadot, 2000),
+				expiry_timestamp,
+				active: true,
+				dormant: false,
+				credit: 500
+			}
+		);
+
+		let response = client.get("/subscription/kusama/2000").dispatch();
+		assert_eq!(
+			parse_ok_response(response),
+			SubscriptionStatus {
+				para: (Kusama, 2000),
+				expiry_timestamp: 0,
+				active: false,
+				dormant: true,
+				credit: 0
+			}
+		);
+	});
+}
+
+#[test]
+fn subscription_status_for_unregistered_fails() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![subscription_status]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let response = client.get("/subscription/polkadot/42").dispatch();
+		assert_eq!(response.status(), Status::NotFound);
+		assert_eq!(parse_err_response(response), Error::NotRegistered);
+	});
+}
+
+#[test]
+fn bulk_subscription_status_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket =
+			rocket::build().mount("/", routes![subscription_status, subscription_statuses]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let response = client.get("/subscription/status?paras=polkadot:2000,kusama:42").dispatch();
+		assert_eq!(response.status(), Status::Ok);
+		let statuses: BTreeMap<String, Option<SubscriptionStatus>> =
+			serde_json::from_str(&response.into_string().unwrap()).unwrap();
+
+		// The status is the same as the one returned for a single parachain:
+		let single = parse_ok_response(client.get("/subscription/polkadot/2000").dispatch());
+		assert_eq!(statuses.get("polkadot:2000"), Some(&Some(single)));
+		// Unregistered parachains have no status:
+		assert_eq!(statuses.get("kusama:42"), Some(&None));
+
+		let response = client.get("/subscription/status?paras=polkadot-2000").dispatch();
+		assert_eq!(parse_err_response(response), Error::InvalidParaIdentifier);
+
+		let paras = vec!["polkadot:2000"; MAX_STATUS_PARAS + 1].join(",");
+		let response = client.get(format!("/subscription/status?paras={}", paras)).dispatch();
+		assert_eq!(parse_err_response(response), Error::TooManyParas);
+	});
+}
+
+#[test]
+fn expiry_warnings_are_sent_once_per_period() {
+	MockEnvironment::new().execute_with(|| {
+		let now = current_timestamp();
+		let threshold = 100;
+
+		let mut expiring = get_para(Polkadot, 2000).unwrap();
+		expiring.expiry_timestamp = now + 50;
+		let mut active = get_para(Polkadot, 2004).unwrap();
+		active.expiry_timestamp = now + 500;
+		let mut expired = get_para(Kusama, 2000).unwrap();
+		expired.expiry_timestamp = now - 1;
+		let paras = vec![expiring.clone(), active, expired];
+
+		// Only the subscription expiring within the threshold is warned about.
+		let pending = pending_expiry_warnings(&paras, now, threshold, &[]);
+		assert_eq!(
+			pending,
+			vec![ExpiryWarning {
+				relay_chain: Polkadot,
+				para_id: 2000,
+				expiry_timestamp: now + 50
+			}]
+		);
+
+		// Once sent, the warning isn't sent again.
+		assert!(pending_expiry_warnings(&paras, now, threshold, &pending).is_empty());
+
+		// After renewing, the warning is sent again for the new subscription period.
+		expiring.expiry_timestamp = now + 60;
+		let pending = pending_expiry_warnings(&[expiring], now, threshold, &pending);
+		assert_eq!(pending.len(), 1);
+		assert_eq!(pending[0].expiry_timestamp, now + 60);
+	});
+}
+
+fn parse_ok_response<'a>(response: LocalResponse<'a>) -> SubscriptionStatus {
+	let body = response.into_string().unwrap();
+	serde_json::from_str(&body).expect("can't parse value")
+}
+
+fn parse_err_response<'a>(response: LocalResponse<'a>) -> Error {
+	let body = response.into_string().unwrap();
+	let response: ErrorResponse = serde_json::from_str(&body).expect("can't parse error");
+	response.error.into()
+}
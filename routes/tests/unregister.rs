@@ -0,0 +1,147 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use rocket::{
+	http::{ContentType, Status},
+	local::blocking::{Client, LocalResponse},
+	routes,
+};
+use routes::{
+	unregister::{unregister_para, unregister_remark, UnregistrationData},
+	Error, ErrorResponse,
+};
+use shared::{
+	chaindata::get_para,
+	registry::{registered_para, registered_paras, update_registry},
+};
+use types::{PaymentDetails, RelayChain::*};
+
+mod mock;
+use mock::MockEnvironment;
+
+#[test]
+fn unregistering_unregistered_para_fails() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![unregister_para]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let data = UnregistrationData { para: (Kusama, 2000), proof_block_number: None };
+		let response = unregister(&client, &data);
+		assert_eq!(response.status(), Status::NotFound);
+		assert_eq!(parse_err_response(response), Error::NotRegistered);
+	});
+}
+
+#[test]
+fn unregistering_requires_ownership_proof() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![unregister_para]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		// The mock config requires payment, so a proof of ownership is required:
+		let data = UnregistrationData { para: (Polkadot, 2000), proof_block_number: None };
+		let response = unregister(&client, &data);
+		assert_eq!(parse_err_response(response), Error::OwnershipProofRequired);
+
+		// A para registered without a payment has no owner which could provide the proof:
+		let data = UnregistrationData { para: (Polkadot, 2000), proof_block_number: Some(1) };
+		let response = unregister(&client, &data);
+		assert_eq!(parse_err_response(response), Error::OwnerUnknown);
+
+		// The para remains registered:
+		assert!(registered_para(Polkadot, 2000).is_some());
+	});
+}
+
+#[test]
+fn unregister_works() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![unregister_para]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		// The mock config exempts the Polkadot Asset Hub from payment, so it can be unregistered
+		// without a proof of ownership:
+		let asset_hub = get_para(Polkadot, 1000).unwrap();
+		let mut paras = registered_paras();
+		paras.push(asset_hub);
+		update_registry(paras).unwrap();
+
+		let data = UnregistrationData { para: (Polkadot, 1000), proof_block_number: None };
+		let response = unregister(&client, &data);
+		assert_eq!(response.status(), Status::Ok);
+
+		// Only the unregistered para is removed:
+		assert!(registered_para(Polkadot, 1000).is_none());
+		assert!(registered_para(Polkadot, 2000).is_some());
+		assert!(registered_para(Polkadot, 2004).is_some());
+
+		// It can't be unregistered twice:
+		let response = unregister(&client, &data);
+		assert_eq!(parse_err_response(response), Error::NotRegistered);
+	});
+}
+
+#[test]
+fn ownership_proof_preceding_the_payment_is_rejected() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![unregister_para]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let mut para = get_para(Polkadot, 2000).unwrap();
+		para.payment = Some(PaymentDetails {
+			block_number: 100,
+			block_hash: None,
+			extrinsic_index: 1,
+			payer: Some(
+				"0x0101010101010101010101010101010101010101010101010101010101010101".into(),
+			),
+		});
+		update_registry(vec![para]).unwrap();
+
+		// A proof made before the payment might belong to a previous registration:
+		for proof_block_number in [99, 100] {
+			let data = UnregistrationData {
+				para: (Polkadot, 2000),
+				proof_block_number: Some(proof_block_number),
+			};
+			let response = unregister(&client, &data);
+			assert_eq!(response.status(), Status::BadRequest);
+			assert_eq!(parse_err_response(response), Error::StaleOwnershipProof);
+		}
+
+		// The para remains registered:
+		assert!(registered_para(Polkadot, 2000).is_some());
+	});
+}
+
+#[test]
+fn unregister_remark_works() {
+	let para = get_para(Polkadot, 2000).unwrap();
+	assert_eq!(unregister_remark(&para), b"regionx-weigher::unregister:Polkadot:2000".to_vec());
+}
+
+fn unregister<'a>(client: &'a Client, data: &UnregistrationData) -> LocalResponse<'a> {
+	client
+		.post("/unregister_para")
+		.header(ContentType::JSON)
+		.body(serde_json::to_string(data).unwrap())
+		.dispatch()
+}
+
+fn parse_err_response<'a>(response: LocalResponse<'a>) -> Error {
+	let body = response.into_string().unwrap();
+	let response: ErrorResponse = serde_json::from_str(&body).expect("can't parse error");
+	response.error.into()
+}
@@ -0,0 +1,200 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use rocket::{
+	http::{ContentType, Status},
+	local::blocking::{Client, LocalResponse},
+	routes,
+};
+use routes::{
+	update::{update_registration, update_remark, RegistrationChanges, RegistrationUpdate},
+	Error, ErrorResponse,
+};
+use shared::{
+	chaindata::{get_para, ChainDataError},
+	registry::{registered_para, update_registry},
+};
+use types::{PaymentDetails, RelayChain::*};
+
+mod mock;
+use mock::MockEnvironment;
+
+#[test]
+fn immutable_fields_cannot_be_updated() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![update_registration]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let changes = RegistrationChanges { relay_chain: Some(Kusama), ..Default::default() };
+		let response = patch(&client, "/registration/polkadot/2000", changes);
+		assert_eq!(response.status(), Status::BadRequest);
+		assert_eq!(parse_err_response(response), Error::ImmutableField);
+
+		let changes = RegistrationChanges { para_id: Some(2004), ..Default::default() };
+		let response = patch(&client, "/registration/polkadot/2000", changes);
+		assert_eq!(parse_err_response(response), Error::ImmutableField);
+
+		// Only registered paras can be updated:
+		let response = patch(&client, "/registration/kusama/2000", Default::default());
+		assert_eq!(parse_err_response(response), Error::NotRegistered);
+
+		assert_eq!(registered_para(Polkadot, 2000), Some(get_para(Polkadot, 2000).unwrap()));
+	});
+}
+
+#[test]
+fn updating_requires_ownership_proof() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![update_registration]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let changes =
+			RegistrationChanges { name: Some("Renamed".to_string()), ..Default::default() };
+
+		// The mock config requires payment, so a proof of ownership is required:
+		let response = patch(&client, "/registration/polkadot/2000", changes.clone());
+		assert_eq!(parse_err_response(response), Error::OwnershipProofRequired);
+
+		// A para registered without a payment has no owner which could provide the proof:
+		let update = RegistrationUpdate { changes: changes.clone(), proof_block_number: Some(1) };
+		let response = client
+			.patch("/registration/polkadot/2000")
+			.header(ContentType::JSON)
+			.body(serde_json::to_string(&update).unwrap())
+			.dispatch();
+		assert_eq!(parse_err_response(response), Error::OwnerUnknown);
+
+		assert_eq!(
+			registered_para(Polkadot, 2000).unwrap().name,
+			get_para(Polkadot, 2000).unwrap().name
+		);
+	});
+}
+
+#[test]
+fn replayed_ownership_proof_is_rejected() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![update_registration]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let mut para = get_para(Polkadot, 2000).unwrap();
+		para.payment = Some(PaymentDetails {
+			block_number: 100,
+			block_hash: None,
+			extrinsic_index: 1,
+			payer: Some(
+				"0x0101010101010101010101010101010101010101010101010101010101010101".into(),
+			),
+		});
+		para.last_update_block = Some(200);
+		update_registry(vec![para]).unwrap();
+
+		// Proofs made before the payment or the latest update might have been made for them:
+		let changes = RegistrationChanges {
+			rpcs: Some(vec!["wss://rpc.io".to_string()]),
+			..Default::default()
+		};
+		for proof_block_number in [99, 100, 150, 200] {
+			let update = RegistrationUpdate {
+				changes: changes.clone(),
+				proof_block_number: Some(proof_block_number),
+			};
+			let response = client
+				.patch("/registration/polkadot/2000")
+				.header(ContentType::JSON)
+				.body(serde_json::to_string(&update).unwrap())
+				.dispatch();
+			assert_eq!(response.status(), Status::BadRequest);
+			assert_eq!(parse_err_response(response), Error::StaleOwnershipProof);
+		}
+
+		assert_eq!(
+			registered_para(Polkadot, 2000).unwrap().rpcs,
+			get_para(Polkadot, 2000).unwrap().rpcs
+		);
+	});
+}
+
+#[test]
+fn invalid_rpcs_are_rejected() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![update_registration]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let changes = RegistrationChanges { rpcs: Some(vec![]), ..Default::default() };
+		let response = patch(&client, "/registration/polkadot/2000", changes);
+		assert_eq!(parse_err_response(response), Error::InvalidData);
+
+		// Each of the rpcs has to be a WebSocket url:
+		let rpcs = vec!["wss://rpc.io".to_string(), "http://rpc.io".to_string()];
+		let changes = RegistrationChanges { rpcs: Some(rpcs), ..Default::default() };
+		let response = patch(&client, "/registration/polkadot/2000", changes);
+		assert_eq!(
+			parse_err_response(response),
+			Error::ChainDataError(ChainDataError::InvalidRpcUrl)
+		);
+
+		assert_eq!(registered_para(Polkadot, 2000), Some(get_para(Polkadot, 2000).unwrap()));
+	});
+}
+
+#[test]
+fn update_remark_commits_to_changes() {
+	let para = get_para(Polkadot, 2000).unwrap();
+
+	let rename = RegistrationChanges { name: Some("Renamed".to_string()), ..Default::default() };
+	let rpcs =
+		RegistrationChanges { rpcs: Some(vec!["wss://rpc.io".to_string()]), ..Default::default() };
+
+	assert!(String::from_utf8(update_remark(&para, &rename))
+		.unwrap()
+		.starts_with("regionx-weigher::update:Polkadot:2000:"));
+	assert_ne!(update_remark(&para, &rename), update_remark(&para, &rpcs));
+}
+
+#[test]
+fn unknown_fields_are_rejected() {
+	MockEnvironment::new().execute_with(|| {
+		let rocket = rocket::build().mount("/", routes![update_registration]);
+		let client = Client::tracked(rocket).expect("valid rocket instance");
+
+		let response = client
+			.patch("/registration/polkadot/2000")
+			.header(ContentType::JSON)
+			.body(r#"{"changes":{"expiry_timestamp":0}}"#)
+			.dispatch();
+		assert_eq!(response.status(), Status::UnprocessableEntity);
+	});
+}
+
+fn patch<'a>(
+	client: &'a Client,
+	uri: &'static str,
+	changes: RegistrationChanges,
+) -> LocalResponse<'a> {
+	let update = RegistrationUpdate { changes, proof_block_number: None };
+
+	client
+		.patch(uri)
+		.header(ContentType::JSON)
+		.body(serde_json::to_string(&update).unwrap())
+		.dispatch()
+}
+
+fn parse_err_response<'a>(response: LocalResponse<'a>) -> Error {
+	let body = response.into_string().unwrap();
+	let response: ErrorResponse = serde_json::from_str(&body).expect("can't parse error");
+	response.error.into()
+}
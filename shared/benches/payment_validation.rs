@@ -0,0 +1,61 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Benchmarks looking for a payment among the extrinsics of a full block, serially and in
+//! parallel.
+//!
+//! Run with `cargo bench -p shared`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use shared::payment::find_match;
+use std::sync::Arc;
+
+/// The number of extrinsics in a full block of balance transfers on the relay chain.
+const FULL_BLOCK_EXTRINSICS: usize = 4_000;
+
+/// The size of an encoded batch of a transfer and a payment remark.
+const CALL_SIZE: usize = 120;
+
+fn payment_validation(c: &mut Criterion) {
+	let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+
+	// The calls of the block only differ in their last bytes, like transfers of different amounts,
+	// and the payment is the last extrinsic of the block, which is the worst case.
+	let calls: Arc<[Vec<u8>]> = (0..FULL_BLOCK_EXTRINSICS)
+		.map(|index| {
+			let mut call = vec![0u8; CALL_SIZE];
+			call[CALL_SIZE - 4..].copy_from_slice(&(index as u32).to_le_bytes());
+			call
+		})
+		.collect();
+	let payments: Arc<Vec<Vec<u8>>> = Arc::new(vec![calls[FULL_BLOCK_EXTRINSICS - 1].clone()]);
+
+	let mut group = c.benchmark_group("find_match");
+	for (name, threshold) in [("serial", None), ("parallel", Some(0))] {
+		group.bench_function(BenchmarkId::new(name, FULL_BLOCK_EXTRINSICS), |b| {
+			b.iter(|| {
+				let payments = payments.clone();
+				let position = runtime.block_on(find_match(calls.clone(), threshold, move |call| {
+					payments.iter().any(|payment| payment == call)
+				}));
+				assert_eq!(position, Some(FULL_BLOCK_EXTRINSICS - 1));
+			})
+		});
+	}
+	group.finish();
+}
+
+criterion_group!(benches, payment_validation);
+criterion_main!(benches);
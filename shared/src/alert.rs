@@ -0,0 +1,120 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Alerts when the consumption of a parachain consistently exceeds its configured threshold.
+//!
+//! An alert is fired once the threshold is exceeded for the configured number of consecutive
+//! blocks, and isn't fired again until the consumption drops below the threshold.
+
+use crate::{
+	config::{config, WeightThreshold},
+	LOG_TARGET,
+};
+use serde::{Deserialize, Serialize};
+use types::{DispatchClassConsumption, ParaId, Parachain, RelayChain, WeightConsumption};
+
+/// The payload sent to the webhook when the consumption of a parachain exceeds its threshold.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WeightAlert {
+	/// The relay chain of the parachain.
+	pub relay_chain: RelayChain,
+	/// The id of the parachain.
+	pub para_id: ParaId,
+	/// The block at which the alert was fired.
+	pub block_number: u32,
+	/// The ref_time consumption of the block, summed over all the dispatch classes.
+	pub ref_time: Option<f32>,
+	/// The proof size consumption of the block, summed over all the dispatch classes.
+	pub proof_size: Option<f32>,
+	/// The threshold which was exceeded.
+	pub threshold: f32,
+	/// The number of consecutive blocks which exceeded the threshold.
+	pub consecutive_blocks: u32,
+}
+
+/// Evaluates the consumption of each processed block of a parachain against its threshold.
+pub struct WeightMonitor {
+	para: Parachain,
+	threshold: WeightThreshold,
+	/// The number of consecutive blocks that exceeded the threshold so far.
+	exceeded: u32,
+}
+
+impl WeightMonitor {
+	pub fn new(para: Parachain, threshold: WeightThreshold) -> Self {
+		Self { para, threshold, exceeded: 0 }
+	}
+
+	/// Records the consumption of the next block, returning an alert if the threshold has now
+	/// been exceeded for the configured number of consecutive blocks.
+	pub fn observe(&mut self, consumption: &WeightConsumption) -> Option<WeightAlert> {
+		let ref_time = consumption.ref_time.as_ref().map(total);
+		let proof_size = consumption.proof_size.as_ref().map(total);
+
+		let exceeds = |total: Option<f32>| total.map_or(false, |t| t > self.threshold.threshold);
+		if !exceeds(ref_time) && !exceeds(proof_size) {
+			self.exceeded = 0;
+			return None
+		}
+
+		self.exceeded = self.exceeded.saturating_add(1);
+		// Only a single alert is fired for each streak of blocks exceeding the threshold.
+		if self.exceeded != self.threshold.consecutive_blocks.max(1) {
+			return None
+		}
+
+		Some(WeightAlert {
+			relay_chain: self.para.relay_chain.clone(),
+			para_id: self.para.para_id,
+			block_number: consumption.block_number,
+			ref_time,
+			proof_size,
+			threshold: self.threshold.threshold,
+			consecutive_blocks: self.exceeded,
+		})
+	}
+}
+
+/// Logs the alert and posts it to the `weight_alert_webhook_url`, if one is configured.
+pub async fn fire_weight_alert(alert: &WeightAlert) {
+	log::warn!(
+		target: LOG_TARGET,
+		"{}-{} - Consumption exceeded the threshold of {} for {} consecutive blocks at block {}: ref_time: {:?}, proof_size: {:?}",
+		alert.relay_chain,
+		alert.para_id,
+		alert.threshold,
+		alert.consecutive_blocks,
+		alert.block_number,
+		alert.ref_time,
+		alert.proof_size,
+	);
+
+	let Some(url) = config().weight_alert_webhook_url else { return };
+	// The alerts are rare, so there's no need to keep the client around.
+	let client = reqwest::Client::new();
+	if let Err(err) = client.post(url).json(alert).send().await.and_then(|r| r.error_for_status()) {
+		log::warn!(
+			target: LOG_TARGET,
+			"{}-{} - Failed to send the weight alert: {:?}",
+			alert.relay_chain,
+			alert.para_id,
+			err
+		);
+	}
+}
+
+fn total(consumption: &DispatchClassConsumption) -> f32 {
+	consumption.normal + consumption.operational + consumption.mandatory
+}
@@ -12,9 +12,10 @@
 
 // You should have received a copy of the GNU General Public License
 // along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
-use crate::config::config;
+use crate::{config::config, LOG_TARGET};
 use serde::{Deserialize, Serialize};
-use std::{fs::File, io::Read};
+use std::{collections::HashSet, fs::File, io::Read, time::Duration};
+use subxt::backend::rpc::{rpc_params, RpcClient};
 use types::{ParaId, Parachain, RelayChain};
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
@@ -33,17 +34,45 @@ struct ChainData {
 	pub para_id: ParaId,
 	pub relay: Relay,
 	pub rpcs: Vec<Rpc>,
+	#[serde(default)]
+	pub logo: Option<String>,
+	#[serde(default)]
+	pub category: Option<String>,
+}
+
+/// Descriptive metadata of a parachain which can be used for displaying it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParaMetadata {
+	/// Human readable name of the parachain.
+	pub name: String,
+	/// URL of the parachain's logo.
+	pub logo: Option<String>,
+	/// The category of the parachain, e.g. `defi`.
+	pub category: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
 pub enum ChainDataError {
 	ParaNotFound,
+	/// The rpc provided for a parachain that isn't part of the chaindata is unreachable.
+	RpcUnreachable,
+	/// The rpc provided for a parachain that isn't part of the chaindata isn't a `ws://` or
+	/// `wss://` url.
+	InvalidRpcUrl,
+	/// No `chaindata_url` is configured from which the chaindata could be refreshed.
+	RefreshDisabled,
+	/// The chaindata couldn't be fetched from the `chaindata_url`, or is invalid.
+	RefreshFailed,
 }
 
 impl From<String> for ChainDataError {
 	fn from(v: String) -> Self {
 		match v.as_str() {
 			"ParaNotFound" => Self::ParaNotFound,
+			"RpcUnreachable" => Self::RpcUnreachable,
+			"InvalidRpcUrl" => Self::InvalidRpcUrl,
+			"RefreshDisabled" => Self::RefreshDisabled,
+			"RefreshFailed" => Self::RefreshFailed,
 			_ => panic!("UnknownError"),
 		}
 	}
@@ -51,28 +80,188 @@ impl From<String> for ChainDataError {
 
 /// Get the rpcs of a parachain.
 pub fn get_para(relay: RelayChain, para_id: ParaId) -> Result<Parachain, ChainDataError> {
-	let mut file = File::open(config().chaindata).expect("ChainData not found");
-	let mut content = String::new();
+	let para_chaindata = find_chaindata(relay.clone(), para_id)?;
 
-	file.read_to_string(&mut content).expect("Failed to load chaindata");
-	let chaindata: Vec<ChainData> = serde_json::from_str(&content).expect("Failed to serialize");
-
-	let index = chaindata
-		.iter()
-		.position(|para| para.para_id == para_id && para.relay == Relay { id: relay.clone() })
-		.ok_or(ChainDataError::ParaNotFound)?;
-
-	let para_chaindata = chaindata.get(index).expect("We just found the index; qed");
-
-	let rpcs: Vec<String> = para_chaindata.rpcs.clone().into_iter().map(|rpc| rpc.url).collect();
+	let rpcs: Vec<String> = para_chaindata.rpcs.into_iter().map(|rpc| rpc.url).collect();
 
 	let para = Parachain {
 		relay_chain: relay,
 		para_id,
-		name: para_chaindata.name.clone(),
+		name: para_chaindata.name,
 		rpcs,
 		expiry_timestamp: Default::default(),
+		credit: Default::default(),
+		last_credited_block: None,
+		payment: None,
+		last_update_block: None,
+		paused: false,
+		backfill_from: None,
+		tier: None,
 	};
 
 	Ok(para)
 }
+
+/// Returns whether the chaindata contains the parachain.
+///
+/// Registered parachains can be missing from the chaindata, e.g. after it was refreshed, or if
+/// they were registered with their own rpc. Unlike `get_para`, this doesn't panic if the chaindata
+/// can't be read, in which case it is unknown whether the parachain is missing and `None` is
+/// returned.
+///
+/// Use `chaindata_paras` when looking up multiple parachains, which only reads the chaindata once.
+pub fn has_chaindata(relay: RelayChain, para_id: ParaId) -> Option<bool> {
+	chaindata_paras().map(|paras| paras.contains(&(relay, para_id)))
+}
+
+/// Returns the parachains contained in the chaindata, or `None` if the chaindata can't be read.
+pub fn chaindata_paras() -> Option<HashSet<(RelayChain, ParaId)>> {
+	read_chaindata_paras(&config().chaindata)
+}
+
+/// Returns the parachains contained in the chaindata file at `path`, or `None` if it can't be
+/// read.
+pub fn read_chaindata_paras(path: &str) -> Option<HashSet<(RelayChain, ParaId)>> {
+	let content = std::fs::read_to_string(path).ok()?;
+	let chaindata: Vec<ChainData> = serde_json::from_str(&content).ok()?;
+
+	Some(chaindata.into_iter().map(|para| (para.relay.id, para.para_id)).collect())
+}
+
+/// The maximum time, in seconds, to wait for an rpc to respond when checking its reachability.
+const RPC_PROBE_TIMEOUT: u64 = 5;
+
+/// Get a parachain which isn't part of the chaindata, using the provided rpc.
+///
+/// The rpc is queried for the name of the chain, which also ensures that it is reachable within
+/// `RPC_PROBE_TIMEOUT` seconds. Only WebSocket urls are accepted, so that the server can't be made
+/// to request arbitrary resources, e.g. through `http://` or `file://` urls.
+pub async fn get_unknown_para(
+	relay: RelayChain,
+	para_id: ParaId,
+	rpc_url: String,
+) -> Result<Parachain, ChainDataError> {
+	ensure_websocket_url(&rpc_url)?;
+
+	// The registration is held up until the rpc answers, so an unresponsive one is given up on.
+	let probe = async {
+		let rpc_client = RpcClient::from_url(&rpc_url).await.ok()?;
+		rpc_client.request::<String>("system_chain", rpc_params![]).await.ok()
+	};
+	let name = match tokio::time::timeout(Duration::from_secs(RPC_PROBE_TIMEOUT), probe).await {
+		Ok(Some(name)) => name,
+		_ => return Err(ChainDataError::RpcUnreachable),
+	};
+
+	Ok(Parachain {
+		relay_chain: relay,
+		para_id,
+		name,
+		rpcs: vec![rpc_url],
+		expiry_timestamp: Default::default(),
+		credit: Default::default(),
+		last_credited_block: None,
+		payment: None,
+		last_update_block: None,
+		paused: false,
+		backfill_from: None,
+		tier: None,
+	})
+}
+
+/// Ensures that the url is a `ws://` or `wss://` url with a host.
+pub fn ensure_websocket_url(rpc_url: &str) -> Result<(), ChainDataError> {
+	let Some((scheme, rest)) = rpc_url.split_once("://") else {
+		return Err(ChainDataError::InvalidRpcUrl);
+	};
+
+	let is_websocket = scheme.eq_ignore_ascii_case("ws") || scheme.eq_ignore_ascii_case("wss");
+	if !is_websocket || rest.is_empty() || rest.starts_with(&['/', '?', '#'][..]) {
+		return Err(ChainDataError::InvalidRpcUrl);
+	}
+
+	Ok(())
+}
+
+/// Checks whether the rpc is reachable by querying its last finalized head.
+pub async fn ensure_rpc_reachable(rpc_url: &str) -> Result<(), ChainDataError> {
+	let probe = async {
+		let rpc_client = RpcClient::from_url(rpc_url).await.ok()?;
+		rpc_client
+			.request::<serde_json::Value>("chain_getFinalizedHead", rpc_params![])
+			.await
+			.ok()
+	};
+
+	match tokio::time::timeout(Duration::from_secs(RPC_PROBE_TIMEOUT), probe).await {
+		Ok(Some(_)) => Ok(()),
+		_ => Err(ChainDataError::RpcUnreachable),
+	}
+}
+
+/// Get the descriptive metadata of a parachain.
+pub fn get_para_metadata(
+	relay: RelayChain,
+	para_id: ParaId,
+) -> Result<ParaMetadata, ChainDataError> {
+	let para_chaindata = find_chaindata(relay, para_id)?;
+
+	Ok(ParaMetadata {
+		name: para_chaindata.name,
+		logo: para_chaindata.logo,
+		category: para_chaindata.category,
+	})
+}
+
+/// Fetches the chaindata from the configured `chaindata_url` and stores it, returning the number
+/// of parachains it contains.
+///
+/// Since the chaindata file is read on every lookup, the refreshed chaindata takes effect without
+/// restarting the service.
+pub async fn refresh_chaindata() -> Result<usize, ChainDataError> {
+	let url = config().chaindata_url.ok_or(ChainDataError::RefreshDisabled)?;
+
+	let response = reqwest::get(&url).await.and_then(|response| response.error_for_status());
+	let content = match response {
+		Ok(response) => response.text().await.map_err(|_| ChainDataError::RefreshFailed)?,
+		Err(err) => {
+			log::error!(target: LOG_TARGET, "Failed to fetch the chaindata: {:?}", err);
+			return Err(ChainDataError::RefreshFailed)
+		},
+	};
+
+	write_chaindata(&config().chaindata, &content)
+}
+
+/// Replaces the chaindata file at `path` with the provided content, if it is valid chaindata.
+pub fn write_chaindata(path: &str, content: &str) -> Result<usize, ChainDataError> {
+	let chaindata: Vec<ChainData> =
+		serde_json::from_str(content).map_err(|_| ChainDataError::RefreshFailed)?;
+
+	// The file is replaced through a rename, so that lookups never read a partially written file.
+	let tmp_path = format!("{}.tmp", path);
+	std::fs::write(&tmp_path, content)
+		.and_then(|_| std::fs::rename(&tmp_path, path))
+		.map_err(|err| {
+			log::error!(target: LOG_TARGET, "Failed to store the chaindata: {:?}", err);
+			ChainDataError::RefreshFailed
+		})?;
+
+	Ok(chaindata.len())
+}
+
+fn find_chaindata(relay: RelayChain, para_id: ParaId) -> Result<ChainData, ChainDataError> {
+	let mut file = File::open(config().chaindata).expect("ChainData not found");
+	let mut content = String::new();
+
+	file.read_to_string(&mut content).expect("Failed to load chaindata");
+	let mut chaindata: Vec<ChainData> =
+		serde_json::from_str(&content).expect("Failed to serialize");
+
+	let index = chaindata
+		.iter()
+		.position(|para| para.para_id == para_id && para.relay == Relay { id: relay.clone() })
+		.ok_or(ChainDataError::ParaNotFound)?;
+
+	Ok(chaindata.swap_remove(index))
+}
@@ -13,8 +13,10 @@
 // You should have received a copy of the GNU General Public License
 // along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
 
+use polkadot_core_primitives::BlockNumber;
+use std::{net::IpAddr, ops::Range};
 use subxt::utils::AccountId32;
-use types::Timestamp;
+use types::{ParaId, Parachain, RelayChain, Timestamp, WeightDimension};
 
 const CONFIG_FILE: &str = "config.toml";
 
@@ -22,6 +24,11 @@ const CONFIG_FILE: &str = "config.toml";
 pub struct PaymentInfo {
 	/// The rpc url from the chain where the payment is required to occur.
 	pub rpc_url: String,
+	/// Path to a PEM encoded bundle of CA certificates, against which the TLS certificate of a
+	/// `wss://` rpc url is verified instead of against the default roots, e.g. for self-signed
+	/// rpc endpoints or when behind a corporate proxy.
+	#[serde(default)]
+	pub ca_bundle: Option<String>,
 	/// The account that the payment should be sent to.
 	pub receiver: AccountId32,
 	/// The cost of the payment.
@@ -32,6 +39,346 @@ pub struct PaymentInfo {
 	pub subscription_duration: Timestamp,
 	/// Defines how much before the expiry can the subscription be renewed.
 	pub renewal_period: Timestamp,
+	/// Defines how the time during which a subscription was lapsed is handled on renewal.
+	///
+	/// The lapsed time is charged for by default.
+	#[serde(default)]
+	pub lapse_policy: LapsePolicy,
+	/// The number of seconds after the expiry during which a subscription is considered to be in
+	/// its grace period rather than expired.
+	#[serde(default)]
+	pub grace_period: Timestamp,
+	/// The maximum number of blocks a payment can be behind the last finalized block of the
+	/// payment chain to still be accepted.
+	///
+	/// There is no limit if not set.
+	#[serde(default)]
+	pub payment_max_age: Option<BlockNumber>,
+	/// The minimum number of blocks the payment block has to be behind the last finalized block
+	/// of the payment chain, for stronger confirmation guarantees than finality alone.
+	///
+	/// Unlike `min_finalized_depth` of the relay chains, this applies to all the payments.
+	#[serde(default)]
+	pub min_confirmations: BlockNumber,
+	/// Allows accumulating credit through multiple smaller payments, which can then be drawn
+	/// from to extend the subscription.
+	#[serde(default)]
+	pub micro_payments: bool,
+	/// Estimates the fee of the payment extrinsic when returning the payment call data.
+	///
+	/// Requires the payment node to expose the `payment_queryInfo` RPC.
+	#[serde(default)]
+	pub estimate_fees: bool,
+	/// Instead of paying the receiver directly, each parachain pays a sub-account derived from
+	/// the receiver, which makes the remark of the payment optional.
+	///
+	/// See `payment::derive_sub_account` for the derivation scheme.
+	#[serde(default)]
+	pub derive_receiver: bool,
+	/// Namespaces the payment remarks of this deployment, e.g. with a deployment id, so that
+	/// deployments sharing a receiver can't claim each other's payments.
+	///
+	/// The remarks become `regionx-weigher::<remark_prefix>:<relay>:<para_id>` when set.
+	#[serde(default)]
+	pub remark_prefix: Option<String>,
+	/// Allows unregistering a parachain without proving the ownership of its registration.
+	///
+	/// By default, the account which paid for the registration has to prove the ownership.
+	#[serde(default)]
+	pub allow_unproven_unregistration: bool,
+	/// Only accepts payments made by an account authorized to manage the parachain, so that a
+	/// parachain can't be registered by anyone else.
+	///
+	/// The authorized accounts are the manager of the parachain in the `registrar` pallet of its
+	/// relay chain, which requires the `rpc_url` of the relay chain to be configured, and the
+	/// accounts listed in `para_managers`.
+	#[serde(default)]
+	pub verify_payer: bool,
+	/// The accounts authorized to pay for specific parachains when `verify_payer` is set, in
+	/// addition to the ones resolved from the relay chain.
+	#[serde(default)]
+	pub para_managers: Vec<(RelayChain, ParaId, AccountId32)>,
+	/// The id of the asset in which the payments are made, through the `assets` pallet of the
+	/// payment chain, e.g. USDT on the Asset Hubs.
+	///
+	/// The payments are made in the native token if not set.
+	#[serde(default)]
+	pub asset_id: Option<u32>,
+	/// The pallet and call index of `assets.transfer_keep_alive` on the payment chain.
+	///
+	/// Only used if `asset_id` is set. Defaults to the index of the call on the Asset Hubs.
+	#[serde(default = "default_asset_transfer_call")]
+	pub asset_transfer_call: [u8; 2],
+	/// The kind of chain on which the payments are made, which determines how the payment calls
+	/// are encoded.
+	#[serde(default)]
+	pub payment_chain: PaymentChain,
+	/// The pallet and call indices of the calls with which the payments are made on the payment
+	/// chain.
+	///
+	/// Overrides the ones of the `payment_chain` if set.
+	#[serde(default)]
+	pub call_indices: Option<CallIndices>,
+	/// Subscription tiers which parachains can register for instead of the default tier, each
+	/// with its own cost and recording granularity.
+	///
+	/// The default tier costs `cost` and records the consumption of every block.
+	#[serde(default)]
+	pub tiers: Vec<SubscriptionTier>,
+	/// Overrides the rpc url, the receiver and the cost for the parachains of specific relay
+	/// chains, so that each relay chain can be paid for on its own chain.
+	///
+	/// When set, only the parachains of the listed relay chains can pay.
+	#[serde(default)]
+	pub relay_chains: Vec<RelayChainPaymentInfo>,
+}
+
+impl PaymentInfo {
+	/// Returns the subscription tier with the specified name.
+	pub fn tier(&self, name: &str) -> Option<SubscriptionTier> {
+		self.tiers.iter().find(|tier| tier.name == name).cloned()
+	}
+
+	/// Returns the payment configuration which applies to the parachains of the relay chain.
+	///
+	/// `None` if the payments are configured per relay chain, but not for the specified one.
+	pub fn for_relay_chain(&self, relay_chain: &RelayChain) -> Option<PaymentInfo> {
+		if self.relay_chains.is_empty() {
+			return Some(self.clone());
+		}
+
+		let info = self.relay_chains.iter().find(|info| info.relay_chain == *relay_chain)?;
+		// The tiers are paid for in the token of the payment chain of the relay chain as well.
+		let tiers = self
+			.tiers
+			.iter()
+			.filter_map(|tier| {
+				let cost = info.tier_cost(&tier.name)?;
+				Some(SubscriptionTier { cost, ..tier.clone() })
+			})
+			.collect();
+		Some(PaymentInfo {
+			rpc_url: info.rpc_url.clone(),
+			receiver: info.receiver.clone(),
+			cost: info.cost.clone(),
+			tiers,
+			payment_chain: info.payment_chain.unwrap_or(self.payment_chain),
+			call_indices: info.call_indices.or(self.call_indices),
+			// The resolved configuration applies regardless of the relay chain.
+			relay_chains: vec![],
+			..self.clone()
+		})
+	}
+}
+
+#[derive(serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SubscriptionTier {
+	/// The name with which parachains register for the tier, e.g. `basic`.
+	pub name: String,
+	/// The cost of a subscription in the tier, which replaces the `cost` of the default tier.
+	//
+	// Defined as a `String` since the `toml` crate has issues parsing `u128`.
+	pub cost: String,
+	/// The consumption is only recorded for the blocks whose number is a multiple of this.
+	pub sampling_interval: u32,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct RelayChainPaymentInfo {
+	/// The relay chain to whose parachains the configuration applies.
+	pub relay_chain: RelayChain,
+	/// The rpc url from the chain where the payment is required to occur.
+	pub rpc_url: String,
+	/// The account that the payment should be sent to.
+	pub receiver: AccountId32,
+	/// The cost of the payment.
+	pub cost: String,
+	/// The kind of chain on which the payments are made. Defaults to the `payment_chain` of the
+	/// payment configuration.
+	#[serde(default)]
+	pub payment_chain: Option<PaymentChain>,
+	/// The call indices of the payment chain. Defaults to the `call_indices` of the payment
+	/// configuration.
+	#[serde(default)]
+	pub call_indices: Option<CallIndices>,
+	/// The cost of each of the subscription tiers, which replaces the `cost` of the tier for the
+	/// parachains of the relay chain.
+	///
+	/// Required for every tier, since the tier costs are denominated in the token of the payment
+	/// chain of the relay chain.
+	#[serde(default)]
+	pub tier_costs: Vec<TierCost>,
+}
+
+impl RelayChainPaymentInfo {
+	/// Returns the cost of the specified subscription tier.
+	pub fn tier_cost(&self, tier: &str) -> Option<String> {
+		self.tier_costs
+			.iter()
+			.find(|cost| cost.tier == tier)
+			.map(|cost| cost.cost.clone())
+	}
+}
+
+#[derive(serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TierCost {
+	/// The name of the subscription tier.
+	pub tier: String,
+	/// The cost of a subscription in the tier.
+	pub cost: String,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentChain {
+	/// The payments are made on a relay chain, whose calls are encoded with the static metadata.
+	#[default]
+	RelayChain,
+	/// The payments are made on a parachain, e.g. on the Asset Hub, whose calls are encoded with
+	/// the indices resolved from the metadata of the payment chain.
+	Parachain,
+}
+
+/// The pallet and call indices with which the payment calls are encoded.
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CallIndices {
+	/// The index of `balances.transfer_keep_alive`.
+	pub transfer_keep_alive: [u8; 2],
+	/// The index of `balances.transfer_allow_death`.
+	pub transfer_allow_death: [u8; 2],
+	/// The index of `utility.batch_all`.
+	pub batch_all: [u8; 2],
+	/// The index of `system.remark`.
+	pub remark: [u8; 2],
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LapsePolicy {
+	/// The lapsed time is forgiven and the renewed subscription starts from the moment of renewal.
+	Forgive,
+	/// The lapsed time has to be paid for, in whole subscription periods, on renewal.
+	#[default]
+	Charge,
+}
+
+/// The format in which the tracker and the processor write their logs.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+	/// Human readable lines.
+	#[default]
+	Text,
+	/// One JSON object per line, with the relay chain and the parachain as separate keys.
+	Json,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct RecordedDimensions {
+	/// The parachain to which the configuration applies.
+	pub para: (RelayChain, ParaId),
+	/// The weight dimensions that are recorded for the parachain.
+	pub dimensions: Vec<WeightDimension>,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct RelayChainConfig {
+	/// The relay chain to which the configuration applies.
+	pub relay_chain: RelayChain,
+	/// The minimum number of blocks the payment block of a parachain using this relay chain has
+	/// to be behind the last finalized block.
+	#[serde(default)]
+	pub min_finalized_depth: BlockNumber,
+	/// The expected block time, in seconds, of the parachains using this relay chain.
+	///
+	/// Defaults to the known block time of the relay chain if not set.
+	pub block_time: Option<Timestamp>,
+	/// The rpc url of the relay chain, used for checking whether the registered parachains are
+	/// still onboarded.
+	pub rpc_url: Option<String>,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReconciliationMode {
+	/// Parachains that are no longer onboarded are only flagged.
+	Flag,
+	/// Parachains that are no longer onboarded are flagged and their tracking is paused.
+	Pause,
+}
+
+/// The cross-origin requests allowed by the web API.
+#[derive(serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CorsConfig {
+	/// The origins from which the browsers are allowed to call the web API, e.g.
+	/// `https://app.regionx.tech`, or `*` for any origin.
+	///
+	/// Only same-origin requests are allowed if empty, which is the default.
+	#[serde(default)]
+	pub allowed_origins: Vec<String>,
+	/// The HTTP methods allowed for cross-origin requests. Defaults to `GET` and `POST`.
+	#[serde(default = "default_cors_methods")]
+	pub allowed_methods: Vec<String>,
+	/// The headers allowed in cross-origin requests. All headers are allowed if empty, which is
+	/// the default.
+	#[serde(default)]
+	pub allowed_headers: Vec<String>,
+}
+
+impl Default for CorsConfig {
+	fn default() -> Self {
+		Self {
+			allowed_origins: vec![],
+			allowed_methods: default_cors_methods(),
+			allowed_headers: vec![],
+		}
+	}
+}
+
+#[derive(serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct WeightThreshold {
+	/// The parachain to which the threshold applies.
+	pub para: (RelayChain, ParaId),
+	/// The consumption, as a fraction of the maximum block weight summed over all the dispatch
+	/// classes, above which a block exceeds the threshold. Either dimension exceeding it suffices.
+	pub threshold: f32,
+	/// The number of consecutive blocks which have to exceed the threshold before an alert is
+	/// fired.
+	#[serde(default = "default_consecutive_blocks")]
+	pub consecutive_blocks: u32,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct ParaBlockTime {
+	/// The parachain to which the configuration applies.
+	pub para: (RelayChain, ParaId),
+	/// The expected block time of the parachain in seconds.
+	pub block_time: Timestamp,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct PartnerConfig {
+	/// The name identifying the partner.
+	pub name: String,
+	/// The hex encoded Ed25519 public key used for verifying the orders of the partner.
+	pub public_key: String,
+	/// The number of seconds within which the payment of an order needs to be settled.
+	#[serde(default = "default_settlement_window")]
+	pub settlement_window: Timestamp,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct KafkaConfig {
+	/// Comma separated list of the Kafka brokers.
+	pub brokers: String,
+	/// The topic to which the consumption records are published.
+	pub topic: String,
+	/// The maximum number of records waiting to be published.
+	///
+	/// New records are dropped once the buffer is full, so that publishing never blocks the
+	/// tracker.
+	#[serde(default = "default_kafka_buffer_size")]
+	pub buffer_size: usize,
 }
 
 #[derive(serde::Deserialize)]
@@ -42,10 +389,270 @@ pub struct Config {
 	pub registry: String,
 	/// Path to the chaindata file.
 	pub chaindata: String,
+	/// The url from which the admin can refresh the chaindata file, e.g. as new parachains
+	/// onboard.
+	///
+	/// The chaindata can't be refreshed if this is not set.
+	pub chaindata_url: Option<String>,
+	/// Keeps an in-memory copy of the registry so that reads don't need to parse it.
+	///
+	/// The copy is only used as long as the stored registry didn't change, so the writes of other
+	/// processes are picked up as well. Unlike most of the config, this is only read once, along
+	/// with the location of the registry, so changing either requires a restart.
+	#[serde(default)]
+	pub cache_registry: bool,
+	/// Path to a SQLite database in which the registry is stored instead of the `registry` file.
+	///
+	/// Only used if built with the `sqlite` feature.
+	pub registry_database: Option<String>,
 	/// The payment configuration.
 	pub payment_info: Option<PaymentInfo>,
 	/// The Number of distinct output directories.
 	pub outputs: usize,
+	/// Allows limiting the weight dimensions recorded for specific parachains.
+	///
+	/// All dimensions are recorded for parachains that aren't listed.
+	#[serde(default)]
+	pub recorded_dimensions: Vec<RecordedDimensions>,
+	/// The number of seconds after which the latest recorded consumption of a parachain is
+	/// considered stale.
+	///
+	/// This is multiplied by the sampling interval for parachains of a subscription tier.
+	#[serde(default = "default_staleness_threshold")]
+	pub staleness_threshold: Timestamp,
+	/// The number of expected blocks after which the latest recorded consumption of a parachain
+	/// is considered stale.
+	///
+	/// When set, this takes precedence over `staleness_threshold`, and the threshold of each
+	/// parachain is computed from its expected block time.
+	pub staleness_blocks: Option<u32>,
+	/// The number of seconds after which a parachain with no newly recorded blocks is considered
+	/// dormant.
+	///
+	/// This is multiplied by the sampling interval for parachains of a subscription tier.
+	#[serde(default = "default_dormancy_threshold")]
+	pub dormancy_threshold: Timestamp,
+	/// The number of expected blocks after which a parachain with no newly recorded blocks is
+	/// considered dormant.
+	///
+	/// When set, this takes precedence over `dormancy_threshold`, and the threshold of each
+	/// parachain is computed from its expected block time.
+	pub dormancy_blocks: Option<u32>,
+	/// Overrides the expected block time of specific parachains.
+	///
+	/// Parachains that aren't listed are expected to follow the block time of their relay chain.
+	#[serde(default)]
+	pub block_times: Vec<ParaBlockTime>,
+	/// The maximum time range in milliseconds that a single consumption query can cover.
+	///
+	/// Queries without an explicit `start` cover the whole history, and ones without an
+	/// explicit `end` extend up to now. There is no limit if not set.
+	pub max_query_range: Option<Timestamp>,
+	/// The minimum number of records within the queried window required for serving the
+	/// consumption aggregated over a time period.
+	///
+	/// Defaults to 0, in which case aggregates are always served.
+	#[serde(default)]
+	pub min_aggregation_records: usize,
+	/// Path to the file containing the hex encoded Ed25519 seed used for signing the consumption
+	/// responses.
+	///
+	/// Responses are not signed if this is not set.
+	pub signing_key: Option<String>,
+	/// Path to the file containing the key required for performing admin operations.
+	///
+	/// Admin operations are disabled if this is not set.
+	pub admin_key: Option<String>,
+	/// The RPC latency, in milliseconds, measured during payment validation above which new
+	/// registrations are temporarily rejected.
+	///
+	/// Registrations are never rejected because of the latency if this is not set.
+	pub max_rpc_latency: Option<u64>,
+	/// Stores the hash of each block along with its consumption, which allows verifying the
+	/// records against the chain.
+	#[serde(default)]
+	pub record_block_hashes: bool,
+	/// Records the weight consumed by the inherents separately from the weight consumed by the
+	/// other extrinsics.
+	///
+	/// This requires fetching the events of each block.
+	#[serde(default)]
+	pub record_inherent_split: bool,
+	/// The number of seconds after startup during which the tracker discards the recorded
+	/// consumption, while its connections stabilize.
+	#[serde(default)]
+	pub tracker_warmup: Timestamp,
+	/// The maximum number of blocks the consumption of a parachain is backfilled for.
+	///
+	/// When `backfill_from` is further in the past, only the most recent blocks are backfilled.
+	#[serde(default = "default_max_backfill_blocks")]
+	pub max_backfill_blocks: BlockNumber,
+	/// Relay chain specific configuration.
+	#[serde(default)]
+	pub relay_chains: Vec<RelayChainConfig>,
+	/// Allows registering parachains which aren't part of the chaindata, provided that an rpc
+	/// url is supplied on registration.
+	#[serde(default)]
+	pub allow_unknown_paras: bool,
+	/// Parachains which can be registered without a payment in payment mode, e.g. the system
+	/// parachains. Their subscriptions never expire.
+	#[serde(default)]
+	pub payment_exempt_paras: Vec<(RelayChain, ParaId)>,
+	/// Treats extending the subscription of an unregistered parachain as its registration.
+	#[serde(default)]
+	pub register_on_extend: bool,
+	/// The trusted partners which can register parachains through orders with deferred
+	/// settlement.
+	#[serde(default)]
+	pub partners: Vec<PartnerConfig>,
+	/// The number of extrinsics in a payment block above which they are validated in parallel.
+	///
+	/// Extrinsics are always validated serially if this is not set.
+	pub parallel_validation_threshold: Option<usize>,
+	/// On startup, the tracker checks whether the registered parachains are still onboarded on
+	/// their relay chain, and handles the ones that aren't according to this mode.
+	///
+	/// Only relay chains with a configured rpc url are checked, and nothing is checked if this is
+	/// not set.
+	pub reconcile_registry: Option<ReconciliationMode>,
+	/// The number of the most recent tracker errors that are kept, so that they can be queried
+	/// through `/admin/tracker_errors`.
+	///
+	/// The tracker errors aren't persisted if this is not set.
+	pub tracker_error_history: Option<usize>,
+	/// Configuration for publishing the recorded consumption to a Kafka topic.
+	///
+	/// Only used if the tracker is built with the `kafka` feature.
+	pub kafka: Option<KafkaConfig>,
+	/// The url to which a warning is posted when the subscription of a parachain is about to
+	/// expire.
+	///
+	/// No warnings are sent if this is not set.
+	pub expiry_webhook_url: Option<String>,
+	/// The number of seconds before the expiry of a subscription at which the warning is sent.
+	#[serde(default = "default_expiry_warning_threshold")]
+	pub expiry_warning_threshold: Timestamp,
+	/// The number of requests per minute each client IP can make to the endpoints modifying the
+	/// registry.
+	///
+	/// The requests aren't rate limited if this is not set.
+	pub rate_limit: Option<u32>,
+	/// The IPs of the reverse proxies in front of the server, whose forwarded client IP is used
+	/// for the rate limiting instead of their own.
+	///
+	/// The client IP is forwarded through the `ip_header` of Rocket, `X-Real-IP` by default. The
+	/// header is ignored for requests coming from any other peer, since it could be spoofed.
+	#[serde(default)]
+	pub trusted_proxies: Vec<IpAddr>,
+	/// The number of seconds for which the outcome of a request made with an `Idempotency-Key` is
+	/// replayed to the retries of the request.
+	///
+	/// Idempotency keys are ignored if this is set to zero.
+	#[serde(default = "default_idempotency_ttl")]
+	pub idempotency_ttl: Timestamp,
+	/// Alerts when the consumption of specific parachains consistently exceeds a threshold.
+	#[serde(default)]
+	pub weight_thresholds: Vec<WeightThreshold>,
+	/// The url to which the weight alerts are posted, in addition to being logged.
+	///
+	/// The alerts are only logged if this is not set.
+	pub weight_alert_webhook_url: Option<String>,
+	/// Stops recording the consumption of parachains whose subscription expired, after the
+	/// grace period.
+	#[serde(default)]
+	pub stop_tracking_expired: bool,
+	/// Makes the tracker reconnect to parachains from which no new finalized block was received
+	/// within their staleness threshold.
+	#[serde(default)]
+	pub watchdog: bool,
+	/// The number of seconds the tracker and the web API are given to wind down on shutdown.
+	///
+	/// The tracker finishes writing the consumption of the blocks it is processing, while the web
+	/// API finishes serving the pending requests. Defaults to 10 seconds.
+	#[serde(default = "default_shutdown_timeout")]
+	pub shutdown_timeout: Timestamp,
+	/// The number of days for which the consumption is kept. Older consumption is periodically
+	/// pruned.
+	///
+	/// The consumption is kept forever if set to 0, which is the default.
+	#[serde(default)]
+	pub retention_days: u64,
+	/// The cross-origin requests allowed by the web API.
+	///
+	/// Only same-origin requests are allowed if not set.
+	#[serde(default)]
+	pub cors: CorsConfig,
+	/// The format of the tracker and processor logs. Defaults to `text`.
+	#[serde(default)]
+	pub log_format: LogFormat,
+	/// The number of records buffered for each client of the live consumption. Clients falling
+	/// further behind miss the oldest records. Defaults to 1024.
+	#[serde(default = "default_live_channel_capacity")]
+	pub live_channel_capacity: usize,
+}
+
+fn default_max_backfill_blocks() -> BlockNumber {
+	// A week of blocks with a block time of 6 seconds.
+	100_800
+}
+
+fn default_staleness_threshold() -> Timestamp {
+	// 5 minutes.
+	300
+}
+
+fn default_dormancy_threshold() -> Timestamp {
+	// 10 minutes.
+	600
+}
+
+fn default_block_time(relay_chain: &RelayChain) -> Timestamp {
+	match relay_chain {
+		RelayChain::Polkadot => 12,
+		// Async backing is enabled on Kusama and Paseo.
+		RelayChain::Kusama | RelayChain::Paseo => 6,
+	}
+}
+
+fn default_asset_transfer_call() -> [u8; 2] {
+	// The `Assets` pallet is at index 50 on the Asset Hubs, and `transfer_keep_alive` has the call
+	// index 9.
+	[50, 9]
+}
+
+fn default_settlement_window() -> Timestamp {
+	// 1 day.
+	86400
+}
+
+fn default_shutdown_timeout() -> Timestamp {
+	10
+}
+
+fn default_idempotency_ttl() -> Timestamp {
+	// 10 minutes.
+	600
+}
+
+fn default_cors_methods() -> Vec<String> {
+	vec!["GET".into(), "POST".into()]
+}
+
+fn default_kafka_buffer_size() -> usize {
+	1024
+}
+
+fn default_live_channel_capacity() -> usize {
+	1024
+}
+
+fn default_consecutive_blocks() -> u32 {
+	1
+}
+
+fn default_expiry_warning_threshold() -> Timestamp {
+	// 3 days.
+	259200
 }
 
 pub fn config() -> Config {
@@ -53,6 +660,300 @@ pub fn config() -> Config {
 	toml::from_str(&config_str).expect("Failed to parse config file")
 }
 
+/// Returns the configured log format.
+///
+/// Unlike `config`, this falls back to the default format if the config file can't be read or
+/// parsed, so that the binaries can still log why their configuration is invalid.
+pub fn log_format() -> LogFormat {
+	std::fs::read_to_string(CONFIG_FILE)
+		.ok()
+		.and_then(|config_str| toml::from_str::<Config>(&config_str).ok())
+		.map(|config| config.log_format)
+		.unwrap_or_default()
+}
+
+/// A misconfiguration found when validating the config file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+	/// The config file couldn't be read.
+	Unreadable(String),
+	/// The config file couldn't be parsed, e.g. because the `receiver` isn't a valid account.
+	Unparseable(String),
+	/// The `cost` of the specified payment configuration doesn't parse to a `u128`.
+	InvalidCost(String),
+	/// The specified rpc url isn't a well-formed websocket or http url.
+	InvalidRpcUrl(String),
+	/// The specified duration is zero.
+	ZeroDuration(String),
+	/// The CA bundle at the specified path can't be loaded.
+	InvalidCaBundle(String),
+	/// The specified CORS origin or method is invalid, e.g. an origin with a path.
+	InvalidCors(String),
+	/// The cost of the specified subscription tier isn't configured.
+	MissingTierCost(String),
+}
+
+impl std::fmt::Display for ConfigError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Self::Unreadable(err) => write!(f, "Failed to read {}: {}", CONFIG_FILE, err),
+			Self::Unparseable(err) => write!(f, "Failed to parse {}: {}", CONFIG_FILE, err),
+			Self::InvalidCost(field) => write!(f, "`{}` has to be an integer amount", field),
+			Self::InvalidRpcUrl(url) =>
+				write!(f, "`{}` isn't a valid ws://, wss://, http:// or https:// url", url),
+			Self::ZeroDuration(field) => write!(f, "`{}` has to be greater than zero", field),
+			Self::InvalidCaBundle(path) =>
+				write!(f, "`{}` isn't a readable PEM encoded bundle of CA certificates", path),
+			Self::InvalidCors(value) =>
+				write!(f, "`{}` isn't a valid CORS origin or method", value),
+			Self::MissingTierCost(field) => write!(f, "`{}` has to be configured", field),
+		}
+	}
+}
+
+impl Config {
+	/// Checks the parts of the configuration which would otherwise only fail once they are used,
+	/// e.g. when validating the first payment.
+	pub fn validate(&self) -> Result<(), ConfigError> {
+		for relay_chain in &self.relay_chains {
+			if let Some(rpc_url) = &relay_chain.rpc_url {
+				validate_rpc_url(rpc_url)?;
+			}
+		}
+		validate_cors(&self.cors)?;
+
+		let Some(payment_info) = &self.payment_info else { return Ok(()) };
+
+		validate_rpc_url(&payment_info.rpc_url)?;
+		validate_cost(&payment_info.cost, "payment_info.cost")?;
+		if let Some(ca_bundle) = &payment_info.ca_bundle {
+			crate::payment::load_ca_bundle(ca_bundle)
+				.map_err(|_| ConfigError::InvalidCaBundle(ca_bundle.clone()))?;
+		}
+		for tier in &payment_info.tiers {
+			validate_cost(&tier.cost, &format!("payment_info.tiers.{}.cost", tier.name))?;
+			if tier.sampling_interval == 0 {
+				return Err(ConfigError::ZeroDuration(format!(
+					"payment_info.tiers.{}.sampling_interval",
+					tier.name
+				)))
+			}
+		}
+		for info in &payment_info.relay_chains {
+			validate_rpc_url(&info.rpc_url)?;
+			validate_cost(
+				&info.cost,
+				&format!("payment_info.relay_chains.{}.cost", info.relay_chain),
+			)?;
+			for tier in &payment_info.tiers {
+				let field = format!(
+					"payment_info.relay_chains.{}.tier_costs.{}",
+					info.relay_chain, tier.name
+				);
+				let cost = info
+					.tier_cost(&tier.name)
+					.ok_or(ConfigError::MissingTierCost(field.clone()))?;
+				validate_cost(&cost, &field)?;
+			}
+		}
+
+		if payment_info.subscription_duration == 0 {
+			return Err(ConfigError::ZeroDuration("payment_info.subscription_duration".into()))
+		}
+		if payment_info.renewal_period == 0 {
+			return Err(ConfigError::ZeroDuration("payment_info.renewal_period".into()))
+		}
+
+		Ok(())
+	}
+}
+
+/// Reads and validates the config file, so that a misconfiguration is reported on startup.
+pub fn validate() -> Result<(), ConfigError> {
+	let config_str = std::fs::read_to_string(CONFIG_FILE)
+		.map_err(|err| ConfigError::Unreadable(err.to_string()))?;
+	let config: Config =
+		toml::from_str(&config_str).map_err(|err| ConfigError::Unparseable(err.to_string()))?;
+
+	config.validate()
+}
+
+fn validate_cors(cors: &CorsConfig) -> Result<(), ConfigError> {
+	for origin in &cors.allowed_origins {
+		let valid = origin == "*" ||
+			reqwest::Url::parse(origin).map_or(false, |url| {
+				matches!(url.scheme(), "http" | "https") &&
+					url.has_host() && url.path() == "/" &&
+					!origin.ends_with('/') &&
+					url.query().is_none()
+			});
+		if !valid {
+			return Err(ConfigError::InvalidCors(origin.clone()))
+		}
+	}
+
+	for method in &cors.allowed_methods {
+		let valid = matches!(
+			method.to_uppercase().as_str(),
+			"GET" | "POST" | "PUT" | "DELETE" | "PATCH" | "HEAD" | "OPTIONS"
+		);
+		if !valid {
+			return Err(ConfigError::InvalidCors(method.clone()))
+		}
+	}
+
+	Ok(())
+}
+
+fn validate_rpc_url(rpc_url: &str) -> Result<(), ConfigError> {
+	let valid = reqwest::Url::parse(rpc_url).map_or(false, |url| {
+		["ws", "wss", "http", "https"].contains(&url.scheme()) && url.has_host()
+	});
+
+	valid.then_some(()).ok_or_else(|| ConfigError::InvalidRpcUrl(rpc_url.into()))
+}
+
+fn validate_cost(cost: &str, field: &str) -> Result<(), ConfigError> {
+	cost.parse::<u128>()
+		.map(|_| ())
+		.map_err(|_| ConfigError::InvalidCost(field.into()))
+}
+
+/// Returns the weight dimensions which should be recorded for the specified parachain.
+pub fn recorded_dimensions(para: &Parachain) -> Vec<WeightDimension> {
+	config()
+		.recorded_dimensions
+		.into_iter()
+		.find(|c| c.para == (para.relay_chain.clone(), para.para_id))
+		.map(|c| c.dimensions)
+		.unwrap_or(vec![WeightDimension::RefTime, WeightDimension::ProofSize])
+}
+
+/// Returns the expected block time of the specified parachain in seconds.
+pub fn block_time(para: &Parachain) -> Timestamp {
+	let config = config();
+
+	let para_block_time = config
+		.block_times
+		.into_iter()
+		.find(|c| c.para == (para.relay_chain.clone(), para.para_id))
+		.map(|c| c.block_time);
+	let relay_block_time = || {
+		config
+			.relay_chains
+			.into_iter()
+			.find(|c| c.relay_chain == para.relay_chain)
+			.and_then(|c| c.block_time)
+	};
+
+	para_block_time
+		.or_else(relay_block_time)
+		.unwrap_or_else(|| default_block_time(&para.relay_chain))
+}
+
+/// Returns the number of seconds after which the latest consumption of the parachain is
+/// considered stale.
+pub fn staleness_threshold(para: &Parachain) -> Timestamp {
+	let config = config();
+	sampled_threshold(
+		config.staleness_blocks.map(|blocks| blocks as Timestamp * block_time(para)),
+		config.staleness_threshold,
+		sampling_interval(para),
+	)
+}
+
+/// Returns the number of seconds without newly recorded blocks after which the parachain is
+/// considered dormant.
+pub fn dormancy_threshold(para: &Parachain) -> Timestamp {
+	let config = config();
+	sampled_threshold(
+		config.dormancy_blocks.map(|blocks| blocks as Timestamp * block_time(para)),
+		config.dormancy_threshold,
+		sampling_interval(para),
+	)
+}
+
+/// Returns the threshold, in seconds, of a parachain whose consumption is recorded every
+/// `sampling_interval` blocks.
+///
+/// The block based threshold takes precedence over the time based one if set. Either is scaled by
+/// the sampling interval, since the blocks in between aren't recorded.
+pub fn sampled_threshold(
+	block_based: Option<Timestamp>,
+	time_based: Timestamp,
+	sampling_interval: u32,
+) -> Timestamp {
+	block_based
+		.unwrap_or(time_based)
+		.saturating_mul(sampling_interval.max(1) as Timestamp)
+}
+
+/// Returns the interval, in blocks, at which the consumption of the parachain is recorded as
+/// defined by its subscription tier.
+///
+/// The consumption of every block is recorded in the default tier, or if the tier of the
+/// parachain is no longer configured.
+pub fn sampling_interval(para: &Parachain) -> u32 {
+	config()
+		.payment_info
+		.zip(para.tier.as_ref())
+		.and_then(|(payment_info, tier)| payment_info.tier(tier))
+		.map_or(1, |tier| tier.sampling_interval.max(1))
+}
+
+/// Returns whether the consumption of the block is recorded with the sampling interval.
+pub fn is_sampled(block_number: BlockNumber, sampling_interval: u32) -> bool {
+	block_number % sampling_interval.max(1) == 0
+}
+
+/// Returns the blocks within the range whose consumption is recorded with the sampling interval.
+pub fn sampled_blocks(
+	blocks: Range<BlockNumber>,
+	sampling_interval: u32,
+) -> impl Iterator<Item = BlockNumber> {
+	blocks.filter(move |block_number| is_sampled(*block_number, sampling_interval))
+}
+
+/// Returns the weight threshold configured for the parachain, if any.
+pub fn weight_threshold(para: &Parachain) -> Option<WeightThreshold> {
+	config()
+		.weight_thresholds
+		.into_iter()
+		.find(|c| c.para == (para.relay_chain.clone(), para.para_id))
+}
+
+/// Returns the rpc url of the specified relay chain, if configured.
+pub fn relay_rpc_url(relay_chain: &RelayChain) -> Option<String> {
+	config()
+		.relay_chains
+		.into_iter()
+		.find(|c| c.relay_chain == *relay_chain)
+		.and_then(|c| c.rpc_url)
+}
+
+/// Returns whether the parachain can be registered without a payment.
+pub fn payment_exempt(para: &Parachain) -> bool {
+	config()
+		.payment_exempt_paras
+		.contains(&(para.relay_chain.clone(), para.para_id))
+}
+
+/// Returns the configuration of the trusted partner with the specified name.
+pub fn partner(name: &str) -> Option<PartnerConfig> {
+	config().partners.into_iter().find(|p| p.name == name)
+}
+
+/// Returns the minimum finalized depth required for payments of parachains using the specified
+/// relay chain.
+pub fn min_finalized_depth(relay_chain: &RelayChain) -> BlockNumber {
+	config()
+		.relay_chains
+		.into_iter()
+		.find(|c| c.relay_chain == *relay_chain)
+		.map(|c| c.min_finalized_depth)
+		.unwrap_or_default()
+}
+
 pub fn output_directory(rpc_index: Option<usize>) -> String {
 	let output_dir = config().output_directory.trim_end_matches('/').to_string();
 
@@ -62,3 +963,9 @@ pub fn output_directory(rpc_index: Option<usize>) -> String {
 		format!("{}/out", output_dir)
 	}
 }
+
+/// The directory containing the latest recorded consumption of each parachain.
+pub fn latest_directory() -> String {
+	let output_dir = config().output_directory.trim_end_matches('/').to_string();
+	format!("{}/latest", output_dir)
+}
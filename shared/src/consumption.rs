@@ -13,25 +13,151 @@
 // You should have received a copy of the GNU General Public License
 // along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::{config::output_directory, LOG_TARGET};
+use crate::{
+	config::{dormancy_threshold, latest_directory, output_directory, staleness_threshold},
+	current_timestamp, LOG_TARGET,
+};
 use csv::{ReaderBuilder, WriterBuilder};
-use std::fs::{File, OpenOptions};
-use types::{Parachain, WeightConsumption};
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use std::{
+	fs::{File, OpenOptions},
+	io::{Read, Seek, SeekFrom},
+};
+use types::{Parachain, Timestamp, WeightConsumption};
+
+/// Exclusive access to a consumption file, which is held while the file is written or pruned so
+/// that the records appended while it is being pruned aren't lost.
+///
+/// This is an advisory lock on a lock file next to the consumption file, rather than on the file
+/// itself which is replaced when pruned. The lock is taken through a newly opened file every time,
+/// so it serializes the writes of other threads as well as those of other processes, e.g. of the
+/// tracker and of the pruning job of the API server. The writes of different files don't block
+/// each other.
+///
+/// The lock is released once this is dropped.
+struct FileLock(File);
+
+impl FileLock {
+	fn acquire(path: &str) -> Result<Self, std::io::Error> {
+		let file = OpenOptions::new().create(true).write(true).open(lock_file_path(path))?;
+		file.lock_exclusive()?;
+
+		Ok(Self(file))
+	}
+}
+
+impl Drop for FileLock {
+	fn drop(&mut self) {
+		let _ = self.0.unlock();
+	}
+}
+
+/// The latest weight consumption recorded for a parachain.
+///
+/// This is updated every time new consumption is written, which allows reading the latest state
+/// of all parachains without scanning through their whole consumption history.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LatestConsumption {
+	/// The latest recorded weight consumption.
+	pub consumption: WeightConsumption,
+	/// The timestamp at which the consumption was recorded.
+	pub recorded_at: Timestamp,
+}
+
+impl LatestConsumption {
+	/// Returns whether the consumption hasn't been updated within the staleness threshold of the
+	/// parachain.
+	pub fn is_stale(&self, para: &Parachain) -> bool {
+		current_timestamp().saturating_sub(self.recorded_at) > staleness_threshold(para)
+	}
+}
+
+/// The tracking activity of a set of parachains.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TrackingActivity {
+	/// The parachains for which no blocks were recorded within the dormancy threshold.
+	pub dormant: Vec<Parachain>,
+	/// Set when no blocks were recorded for any of the parachains, in which case the issue is
+	/// most likely with the tracker rather than with the parachains themselves.
+	pub stalled: bool,
+}
+
+/// Returns the tracking activity of the provided parachains.
+///
+/// A parachain is considered dormant if no new blocks were recorded for it within its dormancy
+/// threshold while blocks are still being recorded for other parachains.
+///
+/// Paused parachains aren't tracked, so they are left out.
+pub fn tracking_activity(paras: &[Parachain]) -> TrackingActivity {
+	let now = current_timestamp();
+
+	let (active, inactive): (Vec<Parachain>, Vec<Parachain>) =
+		paras.iter().filter(|para| !para.paused).cloned().partition(|para| {
+			latest_consumption(para).map_or(false, |latest| {
+				now.saturating_sub(latest.recorded_at) <= dormancy_threshold(para)
+			})
+		});
+
+	if active.is_empty() && !inactive.is_empty() {
+		TrackingActivity { dormant: vec![], stalled: true }
+	} else {
+		TrackingActivity { dormant: inactive, stalled: false }
+	}
+}
 
 pub fn get_consumption(
 	para: Parachain,
 	rpc_index: Option<usize>,
 ) -> Result<Vec<WeightConsumption>, &'static str> {
+	let consumption: Vec<WeightConsumption> = consumption_iter(para, rpc_index)?.collect();
+
+	Ok(consumption)
+}
+
+/// Returns an iterator over the consumption data of a parachain.
+///
+/// Unlike `get_consumption` the records are lazily read from the file, which allows going
+/// through all the consumption data without loading it into memory.
+pub fn consumption_iter(
+	para: Parachain,
+	rpc_index: Option<usize>,
+) -> Result<impl Iterator<Item = WeightConsumption>, &'static str> {
 	let file =
 		File::open(output_file_path(para, rpc_index)).map_err(|_| "Consumption data not found")?;
-	let mut rdr = ReaderBuilder::new().has_headers(false).from_reader(file);
+	// Records are flexible since dimensions that aren't recorded take up a single column.
+	let rdr = ReaderBuilder::new().has_headers(false).flexible(true).from_reader(file);
 
-	let consumption: Vec<WeightConsumption> = rdr
-		.deserialize::<WeightConsumption>()
-		.filter_map(|result| result.ok())
-		.collect();
+	Ok(rdr.into_deserialize::<WeightConsumption>().filter_map(|result| result.ok()))
+}
 
-	Ok(consumption)
+/// Returns the consumption appended to the consumption file of a parachain after the provided
+/// byte offset, along with the offset up to which the file got read.
+///
+/// Only complete records are read, so a record which is still being written is returned by the
+/// next call. The file is read from the start once it is shorter than the offset, e.g. after it
+/// got pruned.
+pub fn consumption_appended(
+	para: Parachain,
+	rpc_index: Option<usize>,
+	offset: u64,
+) -> Result<(Vec<WeightConsumption>, u64), std::io::Error> {
+	let mut file = File::open(output_file_path(para, rpc_index))?;
+	let offset = if file.metadata()?.len() < offset { 0 } else { offset };
+	file.seek(SeekFrom::Start(offset))?;
+
+	let mut appended = vec![];
+	file.read_to_end(&mut appended)?;
+	let complete = appended.iter().rposition(|&byte| byte == b'\n').map_or(0, |end| end + 1);
+
+	// Records are flexible since dimensions that aren't recorded take up a single column.
+	let rdr = ReaderBuilder::new()
+		.has_headers(false)
+		.flexible(true)
+		.from_reader(&appended[..complete]);
+	let records = rdr.into_deserialize::<WeightConsumption>().filter_map(|result| result.ok());
+
+	Ok((records.collect(), offset + complete as u64))
 }
 
 pub fn write_consumption(
@@ -45,15 +171,48 @@ pub fn write_consumption(
 		para.relay_chain, para.para_id, consumption.block_number
 	);
 
-	let output_file_path = output_file_path(para, rpc_index);
-	let file = OpenOptions::new().create(true).append(true).open(output_file_path)?;
+	let output_file_path = output_file_path(para.clone(), rpc_index);
+	let _lock = FileLock::acquire(&output_file_path)?;
+	let file = OpenOptions::new().create(true).append(true).open(&output_file_path)?;
 
-	let mut wtr = WriterBuilder::new().from_writer(file);
+	let mut wtr = WriterBuilder::new().flexible(true).from_writer(file);
 
 	// The data is stored in the sequence described at the beginning of the file.
 	wtr.write_record(&consumption.to_csv())?;
+	wtr.flush()?;
 
-	wtr.flush()
+	if let Err(e) = write_latest_consumption(&para, consumption) {
+		log::error!(
+			target: LOG_TARGET,
+			"{}-{} - Failed to update latest consumption: {:?}",
+			para.relay_chain, para.para_id, e
+		);
+	}
+
+	Ok(())
+}
+
+/// Returns the latest recorded weight consumption of a parachain.
+pub fn latest_consumption(para: &Parachain) -> Option<LatestConsumption> {
+	let content = std::fs::read_to_string(latest_file_path(para)).ok()?;
+	serde_json::from_str(&content).ok()
+}
+
+fn write_latest_consumption(
+	para: &Parachain,
+	consumption: WeightConsumption,
+) -> Result<(), std::io::Error> {
+	std::fs::create_dir_all(latest_directory())?;
+
+	let latest = LatestConsumption { consumption, recorded_at: current_timestamp() };
+	let content = serde_json::to_string(&latest)?;
+
+	// Multiple trackers may be updating the same file, so we write to a temporary file first and
+	// atomically move it into place.
+	let path = latest_file_path(para);
+	let tmp_path = format!("{}.{}.tmp", path, std::process::id());
+	std::fs::write(&tmp_path, content)?;
+	std::fs::rename(tmp_path, path)
 }
 
 pub fn write_batch_consumption(
@@ -67,9 +226,10 @@ pub fn write_batch_consumption(
 	);
 
 	let output_file_path = output_file_path(para, None);
-	let file = OpenOptions::new().create(true).append(true).open(output_file_path)?;
+	let _lock = FileLock::acquire(&output_file_path)?;
+	let file = OpenOptions::new().create(true).append(true).open(&output_file_path)?;
 
-	let mut wtr = WriterBuilder::new().from_writer(file);
+	let mut wtr = WriterBuilder::new().flexible(true).from_writer(file);
 
 	// TODO: add a to_csv function
 	consumption.iter().try_for_each(|entry| {
@@ -80,6 +240,48 @@ pub fn write_batch_consumption(
 	wtr.flush()
 }
 
+/// Removes the consumption of a parachain which was recorded before the `before` timestamp, in
+/// milliseconds. Returns the number of removed records.
+///
+/// The remaining records are written to a temporary file which then replaces the consumption file,
+/// so that it is never left partially written. Records which can't be parsed are kept as they are.
+pub fn prune_consumption(
+	para: &Parachain,
+	rpc_index: Option<usize>,
+	before: Timestamp,
+) -> Result<usize, std::io::Error> {
+	let output_file_path = output_file_path(para.clone(), rpc_index);
+	let _lock = FileLock::acquire(&output_file_path)?;
+	let Ok(file) = File::open(&output_file_path) else { return Ok(0) };
+
+	let mut rdr = ReaderBuilder::new().has_headers(false).flexible(true).from_reader(file);
+	let tmp_path = format!("{}.{}.tmp", output_file_path, std::process::id());
+	let mut wtr = WriterBuilder::new().flexible(true).from_path(&tmp_path)?;
+
+	let mut pruned = 0;
+	for record in rdr.records() {
+		let record = record?;
+		// The timestamp is stored right after the block number.
+		let timestamp = record.get(1).and_then(|timestamp| timestamp.parse::<Timestamp>().ok());
+		if timestamp.map_or(false, |timestamp| timestamp < before) {
+			pruned += 1;
+			continue;
+		}
+
+		wtr.write_record(&record)?;
+	}
+	wtr.flush()?;
+	drop(wtr);
+
+	if pruned == 0 {
+		std::fs::remove_file(tmp_path)?;
+	} else {
+		std::fs::rename(tmp_path, output_file_path)?;
+	}
+
+	Ok(pruned)
+}
+
 pub fn delete_consumption(para: Parachain, rpc_index: usize) {
 	log::info!(
 		target: LOG_TARGET,
@@ -106,6 +308,14 @@ pub fn delete_consumption(para: Parachain, rpc_index: usize) {
 	}
 }
 
+fn latest_file_path(para: &Parachain) -> String {
+	format!("{}/{}-{}.json", latest_directory(), para.relay_chain, para.para_id)
+}
+
 fn output_file_path(para: Parachain, rpc_index: Option<usize>) -> String {
 	format!("{}/{}-{}.csv", output_directory(rpc_index), para.relay_chain, para.para_id)
 }
+
+fn lock_file_path(output_file_path: &str) -> String {
+	format!("{}.lock", output_file_path)
+}
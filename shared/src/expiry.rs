@@ -0,0 +1,153 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Notifies a webhook about the subscriptions that are about to expire.
+//!
+//! Each warning is sent only once per subscription period: the sent warnings are recorded along
+//! with the expiry they refer to, so a new warning is only sent once the subscription is renewed.
+
+use crate::{
+	config::{config, payment_exempt},
+	current_timestamp,
+	registry::registered_paras,
+	LOG_TARGET,
+};
+use serde::{Deserialize, Serialize};
+use std::{future::Future, time::Duration};
+use types::{ParaId, Parachain, RelayChain, Timestamp};
+
+/// The interval in seconds at which the expiry of the subscriptions is checked.
+pub const EXPIRY_CHECK_INTERVAL: u64 = 300;
+
+/// The payload sent to the webhook for a subscription which is about to expire.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ExpiryWarning {
+	/// The relay chain of the parachain.
+	pub relay_chain: RelayChain,
+	/// The id of the parachain.
+	pub para_id: ParaId,
+	/// The timestamp at which the subscription expires.
+	pub expiry_timestamp: Timestamp,
+}
+
+impl From<&Parachain> for ExpiryWarning {
+	fn from(para: &Parachain) -> Self {
+		Self {
+			relay_chain: para.relay_chain.clone(),
+			para_id: para.para_id,
+			expiry_timestamp: para.expiry_timestamp,
+		}
+	}
+}
+
+/// Returns the warnings that should be sent for the subscriptions expiring within `threshold`
+/// seconds from `now`, excluding the ones that were already sent.
+pub fn pending_expiry_warnings(
+	paras: &[Parachain],
+	now: Timestamp,
+	threshold: Timestamp,
+	sent: &[ExpiryWarning],
+) -> Vec<ExpiryWarning> {
+	paras
+		.iter()
+		.filter(|para| {
+			para.expiry_timestamp >= now && para.expiry_timestamp <= now.saturating_add(threshold)
+		})
+		.map(ExpiryWarning::from)
+		.filter(|warning| !sent.contains(warning))
+		.collect()
+}
+
+/// Returns the warnings which were already sent to the webhook.
+pub fn sent_expiry_warnings() -> Vec<ExpiryWarning> {
+	std::fs::read_to_string(expiry_warnings_path())
+		.ok()
+		.and_then(|content| serde_json::from_str(&content).ok())
+		.unwrap_or_default()
+}
+
+pub fn write_sent_expiry_warnings(warnings: &[ExpiryWarning]) -> Result<(), String> {
+	let content = serde_json::to_string(warnings).map_err(|err| err.to_string())?;
+	std::fs::write(expiry_warnings_path(), content).map_err(|err| err.to_string())
+}
+
+/// The file containing the warnings which were already sent.
+pub fn expiry_warnings_path() -> String {
+	let output_dir = config().output_directory.trim_end_matches('/').to_string();
+	format!("{}/expiry-warnings.json", output_dir)
+}
+
+/// Sends the pending expiry warnings to the webhook.
+pub async fn notify_expiring_subscriptions(client: &reqwest::Client, url: &str) {
+	let config = config();
+	// Without payments the subscriptions never expire.
+	if config.payment_info.is_none() {
+		return
+	}
+
+	// The subscriptions of the parachains exempt from payment never expire.
+	let paras: Vec<Parachain> =
+		registered_paras().into_iter().filter(|para| !payment_exempt(para)).collect();
+	let mut sent = sent_expiry_warnings();
+	// Forget about the warnings of the subscriptions that were renewed or unregistered.
+	sent.retain(|warning| paras.iter().any(|para| ExpiryWarning::from(para) == *warning));
+
+	let pending = pending_expiry_warnings(
+		&paras,
+		current_timestamp(),
+		config.expiry_warning_threshold,
+		&sent,
+	);
+
+	for warning in pending {
+		match client.post(url).json(&warning).send().await.and_then(|r| r.error_for_status()) {
+			Ok(_) => sent.push(warning),
+			Err(err) => log::warn!(
+				target: LOG_TARGET,
+				"{}-{} - Failed to send the expiry warning: {:?}",
+				warning.relay_chain,
+				warning.para_id,
+				err
+			),
+		}
+	}
+
+	if let Err(err) = write_sent_expiry_warnings(&sent) {
+		log::error!(target: LOG_TARGET, "Failed to store the sent expiry warnings: {:?}", err);
+	}
+}
+
+/// Starts the background task which periodically notifies the webhook about the subscriptions
+/// that are about to expire.
+///
+/// Does nothing if no `expiry_webhook_url` is configured. Must be called within a tokio runtime.
+/// The task stops once `shutdown` resolves.
+pub fn spawn_expiry_notifier(shutdown: impl Future<Output = ()> + Send + 'static) {
+	let Some(url) = config().expiry_webhook_url else { return };
+
+	tokio::spawn(async move {
+		let client = reqwest::Client::new();
+		let mut interval = tokio::time::interval(Duration::from_secs(EXPIRY_CHECK_INTERVAL));
+		let mut shutdown = std::pin::pin!(shutdown);
+		loop {
+			// A round of notifications is always completed, so that the sent warnings are
+			// recorded.
+			tokio::select! {
+				_ = &mut shutdown => break,
+				_ = interval.tick() => notify_expiring_subscriptions(&client, &url).await,
+			}
+		}
+	});
+}
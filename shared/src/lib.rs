@@ -13,20 +13,31 @@
 // You should have received a copy of the GNU General Public License
 // along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
 
+#[cfg(feature = "test-utils")]
+use std::sync::RwLock;
 use std::{
 	process::Command,
 	time::{SystemTime, UNIX_EPOCH},
 };
 use types::Timestamp;
 
+pub mod alert;
 pub mod chaindata;
 pub mod config;
 pub mod consumption;
+pub mod expiry;
+pub mod logger;
+pub mod migration;
+pub mod order;
 pub mod payment;
 pub mod registry;
+pub mod retention;
+pub mod storage;
+pub mod subscription;
+pub mod tracker;
 
 #[cfg(feature = "test-utils")]
-use crate::config::output_directory;
+use crate::config::{latest_directory, output_directory};
 
 const LOG_TARGET: &str = "shared";
 
@@ -36,13 +47,29 @@ pub fn round_to(number: f32, decimals: i32) -> f32 {
 	(number * factor).round() / factor
 }
 
+/// Overrides the time returned by `current_timestamp`, so that tests can control the clock.
+#[cfg(feature = "test-utils")]
+static MOCK_TIMESTAMP: RwLock<Option<Timestamp>> = RwLock::new(None);
+
 /// Returns the current time since UNIX EPOCH.
 pub fn current_timestamp() -> Timestamp {
+	#[cfg(feature = "test-utils")]
+	if let Some(now) = *MOCK_TIMESTAMP.read().unwrap_or_else(|poisoned| poisoned.into_inner()) {
+		return now;
+	}
+
 	// It is fine to use `unwrap_or_default` since the current time will never be before the UNIX
 	// EPOCH.
 	SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
 }
 
+/// Fixes the time returned by `current_timestamp` to `now`, or restores the actual clock if `now`
+/// is `None`.
+#[cfg(feature = "test-utils")]
+pub fn set_mock_timestamp(now: Option<Timestamp>) {
+	*MOCK_TIMESTAMP.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = now;
+}
+
 pub fn init_tracker() {
 	let output = Command::new("./scripts/init.sh").output().expect("Failed to execute command");
 
@@ -57,8 +84,11 @@ pub fn init_tracker() {
 // There isn't a good reason to use this other than for testing.
 #[cfg(feature = "test-utils")]
 pub fn reset_mock_environment() {
+	// Restore the actual clock:
+	set_mock_timestamp(None);
+
 	// Reset the registered paras file:
-	let _registry = registry::init_registry();
+	registry::init_registry();
 
 	let output_path = output_directory(None);
 	// Remove the output files:
@@ -71,4 +101,28 @@ pub fn reset_mock_environment() {
 			std::fs::remove_file(path).expect("Failed to remove consumption data")
 		}
 	}
+
+	// Remove the latest consumption of each parachain:
+	let _ = std::fs::remove_dir_all(latest_directory());
+
+	payment::reset_payment_state();
+
+	// Remove the linked migrations:
+	let _ = std::fs::remove_file(migration::migrations_path());
+
+	// Remove the placed orders:
+	let _ = std::fs::remove_file(order::orders_path());
+
+	// Remove the consumed payments:
+	let _ = std::fs::remove_file(payment::consumed_payments_path());
+
+	// Remove the sent expiry warnings:
+	let _ = std::fs::remove_file(expiry::expiry_warnings_path());
+
+	// Remove the state of the trackers:
+	(0..config::config().outputs).for_each(|rpc_index| {
+		let _ = std::fs::remove_file(tracker::tracker_state_path(rpc_index));
+		let _ = std::fs::remove_file(tracker::tracker_errors_path(rpc_index));
+		let _ = std::fs::remove_file(tracker::tracker_metrics_path(rpc_index));
+	});
 }
@@ -0,0 +1,70 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Initializes the logger of the tracker and the processor.
+//!
+//! Logs are written as text by default. When `log_format` is set to `json`, each line is a JSON
+//! object instead, so that the logs can be ingested by log aggregators. Messages prefixed with
+//! the parachain they concern, e.g. `Polkadot-2000 - Processing consumption.`, are split into
+//! separate `relay_chain`, `para_id` and `message` keys.
+
+use crate::config::{log_format, LogFormat};
+use serde_json::{json, Value};
+use std::io::Write;
+
+/// Initializes the logger in the configured format.
+///
+/// The log level is controlled through `RUST_LOG` regardless of the format.
+pub fn init_logger() {
+	let mut builder = env_logger::Builder::from_default_env();
+	if log_format() == LogFormat::Json {
+		builder.format(|buf, record| {
+			let mut line =
+				json_log_line(record.level(), record.target(), &record.args().to_string());
+			line["timestamp"] = buf.timestamp().to_string().into();
+			writeln!(buf, "{}", line)
+		});
+	}
+	builder.init();
+}
+
+/// Builds the JSON object logged for a message.
+pub fn json_log_line(level: log::Level, target: &str, message: &str) -> Value {
+	match split_para_prefix(message) {
+		Some((relay_chain, para_id, message)) => json!({
+			"level": level.as_str(),
+			"target": target,
+			"relay_chain": relay_chain,
+			"para_id": para_id,
+			"message": message,
+		}),
+		None => json!({
+			"level": level.as_str(),
+			"target": target,
+			"message": message,
+		}),
+	}
+}
+
+/// Splits the `<relay chain>-<para id> - ` prefix off a message.
+fn split_para_prefix(message: &str) -> Option<(&str, u32, &str)> {
+	let (prefix, message) = message.split_once(" - ")?;
+	let (relay_chain, para_id) = prefix.split_once('-')?;
+	if relay_chain.is_empty() || !relay_chain.chars().all(|c| c.is_ascii_alphabetic()) {
+		return None
+	}
+
+	Some((relay_chain, para_id.parse().ok()?, message))
+}
@@ -0,0 +1,74 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Linkage between the identities of parachains that migrated to a different relay chain.
+//!
+//! A migrated parachain gets a new `(relay, para_id)` identity, and its consumption recorded
+//! under the previous identity would otherwise be split from its new history.
+
+use crate::config::config;
+use serde::{Deserialize, Serialize};
+use types::{ParaId, RelayChain};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ParaMigration {
+	/// The identity of the parachain before the migration.
+	pub from: (RelayChain, ParaId),
+	/// The identity of the parachain after the migration.
+	pub to: (RelayChain, ParaId),
+}
+
+/// Returns all the linked migrations.
+pub fn migrations() -> Vec<ParaMigration> {
+	std::fs::read_to_string(migrations_path())
+		.ok()
+		.and_then(|content| serde_json::from_str(&content).ok())
+		.unwrap_or_default()
+}
+
+pub fn link_migration(migration: ParaMigration) -> Result<(), String> {
+	let mut migrations = migrations();
+	migrations.push(migration);
+
+	let content =
+		serde_json::to_string(&migrations).map_err(|_| "Failed to serialize migrations")?;
+	std::fs::write(migrations_path(), content).map_err(|_| "Failed to write migrations".to_string())
+}
+
+/// Returns the previous identities of a parachain, starting from the oldest one.
+pub fn previous_identities(relay_chain: RelayChain, para_id: ParaId) -> Vec<(RelayChain, ParaId)> {
+	let migrations = migrations();
+
+	let mut identities = vec![];
+	let mut current = (relay_chain, para_id);
+	while let Some(migration) = migrations.iter().find(|m| m.to == current) {
+		// Guard against cycles, which should never be linked in the first place.
+		if identities.contains(&migration.from) {
+			break;
+		}
+
+		identities.push(migration.from.clone());
+		current = migration.from.clone();
+	}
+
+	identities.reverse();
+	identities
+}
+
+/// The file containing all the linked migrations.
+pub fn migrations_path() -> String {
+	let output_dir = config().output_directory.trim_end_matches('/').to_string();
+	format!("{}/migrations.json", output_dir)
+}
@@ -0,0 +1,66 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Orders placed by trusted partners.
+//!
+//! An order registers the parachain straight away, while the payment is settled later within the
+//! settlement window of the partner. Parachains whose orders aren't settled in time are
+//! unregistered.
+
+use crate::config::config;
+use serde::{Deserialize, Serialize};
+use types::{ParaId, RelayChain, Timestamp};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum OrderState {
+	/// The parachain is registered and the payment is awaiting settlement.
+	Pending,
+	/// The payment was settled.
+	Settled,
+	/// The payment wasn't settled in time and the parachain got unregistered.
+	Expired,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Order {
+	/// The parachain registered through the order.
+	pub para: (RelayChain, ParaId),
+	/// The name of the partner that placed the order.
+	pub partner: String,
+	/// The timestamp at which the order was created by the partner.
+	pub created_at: Timestamp,
+	/// The timestamp by which the payment needs to be settled.
+	pub settlement_deadline: Timestamp,
+	pub state: OrderState,
+}
+
+/// Returns all the placed orders.
+pub fn orders() -> Vec<Order> {
+	std::fs::read_to_string(orders_path())
+		.ok()
+		.and_then(|content| serde_json::from_str(&content).ok())
+		.unwrap_or_default()
+}
+
+pub fn write_orders(orders: &[Order]) -> Result<(), String> {
+	let content = serde_json::to_string(orders).map_err(|_| "Failed to serialize orders")?;
+	std::fs::write(orders_path(), content).map_err(|_| "Failed to write orders".to_string())
+}
+
+/// The file containing all the placed orders.
+pub fn orders_path() -> String {
+	let output_dir = config().output_directory.trim_end_matches('/').to_string();
+	format!("{}/orders.json", output_dir)
+}
@@ -16,35 +16,180 @@
 //! File containing all the payment validation related logic.
 
 use crate::{
-	config::PaymentInfo,
+	config::{config, min_finalized_depth, relay_rpc_url, CallIndices, PaymentChain, PaymentInfo},
 	payment::polkadot::runtime_types::{
 		frame_system::pallet::Call as SystemCall, pallet_balances::pallet::Call as BalancesCall,
-		pallet_utility::pallet::Call as UtilityCall,
+		pallet_utility::pallet::Call as UtilityCall, polkadot_parachain::primitives::Id,
 	},
 	*,
 };
-use parity_scale_codec::Encode;
+use blake2::{digest::consts::U32, Blake2b, Digest};
+use jsonrpsee::{
+	client_transport::ws::{Url, WsTransportClientBuilder},
+	core::client::ClientBuilder,
+};
+use parity_scale_codec::{Compact, Decode, Encode};
 use polkadot_core_primitives::BlockNumber;
 use serde::{Deserialize, Serialize};
+use std::{
+	collections::BTreeMap,
+	ops::RangeInclusive,
+	sync::{
+		atomic::{AtomicU32, Ordering},
+		Arc, Mutex, RwLock,
+	},
+	time::{Duration, Instant},
+};
 use subxt::{
 	backend::rpc::{rpc_params, RpcClient},
-	blocks::Block,
-	utils::H256,
+	blocks::{Block, ExtrinsicDetails},
+	utils::{AccountId32, MultiAddress, MultiSignature, H256},
 	OnlineClient, PolkadotConfig,
 };
-use types::Parachain;
+use tokio::net::TcpStream;
+use tokio_rustls::{rustls, TlsConnector};
+use tokio_util::compat::TokioAsyncReadCompatExt;
+use types::{Balance, Parachain, PaymentDetails, RelayChain, Timestamp};
 
 #[subxt::subxt(runtime_metadata_path = "../artifacts/metadata.scale")]
 mod polkadot {}
 
+/// The number of consecutive payments that weren't found even though their block contained
+/// transfers to the receiver.
+static MISMATCHED_PAYMENTS: AtomicU32 = AtomicU32::new(0);
+
+/// The number of consecutive mismatched payments after which the static metadata is suspected to
+/// be outdated.
+///
+/// A runtime upgrade which changes the call indices would cause all payments to stop matching.
+const METADATA_MISMATCH_THRESHOLD: u32 = 3;
+
+/// The maximum number of threads used for validating the extrinsics of a single block.
+const MAX_VALIDATION_THREADS: usize = 8;
+
+/// The time in seconds for which a measured RPC latency above the configured maximum has an effect.
+///
+/// Since registrations are rejected while the RPC is degraded, no new measurements are taken
+/// during this time.
+pub const RPC_DEGRADATION_PERIOD: Timestamp = 60;
+
+/// The maximum number of blocks following the payment block which are searched for the payment.
+///
+/// This bounds the number of blocks fetched from the payment RPC for a single validation.
+pub const MAX_PAYMENT_SEARCH_WINDOW: BlockNumber = 10;
+
+/// The number of attempts made to reach the payment RPC before giving up.
+pub const RPC_ATTEMPTS: u32 = 3;
+
+/// The delay before retrying to reach the payment RPC, which is doubled after each attempt.
+const RPC_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// The number of seconds for which a fee estimate is cached.
+pub const FEE_ESTIMATE_TTL: Timestamp = 60;
+
+/// The cached fee estimates along with the timestamp of the estimation, keyed by the encoded
+/// payment call.
+static FEE_ESTIMATES: RwLock<BTreeMap<Vec<u8>, (Balance, Timestamp)>> =
+	RwLock::new(BTreeMap::new());
+
+/// The connections to the payment RPCs, keyed by their url.
+///
+/// These are reused across the payment validations, rather than connecting for every validation.
+static PAYMENT_CLIENTS: RwLock<BTreeMap<String, PaymentClients>> = RwLock::new(BTreeMap::new());
+
+type PaymentClients = (RpcClient, OnlineClient<PolkadotConfig>);
+
+/// Serializes the checking and noting of consumed payments, so that concurrent requests can't use
+/// the same payment.
+static CONSUMED_PAYMENTS_LOCK: Mutex<()> = Mutex::new(());
+
+/// The latest measured latency of the payment RPC.
+static RPC_LATENCY: RwLock<Option<RpcLatency>> = RwLock::new(None);
+
+/// A payment extrinsic which was already used for a registration or an extension.
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+pub struct ConsumedPayment {
+	/// The block in which the payment occurred.
+	pub block_number: BlockNumber,
+	/// The index of the payment extrinsic within the block.
+	pub extrinsic_index: u32,
+	/// The hash of the block in which the payment occurred, which tells apart the payments made
+	/// at the same block number of different payment chains.
+	///
+	/// `None` for payments consumed before the hashes were recorded.
+	#[serde(default)]
+	pub block_hash: Option<String>,
+}
+
+impl ConsumedPayment {
+	/// Returns whether both are the same payment extrinsic.
+	///
+	/// Payments without a block hash can't be told apart by their chain, so these are considered
+	/// the same as any payment at their block number and extrinsic index.
+	pub fn is_same(&self, other: &ConsumedPayment) -> bool {
+		self.block_number == other.block_number &&
+			self.extrinsic_index == other.extrinsic_index &&
+			match (&self.block_hash, &other.block_hash) {
+				(Some(hash), Some(other_hash)) => hash == other_hash,
+				_ => true,
+			}
+	}
+}
+
+impl From<&PaymentDetails> for ConsumedPayment {
+	fn from(payment: &PaymentDetails) -> Self {
+		ConsumedPayment {
+			block_number: payment.block_number,
+			extrinsic_index: payment.extrinsic_index,
+			block_hash: payment.block_hash.clone(),
+		}
+	}
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Eq, PartialEq)]
+pub struct RpcLatency {
+	/// The smoothed latency in milliseconds.
+	pub millis: u64,
+	/// The timestamp of the latest measurement.
+	pub measured_at: Timestamp,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
 pub enum PaymentError {
 	/// Failed to validate they payment.
 	ValidationFailed,
-	/// The payment is not part of a finalized block.
+	/// The payment is not part of a finalized block, or the block isn't yet as deep as required
+	/// for the relay chain of the parachain.
 	Unfinalized,
 	/// The payment was not found in the specified block.
 	NotFound,
+	/// The payment is older than the maximum accepted payment age.
+	TooOld,
+	/// A micro-payment from the same or a later block was already credited.
+	AlreadyCredited,
+	/// The payment extrinsic was already used for a registration or an extension.
+	AlreadyUsed,
+	/// The payment block isn't part of the finalized chain, e.g. because it was on a fork that
+	/// got pruned.
+	NotCanonical,
+	/// Payments are configured per relay chain, but not for the relay chain of the parachain.
+	NotConfigured,
+	/// The payment block is finalized, but not yet `min_confirmations` blocks deep.
+	InsufficientConfirmations,
+	/// The payment RPC couldn't be reached, even after retrying, so the payment may still be
+	/// valid.
+	Unreachable,
+	/// The payment was made by an account which isn't authorized to pay for the parachain.
+	UnauthorizedPayer,
+	/// The para id doesn't fit the index of a derivative account, so the parachain can't pay to a
+	/// derived sub-account.
+	UnderivableReceiver,
+	/// The manager of the parachain can't be resolved, because no rpc url is configured for its
+	/// relay chain, or the relay chain has no `registrar` pallet.
+	ManagerUnresolvable,
+	/// The subscription can't be extended by the requested duration, since its expiry would
+	/// overflow.
+	DurationOverflow,
 }
 
 impl From<String> for PaymentError {
@@ -53,81 +198,1138 @@ impl From<String> for PaymentError {
 			"ValidationFailed" => Self::ValidationFailed,
 			"Unfinalized" => Self::Unfinalized,
 			"NotFound" => Self::NotFound,
+			"TooOld" => Self::TooOld,
+			"AlreadyCredited" => Self::AlreadyCredited,
+			"AlreadyUsed" => Self::AlreadyUsed,
+			"NotCanonical" => Self::NotCanonical,
+			"NotConfigured" => Self::NotConfigured,
+			"InsufficientConfirmations" => Self::InsufficientConfirmations,
+			"Unreachable" => Self::Unreachable,
+			"UnauthorizedPayer" => Self::UnauthorizedPayer,
+			"UnderivableReceiver" => Self::UnderivableReceiver,
+			"ManagerUnresolvable" => Self::ManagerUnresolvable,
+			"DurationOverflow" => Self::DurationOverflow,
 			_ => panic!("UnknownError"),
 		}
 	}
 }
 
+/// Validates the registration payment of the parachain, and notes it as consumed.
+///
+/// The payment is searched for in the payment block, followed by up to `search_window` blocks,
+/// which is capped at `MAX_PAYMENT_SEARCH_WINDOW`.
 pub async fn validate_registration_payment(
 	para: Parachain,
 	payment_info: PaymentInfo,
 	payment_block_number: BlockNumber,
-) -> Result<(), PaymentError> {
-	// TODO: Could this code be improved so that we don't have to instantiate both clients?
-	let rpc_client = RpcClient::from_url(&payment_info.rpc_url.clone())
-		.await
-		.map_err(|_| PaymentError::ValidationFailed)?;
+	search_window: BlockNumber,
+) -> Result<PaymentDetails, PaymentError> {
+	let payment =
+		check_registration_payment(para, payment_info, payment_block_number, search_window).await?;
+	note_consumed_payment(&payment)?;
 
-	let online_client = OnlineClient::<PolkadotConfig>::from_url(payment_info.rpc_url.clone())
-		.await
-		.map_err(|_| PaymentError::ValidationFailed)?;
+	Ok(payment)
+}
+
+/// Validates the registration payment of the parachain without noting it as consumed, so that
+/// it can still be used afterwards.
+pub async fn check_registration_payment(
+	para: Parachain,
+	payment_info: PaymentInfo,
+	payment_block_number: BlockNumber,
+	search_window: BlockNumber,
+) -> Result<PaymentDetails, PaymentError> {
+	let payment_info = payment_chain_info(para_payment_info(&para, &payment_info)?).await?;
+
+	let start = Instant::now();
+	let result = validate_payment(para, payment_info, payment_block_number, search_window).await;
+	note_rpc_latency(start.elapsed().as_millis() as u64);
+
+	result
+}
+
+/// Returns the payment configuration which applies to the relay chain and the subscription tier
+/// of the parachain.
+///
+/// The tier of the parachain overrides the cost. The resolved configuration applies regardless of
+/// the tier, so resolving it again doesn't change it.
+pub fn para_payment_info(
+	para: &Parachain,
+	payment_info: &PaymentInfo,
+) -> Result<PaymentInfo, PaymentError> {
+	let mut payment_info = payment_info
+		.for_relay_chain(&para.relay_chain)
+		.ok_or(PaymentError::NotConfigured)?;
+	if let Some(tier) = para.tier.as_ref().and_then(|tier| payment_info.tier(tier)) {
+		payment_info.cost = tier.cost;
+	}
+	payment_info.tiers = vec![];
+
+	Ok(payment_info)
+}
+
+/// Returns the payment extrinsics which were already used for a registration or an extension.
+///
+/// Fails if the file of the consumed payments exists but can't be read or parsed, since treating
+/// it as empty would allow all the payments to be reused.
+pub fn consumed_payments() -> Result<Vec<ConsumedPayment>, PaymentError> {
+	let path = consumed_payments_path();
+	let content = match std::fs::read_to_string(&path) {
+		Ok(content) => content,
+		Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+		Err(err) => {
+			log::error!(target: LOG_TARGET, "Failed to read the consumed payments: {:?}", err);
+			return Err(PaymentError::ValidationFailed);
+		},
+	};
+
+	serde_json::from_str(&content).map_err(|err| {
+		log::error!(target: LOG_TARGET, "Failed to parse the consumed payments: {:?}", err);
+		PaymentError::ValidationFailed
+	})
+}
+
+/// Returns the payment extrinsics which can't be used anymore.
+///
+/// Besides the noted payments, the payments of the registered parachains are considered used,
+/// since these might have been registered before the payments were noted.
+fn used_payments() -> Result<Vec<ConsumedPayment>, PaymentError> {
+	let mut used = consumed_payments()?;
+	used.extend(
+		registry::registered_paras()
+			.into_iter()
+			.filter_map(|para| para.payment.as_ref().map(ConsumedPayment::from)),
+	);
+
+	Ok(used)
+}
+
+/// Notes that the payment was used, so that it can't be used again.
+pub fn note_consumed_payment(payment: &PaymentDetails) -> Result<(), PaymentError> {
+	note_consumed_payments(std::slice::from_ref(payment))
+}
+
+/// Notes that all the payments were used, or none of them if any of them was already used.
+pub fn note_consumed_payments(payments: &[PaymentDetails]) -> Result<(), PaymentError> {
+	let _lock = CONSUMED_PAYMENTS_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+	let used = used_payments()?;
+	let payments: Vec<ConsumedPayment> = payments.iter().map(ConsumedPayment::from).collect();
+	if payments.iter().any(|payment| used.iter().any(|used| used.is_same(payment))) {
+		return Err(PaymentError::AlreadyUsed);
+	}
+
+	let mut consumed = consumed_payments()?;
+	consumed.extend(payments);
+
+	let content = serde_json::to_string(&consumed).map_err(|_| PaymentError::ValidationFailed)?;
+	// A partially written file would lose all the consumed payments.
+	storage::write_atomically(&consumed_payments_path(), content.as_bytes()).map_err(|err| {
+		log::error!(target: LOG_TARGET, "Failed to note the consumed payments: {}", err);
+		PaymentError::ValidationFailed
+	})
+}
+
+/// The file containing all the consumed payments.
+pub fn consumed_payments_path() -> String {
+	let output_dir = config().output_directory.trim_end_matches('/').to_string();
+	format!("{}/consumed-payments.json", output_dir)
+}
+
+/// Notes a newly measured latency of the payment RPC.
+pub fn note_rpc_latency(millis: u64) {
+	let mut latency = RPC_LATENCY.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+	// Smooth out the measurements so that a single slow request doesn't cause registrations to be
+	// rejected.
+	let millis = latency.map_or(millis, |latency| (latency.millis * 3 + millis) / 4);
+	*latency = Some(RpcLatency { millis, measured_at: current_timestamp() });
+}
+
+/// Returns the latest measured latency of the payment RPC.
+pub fn rpc_latency() -> Option<RpcLatency> {
+	*RPC_LATENCY.read().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Returns whether the payment RPC was recently measured to be slower than the configured maximum.
+pub fn rpc_degraded() -> bool {
+	let Some(max_rpc_latency) = config().max_rpc_latency else { return false };
+
+	rpc_latency().map_or(false, |latency| {
+		latency.millis > max_rpc_latency &&
+			current_timestamp().saturating_sub(latency.measured_at) < RPC_DEGRADATION_PERIOD
+	})
+}
+
+#[cfg(feature = "test-utils")]
+pub(crate) fn reset_payment_state() {
+	*RPC_LATENCY.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+	FEE_ESTIMATES.write().unwrap_or_else(|poisoned| poisoned.into_inner()).clear();
+	PAYMENT_CLIENTS.write().unwrap_or_else(|poisoned| poisoned.into_inner()).clear();
+	MISMATCHED_PAYMENTS.store(0, Ordering::Relaxed);
+}
+
+/// Validates the payment of a batch of parachains of the same relay chain, which are registered
+/// together, and notes it as consumed.
+///
+/// The batch is paid for with a single `utility.batchAll` of a transfer of the cost of all the
+/// parachains to the receiver and a remark listing them, see `batch_payment_call`.
+pub async fn validate_batch_payment(
+	paras: &[Parachain],
+	payment_info: PaymentInfo,
+	payment_block_number: BlockNumber,
+	search_window: BlockNumber,
+) -> Result<PaymentDetails, PaymentError> {
+	let para = paras.first().ok_or(PaymentError::ValidationFailed)?;
+	let payment_info = payment_chain_info(para_payment_info(para, &payment_info)?).await?;
+	let payments = accepted_batch_payment_calls(paras, &payment_info)?;
+
+	let start = Instant::now();
+	let result = match authorized_payers(paras, &payment_info).await {
+		Ok(payers) =>
+			find_payment(
+				para,
+				&payment_info,
+				&payments,
+				&payment_info.receiver,
+				payers.as_deref(),
+				payment_block_number,
+				search_window,
+			)
+			.await,
+		Err(err) => Err(err),
+	};
+	note_rpc_latency(start.elapsed().as_millis() as u64);
+
+	let payment = result?;
+	note_consumed_payment(&payment)?;
+
+	Ok(payment)
+}
+
+/// Returns the encoded call with which a batch of parachains of the same relay chain should be
+/// paid for.
+///
+/// This is a `utility.batchAll` of a transfer of the cost of all the parachains to the receiver,
+/// and a remark listing them, e.g. `regionx-weigher::Polkadot:2000,2004`, namespaced the same way
+/// as the regular payment remarks. The payment is never
+/// sent to a derived sub-account, since these are specific to a single parachain.
+pub fn batch_payment_call(
+	paras: &[Parachain],
+	payment_info: &PaymentInfo,
+) -> Result<Vec<u8>, PaymentError> {
+	accepted_batch_payment_calls(paras, payment_info)?
+		.into_iter()
+		.next()
+		.ok_or(PaymentError::ValidationFailed)
+}
+
+/// Returns the encoded calls which are accepted as the payment of a batch of parachains, which
+/// only differ in their transfer, see `accepted_transfer_calls`.
+///
+/// The first call is the one that is returned by `batch_payment_call`.
+fn accepted_batch_payment_calls(
+	paras: &[Parachain],
+	payment_info: &PaymentInfo,
+) -> Result<Vec<Vec<u8>>, PaymentError> {
+	let para = paras.first().ok_or(PaymentError::ValidationFailed)?;
+	let cost = payment_info
+		.cost
+		.parse::<Balance>()
+		.ok()
+		.and_then(|cost| cost.checked_mul(paras.len() as Balance))
+		.ok_or(PaymentError::ValidationFailed)?;
+
+	let para_ids: Vec<String> = paras.iter().map(|para| para.para_id.to_string()).collect();
+	let remark =
+		format!("{}{}:{}", remark_namespace(payment_info), para.relay_chain, para_ids.join(","))
+			.as_bytes()
+			.to_vec();
+	let indices = call_indices(payment_info);
+	let remark = remark_call(&indices, remark);
+
+	Ok(accepted_transfer_calls(payment_info, payment_info.receiver.clone(), cost)
+		.into_iter()
+		.map(|transfer| batch_all(&indices, &[transfer, remark.clone()]))
+		.collect())
+}
+
+async fn validate_payment(
+	para: Parachain,
+	payment_info: PaymentInfo,
+	payment_block_number: BlockNumber,
+	search_window: BlockNumber,
+) -> Result<PaymentDetails, PaymentError> {
+	let receiver = payment_receiver(&para, &payment_info)?;
+	let payments = accepted_payment_calls(para.clone(), payment_info.clone())?;
+	let payers = authorized_payers(std::slice::from_ref(&para), &payment_info).await?;
+
+	find_payment(
+		&para,
+		&payment_info,
+		&payments,
+		&receiver,
+		payers.as_deref(),
+		payment_block_number,
+		search_window,
+	)
+	.await
+}
+
+/// Searches for one of the `payments` in the payment block, or in one of the `search_window`
+/// blocks following it.
+///
+/// If `payers` is set, only the payments made by one of these accounts are accepted.
+async fn find_payment(
+	para: &Parachain,
+	payment_info: &PaymentInfo,
+	payments: &[Vec<u8>],
+	receiver: &AccountId32,
+	payers: Option<&[AccountId32]>,
+	payment_block_number: BlockNumber,
+	search_window: BlockNumber,
+) -> Result<PaymentDetails, PaymentError> {
+	let native_payment = payment_info.asset_id.is_none();
+	for block_number in payment_search_range(payment_block_number, search_window) {
+		let block = match get_payment_block(para, payment_info, block_number).await {
+			Ok(block) => block,
+			// The following blocks aren't finalized or confirmed either.
+			Err(PaymentError::Unfinalized | PaymentError::InsufficientConfirmations)
+				if block_number > payment_block_number =>
+				break,
+			Err(err) => return Err(err),
+		};
+
+		match ensure_contains_payment(payments, receiver, payers, native_payment, block).await {
+			Err(PaymentError::NotFound) => continue,
+			result => return result,
+		}
+	}
 
-	// Ensure that the `payment_block_number` is from a finalized block.
+	Err(PaymentError::NotFound)
+}
+
+/// Returns the blocks which are searched for a payment made in the payment block, or in one of
+/// the `search_window` blocks following it.
+pub fn payment_search_range(
+	payment_block_number: BlockNumber,
+	search_window: BlockNumber,
+) -> RangeInclusive<BlockNumber> {
+	let search_window = search_window.min(MAX_PAYMENT_SEARCH_WINDOW);
+	payment_block_number..=payment_block_number.saturating_add(search_window)
+}
+
+/// Returns the total amount of the micro-payments made for the parachain in the specified block,
+/// and notes them as consumed.
+///
+/// A micro-payment is made the same way as a regular payment, except that any amount can be
+/// transferred to the receiver. Since a regular payment is a valid micro-payment as well, the
+/// payments which were already used, e.g. for a registration, aren't credited again.
+pub async fn validate_micro_payment(
+	para: Parachain,
+	payment_info: PaymentInfo,
+	payment_block_number: BlockNumber,
+) -> Result<Balance, PaymentError> {
+	let payment_info = payment_chain_info(para_payment_info(&para, &payment_info)?).await?;
+	let block = get_payment_block(&para, &payment_info, payment_block_number).await?;
+
+	// The transfers only differ in the transferred amount, which is encoded last.
+	let transfer_prefixes: Vec<Vec<u8>> =
+		accepted_transfer_calls(&payment_info, payment_receiver(&para, &payment_info)?, 0)
+			.into_iter()
+			.map(|mut transfer| {
+				transfer.pop();
+				transfer
+			})
+			.collect();
+	let batch_prefixes: Vec<Vec<u8>> = transfer_prefixes
+		.iter()
+		.map(|transfer_prefix| {
+			let mut batch_prefix = call_indices(&payment_info).batch_all.to_vec();
+			Compact(2u32).encode_to(&mut batch_prefix);
+			batch_prefix.extend(transfer_prefix);
+			batch_prefix
+		})
+		.collect();
+
+	let expected_remark = payment_remark(&para, &payment_info);
+
+	let extrinsics = block.extrinsics().await.map_err(|_| PaymentError::ValidationFailed)?;
+	let micro_payments: Vec<(u32, Balance)> = extrinsics
+		.iter()
+		.filter_map(Result::ok)
+		.filter_map(|ext| {
+			let call = ext.call_bytes();
+			// When paying to a derived sub-account the remark is optional.
+			if payment_info.derive_receiver {
+				let transfer =
+					transfer_prefixes.iter().find_map(|prefix| {
+						match transferred_amount(call, prefix) {
+							Some((value, [])) => Some(value),
+							_ => None,
+						}
+					});
+				if let Some(value) = transfer {
+					return Some((ext.index(), value));
+				}
+			}
+
+			let (value, remark) =
+				batch_prefixes.iter().find_map(|prefix| transferred_amount(call, prefix))?;
+			(remark == expected_remark.as_slice()).then_some((ext.index(), value))
+		})
+		.collect();
+
+	let block_hash = format!("{:?}", block.hash());
+	let used = used_payments()?;
+	let (payments, amounts): (Vec<PaymentDetails>, Vec<Balance>) = micro_payments
+		.iter()
+		.map(|(extrinsic_index, value)| {
+			let payment = PaymentDetails {
+				block_number: payment_block_number,
+				block_hash: Some(block_hash.clone()),
+				extrinsic_index: *extrinsic_index,
+				payer: None,
+			};
+			(payment, *value)
+		})
+		.filter(|(payment, _)| {
+			let payment = ConsumedPayment::from(payment);
+			!used.iter().any(|used| used.is_same(&payment))
+		})
+		.unzip();
+
+	let amount = amounts
+		.into_iter()
+		.fold(0 as Balance, |total, value| total.saturating_add(value));
+	if amount == 0 {
+		return Err(if micro_payments.is_empty() {
+			PaymentError::NotFound
+		} else {
+			PaymentError::AlreadyUsed
+		});
+	}
+
+	note_consumed_payments(&payments)?;
+
+	Ok(amount)
+}
+
+/// Decodes the amount of the transfer with the specified encoded prefix, returning it along with
+/// the remaining bytes of the call.
+fn transferred_amount<'a>(call: &'a [u8], prefix: &[u8]) -> Option<(Balance, &'a [u8])> {
+	let mut remaining = call.strip_prefix(prefix)?;
+	let value = Compact::<Balance>::decode(&mut remaining).ok()?;
+
+	Some((value.0, remaining))
+}
+
+async fn get_payment_block(
+	para: &Parachain,
+	payment_info: &PaymentInfo,
+	payment_block_number: BlockNumber,
+) -> Result<Block<PolkadotConfig, OnlineClient<PolkadotConfig>>, PaymentError> {
+	with_payment_clients(payment_info, |clients| {
+		fetch_payment_block(clients, para, payment_info, payment_block_number)
+	})
+	.await
+}
+
+async fn fetch_payment_block(
+	(rpc_client, online_client): PaymentClients,
+	para: &Parachain,
+	payment_info: &PaymentInfo,
+	payment_block_number: BlockNumber,
+) -> Result<Block<PolkadotConfig, OnlineClient<PolkadotConfig>>, PaymentError> {
 	let last_finalized =
 		get_last_finalized_block(rpc_client.clone(), online_client.clone()).await?;
-	if payment_block_number > last_finalized {
-		return Err(PaymentError::Unfinalized);
+	ensure_valid_payment_block(
+		payment_block_number,
+		last_finalized,
+		min_finalized_depth(&para.relay_chain),
+		payment_info.payment_max_age,
+	)?;
+	ensure_confirmed(payment_block_number, last_finalized, payment_info.min_confirmations)?;
+
+	let block_hash = get_block_hash(rpc_client.clone(), payment_block_number).await?;
+	// The chain might have reorganized since the finalized head was fetched, so the resolved block
+	// is only accepted if it is part of the chain finalized after it was resolved.
+	ensure_finalized_ancestor(rpc_client, online_client.clone(), payment_block_number, block_hash)
+		.await?;
+
+	get_block(online_client, block_hash).await
+}
+
+/// Ensures that the block is an ancestor of, or is itself, the current finalized head.
+async fn ensure_finalized_ancestor(
+	rpc_client: RpcClient,
+	online_client: OnlineClient<PolkadotConfig>,
+	block_number: BlockNumber,
+	block_hash: H256,
+) -> Result<(), PaymentError> {
+	let finalized_hash = get_finalized_head(rpc_client).await?;
+	let finalized = get_block(online_client.clone(), finalized_hash).await?;
+
+	if finalized.number() < block_number {
+		return Err(PaymentError::Unfinalized)
+	}
+	if finalized.number() == block_number {
+		return (finalized_hash == block_hash).then_some(()).ok_or(PaymentError::NotCanonical)
 	}
 
-	let block_hash = get_block_hash(rpc_client, payment_block_number).await?;
-	let block = get_block(online_client, block_hash).await?;
+	let query = polkadot::storage().system().block_hash(block_number);
+	let ancestor_hash = online_client
+		.storage()
+		.at(finalized_hash)
+		.fetch(&query)
+		.await
+		.map_err(rpc_error)?;
 
-	ensure_contains_payment(para, payment_info, block).await
+	match ancestor_hash {
+		Some(ancestor_hash) if ancestor_hash != H256::zero() =>
+			(ancestor_hash == block_hash).then_some(()).ok_or(PaymentError::NotCanonical),
+		// The system pallet only keeps the hashes of the recent blocks. Older blocks are finalized
+		// deep enough for their hash to be the canonical one.
+		_ => Ok(()),
+	}
 }
 
-async fn ensure_contains_payment(
+/// Ensures that the specified block contains a remark with the given content, made by the payer
+/// of the registration.
+///
+/// This is used as a proof of ownership of a registration, e.g. when updating it.
+pub async fn validate_ownership_proof(
 	para: Parachain,
 	payment_info: PaymentInfo,
-	block: Block<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+	proof_block_number: BlockNumber,
+	payer: String,
+	remark: Vec<u8>,
 ) -> Result<(), PaymentError> {
-	let payment = opaque_payment_extrinsic(para, payment_info).await?;
+	let payment_info = payment_chain_info(para_payment_info(&para, &payment_info)?).await?;
+	let block = get_payment_block(&para, &payment_info, proof_block_number).await?;
+
+	let expected_remark = remark_call(&call_indices(&payment_info), remark);
 
 	let extrinsics = block.extrinsics().await.map_err(|_| PaymentError::ValidationFailed)?;
-	let extrinsics: Vec<Vec<u8>> = extrinsics
+	let extrinsics: Arc<[PaymentCandidate]> = extrinsics
 		.iter()
-		.filter_map(|ext| {
-			ext.as_ref().ok().and_then(|e| e.as_root_extrinsic::<polkadot::Call>().ok())
+		.filter_map(Result::ok)
+		.map(|ext| PaymentCandidate::from(&ext))
+		.collect();
+
+	let position = find_match(extrinsics, config().parallel_validation_threshold, move |ext| {
+		let signed_by_payer = ext
+			.address
+			.as_ref()
+			.map_or(false, |address| format!("0x{}", hex::encode(address)) == payer);
+
+		signed_by_payer && ext.call == expected_remark
+	})
+	.await;
+
+	position.map(|_| ()).ok_or(PaymentError::NotFound)
+}
+
+/// Ensures that the payment block is finalized at least `min_depth` blocks deep and not older than
+/// the maximum payment age.
+pub fn ensure_valid_payment_block(
+	payment_block_number: BlockNumber,
+	last_finalized: BlockNumber,
+	min_depth: BlockNumber,
+	max_age: Option<BlockNumber>,
+) -> Result<(), PaymentError> {
+	if payment_block_number.saturating_add(min_depth) > last_finalized {
+		return Err(PaymentError::Unfinalized);
+	}
+
+	match max_age {
+		Some(max_age) if last_finalized - payment_block_number > max_age =>
+			Err(PaymentError::TooOld),
+		_ => Ok(()),
+	}
+}
+
+/// Ensures that the finalized payment block has at least `min_confirmations` blocks on top of it.
+pub fn ensure_confirmed(
+	payment_block_number: BlockNumber,
+	last_finalized: BlockNumber,
+	min_confirmations: BlockNumber,
+) -> Result<(), PaymentError> {
+	if last_finalized.saturating_sub(payment_block_number) < min_confirmations {
+		return Err(PaymentError::InsufficientConfirmations);
+	}
+
+	Ok(())
+}
+
+/// Re-fetches the encoded payment extrinsic from the payment chain.
+pub async fn payment_extrinsic(
+	para: &Parachain,
+	payment_info: &PaymentInfo,
+	payment: &PaymentDetails,
+) -> Result<Vec<u8>, PaymentError> {
+	let payment_info = para_payment_info(para, payment_info)?;
+	let block = with_payment_clients(&payment_info, |(rpc_client, online_client)| async move {
+		let block_hash = get_block_hash(rpc_client, payment.block_number).await?;
+		get_block(online_client, block_hash).await
+	})
+	.await?;
+
+	let extrinsics = block.extrinsics().await.map_err(|_| PaymentError::ValidationFailed)?;
+	extrinsics
+		.iter()
+		.filter_map(Result::ok)
+		.find(|ext| ext.index() == payment.extrinsic_index)
+		.map(|ext| ext.bytes().to_vec())
+		.ok_or(PaymentError::NotFound)
+}
+
+/// Returns the estimated fee of the payment extrinsic of the parachain.
+///
+/// The fee is queried through `payment_queryInfo` and cached for `FEE_ESTIMATE_TTL` seconds.
+/// `None` is returned if the payment node can't provide an estimate.
+pub async fn estimate_payment_fee(para: Parachain, payment_info: PaymentInfo) -> Option<Balance> {
+	let payment_info =
+		payment_chain_info(para_payment_info(&para, &payment_info).ok()?).await.ok()?;
+	let call_data = payment_call_data(para.clone(), payment_info.clone()).await.ok()?;
+	if let Some(fee) = cached_fee_estimate(&call_data) {
+		return Some(fee);
+	}
+
+	let fee = query_payment_fee(para, payment_info).await;
+	match fee {
+		Ok(fee) => {
+			note_fee_estimate(call_data, fee);
+			Some(fee)
+		},
+		Err(err) => {
+			log::warn!(
+				target: LOG_TARGET,
+				"Failed to estimate the payment fee: {}",
+				err
+			);
+			None
+		},
+	}
+}
+
+async fn query_payment_fee(para: Parachain, payment_info: PaymentInfo) -> Result<Balance, String> {
+	let rpc_url = payment_info.rpc_url.clone();
+	let (rpc_client, online_client) = payment_clients(&payment_info)
+		.await
+		.map_err(|_| "Failed to connect to the payment RPC")?;
+
+	let call = payment_call(para, payment_info).map_err(|e| format!("{:?}", e))?;
+	let payload = RawCall(call);
+
+	// The signature isn't checked when querying the fee, so any account and signature will do.
+	let extrinsic = online_client
+		.tx()
+		.create_partial_signed_with_nonce(&payload, 0, Default::default())
+		.map_err(|e| format!("Failed to construct the payment extrinsic: {:?}", e))?
+		.sign_with_address_and_signature(
+			&MultiAddress::Id(AccountId32([0; 32])),
+			&MultiSignature::Sr25519([0; 64]),
+		);
+
+	let params = rpc_params![format!("0x{}", hex::encode(extrinsic.encoded()))];
+	let info: serde_json::Value =
+		rpc_client.request("payment_queryInfo", params).await.map_err(|e| {
+			// The connection might have broken, so it gets re-established for the next query.
+			drop_payment_clients(&rpc_url);
+			format!("payment_queryInfo is unavailable: {:?}", e)
+		})?;
+
+	// Depending on the node version the fee is returned either as a number or as a string.
+	match &info["partialFee"] {
+		serde_json::Value::String(fee) => fee.parse().ok(),
+		serde_json::Value::Number(fee) => fee.as_u64().map(Balance::from),
+		_ => None,
+	}
+	.ok_or_else(|| format!("Unexpected payment_queryInfo response: {}", info))
+}
+
+/// Returns the cached fee estimate of the specified payment call.
+pub fn cached_fee_estimate(call_data: &[u8]) -> Option<Balance> {
+	let cache = FEE_ESTIMATES.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+	cache
+		.get(call_data)
+		.filter(|(_, estimated_at)| {
+			current_timestamp().saturating_sub(*estimated_at) < FEE_ESTIMATE_TTL
 		})
-		.map(|ext| ext.encode())
+		.map(|(fee, _)| *fee)
+}
+
+/// Caches the fee estimate of the specified payment call.
+pub fn note_fee_estimate(call_data: Vec<u8>, fee: Balance) {
+	let mut cache = FEE_ESTIMATES.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+	let now = current_timestamp();
+	cache.retain(|_, (_, estimated_at)| now.saturating_sub(*estimated_at) < FEE_ESTIMATE_TTL);
+	cache.insert(call_data, (fee, now));
+}
+
+/// Returns the encoded call that needs to be executed for the payment of the parachain.
+///
+/// This can be used by the frontend to construct the payment extrinsic.
+pub async fn payment_call_data(
+	para: Parachain,
+	payment_info: PaymentInfo,
+) -> Result<Vec<u8>, PaymentError> {
+	let payment_info = payment_chain_info(para_payment_info(&para, &payment_info)?).await?;
+	payment_call(para, payment_info)
+}
+
+/// Ensures that the block contains one of the `payments`, which are encoded with the call indices
+/// of the payment chain.
+async fn ensure_contains_payment(
+	payments: &[Vec<u8>],
+	receiver: &AccountId32,
+	payers: Option<&[AccountId32]>,
+	native_payment: bool,
+	block: Block<PolkadotConfig, OnlineClient<PolkadotConfig>>,
+) -> Result<PaymentDetails, PaymentError> {
+	let extrinsics = block.extrinsics().await.map_err(|_| PaymentError::ValidationFailed)?;
+	let extrinsics: Arc<[PaymentCandidate]> = extrinsics
+		.iter()
+		.filter_map(Result::ok)
+		.map(|ext| PaymentCandidate::from(&ext))
 		.collect();
 
-	if extrinsics.contains(&payment.encode()) {
-		Ok(())
+	let block_number = block.number();
+	let block_hash = format!("{:?}", block.hash());
+	let matcher = Arc::new(PaymentMatcher {
+		payments: payments.to_vec(),
+		payers: payers.map(<[AccountId32]>::to_vec),
+		used: used_payments()?,
+		block_number,
+		block_hash: block_hash.clone(),
+	});
+	let position = {
+		let matcher = matcher.clone();
+		find_match(extrinsics.clone(), config().parallel_validation_threshold, move |ext| {
+			matcher.is_unused_payment(ext) && matcher.is_authorized(ext)
+		})
+		.await
+	};
+
+	if position.is_none() && extrinsics.iter().any(|ext| matcher.is_unused_payment(ext)) {
+		return Err(PaymentError::UnauthorizedPayer);
+	}
+	if position.is_none() && extrinsics.iter().any(|ext| matcher.is_payment(ext)) {
+		return Err(PaymentError::AlreadyUsed);
+	}
+
+	if let Some(ext) = position.map(|position| &extrinsics[position]) {
+		note_payment_lookup(true, true);
+		Ok(PaymentDetails {
+			block_number,
+			block_hash: Some(block_hash),
+			extrinsic_index: ext.index,
+			payer: ext.address.as_ref().map(|address| format!("0x{}", hex::encode(address))),
+		})
 	} else {
+		// Events are looked up by name, so unlike the encoded calls these aren't affected by
+		// changing indices. Asset transfers aren't considered, since their events aren't part
+		// of the static metadata.
+		let transferred_to_receiver = native_payment &&
+			block
+				.events()
+				.await
+				.map(|events| {
+					events
+						.find::<polkadot::balances::events::Transfer>()
+						.flatten()
+						.any(|transfer| transfer.to == *receiver)
+				})
+				.unwrap_or_default();
+
+		note_payment_lookup(false, transferred_to_receiver);
 		Err(PaymentError::NotFound)
 	}
 }
 
-async fn opaque_payment_extrinsic(
+/// The parts of an extrinsic which are compared when looking for a payment.
+///
+/// These are copied out of the block, so that they can be compared on blocking threads.
+struct PaymentCandidate {
+	/// The index of the extrinsic within the block.
+	index: u32,
+	/// The encoded call of the extrinsic.
+	call: Vec<u8>,
+	/// The encoded address of the signer of the extrinsic, if it is signed.
+	address: Option<Vec<u8>>,
+}
+
+impl From<&ExtrinsicDetails<PolkadotConfig, OnlineClient<PolkadotConfig>>> for PaymentCandidate {
+	fn from(ext: &ExtrinsicDetails<PolkadotConfig, OnlineClient<PolkadotConfig>>) -> Self {
+		Self {
+			index: ext.index(),
+			call: ext.call_bytes().to_vec(),
+			address: ext.address_bytes().map(<[u8]>::to_vec),
+		}
+	}
+}
+
+/// Tells whether the extrinsics of a block are payments which can be accepted.
+struct PaymentMatcher {
+	/// The encoded calls which are accepted as payments.
+	payments: Vec<Vec<u8>>,
+	/// The accounts authorized to pay, or `None` if any account can pay.
+	payers: Option<Vec<AccountId32>>,
+	/// The payments which were already used.
+	used: Vec<ConsumedPayment>,
+	block_number: BlockNumber,
+	block_hash: String,
+}
+
+impl PaymentMatcher {
+	/// The raw calls are compared, since asset transfers aren't part of the static metadata.
+	fn is_payment(&self, ext: &PaymentCandidate) -> bool {
+		self.payments.iter().any(|payment| *payment == ext.call)
+	}
+
+	/// Payments which were already used are skipped, in case the block contains another one.
+	fn is_unused_payment(&self, ext: &PaymentCandidate) -> bool {
+		let payment = ConsumedPayment {
+			block_number: self.block_number,
+			extrinsic_index: ext.index,
+			block_hash: Some(self.block_hash.clone()),
+		};
+		self.is_payment(ext) && !self.used.iter().any(|used| used.is_same(&payment))
+	}
+
+	fn is_authorized(&self, ext: &PaymentCandidate) -> bool {
+		self.payers
+			.as_ref()
+			.map_or(true, |payers| is_signed_by(ext.address.as_deref(), payers))
+	}
+}
+
+/// Returns the position of the first item matching the predicate.
+///
+/// When there are more items than the `parallel_threshold`, they are split among a bounded number
+/// of blocking tasks, so that the executor isn't blocked while they are matched.
+pub async fn find_match<T: Send + Sync + 'static>(
+	items: Arc<[T]>,
+	parallel_threshold: Option<usize>,
+	predicate: impl Fn(&T) -> bool + Send + Sync + 'static,
+) -> Option<usize> {
+	if parallel_threshold.map_or(true, |threshold| items.len() <= threshold) {
+		return items.iter().position(predicate);
+	}
+
+	let tasks = std::thread::available_parallelism()
+		.map_or(1, |tasks| tasks.get())
+		.min(MAX_VALIDATION_THREADS);
+	let chunk_size = items.len().div_ceil(tasks).max(1);
+
+	let predicate = Arc::new(predicate);
+	let handles: Vec<_> = (0..items.len())
+		.step_by(chunk_size)
+		.map(|start| {
+			let (items, predicate) = (items.clone(), predicate.clone());
+			tokio::task::spawn_blocking(move || {
+				let end = (start + chunk_size).min(items.len());
+				items[start..end]
+					.iter()
+					.position(|item| predicate(item))
+					.map(|position| start + position)
+			})
+		})
+		.collect();
+
+	// The chunks are in order, so the first match found is the first match overall.
+	for handle in handles {
+		if let Ok(Some(position)) = handle.await {
+			return Some(position)
+		}
+	}
+
+	None
+}
+
+/// Keeps track of the payments that weren't found even though their block contained transfers
+/// to the receiver.
+pub fn note_payment_lookup(found: bool, transferred_to_receiver: bool) {
+	if found {
+		MISMATCHED_PAYMENTS.store(0, Ordering::Relaxed);
+	} else if transferred_to_receiver {
+		let mismatched = MISMATCHED_PAYMENTS.fetch_add(1, Ordering::Relaxed) + 1;
+		if mismatched >= METADATA_MISMATCH_THRESHOLD {
+			log::error!(
+				target: LOG_TARGET,
+				"{} consecutive payments were not found even though transfers to the receiver exist. \
+				The metadata is likely outdated.",
+				mismatched
+			);
+		}
+	}
+}
+
+/// Returns whether the static metadata is suspected to be outdated.
+pub fn metadata_mismatch_suspected() -> bool {
+	MISMATCHED_PAYMENTS.load(Ordering::Relaxed) >= METADATA_MISMATCH_THRESHOLD
+}
+
+/// Returns the encoded call with which the parachain should be paid for.
+fn payment_call(para: Parachain, payment_info: PaymentInfo) -> Result<Vec<u8>, PaymentError> {
+	let indices = call_indices(&payment_info);
+	let mut calls = payment_calls(para, payment_info)?;
+	// Payments to a derived sub-account don't need to be batched with a remark.
+	if calls.len() == 1 {
+		return Ok(calls.remove(0));
+	}
+
+	Ok(batch_all(&indices, &calls))
+}
+
+/// Returns the encoded `utility.batch_all` of the encoded calls.
+///
+/// The batch is encoded manually, since asset transfers can't be represented by the static
+/// metadata, nor can the calls of a parachain payment chain.
+fn batch_all(indices: &CallIndices, calls: &[Vec<u8>]) -> Vec<u8> {
+	// A batch is encoded as its call index, followed by the number of calls and the calls.
+	let mut batch = indices.batch_all.to_vec();
+	Compact(calls.len() as u32).encode_to(&mut batch);
+	calls.iter().for_each(|call| batch.extend(call));
+	batch
+}
+
+/// Returns the encoded transfers of the payment asset to the specified account which are accepted
+/// as payments, starting with the one returned as the payment call data.
+///
+/// Besides `transfer_keep_alive`, native payments can also be made with `transfer_allow_death`,
+/// e.g. by a payer whose account is emptied by the payment.
+fn accepted_transfer_calls(
+	payment_info: &PaymentInfo,
+	dest: AccountId32,
+	value: Balance,
+) -> Vec<Vec<u8>> {
+	let mut transfers = vec![transfer_call(payment_info, dest.clone(), value)];
+	if payment_info.asset_id.is_none() {
+		transfers.push(native_transfer(
+			call_indices(payment_info).transfer_allow_death,
+			dest,
+			value,
+		));
+	}
+
+	transfers
+}
+
+/// Returns the encoded transfer of the payment asset to the specified account.
+fn transfer_call(payment_info: &PaymentInfo, dest: AccountId32, value: Balance) -> Vec<u8> {
+	let Some(asset_id) = payment_info.asset_id else {
+		return native_transfer(call_indices(payment_info).transfer_keep_alive, dest, value);
+	};
+
+	// Encoded the same way as `assets.transfer_keep_alive(id, target, amount)`.
+	let mut call = payment_info.asset_transfer_call.to_vec();
+	Compact(asset_id).encode_to(&mut call);
+	MultiAddress::<AccountId32, ()>::Id(dest).encode_to(&mut call);
+	Compact(value).encode_to(&mut call);
+	call
+}
+
+/// Returns the encoded native transfer with the specified call index, which is either the one of
+/// `balances.transfer_keep_alive` or of `balances.transfer_allow_death`.
+fn native_transfer(index: [u8; 2], dest: AccountId32, value: Balance) -> Vec<u8> {
+	let mut call = index.to_vec();
+	MultiAddress::<AccountId32, ()>::Id(dest).encode_to(&mut call);
+	Compact(value).encode_to(&mut call);
+	call
+}
+
+/// Returns the encoded `system.remark` with the specified content.
+fn remark_call(indices: &CallIndices, remark: Vec<u8>) -> Vec<u8> {
+	let mut call = indices.remark.to_vec();
+	remark.encode_to(&mut call);
+	call
+}
+
+/// An already encoded call which can be submitted as an extrinsic.
+struct RawCall(Vec<u8>);
+
+impl subxt::tx::TxPayload for RawCall {
+	fn encode_call_data_to(
+		&self,
+		_metadata: &subxt::Metadata,
+		out: &mut Vec<u8>,
+	) -> Result<(), subxt::Error> {
+		out.extend_from_slice(&self.0);
+		Ok(())
+	}
+}
+
+/// Returns the encoded calls which are accepted as the payment of the parachain.
+///
+/// The first call is the one that is returned as the payment call data. The calls are encoded
+/// with the `call_indices` of the payment configuration, which for a parachain payment chain are
+/// resolved through `payment_chain_info`.
+pub fn accepted_payment_calls(
 	para: Parachain,
 	payment_info: PaymentInfo,
-) -> Result<polkadot::Call, PaymentError> {
-	if let Ok(cost) = payment_info.cost.parse::<u128>() {
-		let transfer_call = polkadot::Call::Balances(BalancesCall::transfer_keep_alive {
-			dest: payment_info.receiver.into(),
-			value: cost,
-		});
+) -> Result<Vec<Vec<u8>>, PaymentError> {
+	let cost = payment_info
+		.cost
+		.parse::<Balance>()
+		.map_err(|_| PaymentError::ValidationFailed)?;
+	let indices = call_indices(&payment_info);
+	let remark = payment_remark(&para, &payment_info);
+	let receiver = payment_receiver(&para, &payment_info)?;
 
-		let remark = format!("regionx-weigher::{}:{}", para.relay_chain, para.para_id)
-			.as_bytes()
-			.to_vec();
-		let remark_call = polkadot::Call::System(SystemCall::remark { remark });
+	let mut accepted = vec![];
+	for transfer in accepted_transfer_calls(&payment_info, receiver, cost) {
+		if payment_info.derive_receiver {
+			accepted.push(transfer.clone());
+		}
+
+		// Remark-based payments are always accepted, even when paying to a derived sub-account.
+		accepted.push(batch_all(&indices, &[transfer, remark.clone()]));
+	}
+
+	Ok(accepted)
+}
+
+/// Returns the account to which the payments of the parachain are sent.
+pub fn payment_receiver(
+	para: &Parachain,
+	payment_info: &PaymentInfo,
+) -> Result<AccountId32, PaymentError> {
+	if payment_info.derive_receiver {
+		derive_sub_account(&payment_info.receiver, para).ok_or(PaymentError::UnderivableReceiver)
+	} else {
+		Ok(payment_info.receiver.clone())
+	}
+}
+
+/// Returns the accounts authorized to pay for all of the parachains, or `None` if any account can
+/// pay for them, see `PaymentInfo::verify_payer`.
+pub async fn authorized_payers(
+	paras: &[Parachain],
+	payment_info: &PaymentInfo,
+) -> Result<Option<Vec<AccountId32>>, PaymentError> {
+	if !payment_info.verify_payer {
+		return Ok(None);
+	}
+
+	let mut authorized: Option<Vec<AccountId32>> = None;
+	for para in paras {
+		let mut payers = configured_payers(para, payment_info);
+		if let Some(manager) = registrar_manager(para, payment_info).await? {
+			payers.push(manager);
+		}
 
-		let batch_call = polkadot::Call::Utility(UtilityCall::batch_all {
-			calls: vec![transfer_call, remark_call],
+		authorized = Some(match authorized {
+			Some(authorized) =>
+				authorized.into_iter().filter(|account| payers.contains(account)).collect(),
+			None => payers,
 		});
+	}
+
+	Ok(Some(authorized.unwrap_or_default()))
+}
+
+/// Returns the accounts authorized to pay for the parachain which are known without querying the
+/// relay chain, i.e. its configured `para_managers`.
+///
+/// The sovereign accounts of the parachain aren't authorized, since they have no keys with which
+/// they could sign a payment extrinsic.
+pub fn configured_payers(para: &Parachain, payment_info: &PaymentInfo) -> Vec<AccountId32> {
+	payment_info
+		.para_managers
+		.iter()
+		.filter(|(relay_chain, para_id, _)| {
+			*relay_chain == para.relay_chain && *para_id == para.para_id
+		})
+		.map(|(_, _, account)| account.clone())
+		.collect()
+}
+
+/// Returns whether the encoded address of an extrinsic is one of the accounts.
+pub fn is_signed_by(address: Option<&[u8]>, accounts: &[AccountId32]) -> bool {
+	address.map_or(false, |address| {
+		accounts
+			.iter()
+			.any(|account| MultiAddress::<AccountId32, ()>::Id(account.clone()).encode() == address)
+	})
+}
+
+/// Returns the manager of the parachain in the `registrar` pallet of its relay chain, which is
+/// reached through the `rpc_url` configured for the relay chain under `[[relay_chains]]`.
+///
+/// `None` if the parachain has no manager, i.e. it isn't registered in the `registrar` pallet.
+async fn registrar_manager(
+	para: &Parachain,
+	payment_info: &PaymentInfo,
+) -> Result<Option<AccountId32>, PaymentError> {
+	let rpc_url = relay_rpc_url(&para.relay_chain).ok_or(PaymentError::ManagerUnresolvable)?;
+	// The connections to the relay chain are established and cached like the ones to the payment
+	// chain. The CA bundle only applies to the payment RPC.
+	let relay_info = PaymentInfo { rpc_url, ca_bundle: None, ..payment_info.clone() };
+
+	let para_id = para.para_id;
+	with_payment_clients(&relay_info, |(_, online_client)| async move {
+		let query = polkadot::storage().registrar().paras(Id(para_id));
+		let storage = online_client.storage().at_latest().await.map_err(rpc_error)?;
+		match storage.fetch(&query).await {
+			Ok(info) => Ok(info.map(|info| info.manager)),
+			Err(subxt::Error::Metadata(_)) => Err(PaymentError::ManagerUnresolvable),
+			Err(err) => Err(rpc_error(err)),
+		}
+	})
+	.await
+}
+
+/// Derives the sub-account of the base account to which the parachain pays.
+///
+/// The sub-account is the `utility` derivative, at the para id, of the derivative of the base
+/// account at the index of the relay chain. The base account can therefore dispatch calls from the
+/// sub-account, e.g. to sweep it, through
+/// `utility.as_derivative(<relay_index>, utility.as_derivative(<para_id>, <call>))`.
+///
+/// `None` if the para id doesn't fit the `u16` index of a derivative.
+pub fn derive_sub_account(base: &AccountId32, para: &Parachain) -> Option<AccountId32> {
+	let para_index = u16::try_from(para.para_id).ok()?;
+	let relay_account = utility_derivative(base, relay_chain_index(&para.relay_chain));
+
+	Some(utility_derivative(&relay_account, para_index))
+}
+
+/// Returns the account from which `utility.as_derivative(index, ..)` dispatches calls of the base
+/// account, i.e. the blake2b-256 hash of the encoded `(b"modlpy/utilisuba", base, index)`.
+pub fn utility_derivative(base: &AccountId32, index: u16) -> AccountId32 {
+	let mut hasher = Blake2b::<U32>::new();
+	hasher.update((*b"modlpy/utilisuba", base.0, index).encode());
+
+	AccountId32(hasher.finalize().into())
+}
+
+/// The derivative index under which the sub-accounts of the parachains of the relay chain are
+/// derived.
+pub fn relay_chain_index(relay_chain: &RelayChain) -> u16 {
+	match relay_chain {
+		RelayChain::Polkadot => 0,
+		RelayChain::Kusama => 1,
+		RelayChain::Paseo => 2,
+	}
+}
+
+/// Returns the encoded calls which make up the payment of the parachain.
+///
+/// These are a transfer to the receiver along with the payment remark, or only a transfer when
+/// paying to a derived sub-account.
+fn payment_calls(para: Parachain, payment_info: PaymentInfo) -> Result<Vec<Vec<u8>>, PaymentError> {
+	if let Ok(cost) = payment_info.cost.parse::<u128>() {
+		let transfer_call =
+			transfer_call(&payment_info, payment_receiver(&para, &payment_info)?, cost);
+
+		if payment_info.derive_receiver {
+			return Ok(vec![transfer_call]);
+		}
 
-		Ok(batch_call)
+		Ok(vec![transfer_call, payment_remark(&para, &payment_info)])
 	} else {
 		log::error!(
 			target: LOG_TARGET,
@@ -137,26 +1339,273 @@ async fn opaque_payment_extrinsic(
 	}
 }
 
+fn payment_remark(para: &Parachain, payment_info: &PaymentInfo) -> Vec<u8> {
+	let remark = format!("{}{}:{}", remark_namespace(payment_info), para.relay_chain, para.para_id)
+		.as_bytes()
+		.to_vec();
+	remark_call(&call_indices(payment_info), remark)
+}
+
+/// Returns what the payment remarks start with, i.e. `regionx-weigher::` followed by the
+/// configured `remark_prefix`, if any.
+fn remark_namespace(payment_info: &PaymentInfo) -> String {
+	match &payment_info.remark_prefix {
+		Some(prefix) => format!("regionx-weigher::{}:", prefix),
+		None => "regionx-weigher::".to_string(),
+	}
+}
+
+/// Resolves the call indices of the payment chain, so that the payment calls are encoded the way
+/// the payment chain expects them.
+///
+/// Configured `call_indices` take precedence. Otherwise, the calls of a parachain payment chain are
+/// resolved from its metadata, whereas those of a relay chain are encoded with the static
+/// metadata. Resolving the configuration again doesn't change it.
+pub async fn payment_chain_info(payment_info: PaymentInfo) -> Result<PaymentInfo, PaymentError> {
+	if payment_info.call_indices.is_some() || payment_info.payment_chain == PaymentChain::RelayChain
+	{
+		return Ok(payment_info);
+	}
+
+	let metadata = with_payment_clients(&payment_info, |(_, online_client)| async move {
+		Ok(online_client.metadata())
+	})
+	.await?;
+	let Some(call_indices) = metadata_call_indices(&metadata) else {
+		log::error!(
+			target: LOG_TARGET,
+			"The payment chain at {} doesn't support the payment calls",
+			payment_info.rpc_url
+		);
+		return Err(PaymentError::ValidationFailed);
+	};
+
+	Ok(PaymentInfo { call_indices: Some(call_indices), ..payment_info })
+}
+
+/// Returns the call indices with which the payment calls are encoded, which default to the ones
+/// of the relay chain.
+fn call_indices(payment_info: &PaymentInfo) -> CallIndices {
+	payment_info.call_indices.unwrap_or_else(relay_chain_call_indices)
+}
+
+/// Returns the call indices of the relay chain, according to the static metadata.
+pub fn relay_chain_call_indices() -> CallIndices {
+	let index = |call: polkadot::Call| {
+		let call = call.encode();
+		[call[0], call[1]]
+	};
+
+	CallIndices {
+		transfer_keep_alive: index(polkadot::Call::Balances(BalancesCall::transfer_keep_alive {
+			dest: AccountId32([0; 32]).into(),
+			value: 0,
+		})),
+		transfer_allow_death: index(polkadot::Call::Balances(BalancesCall::transfer_allow_death {
+			dest: AccountId32([0; 32]).into(),
+			value: 0,
+		})),
+		batch_all: index(polkadot::Call::Utility(UtilityCall::batch_all { calls: vec![] })),
+		remark: index(polkadot::Call::System(SystemCall::remark { remark: vec![] })),
+	}
+}
+
+/// Resolves the call indices from the metadata of the payment chain.
+///
+/// `None` if the payment chain lacks any of the payment calls.
+pub fn metadata_call_indices(metadata: &subxt::Metadata) -> Option<CallIndices> {
+	let index = |pallet: &str, call: &str| {
+		let pallet = metadata.pallet_by_name(pallet)?;
+		Some([pallet.index(), pallet.call_variant_by_name(call)?.index])
+	};
+
+	Some(CallIndices {
+		transfer_keep_alive: index("Balances", "transfer_keep_alive")?,
+		transfer_allow_death: index("Balances", "transfer_allow_death")?,
+		batch_all: index("Utility", "batch_all")?,
+		remark: index("System", "remark")?,
+	})
+}
+
+/// Runs `f` with the connections to the payment RPC.
+///
+/// If the payment RPC can't be reached, the connections might have broken, so `f` is retried with
+/// newly established connections.
+async fn with_payment_clients<T, F, Fut>(
+	payment_info: &PaymentInfo,
+	f: F,
+) -> Result<T, PaymentError>
+where
+	F: Fn(PaymentClients) -> Fut,
+	Fut: std::future::Future<Output = Result<T, PaymentError>>,
+{
+	let f = &f;
+	let rpc_url = &payment_info.rpc_url;
+	retry_unreachable(move || async move {
+		let result = f(payment_clients(payment_info).await?).await;
+		if matches!(result, Err(PaymentError::Unreachable)) {
+			drop_payment_clients(rpc_url);
+		}
+
+		result
+	})
+	.await
+}
+
+/// Runs `f` up to `RPC_ATTEMPTS` times, with an exponentially increasing delay in between, for as
+/// long as it fails with `PaymentError::Unreachable`.
+///
+/// Any other result is returned immediately, since retrying wouldn't change it.
+pub async fn retry_unreachable<T, F, Fut>(f: F) -> Result<T, PaymentError>
+where
+	F: Fn() -> Fut,
+	Fut: std::future::Future<Output = Result<T, PaymentError>>,
+{
+	let mut delay = RPC_RETRY_DELAY;
+	for attempt in 1..RPC_ATTEMPTS {
+		match f().await {
+			Err(PaymentError::Unreachable) => {
+				log::warn!(
+					target: LOG_TARGET,
+					"Failed to reach the payment RPC, retrying in {}ms (attempt {}/{})",
+					delay.as_millis(),
+					attempt,
+					RPC_ATTEMPTS
+				);
+				tokio::time::sleep(delay).await;
+				delay = delay.saturating_mul(2);
+			},
+			result => return result,
+		}
+	}
+
+	f().await
+}
+
+/// Returns the connections to the payment RPC.
+///
+/// New connections are only established if there are none yet.
+async fn payment_clients(payment_info: &PaymentInfo) -> Result<PaymentClients, PaymentError> {
+	let rpc_url = payment_info.rpc_url.as_str();
+	let cached = PAYMENT_CLIENTS
+		.read()
+		.unwrap_or_else(|poisoned| poisoned.into_inner())
+		.get(rpc_url)
+		.cloned();
+	if let Some(clients) = cached {
+		return Ok(clients);
+	}
+
+	let rpc_client = match &payment_info.ca_bundle {
+		Some(ca_bundle) if rpc_url.starts_with("wss://") =>
+			connect_with_ca_bundle(rpc_url, ca_bundle).await?,
+		_ => RpcClient::from_url(rpc_url).await.map_err(rpc_error)?,
+	};
+	let online_client = OnlineClient::<PolkadotConfig>::from_rpc_client(rpc_client.clone())
+		.await
+		.map_err(rpc_error)?;
+
+	let clients = (rpc_client, online_client);
+	PAYMENT_CLIENTS
+		.write()
+		.unwrap_or_else(|poisoned| poisoned.into_inner())
+		.insert(rpc_url.to_string(), clients.clone());
+
+	Ok(clients)
+}
+
+/// Distinguishes the failures to reach the payment RPC from the other failures, e.g. decoding
+/// ones, which retrying wouldn't resolve.
+fn rpc_error(err: subxt::Error) -> PaymentError {
+	match err {
+		subxt::Error::Io(_) | subxt::Error::Rpc(_) => PaymentError::Unreachable,
+		_ => PaymentError::ValidationFailed,
+	}
+}
+
+/// Connects to the websocket rpc, verifying its TLS certificate against the CA certificates of
+/// the PEM encoded `ca_bundle` rather than against the default roots.
+async fn connect_with_ca_bundle(rpc_url: &str, ca_bundle: &str) -> Result<RpcClient, PaymentError> {
+	let url = Url::parse(rpc_url).map_err(|_| PaymentError::ValidationFailed)?;
+	let host = url.host_str().ok_or(PaymentError::ValidationFailed)?;
+	let port = url.port_or_known_default().ok_or(PaymentError::ValidationFailed)?;
+
+	let roots = load_ca_bundle(ca_bundle).map_err(|err| {
+		log::error!(
+			target: LOG_TARGET,
+			"Failed to load the CA bundle {}: {}",
+			ca_bundle,
+			err
+		);
+		PaymentError::ValidationFailed
+	})?;
+	let tls_config = rustls::ClientConfig::builder()
+		.with_safe_defaults()
+		.with_root_certificates(roots)
+		.with_no_client_auth();
+	let server_name =
+		rustls::ServerName::try_from(host).map_err(|_| PaymentError::ValidationFailed)?;
+
+	let stream = TcpStream::connect((host, port)).await.map_err(|_| PaymentError::Unreachable)?;
+	let stream = TlsConnector::from(Arc::new(tls_config))
+		.connect(server_name, stream)
+		.await
+		.map_err(|_| PaymentError::Unreachable)?;
+
+	let (sender, receiver) = WsTransportClientBuilder::default()
+		.build_with_stream(url, stream.compat())
+		.await
+		.map_err(|_| PaymentError::Unreachable)?;
+	let client = ClientBuilder::default().build_with_tokio(sender, receiver);
+
+	Ok(RpcClient::new(client))
+}
+
+/// Loads the CA certificates of the PEM encoded bundle at the specified path.
+pub fn load_ca_bundle(path: &str) -> Result<rustls::RootCertStore, String> {
+	let file = std::fs::File::open(path).map_err(|err| err.to_string())?;
+	let certs =
+		rustls_pemfile::certs(&mut std::io::BufReader::new(file)).map_err(|err| err.to_string())?;
+	if certs.is_empty() {
+		return Err("The bundle doesn't contain any certificates".into())
+	}
+
+	let mut roots = rustls::RootCertStore::empty();
+	for cert in certs {
+		roots.add(&rustls::Certificate(cert)).map_err(|err| err.to_string())?;
+	}
+
+	Ok(roots)
+}
+
+/// Drops the connections to the payment RPC, so that they get re-established when next used.
+fn drop_payment_clients(rpc_url: &str) {
+	PAYMENT_CLIENTS
+		.write()
+		.unwrap_or_else(|poisoned| poisoned.into_inner())
+		.remove(rpc_url);
+}
+
 async fn get_last_finalized_block(
 	rpc_client: RpcClient,
 	online_client: OnlineClient<PolkadotConfig>,
 ) -> Result<BlockNumber, PaymentError> {
-	let params = rpc_params![];
-	let block_hash: H256 = rpc_client
-		.request("chain_getFinalizedHead", params)
-		.await
-		.map_err(|_| PaymentError::ValidationFailed)?;
-
+	let block_hash = get_finalized_head(rpc_client).await?;
 	let block = get_block(online_client, block_hash).await?;
 
 	Ok(block.number())
 }
 
+async fn get_finalized_head(rpc_client: RpcClient) -> Result<H256, PaymentError> {
+	let params = rpc_params![];
+	rpc_client.request("chain_getFinalizedHead", params).await.map_err(rpc_error)
+}
+
 async fn get_block(
 	api: OnlineClient<PolkadotConfig>,
 	block_hash: H256,
 ) -> Result<Block<PolkadotConfig, OnlineClient<PolkadotConfig>>, PaymentError> {
-	api.blocks().at(block_hash).await.map_err(|_| PaymentError::ValidationFailed)
+	api.blocks().at(block_hash).await.map_err(rpc_error)
 }
 
 async fn get_block_hash(
@@ -164,10 +1613,8 @@ async fn get_block_hash(
 	block_number: BlockNumber,
 ) -> Result<H256, PaymentError> {
 	let params = rpc_params![Some(block_number)];
-	let block_hash: H256 = rpc_client
-		.request("chain_getBlockHash", params)
-		.await
-		.map_err(|_| PaymentError::ValidationFailed)?;
+	let block_hash: Option<H256> =
+		rpc_client.request("chain_getBlockHash", params).await.map_err(rpc_error)?;
 
-	Ok(block_hash)
+	block_hash.ok_or(PaymentError::ValidationFailed)
 }
@@ -13,20 +13,57 @@
 // You should have received a copy of the GNU General Public License
 // along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::config::config;
-use std::{
-	fs::{File, OpenOptions},
-	io::{Read, Seek, Write},
+use crate::{
+	config::config,
+	storage::{registry_storage, RegistryStorage, RegistryVersion},
+	LOG_TARGET,
 };
+use std::sync::{OnceLock, RwLock};
 use types::{ParaId, Parachain, RelayChain};
 
+/// In-memory copy of the registry, along with the version of the stored registry it was read
+/// from. Used when `cache_registry` is enabled in the config.
+static REGISTRY_CACHE: RwLock<Option<(RegistryVersion, Vec<Parachain>)>> = RwLock::new(None);
+
+/// Whether `cache_registry` is enabled, along with the storage of the registry, which are read
+/// from the config once so that cached reads don't parse the config.
+static CACHED_STORAGE: OnceLock<Option<Box<dyn RegistryStorage>>> = OnceLock::new();
+
+fn cached_storage() -> Option<&'static dyn RegistryStorage> {
+	CACHED_STORAGE
+		.get_or_init(|| config().cache_registry.then(registry_storage))
+		.as_deref()
+}
+
+/// Returns the registered parachains.
+///
+/// When cached, the copy is only used as long as the version of the stored registry didn't
+/// change, so that the writes of other processes, e.g. of the tracker, are picked up as well.
 pub fn registered_paras() -> Vec<Parachain> {
-	let mut registry = get_registry();
-	let mut content = String::new();
+	let Some(storage) = cached_storage() else { return read_registry() };
+	// A registry without a known version is never cached.
+	let Some(version) = storage.version() else { return read_registry() };
 
-	// If this fails it simply means that the registry is empty.
-	let _ = registry.read_to_string(&mut content);
-	let paras: Vec<Parachain> = serde_json::from_str(&content).expect("Failed to serialize");
+	if let Some((cached_version, paras)) =
+		REGISTRY_CACHE.read().unwrap_or_else(|e| e.into_inner()).as_ref()
+	{
+		if *cached_version == version {
+			return paras.clone();
+		}
+	}
+
+	let mut cache = REGISTRY_CACHE.write().unwrap_or_else(|e| e.into_inner());
+	// The cache might have been populated while we were waiting for the lock.
+	if let Some((cached_version, paras)) = cache.as_ref() {
+		if *cached_version == version {
+			return paras.clone();
+		}
+	}
+
+	// The version is determined before reading, so that a write in between is detected by the
+	// next read.
+	let paras = storage.read().expect("Failed to read the registry");
+	*cache = Some((version, paras.clone()));
 
 	paras
 }
@@ -39,33 +76,84 @@ pub fn registered_para(relay_chain: RelayChain, para_id: ParaId) -> Option<Parac
 }
 
 pub fn update_registry(paras: Vec<Parachain>) -> Result<(), String> {
-	let mut registry = get_registry();
-	let json_data = serde_json::to_string_pretty(&paras).map_err(|_| "Failed to serialize")?;
+	// The lock is held during the whole write so that readers never get a cached registry which
+	// is out of sync with the file.
+	let mut cache = REGISTRY_CACHE.write().unwrap_or_else(|e| e.into_inner());
+	*cache = None;
+
+	// The cache is repopulated by the next read, since the version of the written registry can't
+	// be told apart from the one of a concurrent write of another process.
+	write_registry(&paras)
+}
+
+/// Ensures that the registry can be read from its storage, bypassing the cache.
+pub fn ensure_registry_readable() -> Result<(), String> {
+	registry_storage().read().map(|_| ())
+}
+
+fn read_registry() -> Vec<Parachain> {
+	registry_storage().read().expect("Failed to read the registry")
+}
 
-	registry.set_len(0).map_err(|_| "Failed to truncate file")?;
-	registry
-		.seek(std::io::SeekFrom::Start(0))
-		.map_err(|_| "Failed to seek to the beginning")?;
+fn write_registry(paras: &[Parachain]) -> Result<(), String> {
+	registry_storage().write(paras)
+}
+
+/// Removes duplicate `(relay_chain, para_id)` entries from the registry.
+///
+/// Non-atomic writes from the past can leave the registry with multiple entries for the same
+/// parachain. In that case the entry with the latest `expiry_timestamp` is kept and the registry
+/// is rewritten.
+pub fn dedup_registry() -> Result<(), String> {
+	let paras = registered_paras();
+	let total = paras.len();
 
-	registry
-		.write_all(json_data.as_bytes())
-		.map_err(|_| "Failed to write into file")?;
+	let deduped = dedup_paras(paras);
+	if deduped.len() == total {
+		return Ok(());
+	}
 
-	Ok(())
+	log::warn!(
+		target: LOG_TARGET,
+		"Removed {} duplicate registry entries, rewriting registry",
+		total - deduped.len()
+	);
+
+	update_registry(deduped)
 }
 
-fn get_registry() -> File {
-	match OpenOptions::new().read(true).write(true).open(config().registry) {
-		Ok(file) => file,
-		Err(_) => init_registry(),
+/// Deduplicates the given parachains by `(relay_chain, para_id)`, keeping the entry with the
+/// latest `expiry_timestamp`.
+///
+/// The order of the first occurrence of each parachain is preserved.
+pub fn dedup_paras(paras: Vec<Parachain>) -> Vec<Parachain> {
+	let mut deduped: Vec<Parachain> = vec![];
+
+	for para in paras {
+		let existing = deduped
+			.iter_mut()
+			.find(|p| p.relay_chain == para.relay_chain && p.para_id == para.para_id);
+
+		if let Some(existing) = existing {
+			log::warn!(
+				target: LOG_TARGET,
+				"{}-{} - Found duplicate registry entry",
+				para.relay_chain, para.para_id
+			);
+
+			if para.expiry_timestamp > existing.expiry_timestamp {
+				*existing = para;
+			}
+		} else {
+			deduped.push(para);
+		}
 	}
+
+	deduped
 }
 
-pub fn init_registry() -> File {
-	let mut registry =
-		File::create(config().registry).expect("Failed to create registered para file");
-	// An empty vector
-	registry.write_all(b"[]").expect("Failed to write into registered para file");
+pub fn init_registry() {
+	*REGISTRY_CACHE.write().unwrap_or_else(|e| e.into_inner()) = None;
 
-	registry
+	registry_storage().init().expect("Failed to initialize the registry");
 }
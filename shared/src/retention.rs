@@ -0,0 +1,88 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Prunes the consumption which is older than the configured retention period.
+//!
+//! Each process prunes the consumption files it writes to, so that pruning holds the same lock as
+//! its writes: the tracker prunes its own output, and the processor the processed output.
+
+use crate::{
+	config::config, consumption::prune_consumption, current_timestamp, registry::registered_paras,
+	LOG_TARGET,
+};
+use std::time::Duration;
+
+/// The interval, in seconds, at which the old consumption is pruned.
+const RETENTION_CHECK_INTERVAL: u64 = 3600;
+
+const DAY: u64 = 86400;
+
+/// Removes the consumption of all the registered parachains which is older than `retention_days`.
+///
+/// Does nothing if `retention_days` is 0.
+pub fn prune_old_consumption(rpc_index: Option<usize>) {
+	let retention_days = config().retention_days;
+	if retention_days == 0 {
+		return
+	}
+
+	// The consumption is timestamped in milliseconds.
+	let before = current_timestamp()
+		.saturating_sub(retention_days.saturating_mul(DAY))
+		.saturating_mul(1000);
+
+	for para in registered_paras() {
+		match prune_consumption(&para, rpc_index, before) {
+			Ok(0) => {},
+			Ok(pruned) => log::info!(
+				target: LOG_TARGET,
+				"{}-{} - Pruned {} consumption records older than {} days",
+				para.relay_chain,
+				para.para_id,
+				pruned,
+				retention_days
+			),
+			Err(err) => log::error!(
+				target: LOG_TARGET,
+				"{}-{} - Failed to prune the consumption: {:?}",
+				para.relay_chain,
+				para.para_id,
+				err
+			),
+		}
+	}
+}
+
+/// Starts the background task which periodically prunes the old consumption of the specified
+/// output.
+///
+/// Does nothing if `retention_days` is 0. Must be called within a tokio runtime.
+pub fn spawn_consumption_pruner(rpc_index: Option<usize>) {
+	if config().retention_days == 0 {
+		return
+	}
+
+	tokio::spawn(async move {
+		let mut interval = tokio::time::interval(Duration::from_secs(RETENTION_CHECK_INTERVAL));
+		loop {
+			interval.tick().await;
+			// Pruning rewrites whole files, so it is kept off the runtime's worker threads.
+			let pruning = tokio::task::spawn_blocking(move || prune_old_consumption(rpc_index));
+			if let Err(err) = pruning.await {
+				log::error!(target: LOG_TARGET, "Failed to prune the consumption: {:?}", err);
+			}
+		}
+	});
+}
@@ -0,0 +1,208 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Backends in which the registry is persisted.
+//!
+//! The registry is stored in a JSON file by default. When built with the `sqlite` feature and a
+//! `registry_database` is configured, it is stored in a SQLite database instead, which serializes
+//! the concurrent writes of the tracker and the server.
+
+use crate::{config::config, LOG_TARGET};
+use std::{
+	fs::{File, OpenOptions},
+	io::{Read, Write},
+	path::Path,
+	sync::atomic::{AtomicU64, Ordering},
+	time::UNIX_EPOCH,
+};
+use types::Parachain;
+
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+/// Identifies the state of a stored registry, which changes whenever the registry is written,
+/// including by other processes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegistryVersion(Vec<u128>);
+
+/// A backend in which the registered parachains are persisted.
+pub trait RegistryStorage: Send + Sync {
+	/// Reads all the registered parachains, in the order in which they were written.
+	fn read(&self) -> Result<Vec<Parachain>, String>;
+
+	/// Returns the version of the stored registry, without reading the registry itself.
+	///
+	/// Returns `None` if the version can't be determined, e.g. if the registry doesn't exist.
+	fn version(&self) -> Option<RegistryVersion>;
+
+	/// Replaces all the registered parachains.
+	fn write(&self, paras: &[Parachain]) -> Result<(), String>;
+
+	/// Resets the registry to an empty one.
+	fn init(&self) -> Result<(), String>;
+}
+
+/// Returns the configured registry backend.
+pub fn registry_storage() -> Box<dyn RegistryStorage> {
+	#[cfg(feature = "sqlite")]
+	if let Some(path) = config().registry_database {
+		return Box::new(sqlite::SqliteStorage::new(path));
+	}
+
+	Box::new(FileStorage::new(config().registry))
+}
+
+/// Distinguishes the temporary files of the concurrent writes within the same process.
+static TMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Stores the registry as a JSON encoded list of parachains.
+///
+/// Writes go to a temporary file which is then renamed into place, so that the registry is never
+/// left partially written. The previous registry is kept as a `.bak` copy, which is read instead
+/// if the registry can't be deserialized. The backup is replaced the same way.
+pub struct FileStorage {
+	path: String,
+}
+
+impl FileStorage {
+	pub fn new(path: String) -> Self {
+		Self { path }
+	}
+
+	fn open(&self) -> Result<File, String> {
+		match OpenOptions::new().read(true).write(true).open(&self.path) {
+			Ok(file) => Ok(file),
+			Err(_) => self.create(),
+		}
+	}
+
+	fn create(&self) -> Result<File, String> {
+		let mut registry =
+			File::create(&self.path).map_err(|_| "Failed to create registered para file")?;
+		// An empty vector
+		registry
+			.write_all(b"[]")
+			.map_err(|_| "Failed to write into registered para file")?;
+
+		Ok(registry)
+	}
+
+	pub fn backup_path(&self) -> String {
+		format!("{}.bak", self.path)
+	}
+
+	fn read_from(path: &str) -> Result<Vec<Parachain>, String> {
+		let content = std::fs::read_to_string(path).map_err(|_| "Failed to read the registry")?;
+		serde_json::from_str(&content).map_err(|_| "Failed to deserialize the registry".into())
+	}
+}
+
+/// Replaces the content of the file at `path`, so that it is either fully written or left as is,
+/// even if the process crashes.
+///
+/// The content is written to a temporary file, unique to the write so that concurrent writes
+/// from this or other processes don't interfere, which is then renamed into place. The directory
+/// is synced as well, so that the rename itself is persisted.
+pub(crate) fn write_atomically(path: &str, content: &[u8]) -> Result<(), String> {
+	let tmp_path = format!(
+		"{}.{}.{}.tmp",
+		path,
+		std::process::id(),
+		TMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+	);
+
+	let written = File::create(&tmp_path)
+		.and_then(|mut tmp| {
+			tmp.write_all(content)?;
+			tmp.sync_all()
+		})
+		.and_then(|_| std::fs::rename(&tmp_path, path));
+	if let Err(err) = written {
+		let _ = std::fs::remove_file(&tmp_path);
+		return Err(format!("Failed to write {}: {}", path, err));
+	}
+
+	sync_parent_dir(path)
+}
+
+#[cfg(unix)]
+fn sync_parent_dir(path: &str) -> Result<(), String> {
+	let dir = match Path::new(path).parent() {
+		Some(dir) if !dir.as_os_str().is_empty() => dir,
+		_ => Path::new("."),
+	};
+
+	File::open(dir)
+		.and_then(|dir| dir.sync_all())
+		.map_err(|err| format!("Failed to sync the directory of {}: {}", path, err))
+}
+
+// Directories can't be opened, and therefore synced, on other platforms.
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &str) -> Result<(), String> {
+	Ok(())
+}
+
+impl RegistryStorage for FileStorage {
+	fn read(&self) -> Result<Vec<Parachain>, String> {
+		let mut registry = self.open()?;
+		let mut content = String::new();
+
+		// If this fails it simply means that the registry is empty.
+		let _ = registry.read_to_string(&mut content);
+		serde_json::from_str(&content).or_else(|_| {
+			let paras = Self::read_from(&self.backup_path())?;
+			log::warn!(
+				target: LOG_TARGET,
+				"The registry is corrupt, falling back to its backup"
+			);
+
+			Ok(paras)
+		})
+	}
+
+	fn version(&self) -> Option<RegistryVersion> {
+		let metadata = std::fs::metadata(&self.path).ok()?;
+		let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_nanos();
+
+		// Every write renames a new file into place, so on unix the inode changes even if the
+		// modification time doesn't, e.g. because of its granularity.
+		#[cfg(unix)]
+		let inode = std::os::unix::fs::MetadataExt::ino(&metadata) as u128;
+		#[cfg(not(unix))]
+		let inode = 0;
+
+		Some(RegistryVersion(vec![inode, modified, metadata.len() as u128]))
+	}
+
+	fn write(&self, paras: &[Parachain]) -> Result<(), String> {
+		let json_data = serde_json::to_string_pretty(paras).map_err(|_| "Failed to serialize")?;
+
+		// A corrupt registry isn't backed up, so that the backup stays usable.
+		if let Ok(current) = std::fs::read_to_string(&self.path) {
+			if serde_json::from_str::<Vec<Parachain>>(&current).is_ok() {
+				write_atomically(&self.backup_path(), current.as_bytes())?;
+			}
+		}
+
+		write_atomically(&self.path, json_data.as_bytes())
+	}
+
+	fn init(&self) -> Result<(), String> {
+		// The backup of the previous registry mustn't be restored into the new one.
+		let _ = std::fs::remove_file(self.backup_path());
+		self.create().map(|_| ())
+	}
+}
@@ -0,0 +1,120 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::{RegistryStorage, RegistryVersion};
+use rusqlite::{params, Connection};
+use std::time::Duration;
+use types::Parachain;
+
+/// How long a connection waits for the database to be unlocked by a concurrent writer.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Stores the registry in a SQLite database, with each parachain JSON encoded in its own row.
+pub struct SqliteStorage {
+	path: String,
+}
+
+impl SqliteStorage {
+	pub fn new(path: String) -> Self {
+		Self { path }
+	}
+
+	fn connect(&self) -> Result<Connection, String> {
+		let connection = Connection::open(&self.path).map_err(|_| "Failed to open the database")?;
+		connection
+			.busy_timeout(BUSY_TIMEOUT)
+			.map_err(|_| "Failed to set the busy timeout")?;
+		// Allows reading the registry while it is being written.
+		connection
+			.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))
+			.map_err(|_| "Failed to enable write-ahead logging")?;
+
+		connection
+			.execute(
+				"CREATE TABLE IF NOT EXISTS registry (
+					position INTEGER PRIMARY KEY,
+					para TEXT NOT NULL
+				)",
+				[],
+			)
+			.map_err(|_| "Failed to create the registry table")?;
+
+		Ok(connection)
+	}
+}
+
+impl RegistryStorage for SqliteStorage {
+	fn read(&self) -> Result<Vec<Parachain>, String> {
+		let connection = self.connect()?;
+		let mut statement = connection
+			.prepare("SELECT para FROM registry ORDER BY position")
+			.map_err(|_| "Failed to prepare the registry query")?;
+
+		let rows = statement
+			.query_map([], |row| row.get::<_, String>(0))
+			.map_err(|_| "Failed to query the registry")?;
+
+		rows.map(|row| -> Result<Parachain, String> {
+			let para = row.map_err(|_| "Failed to read a registry entry")?;
+			serde_json::from_str(&para).map_err(|_| "Failed to deserialize a registry entry".into())
+		})
+		.collect()
+	}
+
+	fn version(&self) -> Option<RegistryVersion> {
+		let version: i64 = self
+			.connect()
+			.ok()?
+			.pragma_query_value(None, "user_version", |row| row.get(0))
+			.ok()?;
+
+		Some(RegistryVersion(vec![version as u128]))
+	}
+
+	fn write(&self, paras: &[Parachain]) -> Result<(), String> {
+		let mut connection = self.connect()?;
+		// The registry is replaced atomically, so readers never observe a partial write.
+		let transaction = connection.transaction().map_err(|_| "Failed to start a transaction")?;
+
+		// The version is bumped within the transaction, so it changes along with the registry.
+		let version: i64 = transaction
+			.pragma_query_value(None, "user_version", |row| row.get(0))
+			.map_err(|_| "Failed to read the registry version")?;
+		transaction
+			.pragma_update(None, "user_version", version.wrapping_add(1))
+			.map_err(|_| "Failed to update the registry version")?;
+
+		transaction
+			.execute("DELETE FROM registry", [])
+			.map_err(|_| "Failed to clear the registry")?;
+
+		for (position, para) in paras.iter().enumerate() {
+			let para = serde_json::to_string(para).map_err(|_| "Failed to serialize")?;
+			transaction
+				.execute(
+					"INSERT INTO registry (position, para) VALUES (?1, ?2)",
+					params![position as i64, para],
+				)
+				.map_err(|_| "Failed to write a registry entry")?;
+		}
+
+		transaction.commit().map_err(|_| "Failed to commit the registry".into())
+	}
+
+	fn init(&self) -> Result<(), String> {
+		// Cleared through a write, so that the version changes as well.
+		self.write(&[])
+	}
+}
@@ -0,0 +1,158 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+//! File containing the subscription related logic.
+
+use crate::{
+	config::{config, payment_exempt, LapsePolicy, PaymentInfo},
+	current_timestamp,
+	payment::PaymentError,
+};
+use serde::{Deserialize, Serialize};
+use types::{Balance, Parachain, Timestamp};
+
+/// The state of the subscription of a parachain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SubscriptionState {
+	/// The subscription hasn't expired yet. Subscriptions never expire in free mode, nor for the
+	/// parachains which are exempt from payment.
+	Active,
+	/// The subscription expired less than the configured `grace_period` ago.
+	GracePeriod,
+	/// The subscription expired, and its grace period is over.
+	Expired,
+}
+
+/// Returns the current state of the subscription of the parachain.
+pub fn subscription_state(para: &Parachain) -> SubscriptionState {
+	let Some(payment_info) = config().payment_info else { return SubscriptionState::Active };
+	if payment_exempt(para) {
+		return SubscriptionState::Active
+	}
+
+	let now = current_timestamp();
+
+	if para.expiry_timestamp > now {
+		SubscriptionState::Active
+	} else if now < para.expiry_timestamp.saturating_add(payment_info.grace_period) {
+		SubscriptionState::GracePeriod
+	} else {
+		SubscriptionState::Expired
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Renewal {
+	/// The payment required for the renewal.
+	pub cost: Balance,
+	/// The expiry timestamp of the subscription once renewed.
+	pub expiry_timestamp: Timestamp,
+}
+
+/// Computes the cost and the resulting expiry of renewing a subscription at `now`.
+///
+/// A subscription that hasn't expired yet is simply extended by a single subscription period.
+/// For a lapsed subscription the outcome depends on the configured `LapsePolicy`.
+pub fn renewal(
+	expiry_timestamp: Timestamp,
+	payment_info: &PaymentInfo,
+	now: Timestamp,
+) -> Result<Renewal, PaymentError> {
+	extension(expiry_timestamp, payment_info, now, payment_info.subscription_duration)
+}
+
+/// Computes the cost and the resulting expiry of extending a subscription by `duration` seconds
+/// at `now`.
+///
+/// The cost is prorated to the extended duration. A lapsed subscription is handled the same way
+/// as when renewing it, with the lapsed periods paid for in full in case of `LapsePolicy::Charge`.
+pub fn extension(
+	expiry_timestamp: Timestamp,
+	payment_info: &PaymentInfo,
+	now: Timestamp,
+	duration: Timestamp,
+) -> Result<Renewal, PaymentError> {
+	let cost = payment_info
+		.cost
+		.parse::<Balance>()
+		.map_err(|_| PaymentError::ValidationFailed)?;
+	let subscription_duration = payment_info.subscription_duration;
+	let extension_cost = prorated_cost(cost, duration, subscription_duration);
+
+	if expiry_timestamp >= now {
+		let expiry_timestamp =
+			expiry_timestamp.checked_add(duration).ok_or(PaymentError::DurationOverflow)?;
+		return Ok(Renewal { cost: extension_cost, expiry_timestamp });
+	}
+
+	match payment_info.lapse_policy {
+		LapsePolicy::Forgive => {
+			let expiry_timestamp =
+				now.checked_add(duration).ok_or(PaymentError::DurationOverflow)?;
+			Ok(Renewal { cost: extension_cost, expiry_timestamp })
+		},
+		LapsePolicy::Charge => {
+			let lapse = now - expiry_timestamp;
+			let lapsed_periods =
+				if subscription_duration == 0 { 0 } else { lapse.div_ceil(subscription_duration) };
+			let expiry_timestamp = lapsed_periods
+				.checked_mul(subscription_duration)
+				.and_then(|lapsed| expiry_timestamp.checked_add(lapsed))
+				.and_then(|renewed| renewed.checked_add(duration))
+				.ok_or(PaymentError::DurationOverflow)?;
+
+			// The lapsed periods are paid for in addition to the extension.
+			Ok(Renewal {
+				cost: cost.saturating_mul(lapsed_periods as Balance).saturating_add(extension_cost),
+				expiry_timestamp,
+			})
+		},
+	}
+}
+
+/// Returns the cost of a subscription lasting `duration` seconds, given the cost of a full
+/// subscription period, rounded up.
+pub fn prorated_cost(
+	cost: Balance,
+	duration: Timestamp,
+	subscription_duration: Timestamp,
+) -> Balance {
+	if subscription_duration == 0 {
+		return cost;
+	}
+
+	cost.saturating_mul(duration as Balance)
+		.div_ceil(subscription_duration as Balance)
+}
+
+/// Adds the amount of the micro-payments made in `payment_block_number` to the credit of the
+/// parachain.
+///
+/// Payment blocks have to be credited in order, which ensures that the same payment can't be
+/// credited twice.
+pub fn add_credit(
+	para: &mut Parachain,
+	amount: Balance,
+	payment_block_number: u32,
+) -> Result<(), PaymentError> {
+	if para.last_credited_block.map_or(false, |last| payment_block_number <= last) {
+		return Err(PaymentError::AlreadyCredited);
+	}
+
+	para.credit = para.credit.saturating_add(amount);
+	para.last_credited_block = Some(payment_block_number);
+
+	Ok(())
+}
@@ -0,0 +1,230 @@
+// This file is part of RegionX.
+//
+// RegionX is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// RegionX is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with RegionX.  If not, see <https://www.gnu.org/licenses/>.
+
+//! File containing the state shared by the tracker with the other services.
+
+use crate::{config::config, consumption::latest_consumption, current_timestamp};
+use polkadot_core_primitives::BlockNumber;
+use serde::{Deserialize, Serialize};
+use std::{ops::Range, sync::Mutex};
+use types::{ParaId, Parachain, RelayChain, Timestamp};
+
+/// Serializes the updates of the error history, since the parachains are tracked concurrently.
+static ERROR_HISTORY_LOCK: Mutex<()> = Mutex::new(());
+
+/// Serializes the updates of the metrics, since the parachains are tracked concurrently.
+static METRICS_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TrackerState {
+	/// The timestamp at which the tracker was started.
+	pub started_at: Timestamp,
+	/// The registered parachains which were found to no longer be onboarded on their relay chain
+	/// when the tracker was started.
+	#[serde(default)]
+	pub offboarded: Vec<(RelayChain, ParaId)>,
+}
+
+impl TrackerState {
+	/// Returns whether the tracker is still within the configured warmup period, during which
+	/// the recorded consumption is discarded.
+	pub fn warming_up(&self) -> bool {
+		current_timestamp() < self.started_at.saturating_add(config().tracker_warmup)
+	}
+}
+
+/// An error which caused the tracking of a parachain to stop.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TrackerError {
+	/// The parachain whose tracking failed.
+	pub para: (RelayChain, ParaId),
+	/// The timestamp at which the error occurred.
+	pub timestamp: Timestamp,
+	/// The description of the error.
+	pub message: String,
+	/// The number of times the tracking of the parachain was restarted without recording any
+	/// consumption since the previous error.
+	pub reconnect_attempts: u32,
+}
+
+/// Returns the state of the tracker using the specified rpc index.
+pub fn tracker_state(rpc_index: usize) -> Option<TrackerState> {
+	let content = std::fs::read_to_string(tracker_state_path(rpc_index)).ok()?;
+	serde_json::from_str(&content).ok()
+}
+
+pub fn write_tracker_state(rpc_index: usize, state: &TrackerState) -> Result<(), String> {
+	let content = serde_json::to_string(state).map_err(|_| "Failed to serialize tracker state")?;
+	std::fs::write(tracker_state_path(rpc_index), content)
+		.map_err(|_| "Failed to write tracker state".to_string())
+}
+
+/// The file containing the state of the tracker using the specified rpc index.
+pub fn tracker_state_path(rpc_index: usize) -> String {
+	let output_dir = config().output_directory.trim_end_matches('/').to_string();
+	format!("{}/tracker-{}.json", output_dir, rpc_index)
+}
+
+/// Returns the recent errors of the tracker using the specified rpc index, oldest first.
+pub fn tracker_errors(rpc_index: usize) -> Vec<TrackerError> {
+	std::fs::read_to_string(tracker_errors_path(rpc_index))
+		.ok()
+		.and_then(|content| serde_json::from_str(&content).ok())
+		.unwrap_or_default()
+}
+
+/// Persists an error which caused the tracking of the parachain to stop.
+///
+/// Only the configured number of the most recent errors are kept, and nothing is persisted if
+/// `tracker_error_history` isn't configured.
+pub fn note_tracker_error(
+	rpc_index: usize,
+	para: &Parachain,
+	message: String,
+) -> Result<(), String> {
+	let Some(history) = config().tracker_error_history else { return Ok(()) };
+	let _lock = ERROR_HISTORY_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+	let mut errors = tracker_errors(rpc_index);
+	let id = (para.relay_chain.clone(), para.para_id);
+
+	// The tracking is considered to have recovered if consumption was recorded since the
+	// previous error.
+	let recorded_at = latest_consumption(para).map(|latest| latest.recorded_at);
+	let reconnect_attempts = errors
+		.iter()
+		.rev()
+		.find(|error| error.para == id)
+		.filter(|error| recorded_at.map_or(true, |recorded_at| recorded_at <= error.timestamp))
+		.map_or(0, |error| error.reconnect_attempts.saturating_add(1));
+
+	errors.push(TrackerError {
+		para: id,
+		timestamp: current_timestamp(),
+		message,
+		reconnect_attempts,
+	});
+	let excess = errors.len().saturating_sub(history);
+	errors.drain(..excess);
+
+	let content =
+		serde_json::to_string(&errors).map_err(|_| "Failed to serialize tracker errors")?;
+	std::fs::write(tracker_errors_path(rpc_index), content)
+		.map_err(|_| "Failed to write tracker errors".to_string())
+}
+
+/// The file containing the recent errors of the tracker using the specified rpc index.
+pub fn tracker_errors_path(rpc_index: usize) -> String {
+	let output_dir = config().output_directory.trim_end_matches('/').to_string();
+	format!("{}/tracker-errors-{}.json", output_dir, rpc_index)
+}
+
+/// The metrics of the tracking of a parachain since the tracker started.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ParaMetrics {
+	/// The tracked parachain.
+	pub para: (RelayChain, ParaId),
+	/// The number of blocks whose consumption was recorded.
+	pub blocks_processed: u64,
+	/// The number of times the tracker reconnected to the parachain.
+	pub reconnects: u64,
+}
+
+/// Returns the metrics of the tracker using the specified rpc index.
+pub fn tracker_metrics(rpc_index: usize) -> Vec<ParaMetrics> {
+	std::fs::read_to_string(tracker_metrics_path(rpc_index))
+		.ok()
+		.and_then(|content| serde_json::from_str(&content).ok())
+		.unwrap_or_default()
+}
+
+/// Notes that the consumption of a block was recorded.
+pub fn note_processed_block(rpc_index: usize, para: &Parachain) -> Result<(), String> {
+	update_metrics(rpc_index, para, |metrics| {
+		metrics.blocks_processed = metrics.blocks_processed.saturating_add(1);
+	})
+}
+
+/// Notes that the tracker reconnected to the parachain.
+pub fn note_reconnect(rpc_index: usize, para: &Parachain) -> Result<(), String> {
+	update_metrics(rpc_index, para, |metrics| {
+		metrics.reconnects = metrics.reconnects.saturating_add(1);
+	})
+}
+
+/// Resets the metrics of the tracker using the specified rpc index, e.g. when it restarts.
+pub fn reset_tracker_metrics(rpc_index: usize) -> Result<(), String> {
+	let _lock = METRICS_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+	write_tracker_metrics(rpc_index, &[])
+}
+
+fn update_metrics(
+	rpc_index: usize,
+	para: &Parachain,
+	update: impl FnOnce(&mut ParaMetrics),
+) -> Result<(), String> {
+	let _lock = METRICS_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+	let mut metrics = tracker_metrics(rpc_index);
+	let id = (para.relay_chain.clone(), para.para_id);
+
+	let position = match metrics.iter().position(|m| m.para == id) {
+		Some(position) => position,
+		None => {
+			metrics.push(ParaMetrics { para: id, blocks_processed: 0, reconnects: 0 });
+			metrics.len() - 1
+		},
+	};
+	update(&mut metrics[position]);
+
+	write_tracker_metrics(rpc_index, &metrics)
+}
+
+fn write_tracker_metrics(rpc_index: usize, metrics: &[ParaMetrics]) -> Result<(), String> {
+	let content = serde_json::to_string(metrics).map_err(|_| "Failed to serialize metrics")?;
+
+	// The metrics are read by the server while being updated, so they are written to a temporary
+	// file first and atomically moved into place.
+	let path = tracker_metrics_path(rpc_index);
+	let tmp_path = format!("{}.tmp", path);
+	std::fs::write(&tmp_path, content).map_err(|_| "Failed to write metrics")?;
+	std::fs::rename(tmp_path, path).map_err(|_| "Failed to write metrics".to_string())
+}
+
+/// Returns the blocks whose consumption is backfilled before continuing with `first_live_block`,
+/// the first block received through the finalized block subscription.
+///
+/// The backfill starts at `backfill_from`, or after the latest recorded block when resuming, and
+/// covers at most `max_blocks` blocks. It ends before the first live block, so no block is recorded
+/// twice.
+pub fn backfill_range(
+	backfill_from: BlockNumber,
+	latest_recorded: Option<BlockNumber>,
+	first_live_block: BlockNumber,
+	max_blocks: BlockNumber,
+) -> Range<BlockNumber> {
+	let from = latest_recorded
+		.map_or(backfill_from, |latest| latest.saturating_add(1))
+		.max(backfill_from)
+		.max(first_live_block.saturating_sub(max_blocks));
+
+	from.min(first_live_block)..first_live_block
+}
+
+/// The file containing the metrics of the tracker using the specified rpc index.
+pub fn tracker_metrics_path(rpc_index: usize) -> String {
+	let output_dir = config().output_directory.trim_end_matches('/').to_string();
+	format!("{}/tracker-metrics-{}.json", output_dir, rpc_index)
+}
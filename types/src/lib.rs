@@ -30,6 +30,8 @@ pub type Balance = u128;
 pub enum RelayChain {
 	Polkadot,
 	Kusama,
+	/// The Polkadot testnet.
+	Paseo,
 }
 
 impl fmt::Display for RelayChain {
@@ -37,6 +39,7 @@ impl fmt::Display for RelayChain {
 		match self {
 			RelayChain::Polkadot => write!(f, "Polkadot"),
 			RelayChain::Kusama => write!(f, "Kusama"),
+			RelayChain::Paseo => write!(f, "Paseo"),
 		}
 	}
 }
@@ -46,6 +49,7 @@ impl From<&str> for RelayChain {
 		match s.to_lowercase().as_str() {
 			"polkadot" => RelayChain::Polkadot,
 			"kusama" => RelayChain::Kusama,
+			"paseo" => RelayChain::Paseo,
 			_ => panic!("Invalid relay chain: {}", s),
 		}
 	}
@@ -60,6 +64,7 @@ impl<'de> Deserialize<'de> for RelayChain {
 		match s.as_str() {
 			"polkadot" | "Polkadot" => Ok(RelayChain::Polkadot),
 			"kusama" | "Kusama" => Ok(RelayChain::Kusama),
+			"paseo" | "Paseo" => Ok(RelayChain::Paseo),
 			_ => Err(serde::de::Error::custom(format!("Invalid relay chain: {}", s))),
 		}
 	}
@@ -78,6 +83,65 @@ pub struct Parachain {
 	pub relay_chain: RelayChain,
 	/// The timestamp when the subscription expires.
 	pub expiry_timestamp: Timestamp,
+	/// The credit accumulated through micro-payments which can be drawn from to extend the
+	/// subscription.
+	#[serde(default)]
+	pub credit: Balance,
+	/// The payment block of the latest micro-payment that was credited.
+	#[serde(default)]
+	pub last_credited_block: Option<u32>,
+	/// The payment with which the parachain was registered.
+	///
+	/// `None` for parachains registered in free mode.
+	#[serde(default)]
+	pub payment: Option<PaymentDetails>,
+	/// The block containing the ownership proof of the latest update of the registration.
+	///
+	/// Later updates have to be proven in subsequent blocks, so that the proof of an update can't
+	/// be replayed to revert the ones made after it.
+	#[serde(default)]
+	pub last_update_block: Option<u32>,
+	/// Set while the tracking of the parachain is paused, e.g. during its maintenance.
+	#[serde(default)]
+	pub paused: bool,
+	/// The block from which the consumption of the parachain is backfilled, instead of only
+	/// recording it from the latest finalized block onwards.
+	#[serde(default)]
+	pub backfill_from: Option<u32>,
+	/// The subscription tier of the parachain, which defines the cost of its subscription and
+	/// how often its consumption is recorded.
+	///
+	/// `None` for the default tier, in which the consumption of every block is recorded.
+	#[serde(default)]
+	pub tier: Option<String>,
+}
+
+/// The details of a payment, which can be used for retrieving the payment extrinsic.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+#[serde(crate = "rocket::serde")]
+pub struct PaymentDetails {
+	/// The block in which the payment occurred.
+	pub block_number: u32,
+	/// The hash of the block in which the payment occurred, so that the payment can be verified
+	/// independently of the canonical chain at `block_number`.
+	///
+	/// `None` for payments accepted before the hashes were recorded.
+	#[serde(default)]
+	pub block_hash: Option<String>,
+	/// The index of the payment extrinsic within the block.
+	pub extrinsic_index: u32,
+	/// The hex encoded address of the account that made the payment.
+	pub payer: Option<String>,
+}
+
+/// The dimensions of weight that can be recorded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum WeightDimension {
+	/// The computational time used to execute some logic.
+	RefTime,
+	/// The size of the proof needed to validate the block.
+	ProofSize,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -87,9 +151,23 @@ pub struct WeightConsumption {
 	/// The timestamp of the block.
 	pub timestamp: Timestamp,
 	/// The ref_time consumption over all the dispatch classes.
-	pub ref_time: DispatchClassConsumption,
+	///
+	/// `None` if the ref_time dimension isn't recorded for the parachain.
+	pub ref_time: Option<DispatchClassConsumption>,
 	/// The proof size over all dispatch classes.
-	pub proof_size: DispatchClassConsumption,
+	///
+	/// `None` if the proof size dimension isn't recorded for the parachain.
+	pub proof_size: Option<DispatchClassConsumption>,
+	/// The hex encoded hash of the block.
+	///
+	/// Only recorded if enabled, and `None` for all the records stored before it was.
+	#[serde(default)]
+	pub block_hash: Option<String>,
+	/// The weight consumed by the inherents compared to the other extrinsics of the block.
+	///
+	/// Only recorded if enabled, and `None` for all the records stored before it was.
+	#[serde(default)]
+	pub inherent_split: Option<InherentSplit>,
 }
 
 #[derive(Default, Debug, Serialize, PartialEq, Deserialize, Clone)]
@@ -105,6 +183,24 @@ pub struct DispatchClassConsumption {
 	pub mandatory: f32,
 }
 
+/// The weight consumed by the inherents of a block, such as setting the timestamp or the
+/// validation data, compared to the weight consumed by the other extrinsics.
+///
+/// The weight consumed outside of extrinsics, e.g. by the hooks, is part of neither.
+#[derive(Default, Debug, Serialize, PartialEq, Deserialize, Clone)]
+pub struct InherentSplit {
+	/// The percentage of the ref_time used by the inherents compared to the maximum potential.
+	pub inherent_ref_time: f32,
+	/// The percentage of the ref_time used by the other extrinsics compared to the maximum
+	/// potential.
+	pub non_inherent_ref_time: f32,
+	/// The percentage of the proof size used by the inherents compared to the maximum potential.
+	pub inherent_proof_size: f32,
+	/// The percentage of the proof size used by the other extrinsics compared to the maximum
+	/// potential.
+	pub non_inherent_proof_size: f32,
+}
+
 /// A shorthand for converting a tuple of `f32`s into `DispatchClassConsumption`.
 ///
 /// The order in which the values need to be provided is: `normal`, `operational`, `mandatory`.
@@ -114,15 +210,34 @@ impl From<(f32, f32, f32)> for DispatchClassConsumption {
 	}
 }
 
+impl DispatchClassConsumption {
+	/// Returns the consumption expressed in percentages rather than fractions of the maximum.
+	pub fn as_percent(&self) -> Self {
+		DispatchClassConsumption {
+			normal: self.normal * 100.0,
+			operational: self.operational * 100.0,
+			mandatory: self.mandatory * 100.0,
+		}
+	}
+}
+
 impl fmt::Display for WeightConsumption {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		write!(f, "\n\tNormal ref_time consumption: {}", self.ref_time.normal)?;
-		write!(f, "\n\tOperational ref_time consumption: {}", self.ref_time.operational)?;
-		write!(f, "\n\tMandatory ref_time consumption: {}", self.ref_time.mandatory)?;
+		if let Some(ref_time) = &self.ref_time {
+			write!(f, "\n\tNormal ref_time consumption: {}", ref_time.normal)?;
+			write!(f, "\n\tOperational ref_time consumption: {}", ref_time.operational)?;
+			write!(f, "\n\tMandatory ref_time consumption: {}", ref_time.mandatory)?;
+		} else {
+			write!(f, "\n\tRef_time consumption: not recorded")?;
+		}
 
-		write!(f, "\n\tNormal proof size: {}", self.proof_size.normal)?;
-		write!(f, "\n\tOperational proof size: {}", self.proof_size.operational)?;
-		write!(f, "\n\tMandatory proof size: {}", self.proof_size.mandatory)?;
+		if let Some(proof_size) = &self.proof_size {
+			write!(f, "\n\tNormal proof size: {}", proof_size.normal)?;
+			write!(f, "\n\tOperational proof size: {}", proof_size.operational)?;
+			write!(f, "\n\tMandatory proof size: {}", proof_size.mandatory)?;
+		} else {
+			write!(f, "\n\tProof size: not recorded")?;
+		}
 		Ok(())
 	}
 }
@@ -131,20 +246,47 @@ impl WeightConsumption {
 	/// Returns consumption data as a vector of strings, where each element
 	/// represents a column in a CSV format. Each string in the vector corresponds
 	/// to one column of data.
+	///
+	/// A dimension that isn't recorded is stored as a single empty column. The block hash and the
+	/// inherent split are stored as trailing columns only if they were recorded, with the block
+	/// hash column left empty if only the inherent split was recorded.
 	pub fn to_csv(&self) -> Vec<String> {
-		vec![
+		let mut columns = vec![
 			// Block number:
 			self.block_number.to_string(),
 			// Timestamp:
 			self.timestamp.to_string(),
-			// Reftime consumption:
-			self.ref_time.normal.to_string(),
-			self.ref_time.operational.to_string(),
-			self.ref_time.mandatory.to_string(),
-			// Proof size:
-			self.proof_size.normal.to_string(),
-			self.proof_size.operational.to_string(),
-			self.proof_size.mandatory.to_string(),
-		]
+		];
+
+		// Reftime consumption:
+		columns.extend(dispatch_class_columns(&self.ref_time));
+		// Proof size:
+		columns.extend(dispatch_class_columns(&self.proof_size));
+		// Block hash:
+		if self.block_hash.is_some() || self.inherent_split.is_some() {
+			columns.push(self.block_hash.clone().unwrap_or_default());
+		}
+		// Inherent split:
+		if let Some(split) = &self.inherent_split {
+			columns.extend([
+				split.inherent_ref_time.to_string(),
+				split.non_inherent_ref_time.to_string(),
+				split.inherent_proof_size.to_string(),
+				split.non_inherent_proof_size.to_string(),
+			]);
+		}
+
+		columns
+	}
+}
+
+fn dispatch_class_columns(consumption: &Option<DispatchClassConsumption>) -> Vec<String> {
+	match consumption {
+		Some(consumption) => vec![
+			consumption.normal.to_string(),
+			consumption.operational.to_string(),
+			consumption.mandatory.to_string(),
+		],
+		None => vec![String::new()],
 	}
 }